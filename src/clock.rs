@@ -0,0 +1,23 @@
+use crate::alias::Date;
+
+pub trait Clock {
+    fn today(&self) -> Date;
+}
+
+pub struct UtcClock;
+
+impl Clock for UtcClock {
+    fn today(&self) -> Date {
+        chrono::Utc::now().date_naive()
+    }
+}
+
+//
+// a pinned today, for a reproducible run
+pub struct FixedClock(pub Date);
+
+impl Clock for FixedClock {
+    fn today(&self) -> Date {
+        self.0
+    }
+}
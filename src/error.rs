@@ -8,7 +8,9 @@ pub enum Error {
     Ods(spreadsheet_ods::OdsError),
     Rusqlite(rusqlite::Error),
     SerdeJson(serde_json::Error),
+    Toml(toml::de::Error),
     YahooFinance(yahoo_finance_api::Error),
+    Reqwest(reqwest::Error),
 }
 
 impl Error {
@@ -27,6 +29,28 @@ impl Error {
     pub fn new_output<T: Into<String>>(msg: T) -> Error {
         Error::Output(msg.into())
     }
+
+    //
+    // a failure that may not happen again on a later attempt (network, yahoo),
+    // a historical error (ex: missing ticker, unexpected data) is permanent
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Io(_) | Error::Reqwest(_) => true,
+            Error::YahooFinance(error) => Self::is_transient_yahoo_(error),
+            _ => false,
+        }
+    }
+
+    //
+    // an unknown ticker or a ticker without quotes answers the same on retry
+    fn is_transient_yahoo_(error: &yahoo_finance_api::Error) -> bool {
+        !matches!(
+            error,
+            yahoo_finance_api::Error::NoQuotes
+                | yahoo_finance_api::Error::NoResult
+                | yahoo_finance_api::Error::EmptyDataSet
+        )
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -53,8 +77,20 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::Toml(error)
+    }
+}
+
 impl From<yahoo_finance_api::Error> for Error {
     fn from(error: yahoo_finance_api::Error) -> Self {
         Error::YahooFinance(error)
     }
 }
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Error::Reqwest(error)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::alias::Date;
+use crate::marketdata::{Currency, Instrument, Market};
+use std::rc::Rc;
+
+pub fn make_date_(year: i32, month: u32, day: u32) -> Date {
+    chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+pub fn make_currency_(name: &str) -> Rc<Currency> {
+    Rc::new(Currency {
+        name: String::from(name),
+        parent_currency: None,
+    })
+}
+
+//
+// plain equity quoted on EPA, fields are overridden with the struct update syntax
+pub fn make_instrument_(name: &str, currency: &Rc<Currency>) -> Instrument {
+    Instrument {
+        name: String::from(name),
+        isin: String::from("ISIN"),
+        previous_isins: Default::default(),
+        description: String::from("description"),
+        market: Rc::new(Market {
+            name: String::from("EPA"),
+            description: String::from("EPA"),
+        }),
+        currency: currency.clone(),
+        ticker_yahoo: None,
+        region: None,
+        fund_category: String::from("category"),
+        dividends: None,
+        quote_factor: 1.0,
+        ter: None,
+        history_start: None,
+        tags: Default::default(),
+        instrument_type: Default::default(),
+    }
+}
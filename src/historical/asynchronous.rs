@@ -0,0 +1,206 @@
+use super::{DataFrame, HistoricalCache, Persistance, YahooRequester, YAHOO_CHART_URL};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::marketdata::Instrument;
+
+use log::info;
+
+//
+// the futures are not Send, the instruments and the cache are not shared
+// between threads
+#[allow(async_fn_in_trait)]
+pub trait AsyncProvider {
+    async fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date)
+        -> Result<(), Error>;
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame>;
+}
+
+#[allow(async_fn_in_trait)]
+pub trait AsyncRequester {
+    async fn request(
+        &self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<(Date, Date, Vec<DataFrame>), Error>;
+}
+
+impl AsyncRequester for YahooRequester {
+    async fn request(
+        &self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+        info!(
+            "try to request async historic data for {} between {} to {}",
+            instrument.name,
+            begin.format("%Y-%m-%d"),
+            end.format("%Y-%m-%d")
+        );
+        let end = end
+            .checked_add_days(chrono::Days::new(1))
+            .ok_or_else(|| Error::new_historical(format!("unable to compute next day {}", end)))?;
+
+        //
+        // yahoo_finance_api fork only exposes a blocking client, the chart is
+        // requested with the non blocking reqwest client instead
+        let ticker_yahoo = YahooRequester::ticker_(instrument)?;
+        let client = reqwest::Client::builder()
+            .user_agent("Mozilla/5.0")
+            .build()?;
+        let mut result = Vec::new();
+        for (chunk_begin, chunk_end) in self.chunks_(begin, end) {
+            let content = client
+                .get(format!("{}/{}", YAHOO_CHART_URL, ticker_yahoo))
+                .query(&YahooRequester::chart_query_(chunk_begin, chunk_end))
+                .query(&[("interval", "1d")])
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            YahooRequester::merge_chunk_(
+                &mut result,
+                YahooRequester::parse_chart_(&content, false)?,
+            );
+        }
+        info!("request async historic data for {} done", instrument.name);
+        Ok(YahooRequester::make_range_(result))
+    }
+}
+
+pub struct AsyncHistoricalData<'a, R, P>
+where
+    R: AsyncRequester,
+    P: Persistance,
+{
+    requester: R,
+    cache: HistoricalCache<'a, P>,
+}
+
+impl<'a, R, P> AsyncHistoricalData<'a, R, P>
+where
+    R: AsyncRequester,
+    P: Persistance,
+{
+    pub fn new(requester: R, persistence: &'a P) -> Self {
+        Self {
+            requester,
+            cache: HistoricalCache::new(persistence),
+        }
+    }
+}
+
+impl<R, P> AsyncProvider for AsyncHistoricalData<'_, R, P>
+where
+    R: AsyncRequester,
+    P: Persistance,
+{
+    async fn fetch(
+        &mut self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<(), Error> {
+        if let Some((request_begin, request_end)) = self.cache.missing(instrument, begin, end)? {
+            let result = self
+                .requester
+                .request(instrument, request_begin, request_end)
+                .await?;
+            self.cache
+                .store(instrument, request_begin, request_end, result)?;
+        }
+        Ok(())
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        self.cache.latest(instrument, date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use assert_float_eq::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct MockAsyncRequester {
+        nb_request: Cell<usize>,
+    }
+
+    impl AsyncRequester for MockAsyncRequester {
+        async fn request(
+            &self,
+            _instrument: &Instrument,
+            begin: Date,
+            end: Date,
+        ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+            self.nb_request.set(self.nb_request.get() + 1);
+            let data = begin
+                .iter_days()
+                .take_while(|date| date <= &end)
+                .map(|date| DataFrame::new(date, 10.0, 10.0, 10.0, 10.0))
+                .collect::<Vec<_>>();
+            Ok((begin, end, data))
+        }
+    }
+
+    #[derive(Default)]
+    struct MockPersistance {
+        saved: RefCell<Vec<DataFrame>>,
+    }
+
+    impl Persistance for MockPersistance {
+        fn save(&self, _instrument: &Instrument, datas: &[DataFrame]) -> Result<(), Error> {
+            self.saved.borrow_mut().extend_from_slice(datas);
+            Ok(())
+        }
+
+        fn load(
+            &self,
+            _instrument: &Instrument,
+        ) -> Result<Option<(Date, Date, Vec<DataFrame>)>, Error> {
+            Ok(None)
+        }
+    }
+
+    fn make_date_(year: i32, month: u32, day: u32) -> Date {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn async_fetch_use_cache() {
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let persistence = MockPersistance::default();
+        let mut provider = AsyncHistoricalData::new(MockAsyncRequester::default(), &persistence);
+
+        provider
+            .fetch(&instrument, make_date_(2022, 5, 1), make_date_(2022, 5, 5))
+            .await
+            .unwrap();
+        assert_eq!(provider.requester.nb_request.get(), 1);
+        assert_eq!(persistence.saved.borrow().len(), 5);
+
+        provider
+            .fetch(&instrument, make_date_(2022, 5, 2), make_date_(2022, 5, 4))
+            .await
+            .unwrap();
+        assert_eq!(provider.requester.nb_request.get(), 1);
+
+        provider
+            .fetch(&instrument, make_date_(2022, 5, 2), make_date_(2022, 5, 7))
+            .await
+            .unwrap();
+        assert_eq!(provider.requester.nb_request.get(), 2);
+        assert_eq!(persistence.saved.borrow().len(), 7);
+
+        let spot = provider
+            .latest(&instrument, make_date_(2022, 5, 10))
+            .unwrap();
+        assert_eq!(spot.date, make_date_(2022, 5, 7));
+        assert_float_absolute_eq!(spot.close, 10.0, 1e-7);
+    }
+}
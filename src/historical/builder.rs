@@ -0,0 +1,124 @@
+use super::{
+    CsvProvider, DividendFetcher, HistoricalData, NullRequester, PriceOverride, Provider,
+    Requester, RetryingRequester, YahooRequester, DEFAULT_CHUNK_DAYS,
+};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::persistence::SQLitePersistance;
+
+pub enum ProviderSource {
+    Yahoo,
+    Null,
+    // manual prices read from <dir>/<instrument>.csv, nothing persisted
+    Csv(String),
+}
+
+pub enum ProviderPersistence {
+    Sqlite(String),
+    // sqlite in memory, nothing written on disk
+    Memory,
+}
+
+//
+// spot source with its persistence, the provider built borrows the
+// persistence so the builder must outlive it
+pub struct ProviderBuilder {
+    source: ProviderSource,
+    persistence: SQLitePersistance,
+    chunk_days: u64,
+    retries: u32,
+    price_overrides: Vec<PriceOverride>,
+    intraday: Option<Date>,
+}
+
+impl ProviderBuilder {
+    pub fn new(source: ProviderSource, persistence: ProviderPersistence) -> Result<Self, Error> {
+        let persistence = match persistence {
+            ProviderPersistence::Sqlite(path) => SQLitePersistance::new(&path)?,
+            ProviderPersistence::Memory => SQLitePersistance::new(":memory:")?,
+        };
+        Ok(Self {
+            source,
+            persistence,
+            chunk_days: DEFAULT_CHUNK_DAYS,
+            retries: 0,
+            price_overrides: Default::default(),
+            intraday: None,
+        })
+    }
+
+    pub fn with_lenient_cache(mut self, lenient: bool) -> Self {
+        self.persistence = self.persistence.with_lenient(lenient);
+        self
+    }
+
+    pub fn with_chunk_days(mut self, chunk_days: u64) -> Self {
+        self.chunk_days = chunk_days;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_price_overrides(mut self, price_overrides: Vec<PriceOverride>) -> Self {
+        self.price_overrides = price_overrides;
+        self
+    }
+
+    pub fn with_intraday(mut self, intraday: Option<Date>) -> Self {
+        self.intraday = intraday;
+        self
+    }
+
+    fn make_requester_(&self) -> Box<dyn Requester> {
+        match self.source {
+            ProviderSource::Yahoo => Box::new(RetryingRequester::new(
+                YahooRequester::default().with_chunk_days(self.chunk_days),
+                self.retries,
+            )),
+            _ => Box::new(NullRequester),
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Provider + '_> {
+        if let ProviderSource::Csv(directory) = &self.source {
+            return Box::new(CsvProvider::new(directory));
+        }
+        let mut provider = HistoricalData::new(self.make_requester_(), &self.persistence)
+            .with_price_overrides(self.price_overrides.clone());
+        if let Some(date) = self.intraday {
+            provider.enable_intraday(date);
+        }
+        Box::new(provider)
+    }
+
+    //
+    // manual csv prices have no dividends to request
+    pub fn build_dividend_fetcher(&self) -> DividendFetcher<'_> {
+        DividendFetcher::new(self.make_requester_(), &self.persistence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+
+    #[test]
+    fn memory_null_provider() {
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let date = Date::from_ymd_opt(2022, 3, 17).unwrap();
+        let builder = ProviderBuilder::new(ProviderSource::Null, ProviderPersistence::Memory)
+            .unwrap()
+            .with_price_overrides(vec!["PAEEM:2022-03-15:42.0".parse().unwrap()]);
+        let mut provider = builder.build();
+        provider
+            .fetch(&instrument, Date::from_ymd_opt(2022, 3, 1).unwrap(), date)
+            .unwrap();
+        let spot = provider.latest(&instrument, date).unwrap();
+        assert_eq!(spot.date, Date::from_ymd_opt(2022, 3, 15).unwrap());
+        assert_eq!(spot.close, 42.0);
+    }
+}
@@ -0,0 +1,132 @@
+use super::Requester;
+use crate::alias::Date;
+use crate::error::Error;
+use crate::marketdata::{Dividend, Instrument};
+use crate::persistence::SQLitePersistance;
+
+//
+// dividends of an instrument from the cache, completed by a request
+// from the day after the last cached one
+pub struct DividendFetcher<'a> {
+    requester: Box<dyn Requester>,
+    persistence: &'a SQLitePersistance,
+}
+
+impl<'a> DividendFetcher<'a> {
+    pub fn new(requester: Box<dyn Requester>, persistence: &'a SQLitePersistance) -> Self {
+        Self {
+            requester,
+            persistence,
+        }
+    }
+
+    pub fn fetch(
+        &self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<Vec<Dividend>, Error> {
+        let mut dividends = self.persistence.load_dividends(instrument)?;
+        let request_begin = dividends
+            .last()
+            .and_then(|dividend| dividend.record_date.date().succ_opt())
+            .map_or(begin, |date| date.max(begin));
+        if request_begin <= end {
+            let requested = self
+                .requester
+                .request_dividends(instrument, request_begin, end)?;
+            self.persistence.save_dividends(instrument, &requested)?;
+            dividends.extend(requested);
+        }
+        Ok(dividends)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use crate::historical::DataFrame;
+    use chrono::Datelike;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct MockRequester {
+        requests: Rc<RefCell<Vec<(Date, Date)>>>,
+    }
+
+    impl Requester for MockRequester {
+        fn request(
+            &self,
+            _instrument: &Instrument,
+            _begin: Date,
+            _end: Date,
+        ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+            Ok((Default::default(), Default::default(), Default::default()))
+        }
+
+        fn request_dividends(
+            &self,
+            _instrument: &Instrument,
+            begin: Date,
+            end: Date,
+        ) -> Result<Vec<Dividend>, Error> {
+            self.requests.borrow_mut().push((begin, end));
+            Ok(begin
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .filter(|date| date.day0() == 14)
+                .map(|date| Dividend {
+                    record_date: date.and_hms_opt(0, 0, 0).unwrap(),
+                    payment_date: date.and_hms_opt(0, 0, 0).unwrap(),
+                    value: 0.5,
+                })
+                .collect())
+        }
+    }
+
+    fn make_date_(year: i32, month: u32, day: u32) -> Date {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn fetch_after_last_cached() {
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let persistence = SQLitePersistance::new(":memory:").unwrap();
+        let requests = Rc::new(RefCell::new(Vec::new()));
+        let fetcher = DividendFetcher::new(
+            Box::new(MockRequester {
+                requests: requests.clone(),
+            }),
+            &persistence,
+        );
+
+        let dividends = fetcher
+            .fetch(&instrument, make_date_(2022, 1, 1), make_date_(2022, 3, 20))
+            .unwrap();
+        assert_eq!(dividends.len(), 3);
+
+        let dividends = fetcher
+            .fetch(&instrument, make_date_(2022, 1, 1), make_date_(2022, 4, 20))
+            .unwrap();
+        assert_eq!(
+            dividends
+                .iter()
+                .map(|dividend| dividend.record_date.date())
+                .collect::<Vec<_>>(),
+            vec![
+                make_date_(2022, 1, 15),
+                make_date_(2022, 2, 15),
+                make_date_(2022, 3, 15),
+                make_date_(2022, 4, 15),
+            ]
+        );
+        assert_eq!(
+            *requests.borrow(),
+            vec![
+                (make_date_(2022, 1, 1), make_date_(2022, 3, 20)),
+                (make_date_(2022, 3, 16), make_date_(2022, 4, 20)),
+            ]
+        );
+    }
+}
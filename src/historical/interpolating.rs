@@ -0,0 +1,160 @@
+use super::{DataFrame, Provider};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::marketdata::Instrument;
+use std::collections::HashMap;
+
+struct DenseSeries {
+    begin: Date,
+    end: Date,
+    data: Vec<DataFrame>,
+}
+
+//
+// provider filling gaps between two known spots by a linear interpolation,
+// dates outside the known spots are left to the base provider (no extrapolation)
+pub struct InterpolatingProvider<P> {
+    provider: P,
+    series: HashMap<String, DenseSeries>,
+}
+
+impl<P> InterpolatingProvider<P>
+where
+    P: Provider,
+{
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            series: Default::default(),
+        }
+    }
+
+    fn make_dense_series_(&self, instrument: &Instrument, begin: Date, end: Date) -> DenseSeries {
+        let known = begin
+            .iter_days()
+            .take_while(|date| *date <= end)
+            .filter_map(|date| {
+                self.provider
+                    .latest(instrument, date)
+                    .filter(|spot| spot.date == date)
+                    .copied()
+            })
+            .collect::<Vec<_>>();
+
+        let mut data = Vec::new();
+        for values in known.windows(2) {
+            let (left, right) = (&values[0], &values[1]);
+            let nb_days = (right.date - left.date).num_days() as f64;
+            data.push(*left);
+            for (i, date) in left
+                .date
+                .iter_days()
+                .skip(1)
+                .take_while(|date| *date < right.date)
+                .enumerate()
+            {
+                let weight = (i + 1) as f64 / nb_days;
+                let interpolate = |left: f64, right: f64| left + (right - left) * weight;
+                data.push(
+                    DataFrame::new(
+                        date,
+                        interpolate(left.open, right.open),
+                        interpolate(left.close, right.close),
+                        interpolate(left.high, right.high),
+                        interpolate(left.low, right.low),
+                    )
+                    .as_synthetic(),
+                );
+            }
+        }
+        data.extend(known.last());
+
+        DenseSeries { begin, end, data }
+    }
+}
+
+impl<P> Provider for InterpolatingProvider<P>
+where
+    P: Provider,
+{
+    fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+        self.provider.fetch(instrument, begin, end)?;
+        let (begin, end) = match self.series.get(&instrument.name) {
+            Some(series) => (begin.min(series.begin), end.max(series.end)),
+            None => (begin, end),
+        };
+        let series = self.make_dense_series_(instrument, begin, end);
+        self.series.insert(instrument.name.clone(), series);
+        Ok(())
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        let interpolated = self.series.get(&instrument.name).and_then(|series| {
+            let first = series.data.first()?;
+            let last = series.data.last()?;
+            if date < first.date || date > last.date {
+                return None;
+            }
+            series.data.get((date - first.date).num_days() as usize)
+        });
+        interpolated.or_else(|| self.provider.latest(instrument, date))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use assert_float_eq::*;
+
+    struct MockSpotProvider {
+        spots: Vec<DataFrame>,
+    }
+
+    impl Provider for MockSpotProvider {
+        fn fetch(
+            &mut self,
+            _instrument: &Instrument,
+            _begin: Date,
+            _end: Date,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn latest(&self, _instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+            self.spots.iter().rev().find(|item| item.date <= date)
+        }
+    }
+
+    fn make_date_(year: i32, month: u32, day: u32) -> Date {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn interpolate_gap() {
+        let instrument = make_instrument_("ILLIQUID", &make_currency_("EUR"));
+        let mut provider = InterpolatingProvider::new(MockSpotProvider {
+            spots: vec![
+                DataFrame::new(make_date_(2022, 3, 2), 10.0, 10.0, 10.0, 10.0),
+                DataFrame::new(make_date_(2022, 3, 5), 16.0, 16.0, 16.0, 16.0),
+            ],
+        });
+        provider
+            .fetch(&instrument, make_date_(2022, 3, 1), make_date_(2022, 3, 8))
+            .unwrap();
+
+        assert!(provider
+            .latest(&instrument, make_date_(2022, 3, 1))
+            .is_none());
+        for (day, wanted) in [(2, 10.0), (3, 12.0), (4, 14.0), (5, 16.0), (8, 16.0)] {
+            let spot = provider
+                .latest(&instrument, make_date_(2022, 3, day))
+                .unwrap();
+            assert_float_absolute_eq!(spot.close, wanted, 1e-7);
+        }
+        let spot = provider
+            .latest(&instrument, make_date_(2022, 3, 8))
+            .unwrap();
+        assert_eq!(spot.date, make_date_(2022, 3, 5));
+    }
+}
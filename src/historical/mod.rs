@@ -3,11 +3,28 @@ use log::info;
 use std::collections::HashMap;
 
 use crate::error::Error;
-use crate::marketdata::Instrument;
-
+use crate::marketdata::{Dividend, Instrument};
+
+mod builder;
+mod dividend;
+mod interpolating;
+mod overlay;
+mod retrying;
+mod tracing;
 mod yahoo;
+pub use builder::{ProviderBuilder, ProviderPersistence, ProviderSource};
+pub use dividend::DividendFetcher;
+pub use interpolating::InterpolatingProvider;
+pub use overlay::{CsvProvider, OverlayProvider};
+pub use retrying::RetryingRequester;
+pub use tracing::{PriceSource, SpotLookup, TracingProvider};
 pub use yahoo::*;
 
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "async")]
+pub use asynchronous::*;
+
 #[derive(Copy, Clone)]
 pub struct DataFrame {
     pub date: Date,
@@ -15,6 +32,9 @@ pub struct DataFrame {
     pub close: f64,
     pub high: f64,
     pub low: f64,
+    pub volume: Option<f64>,
+    // not a market quote, built by the pricer (ex: from the last trade price)
+    pub is_synthetic: bool,
 }
 
 impl DataFrame {
@@ -26,8 +46,22 @@ impl DataFrame {
             close,
             high,
             low,
+            volume: None,
+            is_synthetic: false,
         }
     }
+
+    #[inline]
+    pub fn with_volume(mut self, volume: Option<f64>) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    #[inline]
+    pub fn as_synthetic(mut self) -> Self {
+        self.is_synthetic = true;
+        self
+    }
 }
 
 pub trait Provider {
@@ -35,6 +69,19 @@ pub trait Provider {
     fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame>;
 }
 
+impl<P> Provider for Box<P>
+where
+    P: Provider + ?Sized,
+{
+    fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+        (**self).fetch(instrument, begin, end)
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        (**self).latest(instrument, date)
+    }
+}
+
 pub trait Requester {
     fn request(
         &self,
@@ -42,6 +89,23 @@ pub trait Requester {
         begin: Date,
         end: Date,
     ) -> Result<(Date, Date, Vec<DataFrame>), Error>;
+
+    fn latest_quote(
+        &self,
+        _instrument: &Instrument,
+        _date: Date,
+    ) -> Result<Option<DataFrame>, Error> {
+        Ok(None)
+    }
+
+    fn request_dividends(
+        &self,
+        _instrument: &Instrument,
+        _begin: Date,
+        _end: Date,
+    ) -> Result<Vec<Dividend>, Error> {
+        Ok(Vec::new())
+    }
 }
 
 pub struct NullRequester;
@@ -113,37 +177,41 @@ impl CacheInstrument {
     }
 }
 
-pub struct HistoricalData<'a, P>
+struct HistoricalCache<'a, P>
 where
     P: Persistance,
 {
-    requester: Box<dyn Requester>,
     persistence: &'a P,
     cache: HashMap<String, CacheInstrument>,
 }
 
-impl<'a, P> HistoricalData<'a, P>
+impl<'a, P> HistoricalCache<'a, P>
 where
     P: Persistance,
 {
-    pub fn new(requester: Box<dyn Requester>, persistence: &'a P) -> Self {
+    fn new(persistence: &'a P) -> Self {
         Self {
-            requester,
             persistence,
             cache: Default::default(),
         }
     }
 
-    fn make_cache_key(instrument: &Instrument) -> String {
+    fn make_key(instrument: &Instrument) -> String {
         instrument.name.clone()
     }
-}
 
-impl<P> Provider for HistoricalData<'_, P>
-where
-    P: Persistance,
-{
-    fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        self.cache
+            .get(&Self::make_key(instrument))
+            .and_then(|item| item.latest(date))
+    }
+
+    fn missing(
+        &mut self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<Option<(Date, Date)>, Error> {
         info!(
             "try to fetch historic data for {} between {} to {}",
             instrument.name,
@@ -151,9 +219,8 @@ where
             end.format("%Y-%m-%d")
         );
 
-        let key = Self::make_cache_key(instrument);
-        let mut cache_item = self.cache.get_mut(&key);
-        if cache_item.is_none() {
+        let key = Self::make_key(instrument);
+        if !self.cache.contains_key(&key) {
             if let Some((db_begin, db_end, db_result)) = self.persistence.load(instrument)? {
                 info!(
                     "historic data for {} from persistence found begin:{} end:{} nb_record:{}",
@@ -165,36 +232,33 @@ where
 
                 let item = CacheInstrument::new(db_begin, db_end, db_result);
                 self.cache.insert(key.clone(), item);
-                cache_item = self.cache.get_mut(&key);
             }
         }
 
-        let mut request_begin = begin;
-        let mut request_end = end;
-        if let Some(data_cache) = &cache_item {
-            match data_cache.not_in_cache(begin, end) {
-                Some((cache_begin, cache_end)) => {
-                    request_begin = cache_begin;
-                    request_end = cache_end;
-                }
-                None => {
-                    info!("historic data for {} up to date.", instrument.name);
-                    return Ok(());
-                }
-            };
-        }
-
-        info!(
-            "historic data for {} request from provider begin:{} end:{}",
-            instrument.name,
-            request_begin.format("%Y-%m-%d"),
-            request_end.format("%Y-%m-%d")
-        );
+        let missing = match self.cache.get(&key) {
+            Some(data_cache) => data_cache.not_in_cache(begin, end),
+            None => Some((begin, end)),
+        };
 
-        let (result_begin, result_end, result_data) =
-            self.requester
-                .request(instrument, request_begin, request_end)?;
+        match missing {
+            Some((request_begin, request_end)) => info!(
+                "historic data for {} request from provider begin:{} end:{}",
+                instrument.name,
+                request_begin.format("%Y-%m-%d"),
+                request_end.format("%Y-%m-%d")
+            ),
+            None => info!("historic data for {} up to date.", instrument.name),
+        }
+        Ok(missing)
+    }
 
+    fn store(
+        &mut self,
+        instrument: &Instrument,
+        request_begin: Date,
+        request_end: Date,
+        (result_begin, result_end, result_data): (Date, Date, Vec<DataFrame>),
+    ) -> Result<(), Error> {
         if !result_data.is_empty() {
             info!(
                 "historic data for {} from provider found begin:{} end:{} nb_record:{}",
@@ -206,11 +270,12 @@ where
 
             self.persistence.save(instrument, &result_data)?;
 
-            if let Some(data_cache) = cache_item {
+            let key = Self::make_key(instrument);
+            if let Some(data_cache) = self.cache.get_mut(&key) {
                 data_cache.insert(request_begin, request_end, result_data);
             } else {
                 let item = CacheInstrument::new(request_begin, request_end, result_data);
-                self.cache.insert(key.clone(), item);
+                self.cache.insert(key, item);
             }
         } else {
             info!(
@@ -221,18 +286,303 @@ where
 
         Ok(())
     }
+}
+
+//
+// close of an instrument patched on a date ex: ESE:2020-03-12:42.0
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriceOverride {
+    pub instrument: String,
+    pub date: Date,
+    pub close: f64,
+}
+
+impl std::str::FromStr for PriceOverride {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("invalid price override {value}, expected INSTRUMENT:YYYY-MM-DD:CLOSE");
+        let mut items = value.rsplitn(3, ':');
+        let (Some(close), Some(date), Some(instrument)) =
+            (items.next(), items.next(), items.next())
+        else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            instrument: instrument.to_string(),
+            date: chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())?,
+            close: close.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl std::fmt::Display for PriceOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.instrument,
+            self.date.format("%Y-%m-%d"),
+            self.close
+        )
+    }
+}
+
+pub struct HistoricalData<'a, P>
+where
+    P: Persistance,
+{
+    requester: Box<dyn Requester>,
+    cache: HistoricalCache<'a, P>,
+    intraday: Option<Date>,
+    provisional: HashMap<String, DataFrame>,
+    price_overrides: Vec<PriceOverride>,
+    // overridden spots by instrument, never persisted
+    overridden: HashMap<String, Vec<DataFrame>>,
+}
+
+impl<'a, P> HistoricalData<'a, P>
+where
+    P: Persistance,
+{
+    pub fn new(requester: Box<dyn Requester>, persistence: &'a P) -> Self {
+        Self {
+            requester,
+            cache: HistoricalCache::new(persistence),
+            intraday: None,
+            provisional: Default::default(),
+            price_overrides: Default::default(),
+            overridden: Default::default(),
+        }
+    }
+
+    pub fn with_price_overrides(mut self, price_overrides: Vec<PriceOverride>) -> Self {
+        self.price_overrides = price_overrides;
+        self
+    }
+
+    pub fn enable_intraday(&mut self, date: Date) {
+        self.intraday = Some(date);
+    }
+
+    fn fetch_overrides_(&mut self, instrument: &Instrument, begin: Date, end: Date) {
+        let mut data = Vec::new();
+        for price_override in self.price_overrides.iter().filter(|item| {
+            item.instrument == instrument.name && begin <= item.date && item.date <= end
+        }) {
+            let spot = match self
+                .cache
+                .latest(instrument, price_override.date)
+                .filter(|spot| spot.date == price_override.date)
+            {
+                Some(spot) => DataFrame {
+                    close: price_override.close,
+                    ..*spot
+                },
+                None => DataFrame::new(
+                    price_override.date,
+                    price_override.close,
+                    price_override.close,
+                    price_override.close,
+                    price_override.close,
+                ),
+            };
+            info!(
+                "price override for {} at {} close:{}",
+                instrument.name,
+                price_override.date.format("%Y-%m-%d"),
+                price_override.close
+            );
+            data.push(spot);
+        }
+        if !data.is_empty() {
+            data.sort_by_key(|spot| spot.date);
+            self.overridden
+                .insert(HistoricalCache::<P>::make_key(instrument), data);
+        }
+    }
+
+    fn fetch_intraday_(
+        &mut self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<(), Error> {
+        if let Some(date) = self.intraday.filter(|date| begin <= *date && *date <= end) {
+            match self.requester.latest_quote(instrument, date)? {
+                Some(data) => {
+                    info!(
+                        "intraday quote for {} at {} found close:{}",
+                        instrument.name,
+                        date.format("%Y-%m-%d"),
+                        data.close
+                    );
+                    self.provisional
+                        .insert(HistoricalCache::<P>::make_key(instrument), data);
+                }
+                None => {
+                    info!("intraday quote for {} not available", instrument.name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn fetch_historical_(
+        &mut self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<(), Error> {
+        if let Some((request_begin, request_end)) = self.cache.missing(instrument, begin, end)? {
+            let result = self
+                .requester
+                .request(instrument, request_begin, request_end)?;
+            self.cache
+                .store(instrument, request_begin, request_end, result)?;
+        }
+        Ok(())
+    }
+}
+
+impl<P> Provider for HistoricalData<'_, P>
+where
+    P: Persistance,
+{
+    fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+        self.fetch_historical_(instrument, begin, end)?;
+        self.fetch_overrides_(instrument, begin, end);
+        self.fetch_intraday_(instrument, begin, end)
+    }
 
     fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
-        match self.cache.get(&instrument.name) {
-            Some(item) => item.latest(date),
-            None => None,
+        let latest = self.cache.latest(instrument, date);
+        let overridden = self
+            .overridden
+            .get(&instrument.name)
+            .and_then(|data| data.iter().rev().find(|item| item.date <= date));
+        let latest = match (latest, overridden) {
+            (Some(spot), Some(overridden)) if spot.date > overridden.date => latest,
+            (latest, None) => latest,
+            (_, overridden) => overridden,
+        };
+        if self.intraday == Some(date) && latest.is_none_or(|item| item.date < date) {
+            if let Some(item) = self.provisional.get(&instrument.name) {
+                return Some(item);
+            }
         }
+        latest
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use assert_float_eq::*;
+    use std::cell::RefCell;
+
+    struct MockRequester;
+    impl Requester for MockRequester {
+        fn request(
+            &self,
+            _instrument: &Instrument,
+            begin: Date,
+            end: Date,
+        ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+            let data = begin
+                .iter_days()
+                .take_while(|date| date < &end)
+                .map(|date| DataFrame::new(date, 10.0, 10.0, 10.0, 10.0))
+                .collect::<Vec<_>>();
+            Ok((begin, end, data))
+        }
+
+        fn latest_quote(
+            &self,
+            _instrument: &Instrument,
+            date: Date,
+        ) -> Result<Option<DataFrame>, Error> {
+            Ok(Some(DataFrame::new(date, 10.0, 12.0, 12.5, 9.5)))
+        }
+    }
+
+    #[derive(Default)]
+    struct MockPersistance {
+        saved: RefCell<Vec<DataFrame>>,
+    }
+    impl Persistance for MockPersistance {
+        fn save(&self, _instrument: &Instrument, datas: &[DataFrame]) -> Result<(), Error> {
+            self.saved.borrow_mut().extend_from_slice(datas);
+            Ok(())
+        }
+
+        fn load(
+            &self,
+            _instrument: &Instrument,
+        ) -> Result<Option<(Date, Date, Vec<DataFrame>)>, Error> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn intraday_quote_not_saved() {
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let persistence = MockPersistance::default();
+        let today = make_date_(2022, 5, 5);
+        let mut provider = HistoricalData::new(Box::new(MockRequester), &persistence);
+        provider.enable_intraday(today);
+        provider
+            .fetch(&instrument, make_date_(2022, 5, 1), today)
+            .unwrap();
+
+        assert!(!persistence.saved.borrow().is_empty());
+        assert!(persistence
+            .saved
+            .borrow()
+            .iter()
+            .all(|data| data.date < today));
+
+        let spot = provider.latest(&instrument, today).unwrap();
+        assert_eq!(spot.date, today);
+        assert_float_absolute_eq!(spot.close, 12.0, 1e-7);
+
+        let spot = provider
+            .latest(&instrument, make_date_(2022, 5, 4))
+            .unwrap();
+        assert_float_absolute_eq!(spot.close, 10.0, 1e-7);
+    }
+
+    #[test]
+    fn price_override_not_saved() {
+        let instrument = make_instrument_("ESE", &make_currency_("EUR"));
+        let persistence = MockPersistance::default();
+        let date = make_date_(2022, 5, 3);
+        let mut provider = HistoricalData::new(Box::new(MockRequester), &persistence)
+            .with_price_overrides(vec!["ESE:2022-05-03:42.0".parse().unwrap()]);
+        provider
+            .fetch(&instrument, make_date_(2022, 5, 1), make_date_(2022, 5, 6))
+            .unwrap();
+
+        let spot = provider.latest(&instrument, date).unwrap();
+        assert_eq!(spot.date, date);
+        assert_float_absolute_eq!(spot.close, 42.0, 1e-7);
+        assert_float_absolute_eq!(spot.open, 10.0, 1e-7);
+        for date in [make_date_(2022, 5, 2), make_date_(2022, 5, 4)] {
+            let spot = provider.latest(&instrument, date).unwrap();
+            assert_eq!(spot.date, date);
+            assert_float_absolute_eq!(spot.close, 10.0, 1e-7);
+        }
+        assert!(persistence
+            .saved
+            .borrow()
+            .iter()
+            .all(|data| data.close == 10.0));
+
+        assert!("ESE:2022-05-03".parse::<PriceOverride>().is_err());
+        assert!("ESE:03/05/2022:42.0".parse::<PriceOverride>().is_err());
+    }
 
     #[test]
     fn cache_instrument_01() {
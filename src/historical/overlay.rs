@@ -0,0 +1,223 @@
+use super::{DataFrame, Provider};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::marketdata::Instrument;
+use log::warn;
+use std::collections::HashMap;
+
+//
+// manual prices read from <dir>/<instrument>.csv, same layout as --dump-prices
+// date;open;close;high;low (others columns ignored), a missing file has no price
+pub struct CsvProvider {
+    directory: String,
+    spots: HashMap<String, Vec<DataFrame>>,
+}
+
+impl CsvProvider {
+    pub fn new(directory: &str) -> Self {
+        Self {
+            directory: directory.to_string(),
+            spots: Default::default(),
+        }
+    }
+
+    fn parse_line_(filename: &str, line: &str) -> Result<DataFrame, Error> {
+        let invalid = || Error::new_historical(format!("invalid line {} in {}", line, filename));
+        let items = line.split(';').collect::<Vec<_>>();
+        if items.len() < 5 {
+            return Err(invalid());
+        }
+        let date =
+            chrono::NaiveDate::parse_from_str(items[0], "%Y-%m-%d").map_err(|_| invalid())?;
+        let values = items[1..5]
+            .iter()
+            .map(|item| item.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DataFrame::new(
+            date, values[0], values[1], values[2], values[3],
+        ))
+    }
+}
+
+impl Provider for CsvProvider {
+    fn fetch(&mut self, instrument: &Instrument, _begin: Date, _end: Date) -> Result<(), Error> {
+        if self.spots.contains_key(&instrument.name) {
+            return Ok(());
+        }
+        let filename = format!("{}/{}.csv", self.directory, instrument.name);
+        let mut spots = Vec::new();
+        if std::path::Path::new(&filename).is_file() {
+            for line in std::fs::read_to_string(&filename)?
+                .lines()
+                .skip(1)
+                .filter(|line| !line.trim().is_empty())
+            {
+                spots.push(Self::parse_line_(&filename, line)?);
+            }
+            spots.sort_by_key(|spot| spot.date);
+        }
+        self.spots.insert(instrument.name.clone(), spots);
+        Ok(())
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        self.spots
+            .get(&instrument.name)
+            .and_then(|spots| spots.iter().rev().find(|spot| spot.date <= date))
+    }
+}
+
+//
+// overlay spots take precedence over the base ones, unless the base
+// has a more recent spot, ex: manual prices over yahoo
+pub struct OverlayProvider<B, O> {
+    base: B,
+    overlay: O,
+}
+
+impl<B, O> OverlayProvider<B, O> {
+    pub fn new(base: B, overlay: O) -> Self {
+        Self { base, overlay }
+    }
+}
+
+impl<B, O> Provider for OverlayProvider<B, O>
+where
+    B: Provider,
+    O: Provider,
+{
+    //
+    // an instrument only priced by the overlay (ex: no yahoo ticker)
+    // does not fail on the base fetch
+    fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+        self.overlay.fetch(instrument, begin, end)?;
+        match self.base.fetch(instrument, begin, end) {
+            Err(error) if self.overlay.latest(instrument, end).is_some() => {
+                warn!(
+                    "{} only priced by the overlay, base fetch failed: {}",
+                    instrument.name, error
+                );
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        let overlay = self.overlay.latest(instrument, date);
+        match (self.base.latest(instrument, date), overlay) {
+            (Some(base), Some(overlay)) if base.date > overlay.date => Some(base),
+            (base, None) => base,
+            (_, overlay) => overlay,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::make_instrument_;
+    use crate::marketdata::Currency;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::{PortfolioIndicators, PricingOptions};
+    use std::rc::Rc;
+
+    struct MockBaseProvider {
+        spots: Vec<DataFrame>,
+    }
+
+    impl Provider for MockBaseProvider {
+        fn fetch(
+            &mut self,
+            instrument: &Instrument,
+            _begin: Date,
+            _end: Date,
+        ) -> Result<(), Error> {
+            if instrument.name == "LISTED" {
+                Ok(())
+            } else {
+                Err(Error::new_historical(format!(
+                    "missing yahoo ticker on {}",
+                    instrument.name
+                )))
+            }
+        }
+
+        fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+            if instrument.name != "LISTED" {
+                return None;
+            }
+            self.spots.iter().rev().find(|item| item.date <= date)
+        }
+    }
+
+    fn make_date_(year: i32, month: u32, day: u32) -> Date {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn overlay_and_base_instruments() {
+        let directory = std::env::temp_dir().join("portfolio_rs_overlay");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join("UNLISTED.csv"),
+            "date;open;close;high;low\n2022-03-01;50;50;50;50\n2022-03-03;55;55;55;55\n",
+        )
+        .unwrap();
+
+        let currency = Rc::new(Currency {
+            name: String::from("EUR"),
+            parent_currency: None,
+        });
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 4);
+        let make_position_ = |name: &str| Position {
+            instrument: Rc::new(make_instrument_(name, &currency)),
+            trades: vec![Trade {
+                date: begin.and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 20.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![make_position_("LISTED"), make_position_("UNLISTED")],
+            cash: Default::default(),
+        };
+        let mut provider = OverlayProvider::new(
+            MockBaseProvider {
+                spots: vec![DataFrame::new(begin, 21.0, 21.0, 21.0, 21.0)],
+            },
+            CsvProvider::new(directory.to_str().unwrap()),
+        );
+
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let last = indicators.portfolios.last().unwrap();
+        assert_eq!(last.date, end);
+        assert_eq!(
+            last.positions
+                .iter()
+                .map(|position| (position.instrument.name.as_str(), position.valuation))
+                .collect::<Vec<_>>(),
+            vec![("LISTED", 210.0), ("UNLISTED", 550.0)]
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use super::{DataFrame, Requester};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::marketdata::{Dividend, Instrument};
+use log::warn;
+use std::time::Duration;
+
+const FIRST_DELAY: Duration = Duration::from_millis(500);
+
+//
+// retry a transient failure (network, yahoo) with an exponential backoff,
+// a permanent one (ex: missing ticker) is returned at once
+pub struct RetryingRequester<R> {
+    requester: R,
+    retries: u32,
+    first_delay: Duration,
+}
+
+impl<R> RetryingRequester<R> {
+    pub fn new(requester: R, retries: u32) -> Self {
+        Self {
+            requester,
+            retries,
+            first_delay: FIRST_DELAY,
+        }
+    }
+
+    fn retry_<T, F>(&self, instrument: &Instrument, mut call: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Result<T, Error>,
+    {
+        let mut delay = self.first_delay;
+        let mut attempt = 0;
+        loop {
+            match call() {
+                Err(error) if attempt < self.retries && error.is_transient() => {
+                    attempt += 1;
+                    warn!(
+                        "request on {} failed ({:?}), retry {}/{} in {:?}",
+                        instrument.name, error, attempt, self.retries, delay
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<R> Requester for RetryingRequester<R>
+where
+    R: Requester,
+{
+    fn request(
+        &self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+        self.retry_(instrument, || {
+            self.requester.request(instrument, begin, end)
+        })
+    }
+
+    fn latest_quote(
+        &self,
+        instrument: &Instrument,
+        date: Date,
+    ) -> Result<Option<DataFrame>, Error> {
+        self.retry_(instrument, || self.requester.latest_quote(instrument, date))
+    }
+
+    fn request_dividends(
+        &self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<Vec<Dividend>, Error> {
+        self.retry_(instrument, || {
+            self.requester.request_dividends(instrument, begin, end)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use std::cell::Cell;
+
+    struct MockFailingRequester {
+        failures: Cell<u32>,
+        calls: Cell<u32>,
+        permanent: bool,
+    }
+
+    impl Requester for MockFailingRequester {
+        fn request(
+            &self,
+            _instrument: &Instrument,
+            begin: Date,
+            end: Date,
+        ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+            self.calls.set(self.calls.get() + 1);
+            if self.permanent {
+                return Err(Error::new_historical("missing yahoo ticker"));
+            }
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "timeout",
+                )));
+            }
+            Ok((
+                begin,
+                end,
+                vec![DataFrame::new(begin, 10.0, 10.0, 10.0, 10.0)],
+            ))
+        }
+    }
+
+    fn make_requester_(failures: u32, permanent: bool) -> RetryingRequester<MockFailingRequester> {
+        RetryingRequester {
+            requester: MockFailingRequester {
+                failures: Cell::new(failures),
+                calls: Cell::new(0),
+                permanent,
+            },
+            retries: 3,
+            first_delay: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn retry_transient_failures() {
+        let instrument = make_instrument_("ESE", &make_currency_("EUR"));
+        let begin = Date::from_ymd_opt(2022, 3, 1).unwrap();
+        let end = Date::from_ymd_opt(2022, 3, 4).unwrap();
+
+        let requester = make_requester_(2, false);
+        let (first, last, data) = requester.request(&instrument, begin, end).unwrap();
+        assert_eq!((first, last), (begin, end));
+        assert_eq!(data.len(), 1);
+        assert_eq!(requester.requester.calls.get(), 3);
+
+        let requester = make_requester_(4, false);
+        assert!(requester.request(&instrument, begin, end).is_err());
+        assert_eq!(requester.requester.calls.get(), 4);
+
+        let requester = make_requester_(0, true);
+        assert!(requester.request(&instrument, begin, end).is_err());
+        assert_eq!(requester.requester.calls.get(), 1);
+    }
+}
@@ -0,0 +1,260 @@
+use super::{DataFrame, Provider};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::marketdata::Instrument;
+use log::info;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceSource {
+    Fetched,
+    CarriedForward,
+    Interpolated,
+}
+
+impl std::fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PriceSource::Fetched => write!(f, "fetched"),
+            PriceSource::CarriedForward => write!(f, "carried-forward"),
+            PriceSource::Interpolated => write!(f, "interpolated"),
+        }
+    }
+}
+
+impl PriceSource {
+    //
+    // a spot of an earlier date is carried forward to date
+    pub fn from_spot(spot: &DataFrame, date: Date) -> Self {
+        if spot.is_synthetic {
+            PriceSource::Interpolated
+        } else if spot.date < date {
+            PriceSource::CarriedForward
+        } else {
+            PriceSource::Fetched
+        }
+    }
+}
+
+pub struct SpotLookup {
+    pub instrument: String,
+    pub date: Date,
+    // spot used, none if no spot found
+    pub spot: Option<DataFrame>,
+}
+
+impl SpotLookup {
+    pub fn source(&self) -> Option<PriceSource> {
+        self.spot
+            .map(|spot| PriceSource::from_spot(&spot, self.date))
+    }
+}
+
+//
+// transparent provider recording each spot lookup, useful to
+// understand which spot prices a position at a given date
+pub struct TracingProvider<P> {
+    provider: P,
+    lookups: RefCell<Vec<SpotLookup>>,
+}
+
+impl<P> TracingProvider<P> {
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            lookups: Default::default(),
+        }
+    }
+
+    //
+    // one <instrument>.csv by instrument with the spot used at each priced date
+    pub fn dump_prices(&self, output_dir: &str) -> Result<(), Error> {
+        let lookups = self.lookups.borrow();
+        let mut by_instrument: BTreeMap<&str, BTreeMap<Date, &SpotLookup>> = Default::default();
+        for lookup in lookups.iter().filter(|lookup| lookup.spot.is_some()) {
+            by_instrument
+                .entry(&lookup.instrument)
+                .or_default()
+                .insert(lookup.date, lookup);
+        }
+
+        std::fs::create_dir_all(output_dir)?;
+        for (instrument, lookups) in by_instrument {
+            let mut output_stream = File::create(format!("{}/{}.csv", output_dir, instrument))?;
+            output_stream.write_all(b"date;open;close;high;low;source\n")?;
+            for (date, lookup) in lookups {
+                let (Some(spot), Some(source)) = (lookup.spot, lookup.source()) else {
+                    continue;
+                };
+                output_stream.write_all(
+                    format!(
+                        "{};{};{};{};{};{}\n",
+                        date.format("%Y-%m-%d"),
+                        spot.open,
+                        spot.close,
+                        spot.high,
+                        spot.low,
+                        source
+                    )
+                    .as_bytes(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P> Provider for TracingProvider<P>
+where
+    P: Provider,
+{
+    fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+        self.provider.fetch(instrument, begin, end)
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        let spot = self.provider.latest(instrument, date);
+        match spot {
+            Some(spot) => info!(
+                "spot {} at {} uses {} close:{}",
+                instrument.name, date, spot.date, spot.close
+            ),
+            None => info!("spot {} at {} not found", instrument.name, date),
+        }
+        self.lookups.borrow_mut().push(SpotLookup {
+            instrument: instrument.name.clone(),
+            date,
+            spot: spot.copied(),
+        });
+        spot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use crate::marketdata::Currency;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::{PortfolioIndicators, PricingOptions};
+    use std::rc::Rc;
+
+    struct MockSpotProvider {
+        spots: Vec<DataFrame>,
+    }
+
+    impl Provider for MockSpotProvider {
+        fn fetch(
+            &mut self,
+            _instrument: &Instrument,
+            _begin: Date,
+            _end: Date,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn latest(&self, _instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+            self.spots.iter().rev().find(|item| item.date <= date)
+        }
+    }
+
+    fn make_date_(year: i32, month: u32, day: u32) -> Date {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn trace_spot_lookups() {
+        let currency = Rc::new(Currency {
+            name: String::from("EUR"),
+            parent_currency: None,
+        });
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 4);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("ESE", &currency)),
+                trades: vec![Trade {
+                    date: begin.and_hms_opt(10, 0, 0).unwrap(),
+                    way: Way::Buy,
+                    quantity: 10.0,
+                    price: 20.0,
+                    fees: 1.0,
+                    order_id: None,
+                    fx_rate: None,
+                }],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let mut provider = TracingProvider::new(MockSpotProvider {
+            spots: vec![
+                DataFrame::new(make_date_(2022, 3, 2), 21.0, 21.0, 21.0, 21.0),
+                DataFrame::new(make_date_(2022, 3, 4), 22.0, 22.0, 22.0, 22.0),
+            ],
+        });
+
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(indicators.portfolios.len(), 3);
+
+        let lookups = provider.lookups.take();
+        assert!(lookups.iter().all(|lookup| lookup.instrument == "ESE"));
+        assert_eq!(
+            lookups
+                .iter()
+                .map(|lookup| (lookup.date, lookup.spot.map(|spot| (spot.date, spot.close))))
+                .collect::<Vec<_>>(),
+            vec![
+                (make_date_(2022, 3, 1), None),
+                (make_date_(2022, 3, 2), Some((make_date_(2022, 3, 2), 21.0))),
+                (make_date_(2022, 3, 3), Some((make_date_(2022, 3, 2), 21.0))),
+                (make_date_(2022, 3, 4), Some((make_date_(2022, 3, 4), 22.0))),
+            ]
+        );
+        assert!(provider.lookups.borrow().is_empty());
+    }
+
+    #[test]
+    fn dump_used_prices() {
+        let instrument = make_instrument_("ESE", &make_currency_("EUR"));
+        let provider = TracingProvider::new(MockSpotProvider {
+            spots: vec![
+                DataFrame::new(make_date_(2022, 3, 2), 20.5, 21.0, 21.5, 20.0),
+                DataFrame::new(make_date_(2022, 3, 3), 21.5, 21.5, 21.5, 21.5).as_synthetic(),
+                DataFrame::new(make_date_(2022, 3, 4), 21.0, 22.0, 22.5, 20.5),
+            ],
+        });
+        for day in 1..=5 {
+            provider.latest(&instrument, make_date_(2022, 3, day));
+        }
+        provider.latest(&instrument, make_date_(2022, 3, 4));
+
+        let output_dir = std::env::temp_dir().join("portfolio_rs_dump_prices");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        provider.dump_prices(output_dir.to_str().unwrap()).unwrap();
+        let content = std::fs::read_to_string(output_dir.join("ESE.csv")).unwrap();
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(
+            content,
+            "date;open;close;high;low;source\n\
+             2022-03-02;20.5;21;21.5;20;fetched\n\
+             2022-03-03;21.5;21.5;21.5;21.5;interpolated\n\
+             2022-03-04;21;22;22.5;20.5;fetched\n\
+             2022-03-05;21;22;22.5;20.5;carried-forward\n"
+        );
+    }
+}
@@ -1,16 +1,105 @@
 use super::{DataFrame, Requester};
 use crate::alias::Date;
 use crate::error::Error;
-use crate::marketdata::Instrument;
+use crate::marketdata::{Dividend, Instrument};
 
-use chrono::Timelike;
+use chrono::{DateTime, Timelike};
 use log::{debug, info};
 use yahoo_finance_api::{Interval, YahooBuilder};
 
-pub struct YahooRequester;
+pub const DEFAULT_CHUNK_DAYS: u64 = 365;
+
+pub(super) const YAHOO_CHART_URL: &str = "https://query1.finance.yahoo.com/v8/finance/chart";
+
+//
+// a multi decade daily request may be truncated by yahoo, the period
+// is requested by windows of chunk_days then concatenated
+#[derive(Clone, Copy)]
+pub struct YahooRequester {
+    chunk_days: u64,
+}
+
+impl Default for YahooRequester {
+    fn default() -> Self {
+        Self {
+            chunk_days: DEFAULT_CHUNK_DAYS,
+        }
+    }
+}
 
 impl YahooRequester {
-    fn request_data(&self, ticker: &str, begin: Date, end: Date) -> Result<Vec<DataFrame>, Error> {
+    pub fn with_chunk_days(mut self, chunk_days: u64) -> Self {
+        self.chunk_days = chunk_days.max(1);
+        self
+    }
+
+    //
+    // end is excluded, a quote on a window boundary is kept once
+    pub(super) fn request_by_chunk_<F>(
+        &self,
+        begin: Date,
+        end: Date,
+        mut request: F,
+    ) -> Result<Vec<DataFrame>, Error>
+    where
+        F: FnMut(Date, Date) -> Result<Vec<DataFrame>, Error>,
+    {
+        let mut data_frames: Vec<DataFrame> = Vec::new();
+        for (chunk_begin, chunk_end) in self.chunks_(begin, end) {
+            Self::merge_chunk_(&mut data_frames, request(chunk_begin, chunk_end)?);
+        }
+        Ok(data_frames)
+    }
+
+    pub(super) fn chunks_(&self, begin: Date, end: Date) -> Vec<(Date, Date)> {
+        let mut chunks = Vec::new();
+        let mut chunk_begin = begin;
+        while chunk_begin < end {
+            let chunk_end = chunk_begin
+                .checked_add_days(chrono::Days::new(self.chunk_days))
+                .map_or(end, |date| date.min(end));
+            debug!(
+                "request chunk {} to {}",
+                chunk_begin.format("%Y-%m-%d"),
+                chunk_end.format("%Y-%m-%d")
+            );
+            chunks.push((chunk_begin, chunk_end));
+            chunk_begin = chunk_end;
+        }
+        chunks
+    }
+
+    pub(super) fn merge_chunk_(data_frames: &mut Vec<DataFrame>, mut chunk: Vec<DataFrame>) {
+        chunk.sort_by_key(|item| item.date);
+        for item in chunk {
+            if data_frames.last().is_none_or(|last| last.date < item.date) {
+                data_frames.push(item);
+            }
+        }
+    }
+
+    //
+    // query of a chart request between begin and end (excluded)
+    pub(super) fn chart_query_(begin: Date, end: Date) -> [(&'static str, i64); 2] {
+        [
+            (
+                "period1",
+                begin.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp(),
+            ),
+            (
+                "period2",
+                end.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp(),
+            ),
+        ]
+    }
+
+    pub(super) fn request_data(
+        &self,
+        ticker: &str,
+        begin: Date,
+        end: Date,
+        intraday: bool,
+    ) -> Result<Vec<DataFrame>, Error> {
         let request_result = YahooBuilder::new()
             .set_period(begin, end)
             .set_interval(Interval::Day1)
@@ -35,7 +124,7 @@ impl YahooRequester {
                 })?;
 
             for (date_position, date) in result.timestamp.as_ref().unwrap().iter().enumerate() {
-                if date.hour() > 8 || date.minute() != 0 || date.second() != 0 {
+                if !intraday && (date.hour() > 8 || date.minute() != 0 || date.second() != 0) {
                     debug!("skip {} because not a real close", date);
                     continue;
                 }
@@ -90,14 +179,22 @@ impl YahooRequester {
                             instrument_position, date_position
                         ))
                     })?;
+                let volume = quotes
+                    .volume
+                    .as_ref()
+                    .and_then(|volumes| volumes.get(date_position).copied().flatten())
+                    .map(|volume| volume as f64);
                 if open.is_some() && close.is_some() && high.is_some() && low.is_some() {
-                    data_frames.push(DataFrame::new(
-                        date.date(),
-                        open.unwrap(),
-                        close.unwrap(),
-                        high.unwrap(),
-                        low.unwrap(),
-                    ));
+                    data_frames.push(
+                        DataFrame::new(
+                            date.date(),
+                            open.unwrap(),
+                            close.unwrap(),
+                            high.unwrap(),
+                            low.unwrap(),
+                        )
+                        .with_volume(volume),
+                    );
                 } else {
                     info!("value not available at {}", date);
                 }
@@ -105,6 +202,109 @@ impl YahooRequester {
         }
         Ok(data_frames)
     }
+
+    pub(super) fn ticker_(instrument: &Instrument) -> Result<&String, Error> {
+        instrument.ticker_yahoo.as_ref().ok_or_else(|| {
+            Error::new_historical(format!("missing yahoo ticker on {}", instrument.name))
+        })
+    }
+
+    //
+    // daily quotes of a raw chart response, the same filtering as request_data
+    // for a client without yahoo_finance_api
+    pub(super) fn parse_chart_(content: &str, intraday: bool) -> Result<Vec<DataFrame>, Error> {
+        let response: serde_json::Value = serde_json::from_str(content)?;
+        let mut data_frames = Vec::new();
+        for (instrument_position, result) in response["chart"]["result"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let Some(timestamps) = result["timestamp"].as_array() else {
+                continue;
+            };
+            let quotes = result["indicators"]["quote"]
+                .get(instrument_position)
+                .ok_or_else(|| {
+                    Error::new_historical(format!(
+                        "unable to get quote at instrument_position:{}",
+                        instrument_position
+                    ))
+                })?;
+            let value_ = |name: &str, date_position: usize| quotes[name][date_position].as_f64();
+
+            for (date_position, timestamp) in timestamps.iter().enumerate() {
+                let date = timestamp
+                    .as_i64()
+                    .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+                    .ok_or_else(|| {
+                        Error::new_historical(format!("invalid chart date {}", timestamp))
+                    })?;
+                if !intraday && (date.hour() > 8 || date.minute() != 0 || date.second() != 0) {
+                    debug!("skip {} because not a real close", date);
+                    continue;
+                }
+                match (
+                    value_("open", date_position),
+                    value_("close", date_position),
+                    value_("high", date_position),
+                    value_("low", date_position),
+                ) {
+                    (Some(open), Some(close), Some(high), Some(low)) => data_frames.push(
+                        DataFrame::new(date.date_naive(), open, close, high, low)
+                            .with_volume(value_("volume", date_position)),
+                    ),
+                    _ => info!("value not available at {}", date),
+                }
+            }
+        }
+        Ok(data_frames)
+    }
+
+    //
+    // dividend events of a chart response, yahoo only gives the ex date
+    // so it is used as record and payment date
+    pub(super) fn parse_dividends_(content: &str) -> Result<Vec<Dividend>, Error> {
+        let response: serde_json::Value = serde_json::from_str(content)?;
+        let mut dividends = Vec::new();
+        for result in response["chart"]["result"].as_array().into_iter().flatten() {
+            let Some(events) = result["events"]["dividends"].as_object() else {
+                continue;
+            };
+            for event in events.values() {
+                let (Some(timestamp), Some(value)) =
+                    (event["date"].as_i64(), event["amount"].as_f64())
+                else {
+                    return Err(Error::new_historical(format!(
+                        "unexpected dividend event {}",
+                        event
+                    )));
+                };
+                let date = DateTime::from_timestamp(timestamp, 0)
+                    .ok_or_else(|| {
+                        Error::new_historical(format!("invalid dividend date {}", timestamp))
+                    })?
+                    .date_naive()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap();
+                dividends.push(Dividend {
+                    record_date: date,
+                    payment_date: date,
+                    value,
+                });
+            }
+        }
+        dividends.sort_by_key(|dividend| dividend.record_date);
+        Ok(dividends)
+    }
+
+    pub(super) fn make_range_(result: Vec<DataFrame>) -> (Date, Date, Vec<DataFrame>) {
+        match (result.first(), result.last()) {
+            (Some(first), Some(last)) => (first.date, last.date, result),
+            _ => (Default::default(), Default::default(), result),
+        }
+    }
 }
 
 impl Requester for YahooRequester {
@@ -124,21 +324,162 @@ impl Requester for YahooRequester {
             .checked_add_days(chrono::Days::new(1))
             .ok_or_else(|| Error::new_historical(format!("unable to compute next day {}", end)))?;
 
-        let ticker_yahoo = instrument.ticker_yahoo.as_ref().ok_or_else(|| {
-            Error::new_historical(format!("missing yahoo ticker on {}", instrument.name))
-        })?;
+        let ticker_yahoo = Self::ticker_(instrument)?;
         debug!("request historic data for {}", instrument.name);
-        let result = self.request_data(ticker_yahoo, begin, end)?;
-        let result_begin;
-        let result_end;
-        if result.is_empty() {
-            result_begin = Default::default();
-            result_end = Default::default();
-        } else {
-            result_begin = result.first().unwrap().date;
-            result_end = result.last().unwrap().date;
-        }
+        let result = self.request_by_chunk_(begin, end, |begin, end| {
+            self.request_data(ticker_yahoo, begin, end, false)
+        })?;
         info!("request historic data for {} done", instrument.name);
-        Ok((result_begin, result_end, result))
+        Ok(Self::make_range_(result))
+    }
+
+    fn latest_quote(
+        &self,
+        instrument: &Instrument,
+        date: Date,
+    ) -> Result<Option<DataFrame>, Error> {
+        info!(
+            "try to request intraday quote for {} at {}",
+            instrument.name,
+            date.format("%Y-%m-%d")
+        );
+        let end = date
+            .checked_add_days(chrono::Days::new(1))
+            .ok_or_else(|| Error::new_historical(format!("unable to compute next day {}", date)))?;
+
+        let ticker_yahoo = Self::ticker_(instrument)?;
+        let result = self.request_data(ticker_yahoo, date, end, true)?;
+        Ok(result.into_iter().rev().find(|item| item.date == date))
+    }
+
+    fn request_dividends(
+        &self,
+        instrument: &Instrument,
+        begin: Date,
+        end: Date,
+    ) -> Result<Vec<Dividend>, Error> {
+        info!(
+            "try to request dividends for {} between {} to {}",
+            instrument.name,
+            begin.format("%Y-%m-%d"),
+            end.format("%Y-%m-%d")
+        );
+        let end = end
+            .checked_add_days(chrono::Days::new(1))
+            .ok_or_else(|| Error::new_historical(format!("unable to compute next day {}", end)))?;
+
+        let ticker_yahoo = Self::ticker_(instrument)?;
+        let content = reqwest::blocking::Client::builder()
+            .user_agent("Mozilla/5.0")
+            .build()?
+            .get(format!("{}/{}", YAHOO_CHART_URL, ticker_yahoo))
+            .query(&Self::chart_query_(begin, end))
+            .query(&[("interval", "1d"), ("events", "div")])
+            .send()?
+            .error_for_status()?
+            .text()?;
+        let dividends = Self::parse_dividends_(&content)?;
+        info!(
+            "request {} dividends for {} done",
+            dividends.len(),
+            instrument.name
+        );
+        Ok(dividends)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_date_(year: i32, month: u32, day: u32) -> Date {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parse_dividends() {
+        let content = r#"{"chart":{"result":[{"meta":{"currency":"EUR"},"events":{"dividends":{
+            "1657004400":{"amount":0.25,"date":1657004400},
+            "1647500400":{"amount":0.5,"date":1647500400}}}}],"error":null}}"#;
+        let dividends = YahooRequester::parse_dividends_(content).unwrap();
+        assert_eq!(dividends.len(), 2);
+        assert_eq!(dividends[0].record_date.date(), make_date_(2022, 3, 17));
+        assert_eq!(dividends[0].payment_date, dividends[0].record_date);
+        assert_eq!(dividends[0].value, 0.5);
+        assert_eq!(dividends[1].record_date.date(), make_date_(2022, 7, 5));
+        assert_eq!(dividends[1].value, 0.25);
+
+        let content = r#"{"chart":{"result":[{"meta":{"currency":"EUR"}}],"error":null}}"#;
+        assert!(YahooRequester::parse_dividends_(content)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn parse_chart() {
+        //
+        // 2022-03-17 and 2022-03-18 closes, an intraday quote and a missing value
+        let content = r#"{"chart":{"result":[{"meta":{"currency":"EUR"},
+            "timestamp":[1647475200,1647561600,1647597600,1647820800],
+            "indicators":{"quote":[{
+                "open":[10.0,11.0,11.5,null],"close":[10.5,11.5,12.0,12.5],
+                "high":[11.0,12.0,12.0,13.0],"low":[9.5,10.5,11.0,12.0],
+                "volume":[100,200,50,300]}]}}],"error":null}}"#;
+        let data = YahooRequester::parse_chart_(content, false).unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].date, make_date_(2022, 3, 17));
+        assert_eq!(data[0].open, 10.0);
+        assert_eq!(data[0].close, 10.5);
+        assert_eq!(data[0].volume, Some(100.0));
+        assert_eq!(data[1].date, make_date_(2022, 3, 18));
+        assert_eq!(data[1].high, 12.0);
+        assert_eq!(data[1].low, 10.5);
+
+        let data = YahooRequester::parse_chart_(content, true).unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(data[2].close, 12.0);
+
+        let content = r#"{"chart":{"result":[{"meta":{"currency":"EUR"}}],"error":null}}"#;
+        assert!(YahooRequester::parse_chart_(content, false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn chunk_boundary_not_duplicated() {
+        let requester = YahooRequester::default().with_chunk_days(10);
+        let begin = make_date_(2022, 1, 1);
+        let end = make_date_(2022, 1, 26);
+        let mut chunks = Vec::new();
+        //
+        // each chunk also returns its end date, as yahoo may do
+        let data = requester
+            .request_by_chunk_(begin, end, |begin, end| {
+                chunks.push((begin, end));
+                Ok(begin
+                    .iter_days()
+                    .take_while(|date| *date <= end)
+                    .map(|date| DataFrame::new(date, 10.0, 10.0, 10.0, 10.0))
+                    .collect())
+            })
+            .unwrap();
+
+        assert_eq!(
+            chunks,
+            vec![
+                (begin, make_date_(2022, 1, 11)),
+                (make_date_(2022, 1, 11), make_date_(2022, 1, 21)),
+                (make_date_(2022, 1, 21), end),
+            ]
+        );
+        assert_eq!(
+            data.iter().map(|item| item.date).collect::<Vec<_>>(),
+            begin
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .collect::<Vec<_>>()
+        );
+        let (first, last, _) = YahooRequester::make_range_(data);
+        assert_eq!((first, last), (begin, end));
     }
 }
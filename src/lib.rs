@@ -0,0 +1,16 @@
+pub mod alias;
+pub mod clock;
+pub mod error;
+#[cfg(test)]
+mod fixture;
+pub mod historical;
+pub mod marketdata;
+pub mod output;
+pub mod persistence;
+pub mod portfolio;
+pub mod pricer;
+pub mod referential;
+
+//
+// a ready spot provider without assembling the requester and its persistence
+pub use historical::{Provider, ProviderBuilder, ProviderPersistence, ProviderSource};
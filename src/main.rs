@@ -1,31 +1,36 @@
 use chrono::Local;
 use clap::{Parser, ValueEnum};
 use env_logger::Builder;
-use log::info;
 use log::LevelFilter;
-use portfolio::Portfolio;
-use std::io::Write;
-
-mod alias;
-mod error;
-mod historical;
-mod marketdata;
-mod output;
-mod persistence;
-mod portfolio;
-mod pricer;
-mod referential;
-
-use alias::Date;
-use historical::{HistoricalData, NullRequester, Requester, YahooRequester};
-use output::{CsvOutput, OdsOutput, Output, PortfolioPerformanceOutput};
-use persistence::SQLitePersistance;
-use pricer::PortfolioIndicators;
-use referential::Referential;
-
-use error::Error;
-
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+use log::{info, warn};
+use portfolio_rs::portfolio::{Portfolio, QUANTITY_EPSILON};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+
+use portfolio_rs::alias::Date;
+use portfolio_rs::clock::{Clock, UtcClock};
+use portfolio_rs::historical::{
+    CsvProvider, InterpolatingProvider, OverlayProvider, PriceOverride, TracingProvider,
+    DEFAULT_CHUNK_DAYS,
+};
+use portfolio_rs::output::{
+    check_output_dir, ClosePositionOutput, CsvOutput, NameTemplate, OdsLocale, OdsOutput, Output,
+    PortfolioPerformanceOutput, RunManifest, Snapshot, DEFAULT_DELIMITER,
+};
+use portfolio_rs::pricer::{
+    ActiveReturn, ClosePositionIndicator, FeesTreatment, FirstSpot, HeatMapPeriod,
+    PortfolioIndicators, PricingOptions, RiskFreeRate, RollingBeta, Step, ValuationPrice,
+    WeightBase, DEFAULT_BETA_WINDOW_DAYS, MIN_OBSERVATIONS, TRADING_DAYS_PER_YEAR,
+};
+use portfolio_rs::referential::Referential;
+use portfolio_rs::{Provider, ProviderBuilder, ProviderPersistence, ProviderSource};
+
+use portfolio_rs::error::Error;
+use portfolio_rs::marketdata::Dividend;
+
+#[derive(ValueEnum, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 enum SpotSource {
     Null,
     Yahoo,
@@ -40,11 +45,32 @@ impl std::fmt::Display for SpotSource {
     }
 }
 
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(ValueEnum, Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 enum OutputType {
     Csv,
     Ods,
     PortfolioPerformance,
+    // closed positions csv, each closed position is priced alone up to its close
+    ClosePositions,
+    All,
+}
+
+impl OutputType {
+    fn expand(self) -> Vec<OutputType> {
+        match self {
+            OutputType::All => vec![
+                OutputType::Csv,
+                OutputType::Ods,
+                OutputType::PortfolioPerformance,
+            ],
+            output_type => vec![output_type],
+        }
+    }
+
+    fn is_priced(&self) -> bool {
+        matches!(self, OutputType::Csv | OutputType::Ods)
+    }
 }
 
 impl std::fmt::Display for OutputType {
@@ -56,23 +82,56 @@ impl std::fmt::Display for OutputType {
     }
 }
 
+//
+// pricer options and dates are written with their cli value
+fn serialize_display<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: std::fmt::Display,
+    S: Serializer,
+{
+    serializer.collect_str(value)
+}
+
+fn serialize_display_vec<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: std::fmt::Display,
+    S: Serializer,
+{
+    serializer.collect_seq(values.iter().map(ToString::to_string))
+}
+
+fn serialize_display_option<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: std::fmt::Display,
+    S: Serializer,
+{
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
 /// Simple program to greet a person
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[derive(Parser, Serialize, Debug)]
+#[clap(author, version, about, long_about = None, args_override_self = true)]
 struct Args {
     /// Marketdata directory
     #[clap(short, long, value_parser)]
     marketdata_dir: String,
 
-    /// Portfolio file
+    /// Portfolio file, a .csv one is a broker trades export (date;way;instrument;quantity;price;fees)
     #[clap(short, long, value_parser)]
     portfolio: String,
 
+    /// currency of a portfolio loaded from a trades csv
+    #[clap(default_value_t = String::from("EUR"), long, value_parser)]
+    trades_csv_currency: String,
+
     /// db cache file
     #[clap(short, long, value_parser)]
     cache_file: String,
 
-    /// output type
+    /// output type, all writes every output type from a single pricing except close-positions
     #[clap(default_value_t = OutputType::Csv, short =  't', long, value_parser)]
     output_type: OutputType,
 
@@ -88,70 +147,710 @@ struct Args {
     #[clap(default_value_t = String::from("now"), short = 'd', long, value_parser)]
     pricing_date: String,
 
-    /// filter output indicator(s)
-    #[clap(short = 'f', long, value_parser = parse_indicators_filter)]
-    indicators_filter: Option<Date>,
+    /// keep output indicator(s) dated on or after now minus this number of days
+    #[clap(short = 'f', long, visible_alias = "indicators-filter")]
+    since_days: Option<u64>,
+
+    /// round monetary values to cents
+    #[clap(long, value_parser)]
+    round_to_cents: bool,
+
+    /// value open positions with intraday quote on today
+    #[clap(long, value_parser)]
+    intraday: bool,
+
+    /// skip invalid cached spots (duplicated date, nan price) instead of failing
+    #[clap(long, value_parser)]
+    lenient_cache: bool,
+
+    /// fail on a position without trade instead of a warning
+    #[clap(long, value_parser)]
+    strict: bool,
+
+    /// retries of a spot request failing on a transient error (network, yahoo)
+    #[clap(default_value_t = 0, long, value_parser)]
+    fetch_retries: u32,
+
+    /// days of history requested at once, a longer period is split in chunks
+    #[clap(default_value_t = DEFAULT_CHUNK_DAYS, long, value_parser)]
+    fetch_chunk_days: u64,
+
+    /// directory of manual prices <instrument>.csv (date;open;close;high;low) over the spot source
+    #[clap(long, value_parser)]
+    manual_prices: Option<String>,
+
+    /// close of an instrument patched on a date, not persisted ex: ESE:2020-03-12:42.0
+    #[clap(long, value_parser)]
+    #[serde(serialize_with = "serialize_display_vec")]
+    price_override: Vec<PriceOverride>,
+
+    /// linear interpolation of spots between two known quotes
+    #[clap(long, value_parser)]
+    interpolate_gaps: bool,
+
+    /// ods with only the heat map sheet, priced at month ends instead of each day
+    #[clap(long, value_parser)]
+    heat_map_only: bool,
+
+    /// carry the last indicator forward day by day up to the pricing date
+    #[clap(long, value_parser)]
+    pad_to_end: bool,
+
+    /// price only the last N days, positions opened before keep their held quantity
+    #[clap(long, value_parser)]
+    max_history_days: Option<u64>,
+
+    /// drop positions closed before the pricing begin date, their trades and
+    /// dividends are kept in cash as a constant realized earning
+    #[clap(long, value_parser)]
+    only_open: bool,
+
+    /// log each spot lookup done by the pricing
+    #[clap(long, value_parser)]
+    trace_spots: bool,
+
+    /// write the spots used by the pricing to one csv by instrument in this dir
+    #[clap(long, value_parser)]
+    dump_prices: Option<String>,
+
+    /// csv field delimiter, a field holding it is quoted
+    #[clap(default_value_t = DEFAULT_DELIMITER, long, value_parser)]
+    csv_delimiter: char,
+
+    /// indicators csv and ods sheet keep the last day of each period
+    #[clap(long, value_parser)]
+    #[serde(serialize_with = "serialize_display_option")]
+    resample: Option<Step>,
+
+    /// output file name with {portfolio}, {date} (pricing date) and {type} placeholders ex: {type}_{portfolio}_{date}
+    #[clap(long, value_parser)]
+    output_name_template: Option<String>,
+
+    /// csv output nested under a directory by portfolio
+    #[clap(long, value_parser)]
+    csv_nested: bool,
+
+    /// reinvest dividends into the position (total return)
+    #[clap(long, value_parser)]
+    reinvest_dividends: bool,
+
+    /// settlement lag in days between trade date and settlement date
+    #[clap(default_value_t = 0, long, value_parser)]
+    settlement_lag_days: u64,
+
+    /// snapshot file of the previous run, the ods summary shows the valuation change since it, then the file is updated
+    #[clap(long, value_parser)]
+    compare_to_previous_snapshot: Option<String>,
+
+    /// export the loaded portfolio to a json file
+    #[clap(long, value_parser)]
+    export_portfolio: Option<String>,
+
+    /// spot price used for valuation
+    #[clap(default_value_t = ValuationPrice::Close, long, value_parser)]
+    #[serde(serialize_with = "serialize_display")]
+    valuation_price: ValuationPrice,
+
+    /// only log errors
+    #[clap(short, long, value_parser)]
+    quiet: bool,
+
+    /// stress test shock(s) on open positions ex: -0.1,-0.05,0.05,0.1
+    #[clap(long, value_parser, value_delimiter = ',', allow_hyphen_values = true)]
+    stress: Vec<f64>,
+
+    /// date(s) of bad market data skipped by metrics ex: 2020-03-12,2021-01-28
+    #[clap(long, value_parser, value_delimiter = ',')]
+    #[serde(serialize_with = "serialize_display_vec")]
+    exclude_dates: Vec<Date>,
+
+    /// keep raw cagr, sharpe and calmar on a history shorter than a year
+    #[clap(long = "no-annualize-under-1y", value_parser)]
+    no_annualize_under_one_year: bool,
+
+    /// value a position without spot at its last trade price
+    #[clap(long, value_parser)]
+    mark_to_last_trade: bool,
+
+    /// daily observations required before reporting a volatility
+    #[clap(default_value_t = MIN_OBSERVATIONS, long, value_parser)]
+    min_observations: usize,
+
+    /// trading days of a year annualizing volatility, sharpe and risk free rate
+    #[clap(default_value_t = TRADING_DAYS_PER_YEAR, long, value_parser)]
+    trading_days_per_year: f64,
+
+    /// benchmark instrument name, adds a rolling beta, correlation, tracking error and
+    /// information ratio of the portfolio against it
+    #[clap(long, value_parser)]
+    benchmark: Option<String>,
+
+    /// trailing window in days of the rolling beta
+    #[clap(default_value_t = DEFAULT_BETA_WINDOW_DAYS, long, value_parser)]
+    rolling_beta_window: u64,
+
+    /// average daily volume below which the instrument risk sheet flags an instrument as illiquid
+    #[clap(long, value_parser)]
+    min_average_volume: Option<f64>,
+
+    /// fetch dividends from yahoo (cached) for instruments without dividends in their json
+    #[clap(long, value_parser)]
+    fetch_dividends: bool,
+
+    /// absolute quantity below which a position is closed
+    #[clap(default_value_t = QUANTITY_EPSILON, long, value_parser)]
+    quantity_epsilon: f64,
+
+    /// pricing of a position without spot at its first day(s)
+    #[clap(default_value_t = FirstSpot::Skip, long, value_parser)]
+    #[serde(serialize_with = "serialize_display")]
+    first_spot: FirstSpot,
+
+    /// denominator of the position weights, the instrument and region distributions stay on the invested valuation
+    #[clap(default_value_t = WeightBase::Invested, long, value_parser)]
+    #[serde(serialize_with = "serialize_display")]
+    weight_base: WeightBase,
+
+    /// buy fees in the unit price and nominal (capitalized) or only in the earning (expensed)
+    #[clap(default_value_t = FeesTreatment::Capitalized, long = "fees", value_parser)]
+    #[serde(serialize_with = "serialize_display")]
+    fees_treatment: FeesTreatment,
+
+    /// annual risk free rate of risk adjusted indicators
+    #[clap(default_value_t = 0.0, long, value_parser)]
+    risk_free_rate: f64,
+
+    /// annual interest rate compounded daily on a negative cash (margin)
+    #[clap(default_value_t = 0.0, long, value_parser)]
+    margin_rate: f64,
+
+    /// annual interest rate compounded daily on a positive cash (deposit)
+    #[clap(default_value_t = 0.0, long, value_parser)]
+    deposit_rate: f64,
+
+    /// csv file of date;annual_rate overriding the constant risk free rate
+    #[clap(long, value_parser)]
+    risk_free_series: Option<String>,
+
+    /// json file with ods currency format (locale, symbol_position, symbols)
+    #[clap(long, value_parser)]
+    ods_locale: Option<String>,
+
+    /// toml file with flag values ex: output_type = "ods", explicit flags override it
+    #[clap(long, value_parser)]
+    config: Option<String>,
+
+    /// json file mapping instrument name to yahoo ticker
+    #[clap(long, value_parser)]
+    ticker_map: Option<String>,
+}
+
+fn make_since_date(args: &Args, clock: &dyn Clock) -> Option<Date> {
+    args.since_days.map(|days| {
+        clock
+            .today()
+            .checked_sub_days(chrono::naive::Days::new(days))
+            .expect("unable to compute since date")
+    })
+}
+
+fn make_pricing_end_date(args: &Args, clock: &dyn Clock) -> Date {
+    if args.pricing_date == "now" {
+        clock.today()
+    } else {
+        chrono::NaiveDate::parse_from_str(&args.pricing_date, "%Y-%m-%d")
+            .expect("invalid pricing date format")
+    }
+}
+
+//
+// config keys are flag names, they are turned into flags placed before the
+// command line ones and skipped when the command line already has them
+fn parse_args_from(cli_args: Vec<String>) -> Result<Args, Error> {
+    let config = cli_args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == "--config" {
+            cli_args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix("--config=").map(String::from)
+        }
+    });
+    let Some(config) = config else {
+        return Ok(Args::parse_from(cli_args));
+    };
+
+    let table: toml::Table = std::fs::read_to_string(&config)?.parse()?;
+    let mut args = vec![cli_args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| String::from("portfolio-rs"))];
+    for (key, value) in table {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let flag_with_value = format!("{flag}=");
+        if cli_args
+            .iter()
+            .any(|arg| *arg == flag || arg.starts_with(&flag_with_value))
+        {
+            continue;
+        }
+        match value {
+            toml::Value::Boolean(true) => args.push(flag),
+            toml::Value::Boolean(false) => {}
+            toml::Value::String(value) => args.push(format!("{flag_with_value}{value}")),
+            toml::Value::Array(values) => args.push(format!(
+                "{flag_with_value}{}",
+                values
+                    .iter()
+                    .map(|value| match value {
+                        toml::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )),
+            value => args.push(format!("{flag_with_value}{value}")),
+        }
+    }
+    args.extend(cli_args.into_iter().skip(1));
+    Ok(Args::parse_from(args))
 }
 
-fn parse_indicators_filter(arg: &str) -> Result<Date, clap::Error> {
-    let days = chrono::naive::Days::new(
-        arg.parse()
-            .expect("unable to parse to int indicators filter"),
-    );
-    let previous_date = chrono::Utc::now()
-        .date_naive()
-        .checked_sub_days(days)
-        .expect("unable to compute indicators filter");
-    Ok(previous_date)
+fn make_provider_builder(args: &Args, clock: &dyn Clock) -> Result<ProviderBuilder, Error> {
+    let source = match args.spot_source {
+        SpotSource::Null => ProviderSource::Null,
+        SpotSource::Yahoo => ProviderSource::Yahoo,
+    };
+    Ok(
+        ProviderBuilder::new(source, ProviderPersistence::Sqlite(args.cache_file.clone()))?
+            .with_lenient_cache(args.lenient_cache)
+            .with_chunk_days(args.fetch_chunk_days)
+            .with_retries(args.fetch_retries)
+            .with_price_overrides(args.price_override.clone())
+            .with_intraday(args.intraday.then(|| clock.today())),
+    )
 }
 
-fn make_requester(source: SpotSource) -> Result<Box<dyn Requester>, Error> {
-    let value: Box<dyn Requester> = match source {
-        SpotSource::Null => Box::new(NullRequester),
-        SpotSource::Yahoo => Box::new(YahooRequester),
+fn make_pricing_options(args: &Args) -> Result<PricingOptions, Error> {
+    let risk_free = match &args.risk_free_series {
+        Some(filename) => RiskFreeRate::from_csv_file(filename)?,
+        None => RiskFreeRate::Constant(args.risk_free_rate),
     };
-    Ok(value)
+    Ok(PricingOptions {
+        round_to_cents: args.round_to_cents,
+        reinvest_dividends: args.reinvest_dividends,
+        settlement_lag_days: args.settlement_lag_days,
+        valuation_price: args.valuation_price,
+        quantity_epsilon: args.quantity_epsilon,
+        first_spot: args.first_spot,
+        weight_base: args.weight_base,
+        fees_treatment: args.fees_treatment,
+        risk_free,
+        excluded_dates: args.exclude_dates.clone(),
+        no_annualize_under_one_year: args.no_annualize_under_one_year,
+        min_observations: args.min_observations,
+        mark_to_last_trade: args.mark_to_last_trade,
+        margin_rate: args.margin_rate,
+        deposit_rate: args.deposit_rate,
+        strict: args.strict,
+        trading_days_per_year: args.trading_days_per_year,
+    })
 }
 
 fn make_portfolio_indicators(
     args: &Args,
     portfolio: &Portfolio,
+    clock: &dyn Clock,
 ) -> Result<PortfolioIndicators, Error> {
     //
     // get pricing date
-    let pricing_end_date = if args.pricing_date == "now" {
-        chrono::Utc::now().date_naive()
-    } else {
-        chrono::NaiveDate::parse_from_str(&args.pricing_date, "%Y-%m-%d")
-            .expect("invalid pricing date format")
-    };
+    let pricing_end_date = make_pricing_end_date(args, clock);
 
     //
-    // persistence
-    let persistence = SQLitePersistance::new(&args.cache_file)?;
+    // historical data with its persistence
+    let provider_builder = make_provider_builder(args, clock)?;
+    let provider = provider_builder.build();
 
-    //
-    // historical data
-    let requester = make_requester(args.spot_source)?;
-    let mut provider = HistoricalData::new(requester, &persistence);
+    let mut options = make_pricing_options(args)?;
+    if args.fetch_dividends {
+        options.fetched_dividends =
+            make_fetched_dividends(portfolio, pricing_end_date, &provider_builder)?;
+    }
 
     //
     // compute main portfolio
-    let pricing_begin_date = portfolio.get_trade_date()?;
-    let portfolio_indicators = PortfolioIndicators::from_portfolio(
+    let mut portfolio_indicators = match &args.manual_prices {
+        Some(directory) => {
+            let provider = OverlayProvider::new(provider, CsvProvider::new(directory));
+            interpolate_and_price_portfolio(args, portfolio, pricing_end_date, provider, &options)
+        }
+        None => {
+            interpolate_and_price_portfolio(args, portfolio, pricing_end_date, provider, &options)
+        }
+    }?;
+    if args.pad_to_end {
+        portfolio_indicators.pad_to_end();
+    }
+    info!("compute portfolio done");
+    Ok(portfolio_indicators)
+}
+
+//
+// closed positions priced without the daily portfolio indicators
+fn make_close_positions(
+    args: &Args,
+    portfolio: &Portfolio,
+    clock: &dyn Clock,
+) -> Result<Vec<ClosePositionIndicator>, Error> {
+    let pricing_end_date = make_pricing_end_date(args, clock);
+    let provider_builder = make_provider_builder(args, clock)?;
+    let mut provider = provider_builder.build();
+
+    let mut options = make_pricing_options(args)?;
+    if args.fetch_dividends {
+        options.fetched_dividends =
+            make_fetched_dividends(portfolio, pricing_end_date, &provider_builder)?;
+    }
+
+    let close_positions = match &args.manual_prices {
+        Some(directory) => PortfolioIndicators::closed_positions_only(
+            portfolio,
+            pricing_end_date,
+            &mut OverlayProvider::new(provider, CsvProvider::new(directory)),
+            &options,
+        ),
+        None => PortfolioIndicators::closed_positions_only(
+            portfolio,
+            pricing_end_date,
+            &mut provider,
+            &options,
+        ),
+    }?;
+    info!("compute {} close position(s) done", close_positions.len());
+    Ok(close_positions)
+}
+
+//
+// instruments with dividends in their json (or bond coupons) are not requested,
+// dividends are fetched since inception as they are cumulated from the first trade
+fn make_fetched_dividends(
+    portfolio: &Portfolio,
+    pricing_end_date: Date,
+    provider_builder: &ProviderBuilder,
+) -> Result<BTreeMap<String, Vec<Dividend>>, Error> {
+    let inception_date = portfolio.get_inception_date()?;
+    let fetcher = provider_builder.build_dividend_fetcher();
+    let mut fetched_dividends = BTreeMap::new();
+    for position in &portfolio.positions {
+        let instrument = &position.instrument;
+        if instrument.dividends.is_some() || fetched_dividends.contains_key(&instrument.name) {
+            continue;
+        }
+        let dividends = fetcher.fetch(instrument, inception_date, pricing_end_date)?;
+        info!(
+            "fetch {} dividend(s) of {} done",
+            dividends.len(),
+            instrument.name
+        );
+        fetched_dividends.insert(instrument.name.clone(), dividends);
+    }
+    Ok(fetched_dividends)
+}
+
+//
+// rolling beta and active return against the --benchmark instrument
+fn make_benchmark_indicators(
+    args: &Args,
+    referential: &mut Referential,
+    indicators: &PortfolioIndicators,
+    clock: &dyn Clock,
+) -> Result<(Option<Vec<RollingBeta>>, Option<ActiveReturn>), Error> {
+    let Some(benchmark) = &args.benchmark else {
+        return Ok((None, None));
+    };
+    let instrument = referential.get_instrument_by_name(benchmark)?;
+    let provider_builder = make_provider_builder(args, clock)?;
+    let mut provider = provider_builder.build();
+    provider.fetch(&instrument, indicators.begin, indicators.end)?;
+
+    //
+    // only quotes of the priced dates, a stale spot is not a benchmark return
+    let spots = indicators
+        .portfolios
+        .iter()
+        .filter_map(|indicator| {
+            provider
+                .latest(&instrument, indicator.date)
+                .filter(|spot| spot.date == indicator.date)
+                .copied()
+        })
+        .collect::<Vec<_>>();
+    info!("rolling beta and active return against {} done", benchmark);
+    Ok((
+        Some(indicators.rolling_beta(&spots, args.rolling_beta_window)),
+        indicators.active_return(&spots),
+    ))
+}
+
+fn interpolate_and_price_portfolio<P>(
+    args: &Args,
+    portfolio: &Portfolio,
+    pricing_end_date: Date,
+    provider: P,
+    options: &PricingOptions,
+) -> Result<PortfolioIndicators, Error>
+where
+    P: Provider,
+{
+    if args.interpolate_gaps {
+        let provider = InterpolatingProvider::new(provider);
+        trace_and_price_portfolio(args, portfolio, pricing_end_date, provider, options)
+    } else {
+        trace_and_price_portfolio(args, portfolio, pricing_end_date, provider, options)
+    }
+}
+
+fn trace_and_price_portfolio<P>(
+    args: &Args,
+    portfolio: &Portfolio,
+    pricing_end_date: Date,
+    mut provider: P,
+    options: &PricingOptions,
+) -> Result<PortfolioIndicators, Error>
+where
+    P: Provider,
+{
+    if args.trace_spots || args.dump_prices.is_some() {
+        let mut provider = TracingProvider::new(provider);
+        let portfolio_indicators =
+            price_portfolio(args, portfolio, pricing_end_date, &mut provider, options)?;
+        if let Some(output_dir) = &args.dump_prices {
+            provider.dump_prices(output_dir)?;
+            info!("dump prices to {} done", output_dir);
+        }
+        Ok(portfolio_indicators)
+    } else {
+        price_portfolio(args, portfolio, pricing_end_date, &mut provider, options)
+    }
+}
+
+fn make_pricing_begin_date(
+    args: &Args,
+    portfolio: &Portfolio,
+    pricing_end_date: Date,
+) -> Result<Date, Error> {
+    let inception_date = portfolio.get_inception_date()?;
+    Ok(args
+        .max_history_days
+        .and_then(|days| pricing_end_date.checked_sub_days(chrono::naive::Days::new(days)))
+        .map_or(inception_date, |date| date.max(inception_date)))
+}
+
+fn price_portfolio<P>(
+    args: &Args,
+    portfolio: &Portfolio,
+    pricing_end_date: Date,
+    provider: &mut P,
+    options: &PricingOptions,
+) -> Result<PortfolioIndicators, Error>
+where
+    P: Provider,
+{
+    let pricing_begin_date = make_pricing_begin_date(args, portfolio, pricing_end_date)?;
+    if args.heat_map_only {
+        return PortfolioIndicators::from_portfolio_at_period_ends(
+            portfolio,
+            pricing_begin_date,
+            pricing_end_date,
+            provider,
+            options,
+            HeatMapPeriod::Monthly,
+        );
+    }
+    let show_progress = !args.quiet && std::io::stderr().is_terminal();
+    PortfolioIndicators::from_portfolio_with_progress(
         portfolio,
         pricing_begin_date,
         pricing_end_date,
-        &mut provider,
-    )?;
-    info!("compute portfolio done");
-    Ok(portfolio_indicators)
+        provider,
+        options,
+        |done, total| {
+            if show_progress {
+                eprint!("\rfetching {}/{} positions", done, total);
+                if done == total {
+                    eprintln!();
+                }
+            }
+        },
+    )
+}
+
+fn run(args: &Args, clock: &dyn Clock) -> Result<(), Error> {
+    //
+    // check output before any pricing
+    check_output_dir(&args.output_dir)?;
+
+    //
+    // Load portfolio
+    let mut referential = Referential::new(&args.marketdata_dir);
+    if let Some(filename) = &args.ticker_map {
+        referential.load_ticker_map(filename)?;
+    }
+    let mut portfolio = if args.portfolio.ends_with(".csv") {
+        referential.load_trades_csv(&args.portfolio, &args.trades_csv_currency)?
+    } else {
+        referential.preload(&args.portfolio)?;
+        referential.load_portfolio(&args.portfolio)?
+    };
+    referential.check_ticker_map();
+    info!("loading portfolio {} done", portfolio.name);
+
+    if let Some(filename) = &args.export_portfolio {
+        referential.save_portfolio(&portfolio, filename)?;
+        info!("export portfolio to {} done", filename);
+    }
+
+    if args.only_open {
+        let pricing_end_date = make_pricing_end_date(args, clock);
+        let pricing_begin_date = make_pricing_begin_date(args, &portfolio, pricing_end_date)?;
+        let nb_closed = portfolio.retain_open_positions(
+            pricing_begin_date,
+            pricing_end_date,
+            args.quantity_epsilon,
+        );
+        info!(
+            "keep {} open position(s), the cash of {} closed one(s) is realized",
+            portfolio.positions.len(),
+            nb_closed
+        );
+        if nb_closed > 0 && args.fetch_dividends {
+            warn!("fetched dividends of the closed position(s) are not in the realized cash");
+        }
+    }
+
+    //
+    // write output
+    let since = make_since_date(args, clock);
+    let output_types = args.output_type.expand();
+    if args.heat_map_only && output_types != [OutputType::Ods] {
+        return Err(Error::new_output(
+            "heat map only requires the ods output type",
+        ));
+    }
+    let ods_locale = match &args.ods_locale {
+        Some(filename) if output_types.contains(&OutputType::Ods) => {
+            OdsLocale::from_file(filename)?
+        }
+        _ => Default::default(),
+    };
+    let portfolio_indicators = if output_types.iter().any(OutputType::is_priced) {
+        Some(make_portfolio_indicators(args, &portfolio, clock)?)
+    } else {
+        None
+    };
+    let close_positions = if output_types.contains(&OutputType::ClosePositions) {
+        make_close_positions(args, &portfolio, clock)?
+    } else {
+        Vec::new()
+    };
+    let name_template = args
+        .output_name_template
+        .as_ref()
+        .map(|template| NameTemplate::new(template, make_pricing_end_date(args, clock)));
+    let (rolling_beta, active_return) = match &portfolio_indicators {
+        Some(portfolio_indicators) => {
+            make_benchmark_indicators(args, &mut referential, portfolio_indicators, clock)?
+        }
+        None => (None, None),
+    };
+    let previous_snapshot = match &args.compare_to_previous_snapshot {
+        Some(filename) => Snapshot::from_file(filename)?,
+        None => None,
+    };
+    for output_type in output_types {
+        match (output_type, &portfolio_indicators) {
+            (OutputType::Csv, Some(portfolio_indicators)) => {
+                let mut output = CsvOutput::new(
+                    &args.output_dir,
+                    &portfolio,
+                    portfolio_indicators,
+                    &since,
+                    args.csv_nested,
+                    &args.stress,
+                )
+                .with_delimiter(args.csv_delimiter)
+                .with_resample(args.resample)
+                .with_name_template(name_template.clone())
+                .with_rolling_beta(rolling_beta.as_deref());
+                output.write()?;
+            }
+            (OutputType::Ods, Some(portfolio_indicators)) => {
+                let mut output = OdsOutput::new(
+                    &args.output_dir,
+                    &portfolio,
+                    portfolio_indicators,
+                    &since,
+                    &args.stress,
+                    &ods_locale,
+                )?
+                .with_previous_snapshot(previous_snapshot.as_ref())
+                .with_resample(args.resample)
+                .with_name_template(name_template.clone())
+                .with_heat_map_only(args.heat_map_only)
+                .with_rolling_beta(rolling_beta.as_deref())
+                .with_active_return(active_return.as_ref())
+                .with_min_average_volume(args.min_average_volume);
+                output.write()?;
+            }
+            (OutputType::PortfolioPerformance, _) => {
+                let mut output = PortfolioPerformanceOutput::new(
+                    &args.output_dir,
+                    &portfolio,
+                    make_pricing_end_date(args, clock),
+                )
+                .with_delimiter(args.csv_delimiter)
+                .with_name_template(name_template.clone());
+                output.write()?;
+            }
+            (OutputType::ClosePositions, _) => {
+                let mut output =
+                    ClosePositionOutput::new(&args.output_dir, &portfolio, &close_positions)
+                        .with_delimiter(args.csv_delimiter)
+                        .with_name_template(name_template.clone());
+                output.write()?;
+            }
+            (output_type, _) => {
+                return Err(Error::new_output(format!(
+                    "unable to write {output_type} output without pricing"
+                )))
+            }
+        };
+        info!("write {} output done", output_type);
+    }
+
+    if let Some(portfolio_indicators) = &portfolio_indicators {
+        info!("{}", portfolio_indicators.headline());
+    }
+
+    if let (Some(filename), Some(snapshot)) = (
+        &args.compare_to_previous_snapshot,
+        portfolio_indicators
+            .as_ref()
+            .and_then(Snapshot::from_indicators),
+    ) {
+        snapshot.save(filename)?;
+        info!("save snapshot to {} done", filename);
+    }
+
+    RunManifest::new(args, &portfolio, make_pricing_end_date(args, clock))
+        .save(&args.output_dir)?;
+    info!("write run manifest done");
+
+    Ok(())
 }
 
 fn main() -> Result<(), Error> {
     //
     // cli arg
-    let args = Args::parse();
+    let args = parse_args_from(std::env::args().collect())?;
 
     //
     // logger
@@ -165,45 +864,178 @@ fn main() -> Result<(), Error> {
                 record.args()
             )
         })
-        .filter_level(LevelFilter::Info)
+        .filter_level(if args.quiet {
+            LevelFilter::Error
+        } else {
+            LevelFilter::Info
+        })
         .parse_default_env()
         .init();
 
-    //
-    // Load portfolio
-    let mut referential = Referential::new(&args.marketdata_dir);
-    let portfolio = referential.load_portfolio(&args.portfolio)?;
-    info!("loading portfolio {} done", portfolio.name);
+    run(&args, &UtcClock)
+}
 
-    //
-    // write output
-    match args.output_type {
-        OutputType::Csv => {
-            let portfolio_indicators = make_portfolio_indicators(&args, &portfolio)?;
-            let mut output = CsvOutput::new(
-                &args.output_dir,
-                &portfolio,
-                &portfolio_indicators,
-                &args.indicators_filter,
-            );
-            output.write()?;
-        }
-        OutputType::Ods => {
-            let portfolio_indicators = make_portfolio_indicators(&args, &portfolio)?;
-            let mut output = OdsOutput::new(
-                &args.output_dir,
-                &portfolio,
-                &portfolio_indicators,
-                &args.indicators_filter,
-            )?;
-            output.write()?;
-        }
-        OutputType::PortfolioPerformance => {
-            let mut output = PortfolioPerformanceOutput::new(&args.output_dir, &portfolio);
-            output.write()?;
-        }
-    };
-    info!("write output done");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use portfolio_rs::clock::FixedClock;
 
-    Ok(())
+    #[test]
+    fn missing_output_dir() {
+        let cache_file = std::env::temp_dir().join("portfolio_rs_missing_output_dir.db");
+        let _ = std::fs::remove_file(&cache_file);
+
+        let args = Args::parse_from([
+            "portfolio-rs",
+            "--marketdata-dir",
+            "data",
+            "--portfolio",
+            "portfolio.json",
+            "--cache-file",
+            cache_file.to_str().unwrap(),
+            "--output-dir",
+            "/nonexistent/portfolio_rs_output",
+            "--spot-source",
+            "null",
+        ]);
+
+        let result = run(&args, &UtcClock);
+        assert!(matches!(result, Err(Error::Output(_))), "{:?}", result);
+        assert!(!cache_file.exists());
+    }
+
+    #[test]
+    fn config_file_overridden_by_flags() {
+        let config = std::env::temp_dir().join("portfolio_rs_config.toml");
+        std::fs::write(
+            &config,
+            r#"
+marketdata_dir = "data"
+portfolio = "portfolio.json"
+cache_file = "cache.db"
+output_dir = "output"
+output_type = "ods"
+round_to_cents = true
+settlement_lag_days = 2
+stress = [-0.1, 0.1]
+"#,
+        )
+        .unwrap();
+        let config = config.to_str().unwrap().to_string();
+        let make_cli = |flags: &[&str]| {
+            ["portfolio-rs", "--config", &config]
+                .iter()
+                .chain(flags.iter())
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let args = parse_args_from(make_cli(&[])).unwrap();
+        assert_eq!(args.output_type, OutputType::Ods);
+        assert_eq!(args.marketdata_dir, "data");
+        assert!(args.round_to_cents);
+        assert_eq!(args.settlement_lag_days, 2);
+        assert_eq!(args.stress, vec![-0.1, 0.1]);
+
+        let args = parse_args_from(make_cli(&["--output-type", "csv"])).unwrap();
+        assert_eq!(args.output_type, OutputType::Csv);
+        assert_eq!(args.portfolio, "portfolio.json");
+
+        let args = parse_args_from(make_cli(&["-t", "csv", "--stress=0.2"])).unwrap();
+        assert_eq!(args.output_type, OutputType::Csv);
+        assert_eq!(args.stress, vec![0.2]);
+
+        std::fs::remove_file(&config).unwrap();
+    }
+
+    #[test]
+    fn since_days_with_fixed_clock() {
+        let clock = FixedClock(chrono::NaiveDate::from_ymd_opt(2024, 3, 15).unwrap());
+        let args = Args::parse_from([
+            "portfolio-rs",
+            "--marketdata-dir",
+            "data",
+            "--portfolio",
+            "portfolio.json",
+            "--cache-file",
+            "cache.db",
+            "--output-dir",
+            "output",
+            "--since-days",
+            "30",
+        ]);
+        assert_eq!(
+            make_since_date(&args, &clock),
+            chrono::NaiveDate::from_ymd_opt(2024, 2, 14)
+        );
+        assert_eq!(make_pricing_end_date(&args, &clock), clock.today());
+
+        let args = Args::parse_from([
+            "portfolio-rs",
+            "--marketdata-dir",
+            "data",
+            "--portfolio",
+            "portfolio.json",
+            "--cache-file",
+            "cache.db",
+            "--output-dir",
+            "output",
+        ]);
+        assert_eq!(make_since_date(&args, &clock), None);
+    }
+
+    #[test]
+    fn run_manifest_of_sample_run() {
+        let output_dir = std::env::temp_dir().join("portfolio_rs_run_manifest");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let portfolio = output_dir.join("portfolio.json");
+        std::fs::write(
+            &portfolio,
+            r#"{
+                "name": "PEA",
+                "currency": "EUR",
+                "positions": [
+                    {"instrument": "ESE", "trades": [
+                        {"date": "2024-01-02", "way": "buy", "quantity": 10.0, "price": 20.0, "fees": 1.0}
+                    ]}
+                ],
+                "cash": []
+            }"#,
+        )
+        .unwrap();
+
+        let args = Args::parse_from([
+            "portfolio-rs",
+            "--marketdata-dir",
+            "data",
+            "--portfolio",
+            portfolio.to_str().unwrap(),
+            "--cache-file",
+            output_dir.join("cache.db").to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--output-type",
+            "portfolio-performance",
+            "--spot-source",
+            "null",
+            "--pricing-date",
+            "2024-03-15",
+        ]);
+        run(&args, &UtcClock).unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(output_dir.join(portfolio_rs::output::RUN_MANIFEST_FILENAME))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["end_date"], "2024-03-15");
+        assert_eq!(manifest["begin_date"], "2024-01-02");
+        assert_eq!(manifest["args"]["output_type"], "portfolio-performance");
+        assert_eq!(manifest["args"]["spot_source"], "null");
+        assert_eq!(manifest["instruments"], serde_json::json!(["ESE"]));
+        assert_eq!(manifest["version"], env!("CARGO_PKG_VERSION"));
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
 }
@@ -1,4 +1,4 @@
-use crate::alias::DateTime;
+use crate::alias::{Date, DateTime};
 use crate::marketdata::{Currency, Market};
 use std::rc::Rc;
 
@@ -6,6 +6,9 @@ use std::rc::Rc;
 pub struct Instrument {
     pub name: String,
     pub isin: String,
+    // isin(s) of the instrument before a merger or a renaming,
+    // the current one stays isin
+    pub previous_isins: Vec<String>,
     pub description: String,
     pub market: Rc<Market>,
     pub currency: Rc<Currency>,
@@ -13,15 +16,108 @@ pub struct Instrument {
     pub region: Option<String>,
     pub fund_category: String,
     pub dividends: Option<Vec<Dividend>>,
+    // factor applied on the quoted spot to get a price in the instrument
+    // currency (ex: 0.01 for a london stock quoted in pence with a GBP currency),
+    // fx conversion to the portfolio currency is done after on this price
+    pub quote_factor: f64,
+    // annual expense ratio (ex: 0.003 for 0.3%) charged pro-rata on the held valuation
+    pub ter: Option<f64>,
+    // market data is fetched from this date when before the first trade,
+    // pricing still starts at the first trade
+    pub history_start: Option<Date>,
+    // user defined bucket(s) ex: core, satellite, an instrument may have several
+    pub tags: Vec<String>,
+    pub instrument_type: InstrumentType,
+}
+
+#[derive(Debug, Default)]
+pub enum InstrumentType {
+    #[default]
+    Equity,
+    Bond(Bond),
 }
 
 #[derive(Debug)]
+pub struct Bond {
+    pub coupon_rate: f64,
+    pub coupon_frequency: u32,
+    pub face_value: f64,
+    pub issue_date: Date,
+    pub maturity: Date,
+}
+
+impl Bond {
+    //
+    // coupon dates are rolled back from maturity until the issue date
+    pub fn coupons(&self) -> Vec<Dividend> {
+        let frequency = self.coupon_frequency.max(1);
+        let value = self.face_value * self.coupon_rate / frequency as f64;
+        let step = 12 / frequency;
+
+        let mut coupons = Vec::new();
+        let mut shift = 0;
+        while let Some(date) = self
+            .maturity
+            .checked_sub_months(chrono::Months::new(shift * step))
+            .filter(|date| *date > self.issue_date)
+        {
+            let date_time = date.and_hms_opt(0, 0, 0).unwrap();
+            coupons.push(Dividend {
+                record_date: date_time,
+                payment_date: date_time,
+                value,
+            });
+            shift += 1;
+        }
+        coupons.reverse();
+        coupons
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Dividend {
     pub record_date: DateTime,
     pub payment_date: DateTime,
     pub value: f64,
 }
 
+impl Instrument {
+    //
+    // rate of one unit of currency in home_currency (ex: USDEUR=X), its spot
+    // is fetched and cached like any other instrument
+    pub fn new_fx(currency: &Rc<Currency>, home_currency: &Rc<Currency>) -> Self {
+        let name = format!("{}{}", currency.name, home_currency.name);
+        Self {
+            ticker_yahoo: Some(format!("{}=X", name)),
+            isin: name.clone(),
+            previous_isins: Default::default(),
+            description: format!("{} in {}", currency.name, home_currency.name),
+            market: Rc::new(Market {
+                name: String::from("FX"),
+                description: String::from("foreign exchange"),
+            }),
+            currency: home_currency.clone(),
+            region: None,
+            fund_category: String::from("fx"),
+            dividends: None,
+            quote_factor: 1.0,
+            ter: None,
+            history_start: None,
+            tags: Default::default(),
+            instrument_type: Default::default(),
+            name,
+        }
+    }
+
+    pub fn isins(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.isin.as_str()).chain(self.previous_isins.iter().map(String::as_str))
+    }
+
+    pub fn has_isin(&self, isin: &str) -> bool {
+        self.isins().any(|item| item == isin)
+    }
+}
+
 impl std::hash::Hash for Instrument {
     fn hash<H>(&self, state: &mut H)
     where
@@ -37,3 +133,32 @@ impl std::cmp::PartialEq for Instrument {
     }
 }
 impl std::cmp::Eq for Instrument {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn bond_coupons() {
+        let bond = Bond {
+            coupon_rate: 0.04,
+            coupon_frequency: 2,
+            face_value: 100.0,
+            issue_date: Date::from_ymd_opt(2020, 6, 15).unwrap(),
+            maturity: Date::from_ymd_opt(2022, 6, 15).unwrap(),
+        };
+        let coupons = bond.coupons();
+        assert_eq!(coupons.len(), 4);
+        for (coupon, date) in coupons.iter().zip([
+            Date::from_ymd_opt(2020, 12, 15).unwrap(),
+            Date::from_ymd_opt(2021, 6, 15).unwrap(),
+            Date::from_ymd_opt(2021, 12, 15).unwrap(),
+            Date::from_ymd_opt(2022, 6, 15).unwrap(),
+        ]) {
+            assert_eq!(coupon.payment_date.date(), date);
+            assert_eq!(coupon.record_date.date(), date);
+            assert_float_absolute_eq!(coupon.value, 2.0, 1e-7);
+        }
+    }
+}
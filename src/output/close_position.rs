@@ -0,0 +1,61 @@
+use super::csv::write_close_positions;
+use super::csv_writer::DEFAULT_DELIMITER;
+use super::{NameTemplate, Output};
+use crate::error::Error;
+use crate::portfolio::Portfolio;
+use crate::pricer::ClosePositionIndicator;
+
+//
+// close position csv of the closed positions only pricing, without the
+// daily indicators of the csv output
+pub struct ClosePositionOutput<'a> {
+    output_dir: String,
+    portfolio: &'a Portfolio,
+    close_positions: &'a [ClosePositionIndicator],
+    delimiter: char,
+    name_template: Option<NameTemplate>,
+}
+
+impl<'a> ClosePositionOutput<'a> {
+    pub fn new(
+        output_dir: &str,
+        portfolio: &'a Portfolio,
+        close_positions: &'a [ClosePositionIndicator],
+    ) -> Self {
+        Self {
+            output_dir: output_dir.to_string(),
+            portfolio,
+            close_positions,
+            delimiter: DEFAULT_DELIMITER,
+            name_template: None,
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_name_template(mut self, name_template: Option<NameTemplate>) -> Self {
+        self.name_template = name_template;
+        self
+    }
+
+    fn make_filename_(&self, kind: &str) -> String {
+        let name = match &self.name_template {
+            Some(name_template) => name_template.render(&self.portfolio.name, kind),
+            None => format!("{}_{}", kind, self.portfolio.name),
+        };
+        format!("{}/{}.csv", self.output_dir, name)
+    }
+}
+
+impl Output for ClosePositionOutput<'_> {
+    fn write(&mut self) -> Result<(), Error> {
+        write_close_positions(
+            &self.make_filename_("close_position"),
+            self.close_positions,
+            self.delimiter,
+        )
+    }
+}
@@ -1,23 +1,257 @@
 use chrono::Datelike;
 
-use super::Output;
+use super::csv_writer::{CsvWriter, DEFAULT_DELIMITER};
+use super::{
+    is_since, resampled, NameTemplate, Output, HISTOGRAM_BUCKET_WIDTH, RETURN_QUANTILES,
+    TOP_MOVERS, WASH_SALE_WINDOW_DAYS,
+};
 use crate::alias::Date;
 use crate::error::Error;
 use crate::portfolio::Portfolio;
 use crate::pricer::{
-    HeatMap, HeatMapPeriod, InstrumentIndicator, PortfolioIndicators, PositionIndicators,
-    RegionIndicator, RegionIndicatorInstrument,
+    CashLedger, ClosePositionIndicator, Event, HeatMap, HeatMapPeriod, InstrumentIndicator,
+    InstrumentRiskIndicator, Mover, PortfolioIndicators, PositionIndicators, RealizedGain,
+    RegionIndicator, RegionIndicatorInstrument, RollingBeta, SeasonalityBucket,
+    SeasonalityIndicator, Step, WashSale,
 };
 
+use serde::Serialize;
 use std::collections::BTreeMap;
+use std::fmt::Display;
 use std::fs::File;
-use std::io::Write;
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ColumnType {
+    Date,
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+#[derive(Serialize)]
+struct Column {
+    name: &'static str,
+    #[serde(rename = "type")]
+    kind: ColumnType,
+}
+
+const fn column_(name: &'static str, kind: ColumnType) -> Column {
+    Column { name, kind }
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    separator: char,
+    header: bool,
+    columns: &'a [Column],
+}
+
+const DISTRIBUTION_COLUMNS: &[Column] = &[
+    column_("Name", ColumnType::String),
+    column_("Valuation(%)", ColumnType::Number),
+];
+const HEAT_MAP_MONTHLY_COLUMNS: &[Column] = &[
+    column_("Year", ColumnType::Integer),
+    column_("Jan", ColumnType::Number),
+    column_("Feb", ColumnType::Number),
+    column_("Mar", ColumnType::Number),
+    column_("Apr", ColumnType::Number),
+    column_("May", ColumnType::Number),
+    column_("Jun", ColumnType::Number),
+    column_("Jul", ColumnType::Number),
+    column_("Aug", ColumnType::Number),
+    column_("Sep", ColumnType::Number),
+    column_("Oct", ColumnType::Number),
+    column_("Nov", ColumnType::Number),
+    column_("Dec", ColumnType::Number),
+];
+const HEAT_MAP_LONG_COLUMNS: &[Column] = &[
+    column_("Year", ColumnType::Integer),
+    column_("Period", ColumnType::Integer),
+    column_("Value", ColumnType::Number),
+];
+const HEAT_MAP_YEARLY_COLUMNS: &[Column] = &[
+    column_("Year", ColumnType::Integer),
+    column_("Value", ColumnType::Number),
+];
+const RETURN_HISTOGRAM_COLUMNS: &[Column] = &[
+    column_("Bucket(%)", ColumnType::Number),
+    column_("Count", ColumnType::Integer),
+];
+const RETURN_QUANTILES_COLUMNS: &[Column] = &[
+    column_("Quantile(%)", ColumnType::Number),
+    column_("Return(%)", ColumnType::Number),
+];
+const STRESS_TEST_COLUMNS: &[Column] = &[
+    column_("Shock(%)", ColumnType::Number),
+    column_("Valuation", ColumnType::Number),
+];
+const CLOSE_POSITIONS_COLUMNS: &[Column] = &[
+    column_("Instrument", ColumnType::String),
+    column_("Position", ColumnType::Integer),
+    column_("Open Date", ColumnType::Date),
+    column_("Close Date", ColumnType::Date),
+    column_("Quantity", ColumnType::Number),
+    column_("Nominal", ColumnType::Number),
+    column_("Fees", ColumnType::Number),
+    column_("Dividends", ColumnType::Number),
+    column_("P&L", ColumnType::Number),
+    column_("P&L(%)", ColumnType::Number),
+    column_("Holding Days", ColumnType::Number),
+    column_("P&L Annualized(%)", ColumnType::Number),
+    column_("Close Reason", ColumnType::String),
+];
+const REALIZED_GAINS_COLUMNS: &[Column] = &[
+    column_("Instrument", ColumnType::String),
+    column_("ISIN", ColumnType::String),
+    column_("Position", ColumnType::Integer),
+    column_("Open Date", ColumnType::Date),
+    column_("Close Date", ColumnType::Date),
+    column_("Quantity", ColumnType::Number),
+    column_("Proceeds", ColumnType::Number),
+    column_("Cost", ColumnType::Number),
+    column_("Realized Gain", ColumnType::Number),
+    column_("Holding Days", ColumnType::Integer),
+];
+const CASH_LEDGER_COLUMNS: &[Column] = &[
+    column_("Date", ColumnType::Date),
+    column_("Kind", ColumnType::String),
+    column_("Instrument", ColumnType::String),
+    column_("Amount", ColumnType::Number),
+    column_("Balance", ColumnType::Number),
+];
+const EVENTS_COLUMNS: &[Column] = &[
+    column_("Date", ColumnType::Date),
+    column_("Instrument", ColumnType::String),
+    column_("Event", ColumnType::String),
+];
+const MOVERS_COLUMNS: &[Column] = &[
+    column_("Kind", ColumnType::String),
+    column_("Instrument", ColumnType::String),
+    column_("Position", ColumnType::Integer),
+    column_("Contribution", ColumnType::Number),
+    column_("Change", ColumnType::Number),
+];
+const SEASONALITY_WEEKDAY_COLUMNS: &[Column] = &[
+    column_("Weekday", ColumnType::String),
+    column_("Average Return(%)", ColumnType::Number),
+    column_("Count", ColumnType::Integer),
+];
+const SEASONALITY_MONTH_COLUMNS: &[Column] = &[
+    column_("Month", ColumnType::Integer),
+    column_("Average Return(%)", ColumnType::Number),
+    column_("Count", ColumnType::Integer),
+];
+const WASH_SALE_COLUMNS: &[Column] = &[
+    column_("Instrument", ColumnType::String),
+    column_("Position", ColumnType::Integer),
+    column_("Sell Date", ColumnType::Date),
+    column_("Sell Quantity", ColumnType::Number),
+    column_("Loss", ColumnType::Number),
+    column_("Buy Date", ColumnType::Date),
+    column_("Buy Quantity", ColumnType::Number),
+];
+const INSTRUMENT_RISK_COLUMNS: &[Column] = &[
+    column_("Instrument", ColumnType::String),
+    column_("Volatility", ColumnType::Number),
+    column_("Max Drawdown", ColumnType::Number),
+    column_("Average Volume", ColumnType::Number),
+];
+const ROLLING_BETA_COLUMNS: &[Column] = &[
+    column_("Date", ColumnType::Date),
+    column_("Beta", ColumnType::Number),
+    column_("Correlation", ColumnType::Number),
+];
+const INDICATORS_COLUMNS: &[Column] = &[
+    column_("Date", ColumnType::Date),
+    column_("Valuation", ColumnType::Number),
+    column_("Nominal", ColumnType::Number),
+    column_("Incoming Transfert", ColumnType::Number),
+    column_("Outcoming Transfert", ColumnType::Number),
+    column_("Securities Transfert", ColumnType::Number),
+    column_("Cash", ColumnType::Number),
+    column_("Invested Capital", ColumnType::Number),
+    column_("Dividends", ColumnType::Number),
+    column_("Fees", ColumnType::Number),
+    column_("P&L", ColumnType::Number),
+    column_("P&L(%)", ColumnType::Number),
+    column_("TWR", ColumnType::Number),
+    column_("Earning", ColumnType::Number),
+    column_("Earning Latent", ColumnType::Number),
+];
+const POSITION_INDICATORS_COLUMNS: &[Column] = &[
+    column_("Date", ColumnType::Date),
+    column_("Instrument", ColumnType::String),
+    column_("Spot(Close)", ColumnType::Number),
+    column_("Quantity", ColumnType::Number),
+    column_("Quantity Buy", ColumnType::Number),
+    column_("Quantity Sell", ColumnType::Number),
+    column_("Unit Price", ColumnType::Number),
+    column_("Valuation", ColumnType::Number),
+    column_("Nominal", ColumnType::Number),
+    column_("Cashflow", ColumnType::Number),
+    column_("Dividends", ColumnType::Number),
+    column_("Fees", ColumnType::Number),
+    column_("P&L", ColumnType::Number),
+    column_("P&L(%)", ColumnType::Number),
+    column_("TWR", ColumnType::Number),
+    column_("Earning", ColumnType::Number),
+    column_("Earning Latent", ColumnType::Number),
+    column_("Is Close", ColumnType::Boolean),
+];
+
+fn make_manifest_filename_(filename: &str) -> String {
+    format!("{}.meta.json", filename)
+}
+
+//
+// the header line and the <file>.meta.json manifest are both made from columns
+fn create_csv_(
+    filename: &str,
+    columns: &[Column],
+    header: bool,
+    delimiter: char,
+) -> Result<CsvWriter, Error> {
+    let manifest = File::create(make_manifest_filename_(filename))?;
+    serde_json::to_writer_pretty(
+        manifest,
+        &Manifest {
+            separator: delimiter,
+            header,
+            columns,
+        },
+    )?;
+
+    let mut output_stream = CsvWriter::create(filename, delimiter)?;
+    if header {
+        let names = columns
+            .iter()
+            .map(|column| &column.name as &dyn Display)
+            .collect::<Vec<_>>();
+        output_stream.write_record(&names)?;
+    }
+    Ok(output_stream)
+}
+
+fn remove_csv_(filename: &str) -> Result<(), Error> {
+    std::fs::remove_file(filename)?;
+    std::fs::remove_file(make_manifest_filename_(filename))?;
+    Ok(())
+}
 
 pub struct CsvOutput<'a> {
     output_dir: String,
     portfolio: &'a Portfolio,
     indicators: &'a PortfolioIndicators,
-    filter_indicators: &'a Option<Date>,
+    since: &'a Option<Date>,
+    nested: bool,
+    stress_shocks: &'a [f64],
+    delimiter: char,
+    resample: Option<Step>,
+    name_template: Option<NameTemplate>,
+    rolling_beta: Option<&'a [RollingBeta]>,
 }
 
 impl<'a> CsvOutput<'a> {
@@ -25,13 +259,63 @@ impl<'a> CsvOutput<'a> {
         output_dir: &str,
         portfolio: &'a Portfolio,
         indicators: &'a PortfolioIndicators,
-        filter_indicators: &'a Option<Date>,
+        since: &'a Option<Date>,
+        nested: bool,
+        stress_shocks: &'a [f64],
     ) -> Self {
         Self {
             output_dir: output_dir.to_string(),
             portfolio,
             indicators,
-            filter_indicators,
+            since,
+            nested,
+            stress_shocks,
+            delimiter: DEFAULT_DELIMITER,
+            resample: None,
+            name_template: None,
+            rolling_beta: None,
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_resample(mut self, resample: Option<Step>) -> Self {
+        self.resample = resample;
+        self
+    }
+
+    pub fn with_name_template(mut self, name_template: Option<NameTemplate>) -> Self {
+        self.name_template = name_template;
+        self
+    }
+
+    pub fn with_rolling_beta(mut self, rolling_beta: Option<&'a [RollingBeta]>) -> Self {
+        self.rolling_beta = rolling_beta;
+        self
+    }
+
+    fn make_directory_(&self) -> String {
+        if self.nested {
+            format!("{}/{}", self.output_dir, self.portfolio.name)
+        } else {
+            self.output_dir.clone()
+        }
+    }
+
+    fn make_filename_(&self, kind: &str, suffix: &str) -> String {
+        let directory = self.make_directory_();
+        let name = match &self.name_template {
+            Some(name_template) => name_template.render(&self.portfolio.name, kind),
+            None if self.nested => kind.to_string(),
+            None => format!("{}_{}", kind, self.portfolio.name),
+        };
+        if suffix.is_empty() {
+            format!("{}/{}.csv", directory, name)
+        } else {
+            format!("{}/{}_{}.csv", directory, name, suffix)
         }
     }
 
@@ -40,15 +324,9 @@ impl<'a> CsvOutput<'a> {
         filename: &str,
         indicators: &Vec<RegionIndicator>,
     ) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
+        let mut output_stream = create_csv_(filename, DISTRIBUTION_COLUMNS, false, self.delimiter)?;
         for indicator in indicators {
-            output_stream.write_all(
-                format!(
-                    "{};{}\n",
-                    indicator.region_name, indicator.valuation_percent
-                )
-                .as_bytes(),
-            )?;
+            output_stream.write_record(&[&indicator.region_name, &indicator.valuation_percent])?;
         }
         Ok(())
     }
@@ -58,15 +336,10 @@ impl<'a> CsvOutput<'a> {
         filename: &str,
         indicators: &Vec<RegionIndicatorInstrument>,
     ) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
+        let mut output_stream = create_csv_(filename, DISTRIBUTION_COLUMNS, false, self.delimiter)?;
         for indicator in indicators {
-            output_stream.write_all(
-                format!(
-                    "{};{}\n",
-                    indicator.instrument.name, indicator.valuation_percent
-                )
-                .as_bytes(),
-            )?;
+            output_stream
+                .write_record(&[&indicator.instrument.name, &indicator.valuation_percent])?;
         }
         Ok(())
     }
@@ -76,23 +349,17 @@ impl<'a> CsvOutput<'a> {
         filename: &str,
         indicators: &Vec<InstrumentIndicator>,
     ) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
+        let mut output_stream = create_csv_(filename, DISTRIBUTION_COLUMNS, false, self.delimiter)?;
         for indicator in indicators {
-            output_stream.write_all(
-                format!(
-                    "{};{}\n",
-                    indicator.instrument.name, indicator.valuation_percent
-                )
-                .as_bytes(),
-            )?;
+            output_stream
+                .write_record(&[&indicator.instrument.name, &indicator.valuation_percent])?;
         }
         Ok(())
     }
 
     fn write_heat_map_monthly(&self, filename: &str, heat_map: HeatMap) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
-        output_stream
-            .write_all("Year;Jan;Feb;Mar;Apr;May;Jun;Jul;Aug,Sep;Oct;Nov;Dec\n".as_bytes())?;
+        let mut output_stream =
+            create_csv_(filename, HEAT_MAP_MONTHLY_COLUMNS, true, self.delimiter)?;
 
         let mut data: BTreeMap<i32, [Option<f64>; 12]> = Default::default();
         for (date, value) in heat_map.data {
@@ -101,68 +368,283 @@ impl<'a> CsvOutput<'a> {
         }
 
         for (year, values) in data {
-            let mut line = format!("{}", year);
-            for value in values {
-                if let Some(pct) = value {
-                    line += &format!("{}", pct);
-                }
-                line += ";";
-            }
-            line += "\n";
-            output_stream.write_all(line.as_bytes())?;
+            let mut fields: Vec<&dyn Display> = vec![&year];
+            fields.extend(values.iter().map(|value| {
+                value
+                    .as_ref()
+                    .map_or(&"" as &dyn Display, |pct| pct as &dyn Display)
+            }));
+            output_stream.write_record(&fields)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_heat_map_long(&self, filename: &str, heat_map: &HeatMap) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, HEAT_MAP_LONG_COLUMNS, true, self.delimiter)?;
+
+        for (year, period, value) in heat_map.to_long() {
+            output_stream.write_record(&[&year, &period, &(100.0 * value)])?;
         }
 
         Ok(())
     }
 
     fn write_heat_map_yearly(&self, filename: &str, heat_map: HeatMap) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
-        output_stream.write_all("Year;Value\n".as_bytes())?;
+        let mut output_stream =
+            create_csv_(filename, HEAT_MAP_YEARLY_COLUMNS, true, self.delimiter)?;
 
         for (date, value) in heat_map.data {
-            output_stream.write_all(format!("{};{}\n", date.year(), 100.0 * value).as_bytes())?;
+            output_stream.write_record(&[&date.year(), &(100.0 * value)])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_return_histogram(
+        &self,
+        filename: &str,
+        histogram: Vec<(f64, usize)>,
+    ) -> Result<(), Error> {
+        let mut output_stream =
+            create_csv_(filename, RETURN_HISTOGRAM_COLUMNS, true, self.delimiter)?;
+
+        for (bucket, count) in histogram {
+            output_stream.write_record(&[&(100.0 * bucket), &count])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_return_quantiles(
+        &self,
+        filename: &str,
+        quantiles: Vec<(f64, f64)>,
+    ) -> Result<(), Error> {
+        if quantiles.is_empty() {
+            return Ok(());
+        }
+        let mut output_stream =
+            create_csv_(filename, RETURN_QUANTILES_COLUMNS, true, self.delimiter)?;
+
+        for (quantile, value) in quantiles {
+            output_stream.write_record(&[&(100.0 * quantile), &(100.0 * value)])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_stress_test(&self, filename: &str, stress: Vec<(f64, f64)>) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, STRESS_TEST_COLUMNS, true, self.delimiter)?;
+
+        for (shock, valuation) in stress {
+            output_stream.write_record(&[&(100.0 * shock), &valuation])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_seasonality<T>(
+        &self,
+        filename: &str,
+        columns: &[Column],
+        buckets: &[SeasonalityBucket<T>],
+    ) -> Result<(), Error>
+    where
+        T: std::fmt::Display,
+    {
+        let mut output_stream = create_csv_(filename, columns, true, self.delimiter)?;
+
+        for bucket in buckets {
+            output_stream.write_record(&[
+                &bucket.period,
+                &(100.0 * bucket.average_return),
+                &bucket.count,
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_wash_sales(&self, filename: &str, wash_sales: Vec<WashSale>) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, WASH_SALE_COLUMNS, true, self.delimiter)?;
+
+        for wash_sale in wash_sales {
+            output_stream.write_record(&[
+                &wash_sale.instrument.name,
+                &wash_sale.position_index,
+                &wash_sale.sell_date.format("%Y-%m-%d"),
+                &wash_sale.sell_quantity,
+                &wash_sale.loss,
+                &wash_sale.buy_date.format("%Y-%m-%d"),
+                &wash_sale.buy_quantity,
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_realized_gains(
+        &self,
+        filename: &str,
+        realized_gains: Vec<RealizedGain>,
+    ) -> Result<(), Error> {
+        let mut output_stream =
+            create_csv_(filename, REALIZED_GAINS_COLUMNS, true, self.delimiter)?;
+
+        for realized_gain in realized_gains {
+            output_stream.write_record(&[
+                &realized_gain.instrument.name,
+                &realized_gain.instrument.isin,
+                &realized_gain.position_index,
+                &realized_gain.open_date.format("%Y-%m-%d"),
+                &realized_gain.close_date.format("%Y-%m-%d"),
+                &realized_gain.quantity,
+                &realized_gain.proceeds,
+                &realized_gain.cost,
+                &realized_gain.realized_gain,
+                &realized_gain.holding_days,
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_cash_ledger(&self, filename: &str, ledger: CashLedger) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, CASH_LEDGER_COLUMNS, true, self.delimiter)?;
+
+        for movement in ledger.movements {
+            output_stream.write_record(&[
+                &movement.date.format("%Y-%m-%d"),
+                &movement.kind,
+                &movement
+                    .instrument
+                    .as_ref()
+                    .map_or("", |instrument| instrument.name.as_str()),
+                &movement.amount,
+                &movement.balance,
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_events(
+        &self,
+        filename: &str,
+        events: Vec<(Date, String, Event)>,
+    ) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, EVENTS_COLUMNS, true, self.delimiter)?;
+
+        for (date, instrument_name, event) in events {
+            output_stream.write_record(&[&date.format("%Y-%m-%d"), &instrument_name, &event])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_movers(
+        &self,
+        filename: &str,
+        (gainers, losers): (Vec<Mover>, Vec<Mover>),
+    ) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, MOVERS_COLUMNS, true, self.delimiter)?;
+
+        for (kind, mover) in gainers
+            .iter()
+            .map(|mover| ("Gainer", mover))
+            .chain(losers.iter().map(|mover| ("Loser", mover)))
+        {
+            output_stream.write_record(&[
+                &kind,
+                &mover.instrument.name,
+                &mover.position_index,
+                &mover.contribution,
+                &(100.0 * mover.change_percent),
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_instrument_risk(
+        &self,
+        filename: &str,
+        risks: Vec<InstrumentRiskIndicator>,
+    ) -> Result<(), Error> {
+        let mut output_stream =
+            create_csv_(filename, INSTRUMENT_RISK_COLUMNS, true, self.delimiter)?;
+
+        for risk in risks {
+            output_stream.write_record(&[
+                &risk.instrument.name,
+                &risk
+                    .volatility
+                    .map_or(String::new(), |value| value.to_string()),
+                &risk
+                    .max_drawdown
+                    .map_or(String::new(), |value| value.to_string()),
+                &risk
+                    .average_volume
+                    .map_or(String::new(), |value| value.to_string()),
+            ])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_rolling_beta(
+        &self,
+        filename: &str,
+        rolling_beta: &[RollingBeta],
+    ) -> Result<(), Error> {
+        let mut output_stream = create_csv_(filename, ROLLING_BETA_COLUMNS, true, self.delimiter)?;
+
+        for item in rolling_beta {
+            if !is_since(self.since, item.date) {
+                continue;
+            }
+            output_stream.write_record(&[
+                &item.date.format("%Y-%m-%d"),
+                &item.beta.map_or(String::new(), |value| value.to_string()),
+                &item
+                    .correlation
+                    .map_or(String::new(), |value| value.to_string()),
+            ])?;
         }
 
         Ok(())
     }
 
     fn write_position_indicators(&self, filename: &str) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
-        output_stream.write_all(
-            "Date;Valuation;Nominal;Incoming Transfert;Outcoming Transfert;Cash;Dividends;Fees;P&L;P&L(%);TWR;Earning;Earning Latent\n".as_bytes(),
-        )?;
+        let mut output_stream = create_csv_(filename, INDICATORS_COLUMNS, true, self.delimiter)?;
         let mut have_line = false;
-        for portfolio_indicator in self.indicators.portfolios.iter() {
-            if self
-                .filter_indicators
-                .map_or(false, |date| date > portfolio_indicator.date)
-            {
+        for portfolio_indicator in resampled(self.indicators, self.resample) {
+            if !is_since(self.since, portfolio_indicator.date) {
                 continue;
             }
             have_line = true;
-            output_stream.write_all(
-                format!(
-                    "{};{};{};{};{};{};{};{};{};{};{};{};{}\n",
-                    portfolio_indicator.date.format("%Y-%m-%d"),
-                    portfolio_indicator.valuation,
-                    portfolio_indicator.nominal,
-                    portfolio_indicator.incoming_transfer,
-                    portfolio_indicator.outcoming_transfer,
-                    portfolio_indicator.cash,
-                    portfolio_indicator.dividends,
-                    portfolio_indicator.fees,
-                    portfolio_indicator.pnl_currency,
-                    portfolio_indicator.pnl_percent,
-                    portfolio_indicator.twr,
-                    portfolio_indicator.earning,
-                    portfolio_indicator.earning_latent
-                )
-                .as_bytes(),
-            )?;
+            output_stream.write_record(&[
+                &portfolio_indicator.date.format("%Y-%m-%d"),
+                &portfolio_indicator.valuation,
+                &portfolio_indicator.nominal,
+                &portfolio_indicator.incoming_transfer,
+                &portfolio_indicator.outcoming_transfer,
+                &portfolio_indicator.securities_transfer,
+                &portfolio_indicator.cash,
+                &portfolio_indicator.invested_capital,
+                &portfolio_indicator.dividends,
+                &portfolio_indicator.fees,
+                &portfolio_indicator.pnl_currency,
+                &portfolio_indicator.pnl_percent,
+                &portfolio_indicator.twr,
+                &portfolio_indicator.earning,
+                &portfolio_indicator.earning_latent,
+            ])?;
         }
 
         if !have_line {
-            std::fs::remove_file(filename)?;
+            remove_csv_(filename)?;
         }
 
         Ok(())
@@ -173,48 +655,42 @@ impl<'a> CsvOutput<'a> {
         indicators: PositionIndicators,
         filename: &str,
     ) -> Result<(), Error> {
-        let mut output_stream = File::create(filename)?;
-        output_stream.write_all(
-          "Date;Instrument;Spot(Close);Quantity;Quantity Buy;Quantity Sell;Unit Price;Valuation;Nominal;Cashflow;Dividends;Fees;P&L;P&L(%);TWR;Earning;Earning Latent;Is Close\n".as_bytes(),
-        )?;
+        let mut output_stream =
+            create_csv_(filename, POSITION_INDICATORS_COLUMNS, true, self.delimiter)?;
         let mut have_line = false;
         for position_indicator in indicators
             .positions
             .into_iter()
-            .filter(|item| self.filter_indicators.map_or(true, |date| date < item.date))
+            .filter(|item| is_since(self.since, item.date))
         {
             have_line = true;
-            output_stream.write_all(
-                format!(
-                    "{};{};{};{};{};{};{};{};{};{};{};{};{};{};{};{};{};{}\n",
-                    position_indicator.date.format("%Y-%m-%d"),
-                    position_indicator.instrument.name,
-                    position_indicator.spot.close,
-                    position_indicator.quantity,
-                    position_indicator.quantity_buy,
-                    position_indicator.quantity_sell,
-                    position_indicator.unit_price,
-                    position_indicator.valuation,
-                    position_indicator.nominal,
-                    position_indicator.cashflow,
-                    position_indicator.dividends,
-                    position_indicator.fees,
-                    position_indicator.pnl_currency,
-                    position_indicator.pnl_percent,
-                    position_indicator.twr,
-                    position_indicator.earning,
-                    position_indicator.earning_latent,
-                    position_indicator.is_close,
-                )
-                .as_bytes(),
-            )?;
+            output_stream.write_record(&[
+                &position_indicator.date.format("%Y-%m-%d"),
+                &position_indicator.instrument.name,
+                &position_indicator.spot.close,
+                &position_indicator.quantity,
+                &position_indicator.quantity_buy,
+                &position_indicator.quantity_sell,
+                &position_indicator.unit_price,
+                &position_indicator.valuation,
+                &position_indicator.nominal,
+                &position_indicator.cashflow,
+                &position_indicator.dividends,
+                &position_indicator.fees,
+                &position_indicator.pnl_currency,
+                &position_indicator.pnl_percent,
+                &position_indicator.twr,
+                &position_indicator.earning,
+                &position_indicator.earning_latent,
+                &position_indicator.is_close,
+            ])?;
             if position_indicator.is_close {
                 break;
             }
         }
 
         if !have_line {
-            std::fs::remove_file(filename)?;
+            remove_csv_(filename)?;
         }
 
         Ok(())
@@ -223,8 +699,18 @@ impl<'a> CsvOutput<'a> {
 
 impl Output for CsvOutput<'_> {
     fn write(&mut self) -> Result<(), Error> {
-        let filename = format!("{}/indicators_{}.csv", self.output_dir, self.portfolio.name);
-        self.write_position_indicators(&filename)?;
+        std::fs::create_dir_all(self.make_directory_())?;
+
+        //
+        // a failing file doesn't stop the others, failures are reported at the end
+        let mut errors = Vec::new();
+
+        let filename = self.make_filename_("indicators", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_position_indicators(&filename),
+        );
 
         for instrument_name in self.portfolio.get_instrument_name_list() {
             for position_index in self.indicators.get_position_index_list(instrument_name) {
@@ -232,76 +718,471 @@ impl Output for CsvOutput<'_> {
                     .indicators
                     .get_position_indicators(instrument_name, position_index);
 
-                let filename = format!(
-                    "{}/heat_map_{}_{}_{}.csv",
-                    self.output_dir, self.portfolio.name, instrument_name, position_index
-                );
+                let suffix = format!("{}_{}", instrument_name, position_index);
+                let filename = self.make_filename_("heat_map", &suffix);
                 let heat_map = HeatMap::from_positions(
                     &position_indicators,
                     HeatMapPeriod::Monthly,
                     |indicator| indicator.pnl_percent,
                 );
-                self.write_heat_map_monthly(&filename, heat_map)?;
-
-                let filename = format!(
-                    "{}/heat_map_yearly_{}_{}_{}.csv",
-                    self.output_dir, self.portfolio.name, instrument_name, position_index
+                collect_error_(
+                    &mut errors,
+                    &filename,
+                    self.write_heat_map_monthly(&filename, heat_map),
                 );
+
+                let filename = self.make_filename_("heat_map_yearly", &suffix);
                 let heat_map = HeatMap::from_positions(
                     &position_indicators,
                     HeatMapPeriod::Yearly,
                     |indicator| indicator.pnl_percent,
                 );
-                self.write_heat_map_yearly(&filename, heat_map)?;
+                collect_error_(
+                    &mut errors,
+                    &filename,
+                    self.write_heat_map_yearly(&filename, heat_map),
+                );
+
+                let filename = self.make_filename_("return_histogram", &suffix);
+                collect_error_(
+                    &mut errors,
+                    &filename,
+                    self.write_return_histogram(
+                        &filename,
+                        position_indicators.return_histogram(HISTOGRAM_BUCKET_WIDTH),
+                    ),
+                );
+
+                let filename = self.make_filename_("return_quantiles", &suffix);
+                collect_error_(
+                    &mut errors,
+                    &filename,
+                    self.write_return_quantiles(
+                        &filename,
+                        position_indicators.return_quantiles(&RETURN_QUANTILES),
+                    ),
+                );
 
-                let position_filename = format!(
-                    "{}/indicators_{}_{}_{}.csv",
-                    self.output_dir, self.portfolio.name, instrument_name, position_index
+                let position_filename = self.make_filename_("indicators", &suffix);
+                collect_error_(
+                    &mut errors,
+                    &position_filename,
+                    self.write_position_instrument_indicators(
+                        position_indicators,
+                        &position_filename,
+                    ),
                 );
-                self.write_position_instrument_indicators(position_indicators, &position_filename)?;
             }
         }
 
         if let Some(indicator) = self.indicators.portfolios.last() {
             let region_indicators = RegionIndicator::from_portfolio(indicator);
-            let filename = format!(
-                "{}/distribution_by_region_{}.csv",
-                self.output_dir, self.portfolio.name
+            let filename = self.make_filename_("distribution_by_region", "");
+            collect_error_(
+                &mut errors,
+                &filename,
+                self.write_distribution_by_region(&filename, &region_indicators),
             );
-            self.write_distribution_by_region(&filename, &region_indicators)?;
             for region_indicator in region_indicators {
-                let filename = format!(
-                    "{}/distribution_{}_{}.csv",
-                    self.output_dir, self.portfolio.name, region_indicator.region_name
+                let filename = self.make_filename_("distribution", &region_indicator.region_name);
+                collect_error_(
+                    &mut errors,
+                    &filename,
+                    self.write_distribution_by_instrument(&filename, &region_indicator.instruments),
                 );
-                self.write_distribution_by_instrument(&filename, &region_indicator.instruments)?;
             }
 
             let instrument_indicators = InstrumentIndicator::from_portfolio(indicator);
-            let filename = format!(
-                "{}/distribution_global_{}.csv",
-                self.output_dir, self.portfolio.name
+            let filename = self.make_filename_("distribution_global", "");
+            collect_error_(
+                &mut errors,
+                &filename,
+                self.write_distribution_global_by_instrument(&filename, &instrument_indicators),
+            );
+
+            if !self.stress_shocks.is_empty() {
+                let filename = self.make_filename_("stress_test", "");
+                collect_error_(
+                    &mut errors,
+                    &filename,
+                    self.write_stress_test(&filename, indicator.stress_test(self.stress_shocks)),
+                );
+            }
+        }
+
+        let filename = self.make_filename_("return_quantiles", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_return_quantiles(
+                &filename,
+                self.indicators.return_quantiles(&RETURN_QUANTILES),
+            ),
+        );
+
+        let filename = self.make_filename_("instrument_risk", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_instrument_risk(
+                &filename,
+                InstrumentRiskIndicator::from_portfolios(self.indicators),
+            ),
+        );
+
+        if let Some(rolling_beta) = self.rolling_beta {
+            let filename = self.make_filename_("rolling_beta", "");
+            collect_error_(
+                &mut errors,
+                &filename,
+                self.write_rolling_beta(&filename, rolling_beta),
+            );
+        }
+
+        let filename = self.make_filename_("movers", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_movers(&filename, self.indicators.top_movers(TOP_MOVERS)),
+        );
+
+        let seasonality = SeasonalityIndicator::from_portfolios(self.indicators);
+        let filename = self.make_filename_("seasonality_weekday", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_seasonality(
+                &filename,
+                SEASONALITY_WEEKDAY_COLUMNS,
+                &seasonality.by_weekday,
+            ),
+        );
+        let filename = self.make_filename_("seasonality_month", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_seasonality(&filename, SEASONALITY_MONTH_COLUMNS, &seasonality.by_month),
+        );
+
+        let filename = self.make_filename_("events", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_events(&filename, self.indicators.position_events()),
+        );
+
+        let filename = self.make_filename_("cash_ledger", "");
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_cash_ledger(&filename, self.indicators.get_cash_ledger(self.portfolio)),
+        );
+
+        let close_positions = self
+            .indicators
+            .get_close_position_indicators(self.portfolio);
+        if !close_positions.is_empty() {
+            let filename = self.make_filename_("close_position", "");
+            collect_error_(
+                &mut errors,
+                &filename,
+                write_close_positions(&filename, &close_positions, self.delimiter),
+            );
+        }
+
+        let realized_gains = self.indicators.realized_gains(self.portfolio);
+        if !realized_gains.is_empty() {
+            let filename = self.make_filename_("realized_gains", "");
+            collect_error_(
+                &mut errors,
+                &filename,
+                self.write_realized_gains(&filename, realized_gains),
             );
-            self.write_distribution_global_by_instrument(&filename, &instrument_indicators)?;
         }
 
-        let filename = format!("{}/heat_map_{}.csv", self.output_dir, self.portfolio.name);
+        let wash_sales = self
+            .indicators
+            .wash_sale_candidates(self.portfolio, WASH_SALE_WINDOW_DAYS);
+        if !wash_sales.is_empty() {
+            let filename = self.make_filename_("wash_sale", "");
+            collect_error_(
+                &mut errors,
+                &filename,
+                self.write_wash_sales(&filename, wash_sales),
+            );
+        }
+
+        let filename = self.make_filename_("heat_map", "");
         let heat_map =
             HeatMap::from_portfolios(self.indicators, HeatMapPeriod::Monthly, |indicator| {
                 indicator.pnl_percent
             });
-        self.write_heat_map_monthly(&filename, heat_map)?;
-
-        let filename = format!(
-            "{}/heat_map_yearly_{}.csv",
-            self.output_dir, self.portfolio.name
+        let long_filename = self.make_filename_("heat_map_long", "");
+        collect_error_(
+            &mut errors,
+            &long_filename,
+            self.write_heat_map_long(&long_filename, &heat_map),
         );
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_heat_map_monthly(&filename, heat_map),
+        );
+
+        let filename = self.make_filename_("heat_map_yearly", "");
         let heat_map =
             HeatMap::from_portfolios(self.indicators, HeatMapPeriod::Yearly, |indicator| {
                 indicator.pnl_percent
             });
-        self.write_heat_map_yearly(&filename, heat_map)?;
+        collect_error_(
+            &mut errors,
+            &filename,
+            self.write_heat_map_yearly(&filename, heat_map),
+        );
 
-        Ok(())
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new_output(format!(
+                "unable to write {} csv file(s): {}",
+                errors.len(),
+                errors.join(", ")
+            )))
+        }
+    }
+}
+
+pub(super) fn write_close_positions(
+    filename: &str,
+    close_positions: &[ClosePositionIndicator],
+    delimiter: char,
+) -> Result<(), Error> {
+    let mut output_stream = create_csv_(filename, CLOSE_POSITIONS_COLUMNS, true, delimiter)?;
+
+    for close_position in close_positions {
+        output_stream.write_record(&[
+            &close_position.instrument.name,
+            &close_position.position_index,
+            &close_position.open_date.format("%Y-%m-%d"),
+            &close_position.close_date.format("%Y-%m-%d"),
+            &close_position.quantity,
+            &close_position.nominal,
+            &close_position.fees,
+            &close_position.dividends,
+            &close_position.pnl_currency,
+            &close_position.pnl_percent,
+            &close_position.holding_days,
+            &close_position.pnl_percent_annualized,
+            &close_position.close_reason,
+        ])?;
+    }
+
+    Ok(())
+}
+
+fn collect_error_(errors: &mut Vec<String>, filename: &str, result: Result<(), Error>) {
+    if let Err(error) = result {
+        errors.push(format!("{} ({:?})", filename, error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marketdata::Currency;
+    use crate::pricer::PortfolioIndicator;
+    use std::rc::Rc;
+
+    fn make_portfolio_(name: &str) -> Portfolio {
+        Portfolio {
+            name: name.to_string(),
+            currency: Rc::new(Currency {
+                name: String::from("EUR"),
+                parent_currency: None,
+            }),
+            positions: Default::default(),
+            cash: Default::default(),
+        }
+    }
+
+    fn make_indicators_() -> PortfolioIndicators {
+        let date = Date::from_ymd_opt(2025, 3, 17).unwrap();
+        PortfolioIndicators {
+            begin: date,
+            end: date,
+            options: Default::default(),
+            portfolios: vec![PortfolioIndicator {
+                date,
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn nested_layout() {
+        let output_dir = std::env::temp_dir().join("portfolio_rs_csv_nested_layout");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        let portfolio = make_portfolio_("TEST");
+        let indicators = make_indicators_();
+        let mut output = CsvOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            true,
+            &[],
+        );
+        output.write().unwrap();
+
+        assert!(output_dir.join("TEST").is_dir());
+        assert!(output_dir.join("TEST").join("indicators.csv").is_file());
+        assert!(!output_dir.join("indicators_TEST.csv").exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn write_errors_reported_at_end() {
+        let output_dir = std::env::temp_dir().join("portfolio_rs_csv_write_errors");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        //
+        // a directory in place of the movers file can't be created
+        std::fs::create_dir_all(output_dir.join("movers_TEST.csv")).unwrap();
+
+        let portfolio = make_portfolio_("TEST");
+        let indicators = make_indicators_();
+        let mut output = CsvOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            false,
+            &[],
+        );
+        match output.write() {
+            Err(Error::Output(message)) => {
+                assert!(message.contains("1 csv file(s)"), "{message}");
+                assert!(message.contains("movers_TEST.csv"), "{message}");
+            }
+            _ => panic!("movers must fail"),
+        }
+        assert!(output_dir.join("indicators_TEST.csv").is_file());
+        assert!(output_dir.join("seasonality_weekday_TEST.csv").is_file());
+        assert!(output_dir.join("heat_map_yearly_TEST.csv").is_file());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn dated_name_template() {
+        let output_dir = std::env::temp_dir().join("portfolio_rs_csv_name_template");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        let portfolio = make_portfolio_("TEST/PEA");
+        let indicators = make_indicators_();
+        let mut output = CsvOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            false,
+            &[],
+        )
+        .with_name_template(Some(NameTemplate::new(
+            "{portfolio}_{type}_{date}",
+            indicators.end,
+        )));
+        output.write().unwrap();
+
+        assert!(output_dir
+            .join("TEST_PEA_indicators_2025-03-17.csv")
+            .is_file());
+        assert!(!output_dir.join("indicators_TEST_PEA.csv").exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn since_keeps_cutoff_date() {
+        let output_dir = std::env::temp_dir().join("portfolio_rs_csv_since");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        let portfolio = make_portfolio_("TEST");
+        let date = Date::from_ymd_opt(2025, 3, 17).unwrap();
+        let indicators = PortfolioIndicators {
+            begin: date,
+            end: date + chrono::Days::new(2),
+            options: Default::default(),
+            portfolios: date
+                .iter_days()
+                .take(3)
+                .map(|date| PortfolioIndicator {
+                    date,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let since = Some(date + chrono::Days::new(1));
+        let mut output = CsvOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &since,
+            false,
+            &[],
+        );
+        output.write().unwrap();
+
+        let content = std::fs::read_to_string(output_dir.join("indicators_TEST.csv")).unwrap();
+        let dates = content
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split(';').next())
+            .collect::<Vec<_>>();
+        assert_eq!(dates, vec!["2025-03-18", "2025-03-19"]);
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn manifest_matches_header() {
+        let output_dir = std::env::temp_dir().join("portfolio_rs_csv_manifest");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        let portfolio = make_portfolio_("TEST");
+        let indicators = make_indicators_();
+        let mut output = CsvOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            false,
+            &[],
+        );
+        output.write().unwrap();
+
+        let content = std::fs::read_to_string(output_dir.join("indicators_TEST.csv")).unwrap();
+        let header = content.lines().next().unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(output_dir.join("indicators_TEST.csv.meta.json")).unwrap(),
+        )
+        .unwrap();
+        let names = manifest["columns"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|column| column["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names.join(";"), header);
+        assert_eq!(manifest["separator"], ";");
+        assert_eq!(manifest["columns"][0]["type"], "date");
+        assert_eq!(manifest["columns"][1]["type"], "number");
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
     }
 }
@@ -0,0 +1,89 @@
+use crate::error::Error;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Write;
+
+pub const DEFAULT_DELIMITER: char = ';';
+
+//
+// csv lines with a field holding the delimiter, a quote or a new line
+// quoted and its quotes doubled (rfc 4180)
+pub struct CsvWriter {
+    output_stream: File,
+    delimiter: char,
+}
+
+impl CsvWriter {
+    pub fn create(filename: &str, delimiter: char) -> Result<Self, Error> {
+        Ok(Self {
+            output_stream: File::create(filename)?,
+            delimiter,
+        })
+    }
+
+    pub fn write_record(&mut self, fields: &[&dyn Display]) -> Result<(), Error> {
+        let mut line = String::new();
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                line.push(self.delimiter);
+            }
+            line.push_str(&self.escape_(field.to_string()));
+        }
+        line.push('\n');
+        self.output_stream.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn escape_(&self, field: String) -> String {
+        if field.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_record_(line: &str, delimiter: char) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                c if c == delimiter && !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn quote_field_with_delimiter() {
+        let filename = std::env::temp_dir().join("portfolio_rs_csv_writer.csv");
+        let fields: [&dyn Display; 4] = [&"ESE", &"S&P 500; \"EUR\" acc", &1234.5, &"a,b"];
+        for (delimiter, wanted) in [
+            (';', "ESE;\"S&P 500; \"\"EUR\"\" acc\";1234.5;a,b"),
+            (',', "ESE,\"S&P 500; \"\"EUR\"\" acc\",1234.5,\"a,b\""),
+        ] {
+            let mut writer = CsvWriter::create(filename.to_str().unwrap(), delimiter).unwrap();
+            writer.write_record(&fields).unwrap();
+            drop(writer);
+            let content = std::fs::read_to_string(&filename).unwrap();
+            let line = content.strip_suffix('\n').unwrap();
+            assert_eq!(line, wanted);
+            assert_eq!(
+                split_record_(line, delimiter),
+                vec!["ESE", "S&P 500; \"EUR\" acc", "1234.5", "a,b"]
+            );
+        }
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::Error;
+use icu_locid::Locale;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolPosition {
+    Before,
+    #[default]
+    After,
+}
+
+//
+// ods number formats take their decimal and grouping separators from
+// the language/country of the format (ex: fr-FR gives 1 234,56),
+// without locale the spreadsheet application default is used
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OdsLocale {
+    pub locale: Option<String>,
+    pub symbol_position: SymbolPosition,
+    pub symbols: HashMap<String, String>,
+}
+
+impl Default for OdsLocale {
+    fn default() -> Self {
+        Self {
+            locale: None,
+            symbol_position: SymbolPosition::After,
+            symbols: HashMap::from([
+                (String::from("EUR"), String::from("€")),
+                (String::from("USD"), String::from("$")),
+                (String::from("GBP"), String::from("£")),
+            ]),
+        }
+    }
+}
+
+impl OdsLocale {
+    pub fn from_file(filename: &str) -> Result<Self, Error> {
+        let file = File::open(filename)?;
+        let reader = BufReader::new(file);
+        let mut locale: OdsLocale = serde_json::from_reader(reader)?;
+        for (currency, symbol) in OdsLocale::default().symbols {
+            locale.symbols.entry(currency).or_insert(symbol);
+        }
+        locale.language()?;
+        Ok(locale)
+    }
+
+    pub fn language(&self) -> Result<Option<Locale>, Error> {
+        self.locale
+            .as_ref()
+            .map(|locale| {
+                locale
+                    .parse::<Locale>()
+                    .map_err(|err| Error::new_output(format!("invalid locale {locale}: {err}")))
+            })
+            .transpose()
+    }
+
+    //
+    // unknown currency is displayed by its name
+    pub fn symbol<'a>(&'a self, currency_name: &'a str) -> &'a str {
+        self.symbols
+            .get(currency_name)
+            .map_or(currency_name, |symbol| symbol.as_str())
+    }
+
+    //
+    // (grouping, decimal) separators of text outputs, the ods outputs keep
+    // the number formats of the spreadsheet application
+    pub fn separators(&self) -> (char, char) {
+        let language = self
+            .language()
+            .ok()
+            .flatten()
+            .map(|locale| locale.id.language.to_string());
+        match language.as_deref() {
+            Some("en") => (',', '.'),
+            Some("fr") => ('\u{202f}', ','),
+            Some("de" | "es" | "it" | "nl" | "pt") => ('.', ','),
+            _ => (' ', '.'),
+        }
+    }
+
+    //
+    // rounded to decimals with digits grouped by thousands, ex: 1 234 567.89
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        let (grouping, decimal) = self.separators();
+        let formatted = format!("{:.*}", decimals, value.abs());
+        let (integer, fraction) = formatted
+            .split_once('.')
+            .map_or((formatted.as_str(), None), |(integer, fraction)| {
+                (integer, Some(fraction))
+            });
+
+        let mut result = String::new();
+        if value < 0.0 && formatted.chars().any(|c| c != '0' && c != '.') {
+            result.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                result.push(grouping);
+            }
+            result.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            result.push(decimal);
+            result.push_str(fraction);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_number_grouped() {
+        let locale = OdsLocale::default();
+        assert_eq!(locale.format_number(1234567.891, 2), "1 234 567.89");
+        assert_eq!(locale.format_number(-1234.5, 2), "-1 234.50");
+        assert_eq!(locale.format_number(123.0, 0), "123");
+        assert_eq!(locale.format_number(-0.001, 2), "0.00");
+
+        let locale = OdsLocale {
+            locale: Some(String::from("de-DE")),
+            ..Default::default()
+        };
+        assert_eq!(locale.format_number(1234567.891, 2), "1.234.567,89");
+    }
+}
@@ -1,14 +1,65 @@
+use crate::alias::Date;
 use crate::error::Error;
+use crate::pricer::{PortfolioIndicator, PortfolioIndicators, Step};
 
+mod close_position;
 mod csv;
+mod csv_writer;
+mod locale;
+mod name_template;
 mod ods;
 mod ods_helper;
 mod portfolio_performance;
+mod run_manifest;
+mod snapshot;
 
+pub use self::close_position::ClosePositionOutput;
 pub use self::csv::CsvOutput;
+pub use self::csv_writer::DEFAULT_DELIMITER;
+pub use self::locale::{OdsLocale, SymbolPosition};
+pub use self::name_template::NameTemplate;
 pub use self::ods::OdsOutput;
 pub use self::portfolio_performance::PortfolioPerformanceOutput;
+pub use self::run_manifest::{RunManifest, RUN_MANIFEST_FILENAME};
+pub use self::snapshot::{Snapshot, SnapshotPosition};
+
+const HISTOGRAM_BUCKET_WIDTH: f64 = 0.01;
+const RETURN_QUANTILES: [f64; 3] = [0.05, 0.5, 0.95];
+const TOP_MOVERS: usize = 5;
+const WASH_SALE_WINDOW_DAYS: u64 = 30;
+
+//
+// --since-days keeps items dated on or after the cutoff date
+fn is_since(since: &Option<Date>, date: Date) -> bool {
+    since.is_none_or(|since| since <= date)
+}
+
+//
+// --resample keeps the last portfolio indicator of each week or month
+fn resampled(indicators: &PortfolioIndicators, step: Option<Step>) -> Vec<&PortfolioIndicator> {
+    match step {
+        Some(step) => indicators.resample(step),
+        None => indicators.portfolios.iter().collect(),
+    }
+}
 
 pub trait Output {
     fn write(&mut self) -> Result<(), Error>;
 }
+
+pub fn check_output_dir(output_dir: &str) -> Result<(), Error> {
+    let path = std::path::Path::new(output_dir);
+    if !path.is_dir() {
+        return Err(Error::new_output(format!(
+            "output directory {} does not exist or is not a directory",
+            output_dir
+        )));
+    }
+    if path.metadata()?.permissions().readonly() {
+        return Err(Error::new_output(format!(
+            "output directory {} is not writable",
+            output_dir
+        )));
+    }
+    Ok(())
+}
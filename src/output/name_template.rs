@@ -0,0 +1,43 @@
+use crate::alias::Date;
+
+//
+// output file name (without extension) from a template with {portfolio},
+// {date} (pricing date YYYY-MM-DD) and {type} (ex: indicators, trade) placeholders
+#[derive(Clone, Debug)]
+pub struct NameTemplate {
+    template: String,
+    date: Date,
+}
+
+impl NameTemplate {
+    pub fn new(template: &str, date: Date) -> Self {
+        Self {
+            template: template.to_string(),
+            date,
+        }
+    }
+
+    pub fn render(&self, portfolio: &str, kind: &str) -> String {
+        sanitize_(
+            &self
+                .template
+                .replace("{portfolio}", portfolio)
+                .replace("{date}", &self.date.format("%Y-%m-%d").to_string())
+                .replace("{type}", kind),
+        )
+    }
+}
+
+//
+// a path separator or a reserved character of a file system is replaced by _
+fn sanitize_(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
@@ -1,12 +1,17 @@
+use super::locale::{OdsLocale, SymbolPosition};
 use super::ods_helper::{TableBuilder, TableBuilderStyleResolver};
-use super::Output;
+use super::snapshot::Snapshot;
+use super::{is_since, resampled, NameTemplate, Output, HISTOGRAM_BUCKET_WIDTH, TOP_MOVERS};
 use crate::alias::Date;
 use crate::error::Error;
 use crate::marketdata::Instrument;
-use crate::portfolio::{Portfolio, Trade};
+use crate::portfolio::{Order, Portfolio, Trade};
 use crate::pricer::{
-    HeatMap, HeatMapPeriod, InstrumentIndicator, PortfolioIndicator, PortfolioIndicators,
-    PositionIndicator, PositionIndicators, RegionIndicator, RegionIndicatorInstrument,
+    ActiveReturn, BuyAndHoldIndicator, CashMovement, ClosePositionIndicator, CoverageIndicator,
+    Event, HeatMap, HeatMapPeriod, InstrumentIndicator, InstrumentRiskIndicator, Mover,
+    PortfolioIndicator, PortfolioIndicators, PositionIndicator, PositionIndicators,
+    RegionIndicator, RegionIndicatorInstrument, RollingBeta, SeasonalityBucket,
+    SeasonalityIndicator, SleeveIndicator, Step, TagIndicator,
 };
 use chrono::Datelike;
 use log::debug;
@@ -20,11 +25,20 @@ use std::collections::BTreeMap;
 use std::rc::Rc;
 
 pub struct OdsOutput<'a> {
+    output_dir: String,
     output_filename: String,
     work_book: WorkBook,
     portfolio: &'a Portfolio,
     indicators: &'a PortfolioIndicators,
-    filter_indicators: &'a Option<Date>,
+    since: &'a Option<Date>,
+    stress_shocks: &'a [f64],
+    locale: &'a OdsLocale,
+    previous_snapshot: Option<&'a Snapshot>,
+    resample: Option<Step>,
+    heat_map_only: bool,
+    rolling_beta: Option<&'a [RollingBeta]>,
+    active_return: Option<&'a ActiveReturn>,
+    min_average_volume: Option<f64>,
 }
 
 impl TableBuilderStyleResolver for OdsOutput<'_> {
@@ -42,18 +56,99 @@ impl<'a> OdsOutput<'a> {
         output_dir: &str,
         portfolio: &'a Portfolio,
         indicators: &'a PortfolioIndicators,
-        filter_indicators: &'a Option<Date>,
+        since: &'a Option<Date>,
+        stress_shocks: &'a [f64],
+        locale: &'a OdsLocale,
     ) -> Result<Self, Error> {
         let output_filename = format!("{}/{}.ods", output_dir, portfolio.name);
         Ok(Self {
+            output_dir: output_dir.to_string(),
             output_filename,
             work_book: WorkBook::new_empty(),
             portfolio,
             indicators,
-            filter_indicators,
+            since,
+            stress_shocks,
+            locale,
+            previous_snapshot: None,
+            resample: None,
+            heat_map_only: false,
+            rolling_beta: None,
+            active_return: None,
+            min_average_volume: None,
         })
     }
 
+    //
+    // the summary gets a valuation change column since this snapshot
+    pub fn with_previous_snapshot(mut self, previous_snapshot: Option<&'a Snapshot>) -> Self {
+        self.previous_snapshot = previous_snapshot;
+        self
+    }
+
+    pub fn with_resample(mut self, resample: Option<Step>) -> Self {
+        self.resample = resample;
+        self
+    }
+
+    //
+    // indicators priced at period ends only have a meaningful heat map
+    pub fn with_heat_map_only(mut self, heat_map_only: bool) -> Self {
+        self.heat_map_only = heat_map_only;
+        self
+    }
+
+    pub fn with_rolling_beta(mut self, rolling_beta: Option<&'a [RollingBeta]>) -> Self {
+        self.rolling_beta = rolling_beta;
+        self
+    }
+
+    //
+    // the summary gets tracking error and information ratio against the benchmark
+    pub fn with_active_return(mut self, active_return: Option<&'a ActiveReturn>) -> Self {
+        self.active_return = active_return;
+        self
+    }
+
+    //
+    // the instrument risk sheet gets an illiquid column below this average volume
+    pub fn with_min_average_volume(mut self, min_average_volume: Option<f64>) -> Self {
+        self.min_average_volume = min_average_volume;
+        self
+    }
+
+    //
+    // {type} of the workbook is report
+    pub fn with_name_template(mut self, name_template: Option<NameTemplate>) -> Self {
+        if let Some(name_template) = name_template {
+            self.output_filename = format!(
+                "{}/{}.ods",
+                self.output_dir,
+                name_template.render(&self.portfolio.name, "report")
+            );
+        }
+        self
+    }
+
+    fn format_valuation_change_(
+        &self,
+        position: &PositionIndicator,
+        snapshot: &Snapshot,
+    ) -> String {
+        let Some(previous) = snapshot.valuation(&position.instrument.name, position.position_index)
+        else {
+            return String::from("n/a");
+        };
+        let change = position.valuation - previous;
+        let sign = if change >= 0.0 { "+" } else { "" };
+        let value = self.locale.format_number(change, 2);
+        let symbol = self.locale.symbol(&position.instrument.currency.name);
+        match self.locale.symbol_position {
+            SymbolPosition::Before => format!("{sign}{symbol}{value} since {}", snapshot.date),
+            SymbolPosition::After => format!("{sign}{value}{symbol} since {}", snapshot.date),
+        }
+    }
+
     fn add_sheet(&mut self, sheet: Sheet) {
         for i in 0..self.work_book.num_sheets() {
             let i_sheet = self.work_book.sheet(i);
@@ -106,50 +201,81 @@ impl<'a> OdsOutput<'a> {
                 .filter(|position| !position.is_close);
 
             sheet.set_value(0, 0, "Open Position");
-            let mut row = TableBuilder::new()
-                .add("Instrument Description", |position: &&PositionIndicator| {
-                    &position.instrument.description
-                })
-                .add("Quantity", |position: &&PositionIndicator| {
-                    position.quantity
-                })
-                .add("Unit Price", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.unit_price)
-                })
-                .add("Spot (Close)", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.spot.close)
-                })
-                .add("Spot (Date)", |position: &&PositionIndicator| {
-                    position.spot.date
-                })
-                .add("Valuation", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.valuation)
-                })
-                .add("Fees", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.fees)
-                })
-                .add("Nominal", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.nominal)
-                })
-                .add("Dividends", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.dividends)
-                })
-                .add("TWR", |position: &&PositionIndicator| {
-                    percent!(position.twr)
-                })
-                .add("P&L", |position: &&PositionIndicator| {
-                    currency!(&position.instrument.currency.name, position.pnl_currency)
-                })
-                .add("P&L(%)", |position: &&PositionIndicator| {
-                    percent!(position.pnl_percent)
-                })
-                .add_optional("Distribution", |position: &&PositionIndicator| {
-                    intrument_indicators
-                        .iter()
-                        .find(|indicator| indicator.instrument == position.instrument)
-                        .map(|item| percent!(item.valuation_percent))
-                })
-                .write(&mut sheet, self, 0, 1, inputs);
+            //
+            // scoped as the table getters borrow self
+            let mut row = {
+                let mut table = TableBuilder::new();
+                table
+                    .add("Instrument Description", |position: &&PositionIndicator| {
+                        &position.instrument.description
+                    })
+                    .add("Quantity", |position: &&PositionIndicator| {
+                        position.quantity
+                    })
+                    .add("Unit Price", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.unit_price)
+                    })
+                    .add_optional("Break-even Price", |position: &&PositionIndicator| {
+                        position
+                            .break_even_price
+                            .map(|value| currency!(&position.instrument.currency.name, value))
+                    })
+                    .add("Spot (Close)", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.spot.close)
+                    })
+                    .add("Spot (Date)", |position: &&PositionIndicator| {
+                        position.spot.date
+                    })
+                    .add("Valuation", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.valuation)
+                    })
+                    .add("Fees", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.fees)
+                    })
+                    .add("Nominal", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.nominal)
+                    })
+                    .add("Dividends", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.dividends)
+                    })
+                    .add("TWR", |position: &&PositionIndicator| {
+                        percent!(position.twr)
+                    })
+                    .add("P&L", |position: &&PositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.pnl_currency)
+                    })
+                    .add("P&L(%)", |position: &&PositionIndicator| {
+                        percent!(position.pnl_percent)
+                    })
+                    .add_optional("IRR", |position: &&PositionIndicator| {
+                        position
+                            .irr(
+                                &self.portfolio.positions[position.position_index],
+                                &self.indicators.options,
+                            )
+                            .map(|irr| percent!(irr))
+                    })
+                    .add_optional("Omega Ratio", |position: &&PositionIndicator| {
+                        self.indicators
+                            .get_position_indicators(
+                                &position.instrument.name,
+                                position.position_index,
+                            )
+                            .omega_ratio(0.0)
+                    })
+                    .add_optional("Distribution", |position: &&PositionIndicator| {
+                        intrument_indicators
+                            .iter()
+                            .find(|indicator| indicator.instrument == position.instrument)
+                            .map(|item| percent!(item.valuation_percent))
+                    });
+                if let Some(snapshot) = self.previous_snapshot {
+                    table.add("Valuation Change", |position: &&PositionIndicator| {
+                        self.format_valuation_change_(position, snapshot)
+                    });
+                }
+                table.write(&mut sheet, self, 0, 1, inputs)
+            };
 
             TableBuilder::new()
                 .add("", |portfolio: &&PortfolioIndicator| {
@@ -173,10 +299,61 @@ impl<'a> OdsOutput<'a> {
                 .add("", |portfolio: &&PortfolioIndicator| {
                     percent!(portfolio.open_pnl_percent)
                 })
-                .write_line(&mut sheet, self, row + 1, 6, &portfolio);
+                .write_line(&mut sheet, self, row + 1, 7, &portfolio);
+
+            let close_positions = self
+                .indicators
+                .get_close_position_indicators(self.portfolio);
+            if !close_positions.is_empty() {
+                row += 3;
+                sheet.set_value(row, 0, "Close Position");
+                row = TableBuilder::new()
+                    .add(
+                        "Instrument Description",
+                        |position: &&ClosePositionIndicator| &position.instrument.description,
+                    )
+                    .add("Open Date", |position: &&ClosePositionIndicator| {
+                        position.open_date
+                    })
+                    .add("Close Date", |position: &&ClosePositionIndicator| {
+                        position.close_date
+                    })
+                    .add("Quantity", |position: &&ClosePositionIndicator| {
+                        position.quantity
+                    })
+                    .add("Nominal", |position: &&ClosePositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.nominal)
+                    })
+                    .add("Fees", |position: &&ClosePositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.fees)
+                    })
+                    .add("Dividends", |position: &&ClosePositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.dividends)
+                    })
+                    .add("P&L", |position: &&ClosePositionIndicator| {
+                        currency!(&position.instrument.currency.name, position.pnl_currency)
+                    })
+                    .add("P&L(%)", |position: &&ClosePositionIndicator| {
+                        percent!(position.pnl_percent)
+                    })
+                    .add("Holding Days", |position: &&ClosePositionIndicator| {
+                        position.holding_days
+                    })
+                    .add("P&L Annualized(%)", |position: &&ClosePositionIndicator| {
+                        percent!(position.pnl_percent_annualized)
+                    })
+                    .add_optional("IRR", |position: &&ClosePositionIndicator| {
+                        position.irr.map(|irr| percent!(irr))
+                    })
+                    .add("Close Reason", |position: &&ClosePositionIndicator| {
+                        position.close_reason.to_string()
+                    })
+                    .write(&mut sheet, self, row, 1, close_positions.iter());
+            }
 
             row += 3;
             sheet.set_value(row, 0, "Porfolio");
+            let buy_and_hold = BuyAndHoldIndicator::from_portfolios(self.indicators);
             TableBuilder::new()
                 .add("Cash", |portfolio: &&PortfolioIndicator| {
                     currency!(&self.portfolio.currency.name, portfolio.cash)
@@ -193,6 +370,37 @@ impl<'a> OdsOutput<'a> {
                 .add("TWR", |portfolio: &&PortfolioIndicator| {
                     percent!(portfolio.twr)
                 })
+                .add_optional("Buy & Hold TWR", |_: &&PortfolioIndicator| {
+                    buy_and_hold.last().map(|item| percent!(item.twr))
+                })
+                .add_optional("CAGR", |_: &&PortfolioIndicator| {
+                    self.indicators.cagr().map(|cagr| percent!(cagr.value))
+                })
+                .add_optional("Sharpe Ratio", |_: &&PortfolioIndicator| {
+                    self.indicators.sharpe_ratio()
+                })
+                .add_optional("Omega Ratio", |_: &&PortfolioIndicator| {
+                    self.indicators.omega_ratio(0.0)
+                })
+                .add_optional("Calmar Ratio", |_: &&PortfolioIndicator| {
+                    self.indicators.calmar_ratio().map(|calmar| calmar.value)
+                })
+                .add_optional("VaR 95% (1d)", |_: &&PortfolioIndicator| {
+                    self.indicators
+                        .value_at_risk(0.95)
+                        .map(|value| percent!(value))
+                })
+                .add_optional("Tracking Error", |_: &&PortfolioIndicator| {
+                    self.active_return
+                        .map(|active_return| percent!(active_return.tracking_error))
+                })
+                .add_optional("Information Ratio", |_: &&PortfolioIndicator| {
+                    self.active_return
+                        .and_then(|active_return| active_return.information_ratio)
+                })
+                .add("Annualized", |_: &&PortfolioIndicator| {
+                    self.indicators.is_annualized()
+                })
                 .add("Fees", |portfolio: &&PortfolioIndicator| {
                     currency!(&self.portfolio.currency.name, portfolio.fees)
                 })
@@ -207,7 +415,7 @@ impl<'a> OdsOutput<'a> {
                 })
                 .write_reversed(&mut sheet, self, row, 1, std::iter::once(portfolio));
 
-            row += 10;
+            row += 19;
             let region_indicators = RegionIndicator::from_portfolio(portfolio);
             row = self.write_distribution_by_region(
                 &mut sheet,
@@ -236,63 +444,99 @@ impl<'a> OdsOutput<'a> {
     fn write_trades(&mut self) -> Result<(), Error> {
         let inputs = self.portfolio.positions.iter().flat_map(|position| {
             position
-                .trades
-                .iter()
+                .priced_trades(self.indicators.end)
+                .into_owned()
+                .into_iter()
                 .filter(|trade| {
                     (trade.date.date() <= self.indicators.end)
                         && (trade.date.date() >= self.indicators.begin)
-                        && self
-                            .filter_indicators
-                            .map_or(true, |date| date < trade.date.date())
+                        && is_since(self.since, trade.date.date())
                 })
-                .map(|trade| (&position.instrument, trade))
+                .map(move |trade| (&position.instrument, trade))
         });
 
         let mut table = TableBuilder::new();
         table
-            .add("Date", |(_, trade): &(&Rc<Instrument>, &Trade)| trade.date)
+            .add("Date", |(_, trade): &(&Rc<Instrument>, Trade)| trade.date)
             .add(
                 "Instrument",
-                |(instrument, _): &(&Rc<Instrument>, &Trade)| &instrument.name,
+                |(instrument, _): &(&Rc<Instrument>, Trade)| &instrument.name,
             )
-            .add("Quantity", |(_, trade): &(&Rc<Instrument>, &Trade)| {
+            .add("Quantity", |(_, trade): &(&Rc<Instrument>, Trade)| {
                 trade.quantity
             })
-            .add("Way", |(_, trade): &(&Rc<Instrument>, &Trade)| {
+            .add("Way", |(_, trade): &(&Rc<Instrument>, Trade)| {
                 trade.way.to_string()
             })
             .add(
                 "Unit Price",
-                |(instrument, trade): &(&Rc<Instrument>, &Trade)| {
+                |(instrument, trade): &(&Rc<Instrument>, Trade)| {
                     currency!(
                         &instrument.currency.name,
-                        trade.price + trade.fees / trade.quantity
+                        if trade.is_fee_only() {
+                            trade.price
+                        } else {
+                            trade.price + trade.fees / trade.quantity
+                        }
                     )
                 },
             )
-            .add(
-                "Price",
-                |(instrument, trade): &(&Rc<Instrument>, &Trade)| {
-                    currency!(&instrument.currency.name, trade.price)
-                },
-            )
-            .add("Fees", |(instrument, trade): &(&Rc<Instrument>, &Trade)| {
+            .add("Price", |(instrument, trade): &(&Rc<Instrument>, Trade)| {
+                currency!(&instrument.currency.name, trade.price)
+            })
+            .add("Fees", |(instrument, trade): &(&Rc<Instrument>, Trade)| {
                 currency!(&instrument.currency.name, trade.fees)
             });
 
         let mut sheet = Sheet::new("Trades");
-        table.write(&mut sheet, self, 0, 0, inputs);
+        let row = table.write(&mut sheet, self, 0, 0, inputs);
+
+        let orders = self.portfolio.positions.iter().flat_map(|position| {
+            position
+                .get_orders(self.indicators.end)
+                .into_iter()
+                .filter(|order| {
+                    (order.date().date() <= self.indicators.end)
+                        && (order.date().date() >= self.indicators.begin)
+                        && is_since(self.since, order.date().date())
+                })
+                .map(move |order| (&position.instrument, order))
+        });
+
+        let mut table = TableBuilder::new();
+        table
+            .add("Date", |(_, order): &(&Rc<Instrument>, Order)| order.date())
+            .add(
+                "Instrument",
+                |(instrument, _): &(&Rc<Instrument>, Order)| &instrument.name,
+            )
+            .add_optional("Order", |(_, order): &(&Rc<Instrument>, Order)| {
+                order.order_id
+            })
+            .add("Quantity", |(_, order): &(&Rc<Instrument>, Order)| {
+                order.quantity()
+            })
+            .add("Way", |(_, order): &(&Rc<Instrument>, Order)| {
+                order.way().to_string()
+            })
+            .add("Price", |(instrument, order): &(&Rc<Instrument>, Order)| {
+                currency!(&instrument.currency.name, order.price())
+            })
+            .add("Fees", |(instrument, order): &(&Rc<Instrument>, Order)| {
+                currency!(&instrument.currency.name, order.fees())
+            });
+
+        sheet.set_value(row + 1, 0, "By Order");
+        table.write(&mut sheet, self, row + 2, 0, orders);
         self.add_sheet(sheet);
 
         Ok(())
     }
 
     fn write_position_indicators(&mut self) -> Result<(), Error> {
-        let inputs = self
-            .indicators
-            .portfolios
-            .iter()
-            .filter(|item| self.filter_indicators.map_or(true, |date| date < item.date));
+        let inputs = resampled(self.indicators, self.resample)
+            .into_iter()
+            .filter(|item| is_since(self.since, item.date));
 
         let mut table = TableBuilder::new();
         table
@@ -323,9 +567,27 @@ impl<'a> OdsOutput<'a> {
                     )
                 },
             )
+            .add(
+                "Securities Transfert",
+                |portfolio_indicator: &&PortfolioIndicator| {
+                    currency!(
+                        &self.portfolio.currency.name,
+                        portfolio_indicator.securities_transfer
+                    )
+                },
+            )
             .add("Cash", |portfolio_indicator: &&PortfolioIndicator| {
                 currency!(&self.portfolio.currency.name, portfolio_indicator.cash)
             })
+            .add(
+                "Invested Capital",
+                |portfolio_indicator: &&PortfolioIndicator| {
+                    currency!(
+                        &self.portfolio.currency.name,
+                        portfolio_indicator.invested_capital
+                    )
+                },
+            )
             .add("Dividends", |portfolio_indicator: &&PortfolioIndicator| {
                 currency!(&self.portfolio.currency.name, portfolio_indicator.dividends)
             })
@@ -374,7 +636,7 @@ impl<'a> OdsOutput<'a> {
         let inputs = indicators
             .positions
             .iter()
-            .filter(|item| self.filter_indicators.map_or(true, |date| date < item.date));
+            .filter(|item| is_since(self.since, item.date));
 
         let mut table = TableBuilder::new();
         table
@@ -520,6 +782,230 @@ impl<'a> OdsOutput<'a> {
         Ok(())
     }
 
+    fn write_stress_test(&mut self) -> Result<(), Error> {
+        let mut sheet = Sheet::new("Stress Test");
+        if let Some(portfolio) = self
+            .indicators
+            .portfolios
+            .last()
+            .filter(|_| !self.stress_shocks.is_empty())
+        {
+            let mut table = TableBuilder::new();
+            table
+                .add("Shock", |(shock, _): &(f64, f64)| percent!(*shock))
+                .add("Valuation", |(_, valuation): &(f64, f64)| {
+                    currency!(&self.portfolio.currency.name, *valuation)
+                });
+            table.write(
+                &mut sheet,
+                self,
+                0,
+                0,
+                portfolio.stress_test(self.stress_shocks).into_iter(),
+            );
+            self.add_sheet(sheet);
+        } else {
+            self.remove_sheet(sheet.name());
+        }
+        Ok(())
+    }
+
+    fn write_cash_ledger(&mut self) -> Result<(), Error> {
+        let ledger = self.indicators.get_cash_ledger(self.portfolio);
+        let mut table = TableBuilder::new();
+        table
+            .add("Date", |movement: &&CashMovement| movement.date)
+            .add("Kind", |movement: &&CashMovement| movement.kind.to_string())
+            .add_optional("Instrument", |movement: &&CashMovement| {
+                movement
+                    .instrument
+                    .as_ref()
+                    .map(|instrument| instrument.name.clone())
+            })
+            .add("Amount", |movement: &&CashMovement| {
+                currency!(&self.portfolio.currency.name, movement.amount)
+            })
+            .add("Balance", |movement: &&CashMovement| {
+                currency!(&self.portfolio.currency.name, movement.balance)
+            });
+
+        let mut sheet = Sheet::new("Cash Ledger");
+        table.write(&mut sheet, self, 0, 0, ledger.movements.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_events(&mut self) -> Result<(), Error> {
+        let events = self.indicators.position_events();
+        let mut table = TableBuilder::new();
+        table
+            .add("Date", |(date, _, _): &&(Date, String, Event)| *date)
+            .add("Instrument", |(_, name, _): &&(Date, String, Event)| name)
+            .add("Event", |(_, _, event): &&(Date, String, Event)| {
+                event.to_string()
+            });
+
+        let mut sheet = Sheet::new("Events");
+        table.write(&mut sheet, self, 0, 0, events.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_movers(&mut self) -> Result<(), Error> {
+        let (gainers, losers) = self.indicators.top_movers(TOP_MOVERS);
+        let mut table = TableBuilder::new();
+        table
+            .add("Instrument", |mover: &&Mover| &mover.instrument.name)
+            .add("Position", |mover: &&Mover| mover.position_index as u32)
+            .add("Contribution", |mover: &&Mover| {
+                currency!(&mover.instrument.currency.name, mover.contribution)
+            })
+            .add("Change", |mover: &&Mover| percent!(mover.change_percent));
+
+        let mut sheet = Sheet::new("Movers");
+        sheet.set_value(0, 0, "Gainers");
+        let row = table.write(&mut sheet, self, 0, 1, gainers.iter());
+        sheet.set_value(row + 1, 0, "Losers");
+        table.write(&mut sheet, self, row + 1, 1, losers.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_seasonality(&mut self) -> Result<(), Error> {
+        let seasonality = SeasonalityIndicator::from_portfolios(self.indicators);
+        let mut weekday_table = TableBuilder::new();
+        weekday_table
+            .add("Weekday", |bucket: &&SeasonalityBucket<chrono::Weekday>| {
+                bucket.period.to_string()
+            })
+            .add(
+                "Average Return",
+                |bucket: &&SeasonalityBucket<chrono::Weekday>| percent!(bucket.average_return),
+            )
+            .add("Count", |bucket: &&SeasonalityBucket<chrono::Weekday>| {
+                bucket.count as u32
+            });
+        let mut month_table = TableBuilder::new();
+        month_table
+            .add("Month", |bucket: &&SeasonalityBucket<u32>| bucket.period)
+            .add("Average Return", |bucket: &&SeasonalityBucket<u32>| {
+                percent!(bucket.average_return)
+            })
+            .add("Count", |bucket: &&SeasonalityBucket<u32>| {
+                bucket.count as u32
+            });
+
+        let mut sheet = Sheet::new("Seasonality");
+        sheet.set_value(0, 0, "By Weekday");
+        let row = weekday_table.write(&mut sheet, self, 0, 1, seasonality.by_weekday.iter());
+        sheet.set_value(row + 1, 0, "By Month");
+        month_table.write(&mut sheet, self, row + 1, 1, seasonality.by_month.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_instrument_risk(&mut self) -> Result<(), Error> {
+        let risks = InstrumentRiskIndicator::from_portfolios(self.indicators);
+        let mut table = TableBuilder::new();
+        table
+            .add("Instrument", |risk: &&InstrumentRiskIndicator| {
+                &risk.instrument.name
+            })
+            .add_optional("Volatility", |risk: &&InstrumentRiskIndicator| {
+                risk.volatility.map(|value| percent!(value))
+            })
+            .add_optional("Max Drawdown", |risk: &&InstrumentRiskIndicator| {
+                risk.max_drawdown.map(|value| percent!(value))
+            })
+            .add_optional("Average Volume", |risk: &&InstrumentRiskIndicator| {
+                risk.average_volume
+            });
+        if let Some(min_average_volume) = self.min_average_volume {
+            table.add("Illiquid", move |risk: &&InstrumentRiskIndicator| {
+                risk.is_illiquid(min_average_volume)
+            });
+        }
+
+        let mut sheet = Sheet::new("Instrument Risk");
+        table.write(&mut sheet, self, 0, 0, risks.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_coverage(&mut self) -> Result<(), Error> {
+        let coverages = CoverageIndicator::from_portfolios(self.indicators);
+        let mut table = TableBuilder::new();
+        table
+            .add("Instrument", |coverage: &&CoverageIndicator| {
+                &coverage.instrument.name
+            })
+            .add("Days Held", |coverage: &&CoverageIndicator| {
+                coverage.days_held as u32
+            })
+            .add("Fetched Days", |coverage: &&CoverageIndicator| {
+                coverage.fetched_days as u32
+            })
+            .add("Coverage (%)", |coverage: &&CoverageIndicator| {
+                percent!(coverage.coverage)
+            });
+
+        let mut sheet = Sheet::new("Coverage");
+        table.write(&mut sheet, self, 0, 0, coverages.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_rolling_beta(&mut self) -> Result<(), Error> {
+        let Some(rolling_beta) = self.rolling_beta else {
+            return Ok(());
+        };
+        let mut table = TableBuilder::new();
+        table
+            .add("Date", |item: &&RollingBeta| item.date)
+            .add_optional("Beta", |item: &&RollingBeta| item.beta)
+            .add_optional("Correlation", |item: &&RollingBeta| item.correlation);
+
+        let mut sheet = Sheet::new("Rolling Beta");
+        table.write(
+            &mut sheet,
+            self,
+            0,
+            0,
+            rolling_beta
+                .iter()
+                .filter(|item| is_since(self.since, item.date)),
+        );
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_return_histogram(&mut self) -> Result<(), Error> {
+        let mut sheet = Sheet::new("Return Histogram");
+        let mut row = 0;
+        for instrument_name in self.portfolio.get_instrument_name_list() {
+            for position_index in self.indicators.get_position_index_list(instrument_name) {
+                let position_indicators = self
+                    .indicators
+                    .get_position_indicators(instrument_name, position_index);
+
+                sheet.set_value(
+                    row,
+                    0,
+                    Value::Text(format!("{} / {}", instrument_name, position_index)),
+                );
+                for (bucket, count) in position_indicators.return_histogram(HISTOGRAM_BUCKET_WIDTH)
+                {
+                    sheet.set_value(row, 1, percent!(bucket));
+                    sheet.set_value(row, 2, count as u32);
+                    row += 1;
+                }
+                row += 1;
+            }
+        }
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
     fn write_distribution(&mut self) -> Result<(), Error> {
         let mut sheet = Sheet::new("Distribution");
         if let Some(portfolio) = self.indicators.portfolios.last() {
@@ -548,6 +1034,65 @@ impl<'a> OdsOutput<'a> {
         Ok(())
     }
 
+    fn write_distribution_by_tag(&mut self) -> Result<(), Error> {
+        let tag_indicators = self
+            .indicators
+            .portfolios
+            .last()
+            .map(TagIndicator::from_portfolio)
+            .unwrap_or_default();
+        let mut sheet = Sheet::new("Distribution by Tag");
+        TableBuilder::new()
+            .add("Tag", |indicator: &&TagIndicator| {
+                indicator.tag_name.clone()
+            })
+            .add("Valuation", |indicator: &&TagIndicator| {
+                currency!(&self.portfolio.currency.name, indicator.valuation)
+            })
+            .add("Valuation (%)", |indicator: &&TagIndicator| {
+                percent!(indicator.valuation_percent)
+            })
+            .write(&mut sheet, self, 0, 0, tag_indicators.iter());
+        self.add_sheet(sheet);
+        Ok(())
+    }
+
+    fn write_sleeves(&mut self) -> Result<(), Error> {
+        let sleeve_indicators = SleeveIndicator::from_portfolios(self.portfolio, self.indicators);
+        let last_indicators = sleeve_indicators
+            .iter()
+            .filter_map(|(sleeve, indicators)| indicators.last().map(|last| (sleeve, last)))
+            .collect::<Vec<_>>();
+        let mut sheet = Sheet::new("Sleeves");
+        if TableBuilder::new()
+            .add("Sleeve", |(sleeve, _): &&(&String, &SleeveIndicator)| {
+                sleeve.to_string()
+            })
+            .add("Date", |(_, indicator): &&(&String, &SleeveIndicator)| {
+                indicator.date
+            })
+            .add(
+                "Valuation",
+                |(_, indicator): &&(&String, &SleeveIndicator)| {
+                    currency!(&self.portfolio.currency.name, indicator.valuation)
+                },
+            )
+            .add("P&L", |(_, indicator): &&(&String, &SleeveIndicator)| {
+                currency!(&self.portfolio.currency.name, indicator.pnl_currency)
+            })
+            .add("TWR", |(_, indicator): &&(&String, &SleeveIndicator)| {
+                percent!(indicator.twr)
+            })
+            .write(&mut sheet, self, 0, 0, last_indicators.iter())
+            != 1
+        {
+            self.add_sheet(sheet);
+        } else {
+            self.remove_sheet(sheet.name());
+        }
+        Ok(())
+    }
+
     fn write_distribution_by_region(
         &mut self,
         sheet: &mut Sheet,
@@ -653,20 +1198,27 @@ impl<'a> OdsOutput<'a> {
         if let Some(value) = self.work_book.currency_format(name) {
             return Ok(value.format_ref());
         }
-        if name == "EUR" {
-            let mut format_currency = ValueFormatCurrency::new_named(name);
-            format_currency
-                .part_number()
-                .min_integer_digits(1)
-                .decimal_places(2)
-                .min_decimal_places(2)
-                .grouping()
-                .build();
+        let mut format_currency = match self.locale.language()? {
+            Some(language) => ValueFormatCurrency::new_localized(name, language),
+            None => ValueFormatCurrency::new_named(name),
+        };
+        let symbol = self.locale.symbol(name);
+        if self.locale.symbol_position == SymbolPosition::Before {
+            format_currency.part_currency().symbol(symbol).build();
+            format_currency.part_text(" ").build();
+        }
+        format_currency
+            .part_number()
+            .min_integer_digits(1)
+            .decimal_places(2)
+            .min_decimal_places(2)
+            .grouping()
+            .build();
+        if self.locale.symbol_position == SymbolPosition::After {
             format_currency.part_text(" ").build();
-            format_currency.part_currency().symbol("€").build();
-            return Ok(self.work_book.add_currency_format(format_currency));
+            format_currency.part_currency().symbol(symbol).build();
         }
-        Err(Error::new_output(format!("unsupported currency {name}")))
+        Ok(self.work_book.add_currency_format(format_currency))
     }
 
     fn get_date_format(&mut self, name: &str) -> Result<ValueFormatRef, Error> {
@@ -739,18 +1291,57 @@ impl Output for OdsOutput<'_> {
         debug!("create style");
         self.create_style()?;
 
+        if self.heat_map_only {
+            debug!("write heat map");
+            self.write_heat_map()?;
+            return self.save();
+        }
+
         debug!("write summary");
         self.write_summary()?;
 
         debug!("write trades");
         self.write_trades()?;
 
+        debug!("write cash ledger");
+        self.write_cash_ledger()?;
+
+        debug!("write events");
+        self.write_events()?;
+
+        debug!("write movers");
+        self.write_movers()?;
+
         debug!("write heat map");
         self.write_heat_map()?;
 
+        debug!("write stress test");
+        self.write_stress_test()?;
+
+        debug!("write seasonality");
+        self.write_seasonality()?;
+
+        debug!("write instrument risk");
+        self.write_instrument_risk()?;
+
+        debug!("write coverage");
+        self.write_coverage()?;
+
+        debug!("write rolling beta");
+        self.write_rolling_beta()?;
+
+        debug!("write return histogram");
+        self.write_return_histogram()?;
+
         debug!("write distribution");
         self.write_distribution()?;
 
+        debug!("write distribution by tag");
+        self.write_distribution_by_tag()?;
+
+        debug!("write sleeves");
+        self.write_sleeves()?;
+
         debug!("write position indicators");
         self.write_position_indicators()?;
 
@@ -772,3 +1363,343 @@ impl Output for OdsOutput<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::make_instrument_;
+    use crate::marketdata::Currency;
+    use crate::output::CsvOutput;
+
+    #[test]
+    fn currency_format_with_locale() {
+        let portfolio = Portfolio {
+            name: String::from("portfolio_rs_ods_locale"),
+            currency: Rc::new(Currency {
+                name: String::from("EUR"),
+                parent_currency: None,
+            }),
+            positions: Default::default(),
+            cash: Default::default(),
+        };
+        let date = Date::from_ymd_opt(2022, 3, 17).unwrap();
+        let indicators = PortfolioIndicators {
+            begin: date,
+            end: date,
+            options: Default::default(),
+            portfolios: Default::default(),
+        };
+        let locale = OdsLocale {
+            locale: Some(String::from("fr-FR")),
+            symbol_position: SymbolPosition::Before,
+            ..Default::default()
+        };
+        let output_dir = std::env::temp_dir();
+        let mut output = OdsOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            &[],
+            &locale,
+        )
+        .unwrap();
+        output.write().unwrap();
+        output.create_currency_style("USD").unwrap();
+
+        for name in ["EUR", "USD"] {
+            let format = output.work_book.currency_format(name).unwrap();
+            assert_eq!(format.locale(), locale.language().unwrap());
+            assert_eq!(format.parts().len(), 3);
+        }
+        assert!(output.get_currency_style("USD").is_some());
+
+        std::fs::remove_file(&output.output_filename).unwrap();
+    }
+
+    //
+    // a USD position priced in an EUR portfolio
+    fn make_usd_position_(name: &str) -> (Portfolio, PortfolioIndicators) {
+        let eur = Rc::new(Currency {
+            name: String::from("EUR"),
+            parent_currency: None,
+        });
+        let usd = Rc::new(Currency {
+            name: String::from("USD"),
+            parent_currency: None,
+        });
+        let date = Date::from_ymd_opt(2025, 3, 17).unwrap();
+        let position = crate::portfolio::Position {
+            instrument: Rc::new(Instrument {
+                market: Rc::new(crate::marketdata::Market {
+                    name: String::from("NYSE"),
+                    description: String::from("NYSE"),
+                }),
+                ..make_instrument_("SPY", &usd)
+            }),
+            trades: vec![Trade {
+                date: date.and_hms_opt(10, 0, 0).unwrap(),
+                way: crate::portfolio::Way::Buy,
+                quantity: 10.0,
+                price: 380.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let portfolio = Portfolio {
+            name: String::from(name),
+            currency: eur,
+            positions: vec![position],
+            cash: Default::default(),
+        };
+        let position_indicator = PositionIndicator::from_position(
+            &portfolio.positions[0],
+            date,
+            0,
+            &crate::historical::DataFrame::new(date, 400.0, 400.0, 400.0, 400.0),
+            0.9,
+            &[],
+            &Default::default(),
+        );
+        let indicators = PortfolioIndicators {
+            begin: date,
+            end: date,
+            options: Default::default(),
+            portfolios: vec![PortfolioIndicator::from_portfolio(
+                &portfolio,
+                date,
+                vec![position_indicator],
+                &[],
+                &Default::default(),
+            )],
+        };
+        (portfolio, indicators)
+    }
+
+    #[test]
+    fn position_sheet_in_instrument_currency() {
+        let (portfolio, indicators) = make_usd_position_("portfolio_rs_ods_native");
+        let mut output = OdsOutput::new(
+            std::env::temp_dir().to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            &[],
+            &Default::default(),
+        )
+        .unwrap();
+        output
+            .write_position_instrument_indicators(indicators.get_position_indicators("SPY", 0))
+            .unwrap();
+
+        let sheet = (0..output.work_book.num_sheets())
+            .map(|i| output.work_book.sheet(i))
+            .find(|sheet| sheet.name() == "Indicators-SPY-0")
+            .unwrap();
+        for (col, wanted) in [(1, 400.0), (3, 380.0), (4, 4000.0), (9, 200.0)] {
+            match sheet.value(1, col) {
+                Value::Currency(value, currency_name) => {
+                    assert_eq!(currency_name.as_ref(), "USD");
+                    assert_float_eq::assert_float_absolute_eq!(*value, wanted, 1e-7);
+                }
+                value => panic!("column {col} must be a currency not {value:?}"),
+            }
+        }
+
+        //
+        // the portfolio aggregate is converted at the position fx rate
+        output.write_position_indicators().unwrap();
+        let sheet = (0..output.work_book.num_sheets())
+            .map(|i| output.work_book.sheet(i))
+            .find(|sheet| sheet.name() == "Indicators")
+            .unwrap();
+        match sheet.value(1, 1) {
+            Value::Currency(value, currency_name) => {
+                assert_eq!(currency_name.as_ref(), "EUR");
+                assert_float_eq::assert_float_absolute_eq!(*value, 200.0 * 0.9, 1e-7);
+            }
+            value => panic!("valuation must be a currency not {value:?}"),
+        }
+    }
+
+    #[test]
+    fn summary_valuation_change_since_snapshot() {
+        let (portfolio, indicators) = make_usd_position_("portfolio_rs_ods_snapshot");
+        let find_header_ = |output: &OdsOutput, name: &str| {
+            let sheet = (0..output.work_book.num_sheets())
+                .map(|i| output.work_book.sheet(i))
+                .find(|sheet| sheet.name() == "Summary")
+                .unwrap();
+            (1..32)
+                .find(|col| matches!(sheet.value(0, *col), Value::Text(text) if text == name))
+                .map(|col| sheet.value(1, col).clone())
+        };
+
+        let mut output = OdsOutput::new(
+            std::env::temp_dir().to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &None,
+            &[],
+            &Default::default(),
+        )
+        .unwrap();
+        output.write_summary().unwrap();
+        assert!(find_header_(&output, "Valuation Change").is_none());
+
+        for (snapshot_instrument, wanted) in [("SPY", "+400.00$ since 2025-03-10"), ("QQQ", "n/a")]
+        {
+            let snapshot = Snapshot {
+                date: String::from("2025-03-10"),
+                positions: vec![crate::output::SnapshotPosition {
+                    instrument: String::from(snapshot_instrument),
+                    position_index: 0,
+                    valuation: 3600.0,
+                }],
+            };
+            let mut output = OdsOutput::new(
+                std::env::temp_dir().to_str().unwrap(),
+                &portfolio,
+                &indicators,
+                &None,
+                &[],
+                &Default::default(),
+            )
+            .unwrap()
+            .with_previous_snapshot(Some(&snapshot));
+            output.write_summary().unwrap();
+            match find_header_(&output, "Valuation Change") {
+                Some(Value::Text(text)) => assert_eq!(text, wanted),
+                value => panic!("valuation change must be a text not {value:?}"),
+            }
+        }
+
+        let snapshot = Snapshot::from_indicators(&indicators).unwrap();
+        assert_eq!(snapshot.date, "2025-03-17");
+        assert_eq!(snapshot.valuation("SPY", 0), Some(4000.0));
+    }
+
+    #[test]
+    fn single_pricing_feeds_csv_and_ods() {
+        let portfolio = Portfolio {
+            name: String::from("portfolio_rs_ods_csv"),
+            currency: Rc::new(Currency {
+                name: String::from("EUR"),
+                parent_currency: None,
+            }),
+            positions: Default::default(),
+            cash: Default::default(),
+        };
+        let date = Date::from_ymd_opt(2025, 3, 17).unwrap();
+        let indicators = PortfolioIndicators {
+            begin: date,
+            end: date + chrono::Days::new(4),
+            options: Default::default(),
+            portfolios: date
+                .iter_days()
+                .take(5)
+                .map(|date| PortfolioIndicator {
+                    date,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let output_dir = std::env::temp_dir().join("portfolio_rs_ods_csv");
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let output_dir = output_dir.to_str().unwrap();
+
+        CsvOutput::new(output_dir, &portfolio, &indicators, &None, false, &[])
+            .write()
+            .unwrap();
+        let mut output = OdsOutput::new(
+            output_dir,
+            &portfolio,
+            &indicators,
+            &None,
+            &[],
+            &Default::default(),
+        )
+        .unwrap();
+        output.write().unwrap();
+
+        let content =
+            std::fs::read_to_string(format!("{output_dir}/indicators_portfolio_rs_ods_csv.csv"))
+                .unwrap();
+        let csv_dates = content
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split(';').next())
+            .map(|value| Date::parse_from_str(value, "%Y-%m-%d").unwrap())
+            .collect::<Vec<_>>();
+        let sheet = (0..output.work_book.num_sheets())
+            .map(|i| output.work_book.sheet(i))
+            .find(|sheet| sheet.name() == "Indicators")
+            .unwrap();
+        let ods_dates = (1..)
+            .map_while(|row| sheet.value(row, 0).as_date_opt())
+            .collect::<Vec<_>>();
+        assert_eq!(csv_dates.len(), 5);
+        assert_eq!(csv_dates, ods_dates);
+        assert!(std::path::Path::new(&output.output_filename).is_file());
+
+        std::fs::remove_dir_all(output_dir).unwrap();
+    }
+
+    #[test]
+    fn since_keeps_cutoff_date() {
+        let portfolio = Portfolio {
+            name: String::from("portfolio_rs_ods_since"),
+            currency: Rc::new(Currency {
+                name: String::from("EUR"),
+                parent_currency: None,
+            }),
+            positions: Default::default(),
+            cash: Default::default(),
+        };
+        let date = Date::from_ymd_opt(2025, 3, 17).unwrap();
+        let indicators = PortfolioIndicators {
+            begin: date,
+            end: date + chrono::Days::new(2),
+            options: Default::default(),
+            portfolios: date
+                .iter_days()
+                .take(3)
+                .map(|date| PortfolioIndicator {
+                    date,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let since = Some(date + chrono::Days::new(1));
+        let output_dir = std::env::temp_dir();
+        let mut output = OdsOutput::new(
+            output_dir.to_str().unwrap(),
+            &portfolio,
+            &indicators,
+            &since,
+            &[],
+            &Default::default(),
+        )
+        .unwrap();
+        output.write().unwrap();
+
+        let sheet = (0..output.work_book.num_sheets())
+            .map(|i| output.work_book.sheet(i))
+            .find(|sheet| sheet.name() == "Indicators")
+            .unwrap();
+        let dates = (1..)
+            .map_while(|row| sheet.value(row, 0).as_date_opt())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dates,
+            vec![date + chrono::Days::new(1), date + chrono::Days::new(2)]
+        );
+
+        std::fs::remove_file(&output.output_filename).unwrap();
+    }
+}
@@ -1,93 +1,111 @@
-use super::Output;
+use super::csv_writer::{CsvWriter, DEFAULT_DELIMITER};
+use super::{NameTemplate, Output};
+use crate::alias::Date;
 use crate::error::Error;
 use crate::portfolio::{CashVariationSource, Portfolio, Way};
 use log::debug;
-use std::collections::HashSet;
-
-use std::fs::File;
-use std::io::Write;
+use std::collections::BTreeMap;
 
 //
 // doc https://help.portfolio-performance.info/en/reference/file/import/csv-import/
 pub struct PortfolioPerformanceOutput<'a> {
     output_dir: String,
     portfolio: &'a Portfolio,
+    // a bond redemption after it is not a trade yet
+    end: Date,
+    delimiter: char,
+    name_template: Option<NameTemplate>,
 }
 
 impl<'a> PortfolioPerformanceOutput<'a> {
-    pub fn new(output_dir: &str, portfolio: &'a Portfolio) -> Self {
+    pub fn new(output_dir: &str, portfolio: &'a Portfolio, end: Date) -> Self {
         Self {
             output_dir: output_dir.to_string(),
             portfolio,
+            end,
+            delimiter: DEFAULT_DELIMITER,
+            name_template: None,
         }
     }
 
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_name_template(mut self, name_template: Option<NameTemplate>) -> Self {
+        self.name_template = name_template;
+        self
+    }
+
+    fn make_filename_(&self, kind: &str) -> String {
+        let name = match &self.name_template {
+            Some(name_template) => name_template.render(&self.portfolio.name, kind),
+            None => format!("{}_{}", self.portfolio.name, kind),
+        };
+        format!("{}/{}.csv", self.output_dir, name)
+    }
+
     fn write_account(&self) -> Result<(), Error> {
-        let filename = format!("{}/{}_account.csv", self.output_dir, self.portfolio.name);
-        let mut output_stream = File::create(filename)?;
-        output_stream.write_all("Date;Value\n".as_bytes())?;
+        let filename = self.make_filename_("account");
+        let mut output_stream = CsvWriter::create(&filename, self.delimiter)?;
+        output_stream.write_record(&[&"Date", &"Value"])?;
         for cash in self
             .portfolio
             .cash
             .iter()
             .filter(|item| item.source == CashVariationSource::Payment)
         {
-            output_stream.write_all(
-                format!("{};{}\n", cash.date.format("%Y-%m-%d"), cash.position,).as_bytes(),
-            )?;
+            output_stream.write_record(&[&cash.date.format("%Y-%m-%d"), &cash.home_position()])?;
         }
         Ok(())
     }
 
     fn write_trade(&self) -> Result<(), Error> {
-        let filename = format!("{}/{}_trade.csv", self.output_dir, self.portfolio.name);
-        let mut output_stream = File::create(filename)?;
-        output_stream.write_all("Date;Way;Isin;Quantity;Price;Fees\n".as_bytes())?;
-        for (instrument, trade) in self.portfolio.positions.iter().flat_map(|position| {
-            position
-                .trades
-                .iter()
-                .map(|trade| (&position.instrument, trade))
-        }) {
-            output_stream.write_all(
-                format!(
-                    "{};{};{};{};{};{}\n",
-                    trade.date.format("%Y-%m-%d"),
-                    if trade.way == Way::Buy { "Buy" } else { "Sell" },
-                    instrument.isin,
-                    trade.quantity,
-                    trade.price * trade.quantity + trade.fees,
-                    trade.fees
-                )
-                .as_bytes(),
-            )?;
+        let filename = self.make_filename_("trade");
+        let mut output_stream = CsvWriter::create(&filename, self.delimiter)?;
+        output_stream.write_record(&[&"Date", &"Way", &"Isin", &"Quantity", &"Price", &"Fees"])?;
+        for position in self.portfolio.positions.iter() {
+            //
+            // an opening position (first priced trade) is delivered in, not bought
+            let opening_count = usize::from(position.opening_position.is_some());
+            for (index, trade) in position.priced_trades(self.end).iter().enumerate() {
+                let way = match trade.way {
+                    _ if index < opening_count => "Delivery (Inbound)",
+                    Way::Buy => "Buy",
+                    Way::Sell => "Sell",
+                };
+                output_stream.write_record(&[
+                    &trade.date.format("%Y-%m-%d"),
+                    &way,
+                    &position.instrument.isin,
+                    &trade.quantity,
+                    &(trade.price * trade.quantity + trade.fees),
+                    &trade.fees,
+                ])?;
+            }
         }
         Ok(())
     }
 
     fn write_instrument(&self) -> Result<(), Error> {
-        let filename = format!("{}/{}_instrument.csv", self.output_dir, self.portfolio.name);
-        let mut output_stream = File::create(filename)?;
-        output_stream.write_all("Ticker Symbol;ISIN;Security Name;Currency\n".as_bytes())?;
+        let filename = self.make_filename_("instrument");
+        let mut output_stream = CsvWriter::create(&filename, self.delimiter)?;
+        output_stream.write_record(&[&"Ticker Symbol", &"ISIN", &"Security Name", &"Currency"])?;
         for instrument in self
             .portfolio
             .positions
             .iter()
-            .map(|position| &position.instrument)
-            .collect::<HashSet<_>>()
+            .map(|position| (&position.instrument.name, &position.instrument))
+            .collect::<BTreeMap<_, _>>()
+            .into_values()
         {
-            let mut buffer = String::new();
-            if instrument.ticker_yahoo.is_some() {
-                buffer.push_str(instrument.ticker_yahoo.as_ref().unwrap().as_str());
-            }
-            buffer.push(';');
-            buffer.push_str(instrument.isin.as_str());
-            buffer.push(';');
-            buffer.push_str(instrument.description.as_str());
-            buffer.push(';');
-            buffer.push_str(instrument.currency.name.as_str());
-            buffer.push('\n');
-            output_stream.write_all(buffer.as_bytes())?;
+            output_stream.write_record(&[
+                &instrument.ticker_yahoo.as_deref().unwrap_or_default(),
+                &instrument.isin,
+                &instrument.description,
+                &instrument.currency.name,
+            ])?;
         }
 
         Ok(())
@@ -0,0 +1,47 @@
+use crate::alias::Date;
+use crate::error::Error;
+use crate::portfolio::Portfolio;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufWriter;
+
+pub const RUN_MANIFEST_FILENAME: &str = "run_manifest.json";
+
+//
+// parameters of a run written alongside its outputs to reproduce them
+#[derive(Serialize)]
+pub struct RunManifest<'a, A: Serialize> {
+    pub version: &'a str,
+    pub args: &'a A,
+    // dates YYYY-MM-DD, begin is the inception date
+    pub begin_date: Option<String>,
+    pub end_date: String,
+    pub instruments: BTreeSet<&'a str>,
+}
+
+impl<'a, A: Serialize> RunManifest<'a, A> {
+    pub fn new(args: &'a A, portfolio: &'a Portfolio, end_date: Date) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            args,
+            begin_date: portfolio
+                .get_inception_date()
+                .ok()
+                .map(|date| date.format("%Y-%m-%d").to_string()),
+            end_date: end_date.format("%Y-%m-%d").to_string(),
+            instruments: portfolio
+                .positions
+                .iter()
+                .map(|position| position.instrument.name.as_str())
+                .collect(),
+        }
+    }
+
+    pub fn save(&self, output_dir: &str) -> Result<(), Error> {
+        let file = File::create(format!("{}/{}", output_dir, RUN_MANIFEST_FILENAME))?;
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
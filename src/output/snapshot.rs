@@ -0,0 +1,67 @@
+use crate::error::Error;
+use crate::pricer::PortfolioIndicators;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnapshotPosition {
+    pub instrument: String,
+    pub position_index: usize,
+    pub valuation: f64,
+}
+
+//
+// open position valuations of a run, kept to show the change on the next run
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    // pricing date YYYY-MM-DD
+    pub date: String,
+    pub positions: Vec<SnapshotPosition>,
+}
+
+impl Snapshot {
+    pub fn from_indicators(indicators: &PortfolioIndicators) -> Option<Self> {
+        let portfolio = indicators.portfolios.last()?;
+        Some(Self {
+            date: portfolio.date.format("%Y-%m-%d").to_string(),
+            positions: portfolio
+                .positions
+                .iter()
+                .filter(|position| !position.is_close)
+                .map(|position| SnapshotPosition {
+                    instrument: position.instrument.name.clone(),
+                    position_index: position.position_index,
+                    valuation: position.valuation,
+                })
+                .collect(),
+        })
+    }
+
+    //
+    // none on a first run, without previous snapshot file
+    pub fn from_file(filename: &str) -> Result<Option<Self>, Error> {
+        if !std::path::Path::new(filename).is_file() {
+            return Ok(None);
+        }
+        let file = File::open(filename)?;
+        let reader = BufReader::new(file);
+        Ok(Some(serde_json::from_reader(reader)?))
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), Error> {
+        let file = File::create(filename)?;
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn valuation(&self, instrument: &str, position_index: usize) -> Option<f64> {
+        self.positions
+            .iter()
+            .find(|position| {
+                position.instrument == instrument && position.position_index == position_index
+            })
+            .map(|position| position.valuation)
+    }
+}
@@ -1,7 +1,8 @@
 use crate::alias::Date;
 use crate::error::Error;
 use crate::historical::{DataFrame, Persistance};
-use crate::marketdata::Instrument;
+use crate::marketdata::{Dividend, Instrument};
+use log::warn;
 use rusqlite::{Connection, Result};
 
 struct SQLiteDate(Date);
@@ -24,59 +25,182 @@ impl rusqlite::types::FromSql for SQLiteDate {
 
 pub struct SQLitePersistance {
     connection: Connection,
+    // invalid rows (duplicated date, missing or non finite price) are
+    // skipped with a warning instead of failing the load
+    lenient: bool,
 }
 
 impl SQLitePersistance {
     pub fn new(file: &str) -> Result<Self, Error> {
         let connection = Connection::open(file)?;
-        let instance = Self { connection };
+        let instance = Self {
+            connection,
+            lenient: false,
+        };
         instance.setup()?;
         Ok(instance)
     }
 
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    fn check_(
+        &self,
+        instrument: &Instrument,
+        datas: Vec<DataFrame>,
+    ) -> Result<Vec<DataFrame>, Error> {
+        let mut result: Vec<DataFrame> = Vec::with_capacity(datas.len());
+        for data in datas {
+            let reason = if result
+                .last()
+                .is_some_and(|previous| previous.date == data.date)
+            {
+                "duplicated date"
+            } else if [data.open, data.close, data.high, data.low]
+                .iter()
+                .any(|value| !value.is_finite())
+            {
+                "non finite price"
+            } else {
+                result.push(data);
+                continue;
+            };
+            let message = format!(
+                "invalid cached spot of {} at {}: {}",
+                instrument.name, data.date, reason
+            );
+            if !self.lenient {
+                return Err(Error::new_historical(message));
+            }
+            warn!("{message}, skip it");
+        }
+        Ok(result)
+    }
+
     fn setup(&self) -> Result<(), Error> {
         self.connection.execute(
-          "CREATE TABLE IF NOT EXISTS Historical (instrument TEXT, date TEXT, open REAL, close REAL, high REAL, low REAL, PRIMARY KEY(\"instrument\",\"date\"))",
+          "CREATE TABLE IF NOT EXISTS Historical (instrument TEXT, date TEXT, open REAL, close REAL, high REAL, low REAL, volume REAL, PRIMARY KEY(\"instrument\",\"date\"))",
+          (),
+        )?;
+
+        //
+        // cache created before volume was stored
+        let has_volume = self
+            .connection
+            .prepare("SELECT name FROM pragma_table_info('Historical') WHERE name = 'volume'")?
+            .exists(())?;
+        if !has_volume {
+            self.connection
+                .execute("ALTER TABLE Historical ADD COLUMN volume REAL", ())?;
+        }
+
+        self.connection.execute(
+          "CREATE TABLE IF NOT EXISTS Dividends (instrument TEXT, record_date TEXT, payment_date TEXT, value REAL, PRIMARY KEY(\"instrument\",\"record_date\"))",
           (),
         )?;
         Ok(())
     }
-}
 
-impl Persistance for SQLitePersistance {
-    fn save(&self, instrument: &Instrument, datas: &[DataFrame]) -> Result<(), Error> {
-        self.connection.execute_batch("BEGIN TRANSACTION;")?;
+    pub fn save_dividends(
+        &self,
+        instrument: &Instrument,
+        dividends: &[Dividend],
+    ) -> Result<(), Error> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = transaction.prepare_cached(
+              "INSERT OR REPLACE INTO Dividends (instrument, record_date, payment_date, value) VALUES(?1, ?2, ?3, ?4)",
+            )?;
+            for dividend in dividends {
+                stmt.execute((
+                    &instrument.name,
+                    dividend.record_date.format("%Y-%m-%d").to_string(),
+                    dividend.payment_date.format("%Y-%m-%d").to_string(),
+                    dividend.value,
+                ))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    //
+    // cached dividends sorted by record date
+    pub fn load_dividends(&self, instrument: &Instrument) -> Result<Vec<Dividend>, Error> {
         let mut stmt = self.connection.prepare(
-          "INSERT OR REPLACE INTO Historical (instrument, date, open, close, high, low) VALUES(?, ?, ?, ?, ?, ?)",
+            "SELECT record_date, payment_date, value FROM Dividends WHERE instrument = ? ORDER BY record_date",
         )?;
+        let rows = stmt.query_map((&instrument.name,), |row| {
+            Ok(Dividend {
+                record_date: row
+                    .get::<usize, SQLiteDate>(0)?
+                    .0
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap(),
+                payment_date: row
+                    .get::<usize, SQLiteDate>(1)?
+                    .0
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap(),
+                value: row.get(2)?,
+            })
+        })?;
 
-        for data in datas.iter() {
-            stmt.execute((
-                &instrument.name,
-                data.date.to_string(),
-                data.open,
-                data.close,
-                data.high,
-                data.low,
-            ))?;
+        let mut dividends = Vec::new();
+        for item in rows {
+            dividends.push(item?);
         }
+        Ok(dividends)
+    }
+}
 
-        self.connection.execute_batch("COMMIT TRANSACTION;")?;
+impl Persistance for SQLitePersistance {
+    fn save(&self, instrument: &Instrument, datas: &[DataFrame]) -> Result<(), Error> {
+        let transaction = self.connection.unchecked_transaction()?;
+        {
+            let mut stmt = transaction.prepare_cached(
+              "INSERT OR REPLACE INTO Historical (instrument, date, open, close, high, low, volume) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+
+            //
+            // bindings are kept between executions, instrument is bound once
+            stmt.raw_bind_parameter(1, &instrument.name)?;
+            for data in datas.iter() {
+                stmt.raw_bind_parameter(2, data.date.format("%Y-%m-%d").to_string())?;
+                stmt.raw_bind_parameter(3, data.open)?;
+                stmt.raw_bind_parameter(4, data.close)?;
+                stmt.raw_bind_parameter(5, data.high)?;
+                stmt.raw_bind_parameter(6, data.low)?;
+                stmt.raw_bind_parameter(7, data.volume)?;
+                stmt.raw_execute()?;
+            }
+        }
+        transaction.commit()?;
         Ok(())
     }
+
     fn load(&self, instrument: &Instrument) -> Result<Option<(Date, Date, Vec<DataFrame>)>, Error> {
-        let mut stmt = self
-            .connection
-            .prepare("SELECT * FROM Historical WHERE instrument = ?")?;
+        let mut stmt = self.connection.prepare(
+            "SELECT date, open, close, high, low, volume FROM Historical WHERE instrument = ?",
+        )?;
 
+        //
+        // sqlite stores a nan as null, read back as a nan to be checked
         let rows = stmt.query_map((&instrument.name,), |row| {
+            let get_price_ = |index| {
+                row.get::<usize, Option<f64>>(index)
+                    .map(|value| value.unwrap_or(f64::NAN))
+            };
             Ok(DataFrame::new(
-                row.get::<usize, SQLiteDate>(1)?.0,
-                row.get(2)?,
-                row.get(3)?,
-                row.get(4)?,
-                row.get(5)?,
-            ))
+                row.get::<usize, SQLiteDate>(0)?.0,
+                get_price_(1)?,
+                get_price_(2)?,
+                get_price_(3)?,
+                get_price_(4)?,
+            )
+            .with_volume(row.get(5)?))
         })?;
 
         let mut datas = Vec::new();
@@ -84,6 +208,7 @@ impl Persistance for SQLitePersistance {
             datas.push(item?);
         }
         datas.sort_by(|left, right| left.date.cmp(&right.date));
+        let datas = self.check_(instrument, datas)?;
         let first = datas.first();
         let last = datas.last();
 
@@ -93,3 +218,165 @@ impl Persistance for SQLitePersistance {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use assert_float_eq::*;
+
+    #[test]
+    fn save_and_load_bulk() {
+        let persistence = SQLitePersistance::new(":memory:").unwrap();
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let other_instrument = make_instrument_("ESE", &make_currency_("EUR"));
+        let begin = chrono::NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        let datas = begin
+            .iter_days()
+            .take(5000)
+            .enumerate()
+            .map(|(i, date)| DataFrame::new(date, i as f64, i as f64 + 0.5, i as f64 + 1.0, 0.0))
+            .collect::<Vec<_>>();
+
+        persistence.save(&instrument, &datas).unwrap();
+        persistence.save(&instrument, &datas[4990..]).unwrap();
+        persistence.save(&other_instrument, &datas[..10]).unwrap();
+
+        let (first, last, result) = persistence.load(&instrument).unwrap().unwrap();
+        assert_eq!(first, begin);
+        assert_eq!(last, datas[4999].date);
+        assert_eq!(result.len(), 5000);
+        for (loaded, saved) in result.iter().zip(datas.iter()) {
+            assert_eq!(loaded.date, saved.date);
+            assert_float_absolute_eq!(loaded.open, saved.open, 1e-7);
+            assert_float_absolute_eq!(loaded.close, saved.close, 1e-7);
+            assert_float_absolute_eq!(loaded.high, saved.high, 1e-7);
+            assert_float_absolute_eq!(loaded.low, saved.low, 1e-7);
+        }
+
+        let (_, _, result) = persistence.load(&other_instrument).unwrap().unwrap();
+        assert_eq!(result.len(), 10);
+    }
+
+    #[test]
+    fn save_and_load_volume() {
+        let persistence = SQLitePersistance::new(":memory:").unwrap();
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let begin = chrono::NaiveDate::from_ymd_opt(2022, 3, 17).unwrap();
+        let datas = begin
+            .iter_days()
+            .take(3)
+            .zip([Some(1500.0), None, Some(0.0)])
+            .map(|(date, volume)| DataFrame::new(date, 1.0, 1.0, 1.0, 1.0).with_volume(volume))
+            .collect::<Vec<_>>();
+
+        persistence.save(&instrument, &datas).unwrap();
+        let (_, _, result) = persistence.load(&instrument).unwrap().unwrap();
+        assert_eq!(
+            result.iter().map(|data| data.volume).collect::<Vec<_>>(),
+            vec![Some(1500.0), None, Some(0.0)]
+        );
+    }
+
+    #[test]
+    fn load_with_nan_close() {
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let begin = chrono::NaiveDate::from_ymd_opt(2022, 3, 17).unwrap();
+        let datas = begin
+            .iter_days()
+            .take(3)
+            .zip([1.0, f64::NAN, 3.0])
+            .map(|(date, close)| DataFrame::new(date, 1.0, close, 1.0, 1.0))
+            .collect::<Vec<_>>();
+
+        let persistence = SQLitePersistance::new(":memory:").unwrap();
+        persistence.save(&instrument, &datas).unwrap();
+        match persistence.load(&instrument) {
+            Err(Error::Historical(message)) => {
+                assert!(message.contains("non finite price"), "{message}")
+            }
+            _ => panic!("nan close must fail"),
+        }
+
+        let persistence = persistence.with_lenient(true);
+        let (first, last, result) = persistence.load(&instrument).unwrap().unwrap();
+        assert_eq!(first, begin);
+        assert_eq!(last, datas[2].date);
+        assert_eq!(
+            result.iter().map(|data| data.date).collect::<Vec<_>>(),
+            vec![datas[0].date, datas[2].date]
+        );
+    }
+
+    #[test]
+    fn save_and_load_dividends() {
+        let persistence = SQLitePersistance::new(":memory:").unwrap();
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        assert!(persistence.load_dividends(&instrument).unwrap().is_empty());
+
+        let make_dividend_ = |day, value| {
+            let date = chrono::NaiveDate::from_ymd_opt(2022, 3, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap();
+            Dividend {
+                record_date: date,
+                payment_date: date,
+                value,
+            }
+        };
+        persistence
+            .save_dividends(
+                &instrument,
+                &[make_dividend_(17, 0.5), make_dividend_(3, 0.2)],
+            )
+            .unwrap();
+        persistence
+            .save_dividends(&instrument, &[make_dividend_(17, 0.6)])
+            .unwrap();
+        persistence
+            .save_dividends(
+                &make_instrument_("ESE", &make_currency_("EUR")),
+                &[make_dividend_(5, 1.0)],
+            )
+            .unwrap();
+
+        let dividends = persistence.load_dividends(&instrument).unwrap();
+        assert_eq!(dividends.len(), 2);
+        assert_eq!(dividends[0].record_date, make_dividend_(3, 0.0).record_date);
+        assert_float_absolute_eq!(dividends[0].value, 0.2, 1e-7);
+        assert_eq!(
+            dividends[1].payment_date,
+            make_dividend_(17, 0.0).payment_date
+        );
+        assert_float_absolute_eq!(dividends[1].value, 0.6, 1e-7);
+    }
+
+    #[test]
+    fn setup_migrate_volume() {
+        let file = std::env::temp_dir().join("portfolio_rs_migrate_volume.db");
+        let _ = std::fs::remove_file(&file);
+        Connection::open(&file)
+            .unwrap()
+            .execute(
+                "CREATE TABLE Historical (instrument TEXT, date TEXT, open REAL, close REAL, high REAL, low REAL, PRIMARY KEY(\"instrument\",\"date\"))",
+                (),
+            )
+            .unwrap();
+
+        let persistence = SQLitePersistance::new(file.to_str().unwrap()).unwrap();
+        let instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 3, 17).unwrap();
+        persistence
+            .save(
+                &instrument,
+                &[DataFrame::new(date, 1.0, 1.0, 1.0, 1.0).with_volume(Some(10.0))],
+            )
+            .unwrap();
+        let (_, _, result) = persistence.load(&instrument).unwrap().unwrap();
+        assert_eq!(result[0].volume, Some(10.0));
+
+        drop(persistence);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -3,11 +3,26 @@ use crate::alias::DateTime;
 #[derive(Debug, PartialEq)]
 pub enum CashVariationSource {
     Payment,
+    // cash of a position closed before the pricing and no longer priced
+    // (trades and dividends), an earning and not a transfer
+    Realized,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct CashVariation {
     pub position: f64,
     pub date: DateTime,
     pub source: CashVariationSource,
+    // sub account (ex: USD), none is the default account in the portfolio currency
+    pub account: Option<String>,
+    // account currency to portfolio currency, the latest one known converts the account balance
+    pub fx_rate: Option<f64>,
+}
+
+impl CashVariation {
+    //
+    // in the portfolio currency at the rate of the variation date
+    pub fn home_position(&self) -> f64 {
+        self.position * self.fx_rate.unwrap_or(1.0)
+    }
 }
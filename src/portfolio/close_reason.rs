@@ -0,0 +1,19 @@
+//
+// why a position was closed, a position fully sold is a Sell
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    #[default]
+    Sell,
+    Merger,
+    Delisting,
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CloseReason::Sell => write!(f, "Sell"),
+            CloseReason::Merger => write!(f, "Merger"),
+            CloseReason::Delisting => write!(f, "Delisting"),
+        }
+    }
+}
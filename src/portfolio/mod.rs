@@ -1,9 +1,15 @@
 mod cash_variation;
+mod close_reason;
+mod opening_position;
+mod order;
 mod position;
 mod trade;
 mod way;
 
 pub use cash_variation::*;
+pub use close_reason::*;
+pub use opening_position::*;
+pub use order::*;
 pub use position::*;
 pub use trade::*;
 pub use way::*;
@@ -11,7 +17,7 @@ pub use way::*;
 use crate::alias::Date;
 use crate::error::Error;
 use crate::marketdata::Currency;
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
 #[derive(Debug)]
@@ -27,8 +33,7 @@ impl Portfolio {
         let mut trade_dates = self
             .positions
             .iter()
-            .flat_map(|position| position.trades.first())
-            .map(|trade| trade.date)
+            .filter_map(|position| position.get_open_date())
             .collect::<Vec<_>>();
         trade_dates.sort();
 
@@ -39,10 +44,186 @@ impl Portfolio {
         Ok(first_trade.date())
     }
 
-    pub fn get_instrument_name_list(&self) -> HashSet<&String> {
-        self.positions
+    //
+    // the account is funded by its first payment, often before the first trade
+    pub fn get_inception_date(&self) -> Result<Date, Error> {
+        let first_payment = self
+            .cash
+            .iter()
+            .filter(|variation| variation.source == CashVariationSource::Payment)
+            .map(|variation| variation.date.date())
+            .min();
+        match (first_payment, self.get_trade_date()) {
+            (Some(first_payment), Ok(first_trade)) => Ok(first_payment.min(first_trade)),
+            (Some(first_payment), Err(_)) => Ok(first_payment),
+            (None, result) => result,
+        }
+    }
+
+    //
+    // a position closed (net zero quantity) is never reopened, a new trade
+    // opens a new position, so one closed before begin is no longer priced,
+    // its cash is kept as realized cash variations, the number dropped is returned
+    pub fn retain_open_positions(&mut self, begin: Date, end: Date, epsilon: f64) -> usize {
+        let (open, closed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.positions)
+            .into_iter()
+            .partition(|position| {
+                position
+                    .get_close_date(end, epsilon)
+                    .is_none_or(|close_date| close_date.date() >= begin)
+            });
+        self.positions = open;
+        for position in closed.iter() {
+            self.cash.extend(position.realized_cash(end));
+        }
+        closed.len()
+    }
+
+    //
+    // balance of each tagged cash account at date in its own currency with
+    // the latest fx rate known to the portfolio currency (1.0 without any)
+    pub fn get_account_balances(&self, date: Date) -> BTreeMap<&String, (f64, f64)> {
+        let mut result: BTreeMap<&String, (f64, f64)> = Default::default();
+        for variation in self
+            .cash
+            .iter()
+            .filter(|variation| variation.date.date() <= date)
+        {
+            let Some(account) = &variation.account else {
+                continue;
+            };
+            let (balance, fx_rate) = result.entry(account).or_insert((0.0, 1.0));
+            *balance += variation.position;
+            if let Some(variation_fx_rate) = variation.fx_rate {
+                *fx_rate = variation_fx_rate;
+            }
+        }
+        result
+    }
+
+    pub fn get_instrument_name_list(&self) -> Vec<&String> {
+        let mut result = self
+            .positions
             .iter()
             .map(|position| &position.instrument.name)
-            .collect()
+            .collect::<Vec<_>>();
+        result.sort();
+        result.dedup();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::make_instrument_;
+    use assert_float_eq::*;
+
+    fn make_trade_(day: u32, way: Way, quantity: f64) -> Trade {
+        Trade {
+            date: chrono::NaiveDate::from_ymd_opt(2022, 3, day)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap(),
+            way,
+            quantity,
+            price: 20.0,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    fn make_position_(name: &str, currency: &Rc<Currency>) -> Position {
+        Position {
+            instrument: Rc::new(make_instrument_(name, currency)),
+            trades: Default::default(),
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        }
+    }
+
+    #[test]
+    fn sorted_instrument_name_list() {
+        let currency = Rc::new(Currency {
+            name: String::from("EUR"),
+            parent_currency: None,
+        });
+        let portfolio = Portfolio {
+            name: String::from("portfolio"),
+            currency: currency.clone(),
+            positions: ["PAEEM", "ESE", "C40", "ESE", "BRE", "PAEEM"]
+                .into_iter()
+                .map(|name| make_position_(name, &currency))
+                .collect(),
+            cash: Default::default(),
+        };
+
+        let instruments = portfolio.get_instrument_name_list();
+        assert_eq!(instruments, vec!["BRE", "C40", "ESE", "PAEEM"]);
+        assert_eq!(instruments, portfolio.get_instrument_name_list());
+    }
+
+    #[test]
+    fn retain_open_positions() {
+        let currency = Rc::new(Currency {
+            name: String::from("EUR"),
+            parent_currency: None,
+        });
+        let mut closed = make_position_("ESE", &currency);
+        closed.trades = vec![
+            make_trade_(1, Way::Buy, 10.0),
+            Trade {
+                price: 25.0,
+                ..make_trade_(5, Way::Sell, 10.0)
+            },
+        ];
+        let mut open = make_position_("PAEEM", &currency);
+        open.trades = vec![
+            make_trade_(3, Way::Buy, 10.0),
+            make_trade_(7, Way::Sell, 4.0),
+        ];
+        let mut portfolio = Portfolio {
+            name: String::from("portfolio"),
+            currency,
+            positions: vec![closed, open],
+            cash: Default::default(),
+        };
+        assert_eq!(
+            portfolio.get_trade_date().unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 3, 1).unwrap()
+        );
+
+        //
+        // a position closed since begin is still priced
+        let nb_closed = portfolio.retain_open_positions(
+            chrono::NaiveDate::from_ymd_opt(2022, 3, 5).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 3, 31).unwrap(),
+            QUANTITY_EPSILON,
+        );
+        assert_eq!(nb_closed, 0);
+        assert_eq!(portfolio.positions.len(), 2);
+        assert!(portfolio.cash.is_empty());
+
+        let nb_closed = portfolio.retain_open_positions(
+            chrono::NaiveDate::from_ymd_opt(2022, 3, 6).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 3, 31).unwrap(),
+            QUANTITY_EPSILON,
+        );
+        assert_eq!(nb_closed, 1);
+        assert_eq!(portfolio.positions.len(), 1);
+        assert_eq!(portfolio.positions[0].instrument.name, "PAEEM");
+
+        //
+        // its cash is kept at its close date
+        assert_eq!(portfolio.cash.len(), 1);
+        assert_eq!(portfolio.cash[0].source, CashVariationSource::Realized);
+        assert_eq!(portfolio.cash[0].date, make_trade_(5, Way::Sell, 10.0).date);
+        assert_float_absolute_eq!(portfolio.cash[0].home_position(), 50.0, 1e-7);
+        assert_eq!(
+            portfolio.get_trade_date().unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 3, 3).unwrap()
+        );
     }
 }
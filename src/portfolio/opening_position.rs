@@ -0,0 +1,31 @@
+use super::{Trade, Way};
+use crate::alias::DateTime;
+
+//
+// holding migrated from another broker with its cost basis, priced as an
+// initial buy but transferred in (no cash outflow)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningPosition {
+    pub quantity: f64,
+    pub unit_price: f64,
+    pub date: DateTime,
+    pub fees: f64,
+}
+
+impl OpeningPosition {
+    pub fn cost(&self) -> f64 {
+        self.quantity * self.unit_price + self.fees
+    }
+
+    pub fn to_trade(&self) -> Trade {
+        Trade {
+            date: self.date,
+            way: Way::Buy,
+            quantity: self.quantity,
+            price: self.unit_price,
+            fees: self.fees,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use super::{Trade, Way, QUANTITY_EPSILON};
+use crate::alias::DateTime;
+use std::borrow::Cow;
+
+#[derive(Debug)]
+pub struct Order<'a> {
+    pub order_id: Option<&'a str>,
+    pub trades: Vec<Cow<'a, Trade>>,
+}
+
+impl<'a> Order<'a> {
+    pub fn from_trades(trades: &'a [Trade]) -> Vec<Self> {
+        let mut orders: Vec<Order<'a>> = Vec::new();
+        for trade in trades {
+            let position = trade.order_id.as_deref().and_then(|order_id| {
+                orders
+                    .iter()
+                    .position(|order| order.order_id == Some(order_id))
+            });
+            match position {
+                Some(position) => orders[position].trades.push(Cow::Borrowed(trade)),
+                None => orders.push(Order {
+                    order_id: trade.order_id.as_deref(),
+                    trades: vec![Cow::Borrowed(trade)],
+                }),
+            }
+        }
+        orders
+    }
+
+    pub fn date(&self) -> DateTime {
+        self.trades[0].date
+    }
+
+    pub fn way(&self) -> &Way {
+        &self.trades[0].way
+    }
+
+    pub fn quantity(&self) -> f64 {
+        self.trades.iter().map(|trade| trade.quantity).sum()
+    }
+
+    pub fn price(&self) -> f64 {
+        let quantity = self.quantity();
+        if quantity.abs() < QUANTITY_EPSILON {
+            0.0
+        } else {
+            self.trades
+                .iter()
+                .map(|trade| trade.price * trade.quantity)
+                .sum::<f64>()
+                / quantity
+        }
+    }
+
+    pub fn fees(&self) -> f64 {
+        self.trades.iter().map(|trade| trade.fees).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    fn make_trade_(date: &str, quantity: f64, price: f64, order_id: Option<&str>) -> Trade {
+        Trade {
+            date: chrono::DateTime::parse_from_rfc3339(date)
+                .unwrap()
+                .naive_local(),
+            way: Way::Buy,
+            quantity,
+            price,
+            fees: 1.0,
+            order_id: order_id.map(|item| item.to_string()),
+            fx_rate: None,
+        }
+    }
+
+    #[test]
+    fn group_partial_fills() {
+        let trades = vec![
+            make_trade_("2022-03-17T10:00:00-00:00", 10.0, 20.0, Some("A")),
+            make_trade_("2022-03-17T10:05:00-00:00", 30.0, 21.0, Some("A")),
+            make_trade_("2022-03-18T10:00:00-00:00", 5.0, 22.0, None),
+        ];
+        let orders = Order::from_trades(&trades);
+        assert_eq!(orders.len(), 2);
+
+        assert_eq!(orders[0].order_id, Some("A"));
+        assert_eq!(orders[0].trades.len(), 2);
+        assert_eq!(orders[0].date(), trades[0].date);
+        assert_float_absolute_eq!(orders[0].quantity(), 40.0, 1e-7);
+        assert_float_absolute_eq!(orders[0].price(), 20.75, 1e-7);
+        assert_float_absolute_eq!(orders[0].fees(), 2.0, 1e-7);
+
+        assert_eq!(orders[1].order_id, None);
+        assert_eq!(orders[1].trades.len(), 1);
+        assert_float_absolute_eq!(orders[1].quantity(), 5.0, 1e-7);
+        assert_float_absolute_eq!(orders[1].price(), 22.0, 1e-7);
+    }
+}
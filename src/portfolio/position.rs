@@ -1,25 +1,174 @@
-use super::Trade;
+use super::{CashVariation, CashVariationSource, CloseReason, OpeningPosition, Order, Trade};
 use crate::portfolio::Way;
-use crate::{alias::DateTime, marketdata::Instrument};
+use crate::{
+    alias::{Date, DateTime},
+    marketdata::{Instrument, InstrumentType},
+};
+use std::borrow::Cow;
 use std::rc::Rc;
 
+//
+// quantities are sums of f64 trade quantities, a residual below this threshold
+// comes from rounding of fractional shares and the position is considered closed
+pub const QUANTITY_EPSILON: f64 = 1e-7;
+
 #[derive(Debug)]
 pub struct Position {
     pub instrument: Rc<Instrument>,
     pub trades: Vec<Trade>,
+    // explicit reason (ex: merger), none is inferred from the trades
+    pub close_reason: Option<CloseReason>,
+    // grouping across instruments (ex: retirement, trading)
+    pub sleeve: Option<String>,
+    pub opening_position: Option<OpeningPosition>,
 }
 
 impl Position {
-    pub fn get_close_date(&self) -> Option<DateTime> {
-        let quantity: f64 = self
-            .trades
+    //
+    // trades with the opening position as a first buy, trades are
+    // expected after it, and the redemption of a bond held at a maturity before end
+    pub fn priced_trades(&self, end: Date) -> Cow<'_, [Trade]> {
+        let opening_trade = self
+            .opening_position
+            .as_ref()
+            .map(OpeningPosition::to_trade);
+        let redemption_trade = self
+            .make_redemption_trade_()
+            .filter(|trade| trade.date.date() <= end);
+        if opening_trade.is_none() && redemption_trade.is_none() {
+            return Cow::Borrowed(&self.trades);
+        }
+        Cow::Owned(
+            opening_trade
+                .into_iter()
+                .chain(self.trades.iter().cloned())
+                .chain(redemption_trade)
+                .collect(),
+        )
+    }
+
+    //
+    // a bond still held is sold at its face value the day after maturity
+    // so the last coupon (recorded at maturity) is paid
+    fn make_redemption_trade_(&self) -> Option<Trade> {
+        let InstrumentType::Bond(bond) = &self.instrument.instrument_type else {
+            return None;
+        };
+        let quantity = self
+            .opening_position
+            .as_ref()
+            .map_or(0.0, |opening_position| opening_position.quantity)
+            + self
+                .trades
+                .iter()
+                .map(|trade| trade.quantity * if trade.way == Way::Buy { 1.0 } else { -1.0 })
+                .sum::<f64>();
+        if quantity < QUANTITY_EPSILON {
+            return None;
+        }
+        Some(Trade {
+            date: bond.maturity.succ_opt()?.and_hms_opt(0, 0, 0)?,
+            way: Way::Sell,
+            quantity,
+            price: bond.face_value * self.instrument.quote_factor,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        })
+    }
+
+    //
+    // the opening position date or the first trade one, a redemption is never the first
+    pub fn get_open_date(&self) -> Option<DateTime> {
+        self.opening_position
+            .as_ref()
+            .map(|opening_position| opening_position.date)
+            .or_else(|| self.trades.first().map(|trade| trade.date))
+    }
+
+    pub fn get_close_date(&self, end: Date, epsilon: f64) -> Option<DateTime> {
+        let trades = self.priced_trades(end);
+        let quantity: f64 = trades
             .iter()
             .map(|trade| trade.quantity * if trade.way == Way::Buy { -1.0 } else { 1.0 })
             .sum();
-        if quantity.abs() < 1e-7 {
-            self.trades.last().map(|trade| trade.date)
+        if quantity.abs() < epsilon {
+            trades.last().map(|trade| trade.date)
         } else {
             None
         }
     }
+
+    //
+    // cash of the trades at the close date (the opening position is transferred
+    // in, not paid) and of each dividend at its payment date, converted at the
+    // trade fx rates (1.0 without), only the dividends of the instrument are known
+    pub fn realized_cash(&self, end: Date) -> Vec<CashVariation> {
+        let trades = self.priced_trades(end);
+        let Some(close_date) = trades.last().map(|trade| trade.date) else {
+            return Vec::new();
+        };
+        let traded = trades
+            .iter()
+            .skip(usize::from(self.opening_position.is_some()))
+            .map(|trade| {
+                let amount = match trade.way {
+                    Way::Buy => -trade.price * trade.quantity - trade.fees,
+                    Way::Sell => trade.price * trade.quantity - trade.fees,
+                };
+                amount * trade.fx_rate.unwrap_or(1.0)
+            })
+            .sum::<f64>();
+        let dividends = self
+            .instrument
+            .dividends
+            .iter()
+            .flatten()
+            .filter(|dividend| dividend.payment_date.date() <= end)
+            .filter_map(|dividend| {
+                let held_trades = trades
+                    .iter()
+                    .filter(|trade| trade.date.date() <= dividend.record_date.date());
+                let quantity = held_trades
+                    .clone()
+                    .map(|trade| trade.quantity * if trade.way == Way::Buy { 1.0 } else { -1.0 })
+                    .sum::<f64>();
+                (quantity > QUANTITY_EPSILON).then(|| CashVariation {
+                    position: dividend.value * quantity,
+                    date: dividend.payment_date,
+                    source: CashVariationSource::Realized,
+                    account: None,
+                    fx_rate: held_trades.rev().find_map(|trade| trade.fx_rate),
+                })
+            });
+        std::iter::once(CashVariation {
+            position: traded,
+            date: close_date,
+            source: CashVariationSource::Realized,
+            account: None,
+            fx_rate: None,
+        })
+        .chain(dividends)
+        .collect()
+    }
+
+    //
+    // the opening position and a bond redemption (before end) are orders on their own
+    pub fn get_orders(&self, end: Date) -> Vec<Order<'_>> {
+        let make_order_ = |trade: Trade| Order {
+            order_id: None,
+            trades: vec![Cow::Owned(trade)],
+        };
+        self.opening_position
+            .as_ref()
+            .map(|opening_position| make_order_(opening_position.to_trade()))
+            .into_iter()
+            .chain(Order::from_trades(&self.trades))
+            .chain(
+                self.make_redemption_trade_()
+                    .filter(|trade| trade.date.date() <= end)
+                    .map(make_order_),
+            )
+            .collect()
+    }
 }
@@ -1,11 +1,30 @@
 use super::Way;
 use crate::alias::DateTime;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Trade {
     pub date: DateTime,
     pub way: Way,
     pub quantity: f64,
     pub price: f64,
     pub fees: f64,
+    pub order_id: Option<String>,
+    pub fx_rate: Option<f64>,
+}
+
+impl Trade {
+    //
+    // trades are sorted by date, on the same instant buys come before sells,
+    // the sort being stable the input order is kept otherwise
+    pub fn cmp_chronological(&self, other: &Trade) -> std::cmp::Ordering {
+        self.date
+            .cmp(&other.date)
+            .then_with(|| self.way.cmp(&other.way))
+    }
+
+    //
+    // a standalone fee or tax posted by the broker, a pure cost whatever its way
+    pub fn is_fee_only(&self) -> bool {
+        self.quantity == 0.0
+    }
 }
@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq, Eq)]
+//
+// declaration order is the tiebreak of trades on the same instant
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Way {
     Buy,
     Sell,
@@ -0,0 +1,187 @@
+use super::{primitive, PortfolioIndicators};
+use crate::alias::Date;
+
+const DAYS_PER_YEAR: f64 = 365.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Annualized {
+    pub value: f64,
+    // false when the guard on history shorter than a year keeps the raw value
+    pub is_annualized: bool,
+}
+
+impl PortfolioIndicators {
+    pub fn days_held(&self) -> i64 {
+        match (self.portfolios.first(), self.portfolios.last()) {
+            (Some(first), Some(last)) => (last.date - first.date).num_days(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_annualized(&self) -> bool {
+        !self.options.no_annualize_under_one_year || self.days_held() as f64 >= DAYS_PER_YEAR
+    }
+
+    //
+    // compound annual growth of the twr
+    pub fn cagr(&self) -> Option<Annualized> {
+        let twr = self.portfolios.last()?.twr;
+        let days_held = self.days_held();
+        if days_held <= 0 {
+            return None;
+        }
+        Some(if self.is_annualized() {
+            Annualized {
+                value: primitive::annualize(twr, days_held as f64 / DAYS_PER_YEAR),
+                is_annualized: true,
+            }
+        } else {
+            Annualized {
+                value: twr,
+                is_annualized: false,
+            }
+        })
+    }
+
+    //
+    // cagr over the max drawdown of the twr, none without drawdown
+    pub fn calmar_ratio(&self) -> Option<Annualized> {
+        let cagr = self.cagr()?;
+        let wealth = self
+            .portfolios
+            .iter()
+            .filter(|indicator| !indicator.excluded)
+            .map(|indicator| 1.0 + indicator.twr)
+            .collect::<Vec<_>>();
+        let max_drawdown = primitive::max_drawdown(&wealth);
+        if max_drawdown.abs() < 1e-7 {
+            return None;
+        }
+        Some(Annualized {
+            value: cagr.value / max_drawdown.abs(),
+            is_annualized: cagr.is_annualized,
+        })
+    }
+
+    //
+    // annualized volatility of the daily twr returns
+    pub fn volatility(&self) -> Option<f64> {
+        let returns = self.daily_returns_(Date::MIN);
+        if returns.len() < self.options.min_observations {
+            return None;
+        }
+        primitive::volatility(&returns, self.options.trading_days_per_year)
+    }
+
+    //
+    // one line summary of the run ex: "PTF: +12.3% (ann 8.1%), vol 14.2%, Sharpe 0.57"
+    pub fn headline(&self) -> String {
+        let Some(last) = self.portfolios.last() else {
+            return String::from("PTF: no data");
+        };
+        let format_percent = |value: Option<f64>| {
+            value.map_or_else(
+                || String::from("n/a"),
+                |value| format!("{:.1}%", value * 100.0),
+            )
+        };
+        format!(
+            "PTF: {:+.1}% (ann {}), vol {}, Sharpe {}",
+            last.twr * 100.0,
+            format_percent(self.cagr().map(|cagr| cagr.value)),
+            format_percent(self.volatility()),
+            self.sharpe_ratio()
+                .map_or_else(|| String::from("n/a"), |value| format!("{value:.2}"))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricer::{PortfolioIndicator, PricingOptions};
+    use assert_float_eq::*;
+
+    fn make_indicators_(nb_days: usize, no_annualize_under_one_year: bool) -> PortfolioIndicators {
+        let begin = Date::from_ymd_opt(2022, 1, 1).unwrap();
+        let portfolios = begin
+            .iter_days()
+            .take(nb_days + 1)
+            .enumerate()
+            .map(|(i, date)| PortfolioIndicator {
+                date,
+                twr: if i == nb_days / 2 {
+                    -0.05
+                } else {
+                    0.1 * i as f64 / nb_days as f64
+                },
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        PortfolioIndicators {
+            begin,
+            end: portfolios.last().unwrap().date,
+            options: PricingOptions {
+                no_annualize_under_one_year,
+                ..Default::default()
+            },
+            portfolios,
+        }
+    }
+
+    #[test]
+    fn annualize_guard_under_one_year() {
+        let indicators = make_indicators_(100, true);
+        assert_eq!(indicators.days_held(), 100);
+        let cagr = indicators.cagr().unwrap();
+        assert!(!cagr.is_annualized);
+        assert_float_absolute_eq!(cagr.value, 0.1, 1e-7);
+        let calmar = indicators.calmar_ratio().unwrap();
+        assert!(!calmar.is_annualized);
+        assert_float_absolute_eq!(calmar.value, 0.1 / (1.0 - 0.95 / 1.049), 1e-7);
+
+        let cagr = make_indicators_(100, false).cagr().unwrap();
+        assert!(cagr.is_annualized);
+        assert_float_absolute_eq!(cagr.value, 1.1_f64.powf(365.0 / 100.0) - 1.0, 1e-7);
+
+        let cagr = make_indicators_(730, true).cagr().unwrap();
+        assert!(cagr.is_annualized);
+        assert_float_absolute_eq!(cagr.value, 1.1_f64.sqrt() - 1.0, 1e-7);
+    }
+
+    #[test]
+    fn volatility_scales_with_trading_days() {
+        let mut indicators = make_indicators_(100, false);
+        let volatility = indicators.volatility().unwrap();
+        let sharpe_ratio = indicators.sharpe_ratio().unwrap();
+
+        indicators.options.trading_days_per_year = 260.0;
+        let scale = (260.0_f64 / 252.0).sqrt();
+        assert_float_relative_eq!(indicators.volatility().unwrap(), volatility * scale, 1e-9);
+        assert_float_relative_eq!(
+            indicators.sharpe_ratio().unwrap(),
+            sharpe_ratio * scale,
+            1e-9
+        );
+    }
+
+    #[test]
+    fn headline_format() {
+        let indicators = make_indicators_(730, false);
+        let headline = indicators.headline();
+        assert_eq!(
+            headline,
+            format!(
+                "PTF: +10.0% (ann 4.9%), vol {:.1}%, Sharpe {:.2}",
+                indicators.volatility().unwrap() * 100.0,
+                indicators.sharpe_ratio().unwrap()
+            )
+        );
+
+        let mut indicators = make_indicators_(10, false);
+        assert!(indicators.volatility().is_none());
+        assert!(indicators.headline().ends_with("vol n/a, Sharpe n/a"));
+        indicators.portfolios.clear();
+        assert_eq!(indicators.headline(), "PTF: no data");
+    }
+}
@@ -0,0 +1,190 @@
+use super::{primitive, PortfolioIndicators};
+use crate::alias::Date;
+use crate::historical::DataFrame;
+use std::collections::BTreeMap;
+
+pub const DEFAULT_BETA_WINDOW_DAYS: u64 = 90;
+
+pub struct RollingBeta {
+    pub date: Date,
+    pub beta: Option<f64>,
+    pub correlation: Option<f64>,
+}
+
+impl PortfolioIndicators {
+    //
+    // daily twr returns with the benchmark close returns of the same day, a
+    // return is kept when the benchmark is quoted on both days
+    fn benchmark_returns_(&self, benchmark: &[DataFrame]) -> Vec<(Date, f64, f64)> {
+        let closes = benchmark
+            .iter()
+            .map(|spot| (spot.date, spot.close))
+            .collect::<BTreeMap<_, _>>();
+        self.dated_daily_returns_(Date::MIN)
+            .into_iter()
+            .filter_map(|(previous_date, date, value)| {
+                let begin = closes
+                    .get(&previous_date)
+                    .filter(|close| close.abs() > 1e-7)?;
+                let end = closes.get(&date)?;
+                Some((date, value, end / begin - 1.0))
+            })
+            .collect()
+    }
+
+    //
+    // beta of the daily twr returns against the benchmark close returns over the
+    // trailing window_days, blank under min_observations returns in the window
+    pub fn rolling_beta(&self, benchmark: &[DataFrame], window_days: u64) -> Vec<RollingBeta> {
+        let returns = self.benchmark_returns_(benchmark);
+        self.portfolios
+            .iter()
+            .filter(|indicator| !indicator.excluded)
+            .map(|indicator| {
+                let window_begin = indicator
+                    .date
+                    .checked_sub_days(chrono::Days::new(window_days))
+                    .unwrap_or(Date::MIN);
+                let (window_returns, window_benchmark_returns): (Vec<f64>, Vec<f64>) = returns
+                    .iter()
+                    .filter(|(date, _, _)| window_begin < *date && *date <= indicator.date)
+                    .map(|(_, value, benchmark_value)| (*value, *benchmark_value))
+                    .unzip();
+                if window_returns.len() < self.options.min_observations {
+                    return RollingBeta {
+                        date: indicator.date,
+                        beta: None,
+                        correlation: None,
+                    };
+                }
+                RollingBeta {
+                    date: indicator.date,
+                    beta: primitive::beta(&window_returns, &window_benchmark_returns),
+                    correlation: primitive::correlation(&window_returns, &window_benchmark_returns),
+                }
+            })
+            .collect()
+    }
+
+    //
+    // tracking error and information ratio of the daily twr returns against
+    // the benchmark ones over the whole pricing
+    pub fn active_return(&self, benchmark: &[DataFrame]) -> Option<primitive::ActiveReturn> {
+        let (returns, benchmark_returns): (Vec<f64>, Vec<f64>) = self
+            .benchmark_returns_(benchmark)
+            .into_iter()
+            .map(|(_, value, benchmark_value)| (value, benchmark_value))
+            .unzip();
+        if returns.len() < self.options.min_observations {
+            return None;
+        }
+        primitive::active_return(
+            &returns,
+            &benchmark_returns,
+            self.options.trading_days_per_year,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricer::{PortfolioIndicator, PricingOptions};
+    use assert_float_eq::*;
+
+    #[test]
+    fn beta_shift_when_diverging() {
+        let begin = Date::from_ymd_opt(2022, 1, 1).unwrap();
+        let dates = begin.iter_days().take(81).collect::<Vec<_>>();
+        let benchmark_returns = (0..80)
+            .map(|i| 0.01 * ((i as f64) * 0.7).sin())
+            .collect::<Vec<_>>();
+
+        //
+        // tracks the benchmark the first 40 days then twice its moves
+        let mut benchmark = vec![DataFrame::new(dates[0], 100.0, 100.0, 100.0, 100.0)];
+        let mut portfolios = vec![PortfolioIndicator {
+            date: dates[0],
+            ..Default::default()
+        }];
+        for (i, benchmark_return) in benchmark_returns.iter().enumerate() {
+            let close = benchmark.last().unwrap().close * (1.0 + benchmark_return);
+            benchmark.push(DataFrame::new(dates[i + 1], close, close, close, close));
+            let factor = if i < 40 { 1.0 } else { 2.0 };
+            let twr =
+                (1.0 + portfolios.last().unwrap().twr) * (1.0 + factor * benchmark_return) - 1.0;
+            portfolios.push(PortfolioIndicator {
+                date: dates[i + 1],
+                twr,
+                ..Default::default()
+            });
+        }
+        let indicators = PortfolioIndicators {
+            begin,
+            end: dates[80],
+            options: PricingOptions {
+                min_observations: 10,
+                ..Default::default()
+            },
+            portfolios,
+        };
+
+        let rolling_beta = indicators.rolling_beta(&benchmark, 20);
+        assert_eq!(rolling_beta.len(), 81);
+        assert!(rolling_beta[5].beta.is_none());
+        assert_float_absolute_eq!(rolling_beta[40].beta.unwrap(), 1.0, 1e-7);
+        assert_float_absolute_eq!(rolling_beta[40].correlation.unwrap(), 1.0, 1e-7);
+        assert_float_absolute_eq!(rolling_beta[80].beta.unwrap(), 2.0, 1e-7);
+        assert!(rolling_beta[50].beta.unwrap() > 1.0);
+
+        //
+        // no overlap without benchmark quotes
+        let rolling_beta = indicators.rolling_beta(&benchmark[..5], 20);
+        assert!(rolling_beta.iter().all(|item| item.beta.is_none()));
+    }
+
+    #[test]
+    fn active_return_when_outperforming() {
+        let begin = Date::from_ymd_opt(2022, 1, 1).unwrap();
+        let dates = begin.iter_days().take(41).collect::<Vec<_>>();
+
+        //
+        // beats the benchmark by 0.1% a day give or take 0.05%
+        let mut benchmark = vec![DataFrame::new(dates[0], 100.0, 100.0, 100.0, 100.0)];
+        let mut portfolios = vec![PortfolioIndicator {
+            date: dates[0],
+            ..Default::default()
+        }];
+        for i in 0..40 {
+            let benchmark_return = 0.01 * ((i as f64) * 0.7).sin();
+            let active_return = 0.001 + 0.0005 * ((i as f64) * 1.3).cos();
+            let close = benchmark.last().unwrap().close * (1.0 + benchmark_return);
+            benchmark.push(DataFrame::new(dates[i + 1], close, close, close, close));
+            let twr = (1.0 + portfolios.last().unwrap().twr)
+                * (1.0 + benchmark_return + active_return)
+                - 1.0;
+            portfolios.push(PortfolioIndicator {
+                date: dates[i + 1],
+                twr,
+                ..Default::default()
+            });
+        }
+        let indicators = PortfolioIndicators {
+            begin,
+            end: dates[40],
+            options: PricingOptions {
+                min_observations: 10,
+                ..Default::default()
+            },
+            portfolios,
+        };
+
+        let active_return = indicators.active_return(&benchmark).unwrap();
+        assert!(active_return.tracking_error > 0.0);
+        assert!(active_return.information_ratio.unwrap() > 1.0);
+
+        //
+        // not enough benchmark quotes
+        assert!(indicators.active_return(&benchmark[..5]).is_none());
+    }
+}
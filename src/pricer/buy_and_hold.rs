@@ -0,0 +1,211 @@
+use super::{primitive, PortfolioIndicators};
+use crate::alias::Date;
+use std::collections::BTreeMap;
+
+pub struct BuyAndHoldIndicator {
+    pub date: Date,
+    pub valuation: f64,
+    pub nominal: f64,
+    pub twr: f64,
+}
+
+impl BuyAndHoldIndicator {
+    //
+    // each position open at the pricing begin keeps its quantity of that date,
+    // later trades are ignored, a closed position is still valued with its last
+    // known spot, values are in the portfolio currency
+    pub fn from_portfolios(indicators: &PortfolioIndicators) -> Vec<Self> {
+        let Some(first) = indicators
+            .portfolios
+            .iter()
+            .find(|indicator| indicator.date >= indicators.begin)
+        else {
+            return Vec::new();
+        };
+        //
+        // position index -> (quantity, home nominal, last home unit value)
+        let mut held = first
+            .positions
+            .iter()
+            .filter(|position_indicator| {
+                !position_indicator.is_close
+                    && position_indicator.quantity.abs() >= indicators.options.quantity_epsilon
+            })
+            .map(|position_indicator| {
+                (
+                    position_indicator.position_index,
+                    (
+                        position_indicator.quantity,
+                        position_indicator.home_nominal,
+                        0.0,
+                    ),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        let nominal = held.values().map(|(_, nominal, _)| nominal).sum::<f64>();
+
+        let mut result: Vec<Self> = Vec::new();
+        for indicator in indicators
+            .portfolios
+            .iter()
+            .filter(|indicator| indicator.date >= first.date)
+        {
+            for position_indicator in &indicator.positions {
+                if let Some((_, _, unit_value)) = held.get_mut(&position_indicator.position_index) {
+                    *unit_value = indicators
+                        .options
+                        .valuation_price
+                        .price(&position_indicator.spot)
+                        * position_indicator.instrument.quote_factor
+                        * position_indicator.fx_rate;
+                }
+            }
+            let valuation = held
+                .values()
+                .map(|(quantity, _, unit_value)| quantity * unit_value)
+                .sum::<f64>();
+
+            let (previous_twr, begin_valuation) = if let Some(previous) = result.last() {
+                (previous.twr, previous.valuation)
+            } else {
+                (0.0, nominal)
+            };
+
+            result.push(Self {
+                date: indicator.date,
+                valuation,
+                nominal,
+                twr: primitive::twr(begin_valuation, valuation, 0.0, previous_twr),
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn buy_and_hold_without_trade() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 17);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("ESE", &currency)),
+                trades: vec![Trade {
+                    date: begin.and_hms_opt(10, 0, 0).unwrap(),
+                    way: Way::Buy,
+                    quantity: 10.0,
+                    price: 20.0,
+                    fees: 1.5,
+                    order_id: None,
+                    fx_rate: None,
+                }],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::new(
+            [20.5, 19.0, 21.0, 22.5, 21.5]
+                .into_iter()
+                .zip(begin.iter_days())
+                .map(|(value, date)| DataFrame::new(date, value, value, value, value))
+                .collect(),
+        );
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            make_date_(2022, 3, 21),
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let buy_and_hold = BuyAndHoldIndicator::from_portfolios(&indicators);
+        assert_eq!(buy_and_hold.len(), 5);
+        for (item, indicator) in buy_and_hold.iter().zip(indicators.portfolios.iter()) {
+            let position = &indicator.positions[0];
+            assert_eq!(item.date, indicator.date);
+            assert_float_absolute_eq!(item.valuation, position.valuation, 1e-7);
+            assert_float_absolute_eq!(item.nominal, position.nominal, 1e-7);
+            assert_float_absolute_eq!(item.twr, position.twr, 1e-7);
+        }
+    }
+
+    #[test]
+    fn buy_and_hold_of_begin_allocation() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 17);
+        let make_trade_ = |date: Date, way, quantity| Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price: 20.0,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        };
+        let make_position_ = |name, trades| Position {
+            instrument: Rc::new(make_instrument_(name, &currency)),
+            trades,
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        //
+        // the sell and the position opened after the begin are ignored
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                make_position_(
+                    "ESE",
+                    vec![
+                        make_trade_(begin, Way::Buy, 10.0),
+                        make_trade_(make_date_(2022, 3, 19), Way::Sell, 5.0),
+                    ],
+                ),
+                make_position_(
+                    "C40",
+                    vec![make_trade_(make_date_(2022, 3, 18), Way::Buy, 4.0)],
+                ),
+            ],
+            cash: Default::default(),
+        };
+        let spots = [20.5, 19.0, 21.0, 22.5, 21.5];
+        let mut provider = MockProvider::new(
+            spots
+                .into_iter()
+                .zip(begin.iter_days())
+                .map(|(value, date)| DataFrame::new(date, value, value, value, value))
+                .collect(),
+        );
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            make_date_(2022, 3, 21),
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let buy_and_hold = BuyAndHoldIndicator::from_portfolios(&indicators);
+        assert_eq!(buy_and_hold.len(), spots.len());
+        for (item, spot) in buy_and_hold.iter().zip(spots) {
+            assert_float_absolute_eq!(item.valuation, 10.0 * spot, 1e-7);
+            assert_float_absolute_eq!(item.nominal, 200.0, 1e-7);
+        }
+        assert_float_absolute_eq!(buy_and_hold[4].twr, 21.5 / 20.0 - 1.0, 1e-7);
+    }
+}
@@ -0,0 +1,300 @@
+use super::{PortfolioIndicators, PositionIndicator};
+use crate::alias::Date;
+use crate::marketdata::Instrument;
+use crate::portfolio::{CashVariationSource, Portfolio, Way};
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CashMovementKind {
+    Transfer,
+    // cash of a position closed before the pricing and not priced
+    Realized,
+    Buy,
+    Sell,
+    Dividend,
+    Interest,
+    FxRevaluation,
+}
+
+impl std::fmt::Display for CashMovementKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Self::Transfer => "Transfer",
+            Self::Realized => "Realized",
+            Self::Buy => "Buy",
+            Self::Sell => "Sell",
+            Self::Dividend => "Dividend",
+            Self::Interest => "Interest",
+            Self::FxRevaluation => "FxRevaluation",
+        };
+        value.fmt(f)
+    }
+}
+
+pub struct CashMovement {
+    pub date: Date,
+    pub kind: CashMovementKind,
+    pub instrument: Option<Rc<Instrument>>,
+    pub amount: f64,
+    pub balance: f64,
+}
+
+pub struct CashLedger {
+    pub movements: Vec<CashMovement>,
+}
+
+impl CashLedger {
+    //
+    // same cash definition than PortfolioIndicator: transfers, trades
+    // notional with fees on settlement date, dividends paid in cash, interest
+    // on cash and revaluation of the tagged accounts (both taken from the
+    // priced indicators as they depend on the pricing dates)
+    pub fn from_portfolio(portfolio: &Portfolio, indicators: &PortfolioIndicators) -> Self {
+        let end = indicators.end;
+        let options = &indicators.options;
+        let mut movements = Vec::new();
+
+        for variation in portfolio.cash.iter() {
+            movements.push(CashMovement {
+                date: variation.date.date(),
+                kind: match variation.source {
+                    CashVariationSource::Payment => CashMovementKind::Transfer,
+                    CashVariationSource::Realized => CashMovementKind::Realized,
+                },
+                instrument: None,
+                amount: variation.home_position(),
+                balance: 0.0,
+            });
+        }
+
+        for position in portfolio.positions.iter() {
+            //
+            // an opening position (first priced trade) is transferred in, not paid
+            let priced_trades = position.priced_trades(end);
+            for trade in priced_trades
+                .iter()
+                .skip(usize::from(position.opening_position.is_some()))
+            {
+                let (kind, amount) = match trade.way {
+                    Way::Buy => (
+                        CashMovementKind::Buy,
+                        -trade.price * trade.quantity - trade.fees,
+                    ),
+                    Way::Sell => (
+                        CashMovementKind::Sell,
+                        trade.price * trade.quantity - trade.fees,
+                    ),
+                };
+                if let Some(date) = trade
+                    .date
+                    .date()
+                    .checked_add_days(chrono::Days::new(options.settlement_lag_days))
+                {
+                    movements.push(CashMovement {
+                        date,
+                        kind,
+                        instrument: Some(position.instrument.clone()),
+                        amount,
+                        balance: 0.0,
+                    });
+                }
+            }
+
+            if options.reinvest_dividends {
+                continue;
+            }
+            for dividend in options.dividends(&position.instrument) {
+                let quantity = PositionIndicator::compute_settled_quantity_(
+                    &priced_trades,
+                    dividend.record_date.date(),
+                    options,
+                );
+                if quantity.abs() > options.quantity_epsilon {
+                    movements.push(CashMovement {
+                        date: dividend.payment_date.date(),
+                        kind: CashMovementKind::Dividend,
+                        instrument: Some(position.instrument.clone()),
+                        amount: dividend.value * quantity,
+                        balance: 0.0,
+                    });
+                }
+            }
+        }
+
+        let mut previous = (0.0, 0.0);
+        for indicator in indicators.portfolios.iter() {
+            for (kind, amount) in [
+                (
+                    CashMovementKind::Interest,
+                    indicator.cash_interest - previous.0,
+                ),
+                (
+                    CashMovementKind::FxRevaluation,
+                    indicator.account_revaluation - previous.1,
+                ),
+            ] {
+                if amount.abs() > 1e-7 {
+                    movements.push(CashMovement {
+                        date: indicator.date,
+                        kind,
+                        instrument: None,
+                        amount,
+                        balance: 0.0,
+                    });
+                }
+            }
+            previous = (indicator.cash_interest, indicator.account_revaluation);
+        }
+
+        movements.retain(|movement| movement.date <= end);
+        movements.sort_by_key(|movement| movement.date);
+
+        let mut balance = 0.0;
+        for movement in movements.iter_mut() {
+            balance += movement.amount;
+            movement.balance = balance;
+        }
+
+        Self { movements }
+    }
+
+    pub fn balance(&self) -> f64 {
+        self.movements
+            .last()
+            .map_or(0.0, |movement| movement.balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::marketdata::Dividend;
+    use crate::portfolio::{CashVariation, CashVariationSource, Position, Trade};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+
+    fn make_trade_(date: Date, way: Way, quantity: f64, price: f64, fees: f64) -> Trade {
+        Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    fn make_portfolio_() -> Portfolio {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(Instrument {
+            dividends: Some(vec![Dividend {
+                record_date: make_date_(2022, 3, 20).and_hms_opt(0, 0, 0).unwrap(),
+                payment_date: make_date_(2022, 3, 22).and_hms_opt(0, 0, 0).unwrap(),
+                value: 0.5,
+            }]),
+            ..make_instrument_("PAEEM", &currency)
+        });
+        Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![Position {
+                instrument,
+                trades: vec![
+                    make_trade_(make_date_(2022, 3, 17), Way::Buy, 14.0, 21.5, 1.55),
+                    make_trade_(make_date_(2022, 3, 19), Way::Buy, 20.0, 19.5, 1.5),
+                    make_trade_(make_date_(2022, 3, 24), Way::Sell, 10.0, 22.5, 0.7),
+                ],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: vec![CashVariation {
+                position: 2000.0,
+                date: make_date_(2022, 3, 15).and_hms_opt(9, 0, 0).unwrap(),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn ledger_reconcile_with_cash() {
+        let portfolio = make_portfolio_();
+        let begin = make_date_(2022, 3, 17);
+        let end = make_date_(2022, 3, 25);
+        let options = PricingOptions::default();
+        let mut provider = MockProvider::new(vec![DataFrame::new(begin, 20.0, 20.0, 20.0, 20.0)]);
+        let indicators =
+            PortfolioIndicators::from_portfolio(&portfolio, begin, end, &mut provider, &options)
+                .unwrap();
+
+        let ledger = CashLedger::from_portfolio(&portfolio, &indicators);
+        assert_eq!(ledger.movements.len(), 5);
+        assert_eq!(ledger.movements[0].kind, CashMovementKind::Transfer);
+        assert_eq!(ledger.movements[3].kind, CashMovementKind::Dividend);
+        assert_float_absolute_eq!(ledger.movements[3].amount, 17.0, 1e-7);
+        assert_float_absolute_eq!(
+            ledger.balance(),
+            indicators.portfolios.last().unwrap().cash,
+            1e-7
+        );
+    }
+
+    #[test]
+    fn ledger_reconcile_with_interest_and_account() {
+        let mut portfolio = make_portfolio_();
+        //
+        // 500 paid in for about 694 bought, the rest is borrowed
+        portfolio.cash[0].position = 500.0;
+        portfolio.cash.extend([
+            CashVariation {
+                position: 100.0,
+                date: make_date_(2022, 3, 16).and_hms_opt(9, 0, 0).unwrap(),
+                source: CashVariationSource::Payment,
+                account: Some(String::from("USD")),
+                fx_rate: Some(0.9),
+            },
+            CashVariation {
+                position: -20.0,
+                date: make_date_(2022, 3, 23).and_hms_opt(9, 0, 0).unwrap(),
+                source: CashVariationSource::Payment,
+                account: Some(String::from("USD")),
+                fx_rate: Some(0.95),
+            },
+        ]);
+        let begin = make_date_(2022, 3, 17);
+        let end = make_date_(2022, 3, 25);
+        let options = PricingOptions {
+            margin_rate: 0.05,
+            ..Default::default()
+        };
+        let mut provider = MockProvider::new(vec![DataFrame::new(begin, 20.0, 20.0, 20.0, 20.0)]);
+        let indicators =
+            PortfolioIndicators::from_portfolio(&portfolio, begin, end, &mut provider, &options)
+                .unwrap();
+        let last = indicators.portfolios.last().unwrap();
+        assert!(last.cash_interest < 0.0);
+        assert_float_absolute_eq!(last.account_revaluation, 80.0 * 0.95 - (90.0 - 19.0), 1e-7);
+
+        let ledger = CashLedger::from_portfolio(&portfolio, &indicators);
+        let sum_ = |kind: CashMovementKind| {
+            ledger
+                .movements
+                .iter()
+                .filter(|movement| movement.kind == kind)
+                .map(|movement| movement.amount)
+                .sum::<f64>()
+        };
+        assert_float_absolute_eq!(sum_(CashMovementKind::Interest), last.cash_interest, 1e-7);
+        assert_float_absolute_eq!(
+            sum_(CashMovementKind::FxRevaluation),
+            last.account_revaluation,
+            1e-7
+        );
+        assert_float_absolute_eq!(ledger.balance(), last.cash, 1e-7);
+    }
+}
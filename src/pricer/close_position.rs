@@ -0,0 +1,270 @@
+use super::{primitive, PositionIndicator, PricingOptions};
+use crate::alias::Date;
+use crate::marketdata::Instrument;
+use crate::portfolio::{CloseReason, Position, Trade, Way};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+const DAYS_PER_YEAR: f64 = 365.0;
+
+#[derive(Debug)]
+pub struct Lot {
+    pub open_date: Date,
+    pub close_date: Date,
+    pub quantity: f64,
+    pub cost: f64,
+    pub proceeds: f64,
+}
+
+impl Lot {
+    pub fn holding_days(&self) -> i64 {
+        (self.close_date - self.open_date).num_days()
+    }
+
+    //
+    // fifo matching, buy fees are in the cost and sell fees reduce the proceeds,
+    // a residual quantity below epsilon is dropped
+    pub fn from_trades(trades: &[Trade], epsilon: f64) -> Vec<Lot> {
+        let mut opens: VecDeque<(Date, f64, f64)> = VecDeque::new();
+        let mut lots = Vec::new();
+        for trade in trades.iter().filter(|trade| !trade.is_fee_only()) {
+            let unit_fees = trade.fees / trade.quantity;
+            match trade.way {
+                Way::Buy => {
+                    opens.push_back((trade.date.date(), trade.quantity, trade.price + unit_fees))
+                }
+                Way::Sell => {
+                    let mut quantity = trade.quantity;
+                    while quantity > epsilon {
+                        let Some((open_date, open_quantity, unit_cost)) = opens.front_mut() else {
+                            break;
+                        };
+                        let matched = quantity.min(*open_quantity);
+                        lots.push(Lot {
+                            open_date: *open_date,
+                            close_date: trade.date.date(),
+                            quantity: matched,
+                            cost: matched * *unit_cost,
+                            proceeds: matched * (trade.price - unit_fees),
+                        });
+                        *open_quantity -= matched;
+                        quantity -= matched;
+                        if *open_quantity < epsilon {
+                            opens.pop_front();
+                        }
+                    }
+                }
+            }
+        }
+        lots
+    }
+}
+
+pub struct ClosePositionIndicator {
+    pub instrument: Rc<Instrument>,
+    pub position_index: usize,
+    pub open_date: Date,
+    pub close_date: Date,
+    pub quantity: f64,
+    pub nominal: f64,
+    pub dividends: f64,
+    pub fees: f64,
+    pub pnl_currency: f64,
+    pub pnl_percent: f64,
+    pub holding_days: f64,
+    pub pnl_percent_annualized: f64,
+    pub irr: Option<f64>,
+    pub close_reason: CloseReason,
+}
+
+impl ClosePositionIndicator {
+    pub fn from_position(
+        position: &Position,
+        indicator: &PositionIndicator,
+        options: &PricingOptions,
+    ) -> Option<ClosePositionIndicator> {
+        if !indicator.is_close {
+            return None;
+        }
+        let trades = position.priced_trades(indicator.date);
+        let open_date = trades.first()?.date.date();
+        let close_date = trades.last()?.date.date();
+
+        let lots = Lot::from_trades(&trades, options.quantity_epsilon);
+        let quantity = lots.iter().map(|lot| lot.quantity).sum::<f64>();
+        let nominal = lots.iter().map(|lot| lot.cost).sum::<f64>();
+        let holding_days = if quantity.abs() < options.quantity_epsilon {
+            0.0
+        } else {
+            let max_holding_days = (close_date - open_date).num_days() as f64;
+            (lots
+                .iter()
+                .map(|lot| lot.quantity * lot.holding_days() as f64)
+                .sum::<f64>()
+                / quantity)
+                .clamp(0.0, max_holding_days)
+        };
+        let pnl_percent = if nominal.abs() < 1e-7 {
+            0.0
+        } else {
+            indicator.earning / nominal
+        };
+        let pnl_percent_annualized =
+            primitive::annualize(pnl_percent, holding_days / DAYS_PER_YEAR);
+
+        Some(ClosePositionIndicator {
+            instrument: position.instrument.clone(),
+            position_index: indicator.position_index,
+            open_date,
+            close_date,
+            quantity,
+            nominal,
+            dividends: indicator.dividends,
+            fees: indicator.fees,
+            pnl_currency: indicator.earning,
+            pnl_percent,
+            holding_days,
+            pnl_percent_annualized,
+            irr: indicator.irr(position, options),
+            close_reason: position.close_reason.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::portfolio::QUANTITY_EPSILON;
+    use crate::pricer::fixture::{make_currency_, make_instrument_};
+    use assert_float_eq::*;
+
+    fn make_trade_(date: &str, way: Way, quantity: f64, price: f64) -> Trade {
+        Trade {
+            date: chrono::DateTime::parse_from_rfc3339(date)
+                .unwrap()
+                .naive_local(),
+            way,
+            quantity,
+            price,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    fn make_position_(trades: Vec<Trade>) -> Position {
+        Position {
+            instrument: Rc::new(make_instrument_("PAEEM", &make_currency_("EUR"))),
+            trades,
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        }
+    }
+
+    #[test]
+    fn holding_days_weighted_by_quantity() {
+        let position = make_position_(vec![
+            make_trade_("2022-03-01T10:00:00-00:00", Way::Buy, 10.0, 20.0),
+            make_trade_("2022-03-11T10:00:00-00:00", Way::Buy, 30.0, 22.0),
+            make_trade_("2022-03-21T10:00:00-00:00", Way::Sell, 40.0, 25.0),
+        ]);
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 3, 21).unwrap();
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &DataFrame::new(date, 25.0, 25.0, 25.0, 25.0),
+            1.0,
+            &[],
+            &PricingOptions::default(),
+        );
+
+        let lots = Lot::from_trades(&position.trades, QUANTITY_EPSILON);
+        assert_eq!(lots.len(), 2);
+        assert_eq!(lots[0].holding_days(), 20);
+        assert_eq!(lots[1].holding_days(), 10);
+
+        let close_position = ClosePositionIndicator::from_position(
+            &position,
+            &indicator,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(close_position.open_date, position.trades[0].date.date());
+        assert_eq!(close_position.close_date, date);
+        assert_float_absolute_eq!(close_position.quantity, 40.0, 1e-7);
+        assert_float_absolute_eq!(close_position.nominal, 860.0, 1e-7);
+        assert_float_absolute_eq!(close_position.pnl_currency, 140.0, 1e-7);
+        assert_float_absolute_eq!(close_position.holding_days, 12.5, 1e-7);
+    }
+
+    #[test]
+    fn same_day_round_trip_annualized() {
+        let position = make_position_(vec![
+            make_trade_("2022-03-01T09:00:00-00:00", Way::Buy, 10.0, 20.0),
+            make_trade_("2022-03-01T17:00:00-00:00", Way::Sell, 10.0, 20.1),
+        ]);
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 3, 1).unwrap();
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &DataFrame::new(date, 20.1, 20.1, 20.1, 20.1),
+            1.0,
+            &[],
+            &PricingOptions::default(),
+        );
+
+        let close_position = ClosePositionIndicator::from_position(
+            &position,
+            &indicator,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_float_absolute_eq!(close_position.holding_days, 0.0, 1e-7);
+        assert_float_absolute_eq!(close_position.pnl_percent, 0.005, 1e-7);
+        assert!(close_position.pnl_percent_annualized.is_finite());
+        assert_float_relative_eq!(
+            close_position.pnl_percent_annualized,
+            1.005_f64.powf(365.0) - 1.0,
+            1e-7
+        );
+    }
+
+    #[test]
+    fn fully_sold_close_reason() {
+        let mut position = make_position_(vec![
+            make_trade_("2022-03-01T10:00:00-00:00", Way::Buy, 10.0, 20.0),
+            make_trade_("2022-03-21T10:00:00-00:00", Way::Sell, 10.0, 25.0),
+        ]);
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 3, 21).unwrap();
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &DataFrame::new(date, 25.0, 25.0, 25.0, 25.0),
+            1.0,
+            &[],
+            &PricingOptions::default(),
+        );
+
+        let close_position = ClosePositionIndicator::from_position(
+            &position,
+            &indicator,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(close_position.close_reason, CloseReason::Sell);
+
+        position.close_reason = Some(CloseReason::Merger);
+        let close_position = ClosePositionIndicator::from_position(
+            &position,
+            &indicator,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(close_position.close_reason, CloseReason::Merger);
+    }
+}
@@ -0,0 +1,109 @@
+use super::PortfolioIndicators;
+use crate::alias::Date;
+use crate::historical::PriceSource;
+use crate::marketdata::Instrument;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub struct CoverageIndicator {
+    pub instrument: Rc<Instrument>,
+    // calendar days with an open position on the instrument
+    pub days_held: usize,
+    // days held priced with a spot of that day, not carried forward nor interpolated
+    pub fetched_days: usize,
+    pub coverage: f64,
+}
+
+impl CoverageIndicator {
+    pub fn from_portfolios(indicators: &PortfolioIndicators) -> Vec<Self> {
+        //
+        // several positions on an instrument count once a day
+        let mut by_instrument: BTreeMap<&String, (Rc<Instrument>, BTreeMap<Date, bool>)> =
+            Default::default();
+        for portfolio in indicators.portfolios.iter() {
+            for position in portfolio.positions.iter().filter(|item| !item.is_close) {
+                let (_, days) = by_instrument
+                    .entry(&position.instrument.name)
+                    .or_insert_with(|| (position.instrument.clone(), Default::default()));
+                *days.entry(portfolio.date).or_default() |=
+                    PriceSource::from_spot(&position.spot, portfolio.date) == PriceSource::Fetched;
+            }
+        }
+
+        by_instrument
+            .into_values()
+            .map(|(instrument, days)| {
+                let days_held = days.len();
+                let fetched_days = days.values().filter(|fetched| **fetched).count();
+                CoverageIndicator {
+                    instrument,
+                    days_held,
+                    fetched_days,
+                    coverage: fetched_days as f64 / days_held as f64,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+
+    #[test]
+    fn half_days_fetched() {
+        let currency = make_currency_("EUR");
+        let begin = Date::from_ymd_opt(2022, 3, 1).unwrap();
+        let end = Date::from_ymd_opt(2022, 3, 10).unwrap();
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("PAEEM", &currency)),
+                trades: vec![Trade {
+                    date: begin.and_hms_opt(10, 0, 0).unwrap(),
+                    way: Way::Buy,
+                    quantity: 10.0,
+                    price: 20.0,
+                    fees: 0.0,
+                    order_id: None,
+                    fx_rate: None,
+                }],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        //
+        // a spot every other day, the others are carried forward
+        let mut provider = MockProvider::new(
+            begin
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .step_by(2)
+                .map(|date| DataFrame::new(date, 20.0, 20.0, 20.0, 20.0))
+                .collect(),
+        );
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let coverages = CoverageIndicator::from_portfolios(&indicators);
+        assert_eq!(coverages.len(), 1);
+        assert_eq!(coverages[0].instrument.name, "PAEEM");
+        assert_eq!(coverages[0].days_held, 10);
+        assert_eq!(coverages[0].fetched_days, 5);
+        assert_float_absolute_eq!(coverages[0].coverage, 0.5, 1e-7);
+    }
+}
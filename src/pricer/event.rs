@@ -0,0 +1,129 @@
+use super::PortfolioIndicators;
+use crate::alias::Date;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event {
+    Opened,
+    Closed,
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Self::Opened => "Opened",
+            Self::Closed => "Closed",
+        };
+        value.fmt(f)
+    }
+}
+
+impl PortfolioIndicators {
+    pub fn position_events(&self) -> Vec<(Date, String, Event)> {
+        let mut opens: HashMap<(&String, usize), bool> = Default::default();
+        let mut events = Vec::new();
+        for portfolio in self.portfolios.iter() {
+            for position in portfolio.positions.iter() {
+                let is_open = opens
+                    .entry((&position.instrument.name, position.position_index))
+                    .or_default();
+                if !*is_open && !position.is_close {
+                    events.push((
+                        position.date,
+                        position.instrument.name.clone(),
+                        Event::Opened,
+                    ));
+                } else if *is_open && position.is_close {
+                    events.push((
+                        position.date,
+                        position.instrument.name.clone(),
+                        Event::Closed,
+                    ));
+                }
+                *is_open = !position.is_close;
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+    use std::rc::Rc;
+
+    fn make_trade_(date: Date, way: Way, quantity: f64, price: f64) -> Trade {
+        Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees: 1.0,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    fn make_portfolio_() -> Portfolio {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(make_instrument_("ESE", &currency));
+        Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![
+                Position {
+                    instrument: instrument.clone(),
+                    trades: vec![
+                        make_trade_(make_date_(2022, 3, 17), Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 19), Way::Sell, 10.0, 21.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument,
+                    trades: vec![
+                        make_trade_(make_date_(2022, 3, 21), Way::Buy, 5.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 23), Way::Sell, 5.0, 19.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+            ],
+            cash: Default::default(),
+        }
+    }
+
+    #[test]
+    fn open_close_events() {
+        let portfolio = make_portfolio_();
+        let begin = make_date_(2022, 3, 17);
+        let end = make_date_(2022, 3, 25);
+        let mut provider = MockProvider::new(vec![DataFrame::new(begin, 20.0, 20.0, 20.0, 20.0)]);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let events = indicators.position_events();
+        assert_eq!(
+            events,
+            vec![
+                (make_date_(2022, 3, 17), String::from("ESE"), Event::Opened),
+                (make_date_(2022, 3, 19), String::from("ESE"), Event::Closed),
+                (make_date_(2022, 3, 21), String::from("ESE"), Event::Opened),
+                (make_date_(2022, 3, 23), String::from("ESE"), Event::Closed),
+            ]
+        );
+    }
+}
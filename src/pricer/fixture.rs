@@ -0,0 +1,52 @@
+use crate::alias::Date;
+use crate::error::Error;
+use crate::historical::{DataFrame, Provider};
+use crate::marketdata::Instrument;
+use std::collections::HashMap;
+
+pub use crate::fixture::{make_currency_, make_date_, make_instrument_};
+
+//
+// spots are shared by all instruments unless the instrument has its own
+#[derive(Default)]
+pub struct MockProvider {
+    pub spots: Vec<DataFrame>,
+    pub instrument_spots: HashMap<String, Vec<DataFrame>>,
+}
+
+impl MockProvider {
+    pub fn new(spots: Vec<DataFrame>) -> Self {
+        Self {
+            spots,
+            ..Default::default()
+        }
+    }
+
+    pub fn by_instrument<'a, I>(instrument_spots: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, Vec<DataFrame>)>,
+    {
+        Self {
+            instrument_spots: instrument_spots
+                .into_iter()
+                .map(|(name, spots)| (String::from(name), spots))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Provider for MockProvider {
+    fn fetch(&mut self, _instrument: &Instrument, _begin: Date, _end: Date) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+        self.instrument_spots
+            .get(&instrument.name)
+            .unwrap_or(&self.spots)
+            .iter()
+            .rev()
+            .find(|item| item.date <= date)
+    }
+}
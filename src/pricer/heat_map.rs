@@ -3,12 +3,26 @@ use chrono::Datelike;
 use super::{PortfolioIndicator, PortfolioIndicators, PositionIndicator, PositionIndicators};
 use crate::alias::Date;
 
+#[derive(Clone, Copy)]
 pub enum HeatMapPeriod {
     Monthly,
     Yearly,
 }
 
 impl HeatMapPeriod {
+    //
+    // last day of each period between begin and end, end included
+    pub fn end_dates(&self, begin: Date, end: Date) -> Vec<Date> {
+        let mut dates: Vec<Date> = Vec::new();
+        for date in begin.iter_days().take_while(|date| *date <= end) {
+            match dates.last_mut() {
+                Some(last) if self.same(*last, date) => *last = date,
+                _ => dates.push(date),
+            }
+        }
+        dates
+    }
+
     fn same(&self, left: Date, right: Date) -> bool {
         match self {
             HeatMapPeriod::Monthly => left.month() == right.month() && left.year() == right.year(),
@@ -30,9 +44,17 @@ impl HeatMap {
     where
         T: Fn(&PortfolioIndicator) -> f64,
     {
-        Self::from_(&indicators.portfolios, period, get_value, |indicator| {
-            indicator.date
-        })
+        let portfolios = indicators
+            .portfolios
+            .iter()
+            .filter(|indicator| !indicator.excluded)
+            .collect::<Vec<_>>();
+        Self::from_(
+            &portfolios,
+            period,
+            |indicator| get_value(indicator),
+            |indicator| indicator.date,
+        )
     }
 
     pub fn from_positions<T>(
@@ -43,8 +65,14 @@ impl HeatMap {
     where
         T: Fn(&PositionIndicator) -> f64,
     {
+        let positions = indicators
+            .positions
+            .iter()
+            .filter(|indicator| !indicator.excluded)
+            .copied()
+            .collect::<Vec<_>>();
         Self::from_(
-            &indicators.positions,
+            &positions,
             period,
             |indicator| get_value(indicator),
             |indicator| indicator.date,
@@ -89,6 +117,22 @@ impl HeatMap {
 
         HeatMap { data, period }
     }
+
+    //
+    // one (year, period, value) by cell, period is the month (1-12)
+    // for a monthly heat map and 0 for a yearly one
+    pub fn to_long(&self) -> Vec<(i32, u32, f64)> {
+        self.data
+            .iter()
+            .map(|(date, value)| {
+                let period = match self.period {
+                    HeatMapPeriod::Monthly => date.month(),
+                    HeatMapPeriod::Yearly => 0,
+                };
+                (date.year(), period, *value)
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -180,6 +224,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn heat_map_long() {
+        let input = vec![
+            (make_date_(2023, 9, 25), 0.5),
+            (make_date_(2023, 9, 26), 0.6),
+            (make_date_(2023, 10, 31), 0.2),
+            (make_date_(2024, 1, 3), 0.8),
+        ];
+        let heat_map = HeatMap::from_(
+            &input,
+            HeatMapPeriod::Monthly,
+            |indicator| indicator.1,
+            |indicator| indicator.0,
+        );
+        let long = heat_map.to_long();
+        assert_eq!(long.len(), 3);
+        for (i, (wanted_year, wanted_month, wanted_value)) in [
+            (2023, 9, 0.6),
+            (2023, 10, (0.2 + 1.0) / (0.6 + 1.0) - 1.0),
+            (2024, 1, (0.8 + 1.0) / (0.2 + 1.0) - 1.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_eq!(long[i].0, wanted_year);
+            assert_eq!(long[i].1, wanted_month);
+            assert_float_absolute_eq!(long[i].2, wanted_value, 1e-7);
+        }
+    }
+
     #[test]
     fn heat_map_empty() {
         let input: Vec<(Date, f64)> = Default::default();
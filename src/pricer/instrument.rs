@@ -9,6 +9,8 @@ pub struct InstrumentIndicator {
 }
 
 impl InstrumentIndicator {
+    //
+    // percent of the open positions valuation, cash excluded whatever the weight base
     pub fn from_portfolio(indicator: &PortfolioIndicator) -> Vec<Self> {
         let instruments = indicator
             .positions
@@ -24,6 +26,9 @@ impl InstrumentIndicator {
             .map(|position| &position.valuation)
             .sum::<f64>();
 
+        let mut instruments = instruments.into_iter().collect::<Vec<_>>();
+        instruments.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
         instruments
             .into_iter()
             .map(|instrument| {
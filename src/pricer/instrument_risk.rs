@@ -0,0 +1,264 @@
+use super::{primitive, PortfolioIndicators};
+use crate::alias::Date;
+use crate::marketdata::Instrument;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+const TRAILING_DAYS: u64 = 365;
+const VOLUME_DAYS: usize = 20;
+
+pub struct InstrumentRiskIndicator {
+    pub instrument: Rc<Instrument>,
+    pub volatility: Option<f64>,
+    pub max_drawdown: Option<f64>,
+    pub average_volume: Option<f64>,
+}
+
+impl InstrumentRiskIndicator {
+    pub fn is_illiquid(&self, min_average_volume: f64) -> bool {
+        self.average_volume
+            .is_some_and(|volume| volume < min_average_volume)
+    }
+
+    //
+    // trailing one year of the instrument pnl (all its open positions),
+    // blank indicators below the minimum number of observations
+    pub fn from_portfolios(indicators: &PortfolioIndicators) -> Vec<Self> {
+        let Some(last) = indicators.portfolios.last() else {
+            return Vec::new();
+        };
+        let begin = last
+            .date
+            .checked_sub_days(chrono::Days::new(TRAILING_DAYS))
+            .unwrap_or(Date::MIN);
+
+        let mut series: BTreeMap<&String, (Rc<Instrument>, BTreeMap<Date, (f64, f64)>)> =
+            Default::default();
+        let mut volumes: BTreeMap<&String, BTreeMap<Date, f64>> = Default::default();
+        for portfolio in indicators
+            .portfolios
+            .iter()
+            .filter(|portfolio| portfolio.date > begin && !portfolio.excluded)
+        {
+            for position in portfolio.positions.iter().filter(|item| !item.is_close) {
+                let (_, values) = series
+                    .entry(&position.instrument.name)
+                    .or_insert_with(|| (position.instrument.clone(), Default::default()));
+                let value = values.entry(portfolio.date).or_default();
+                value.0 += position.valuation;
+                value.1 += position.nominal;
+                if let Some(volume) = position.spot.volume {
+                    volumes
+                        .entry(&position.instrument.name)
+                        .or_default()
+                        .insert(position.spot.date, volume);
+                }
+            }
+        }
+
+        let mut result = series
+            .into_values()
+            .map(|(instrument, values)| {
+                //
+                // average over the last quoted days with a volume
+                let average_volume = volumes.get(&instrument.name).and_then(|volumes| {
+                    let recent = volumes.values().rev().take(VOLUME_DAYS).collect::<Vec<_>>();
+                    (!recent.is_empty())
+                        .then(|| recent.iter().copied().sum::<f64>() / recent.len() as f64)
+                });
+                let wealth = values
+                    .values()
+                    .map(|(valuation, nominal)| 1.0 + primitive::pnl(*valuation, *nominal).1)
+                    .collect::<Vec<_>>();
+                if wealth.len() < indicators.options.min_observations {
+                    return InstrumentRiskIndicator {
+                        instrument,
+                        volatility: None,
+                        max_drawdown: None,
+                        average_volume,
+                    };
+                }
+                let returns = wealth
+                    .windows(2)
+                    .map(|values| values[1] / values[0] - 1.0)
+                    .collect::<Vec<_>>();
+                InstrumentRiskIndicator {
+                    instrument,
+                    volatility: primitive::volatility(
+                        &returns,
+                        indicators.options.trading_days_per_year,
+                    ),
+                    max_drawdown: Some(primitive::max_drawdown(&wealth)),
+                    average_volume,
+                }
+            })
+            .collect::<Vec<_>>();
+        result.sort_by(|left, right| {
+            right
+                .volatility
+                .unwrap_or(f64::MIN)
+                .total_cmp(&left.volatility.unwrap_or(f64::MIN))
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::marketdata::Currency;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+
+    fn make_position_(name: &str, currency: &Rc<Currency>, date: Date) -> Position {
+        Position {
+            instrument: Rc::new(make_instrument_(name, currency)),
+            trades: vec![Trade {
+                date: date.and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 100.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        }
+    }
+
+    fn make_spots_(begin: Date, nb_days: usize, amplitude: f64) -> Vec<DataFrame> {
+        begin
+            .iter_days()
+            .take(nb_days)
+            .enumerate()
+            .map(|(i, date)| {
+                let value = 100.0 * (1.0 + if i % 2 == 0 { amplitude } else { -amplitude });
+                DataFrame::new(date, value, value, value, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn volatility_ordering() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                make_position_("CALM", &currency, begin),
+                make_position_("WILD", &currency, begin),
+                make_position_("YOUNG", &currency, make_date_(2022, 3, 25)),
+            ],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::by_instrument([
+            ("CALM", make_spots_(begin, 30, 0.001)),
+            ("WILD", make_spots_(begin, 30, 0.05)),
+            ("YOUNG", make_spots_(begin, 30, 0.1)),
+        ]);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            make_date_(2022, 3, 30),
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let risks = InstrumentRiskIndicator::from_portfolios(&indicators);
+        assert_eq!(risks.len(), 3);
+        assert_eq!(risks[0].instrument.name, "WILD");
+        assert_eq!(risks[1].instrument.name, "CALM");
+        assert_eq!(risks[2].instrument.name, "YOUNG");
+        assert!(risks[0].volatility.unwrap() > risks[1].volatility.unwrap());
+        assert!(risks[0].max_drawdown.unwrap() < risks[1].max_drawdown.unwrap());
+        assert!(risks[2].volatility.is_none());
+        assert!(risks.iter().all(|risk| risk.average_volume.is_none()));
+        assert!(risks.iter().all(|risk| !risk.is_illiquid(1000.0)));
+        assert!(risks[2].max_drawdown.is_none());
+    }
+
+    #[test]
+    fn volatility_with_min_observations() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![make_position_("WILD", &currency, begin)],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::by_instrument([("WILD", make_spots_(begin, 30, 0.05))]);
+
+        let mut price_ = |nb_days: u64| {
+            let indicators = PortfolioIndicators::from_portfolio(
+                &portfolio,
+                begin,
+                begin + chrono::Days::new(nb_days - 1),
+                &mut provider,
+                &PricingOptions::default(),
+            )
+            .unwrap();
+            InstrumentRiskIndicator::from_portfolios(&indicators)
+        };
+
+        let risks = price_(3);
+        assert!(risks[0].volatility.is_none());
+        assert!(risks[0].max_drawdown.is_none());
+
+        let risks = price_(25);
+        assert!(risks[0].volatility.unwrap() > 0.0);
+        assert!(risks[0].max_drawdown.is_some());
+    }
+
+    #[test]
+    fn volatility_without_excluded_dates() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let spike = make_date_(2022, 4, 15);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![make_position_("GLITCH", &currency, begin)],
+            cash: Default::default(),
+        };
+        let mut spots = make_spots_(begin, 90, 0.001);
+        let spot = spots.iter_mut().find(|spot| spot.date == spike).unwrap();
+        *spot = DataFrame::new(spike, 190.0, 190.0, 190.0, 190.0);
+        let mut provider = MockProvider::by_instrument([("GLITCH", spots)]);
+
+        let mut price_ = |excluded_dates: Vec<Date>| {
+            PortfolioIndicators::from_portfolio(
+                &portfolio,
+                begin,
+                make_date_(2022, 5, 29),
+                &mut provider,
+                &PricingOptions {
+                    excluded_dates,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let indicators = price_(Vec::new());
+        let risks = InstrumentRiskIndicator::from_portfolios(&indicators);
+        let volatility = risks[0].volatility.unwrap();
+        let max_drawdown = risks[0].max_drawdown.unwrap();
+
+        let indicators = price_(vec![spike]);
+        assert_eq!(indicators.portfolios.len(), 90);
+        assert!(indicators
+            .portfolios
+            .iter()
+            .all(|indicator| indicator.excluded == (indicator.date == spike)));
+        let risks = InstrumentRiskIndicator::from_portfolios(&indicators);
+        assert!(risks[0].volatility.unwrap() * 10.0 < volatility);
+        assert!(risks[0].max_drawdown.unwrap() > max_drawdown);
+    }
+}
@@ -1,23 +1,67 @@
 use crate::alias::Date;
 use crate::error::Error;
-use crate::historical::Provider;
-use crate::portfolio::Portfolio;
-use std::collections::{HashMap, HashSet};
+use crate::historical::{DataFrame, Provider};
+use crate::marketdata::{Currency, Instrument, InstrumentType};
+use crate::portfolio::{Portfolio, Position};
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
-use log::{error, info};
+use log::{info, warn};
 
+mod annualized;
+mod beta;
+mod buy_and_hold;
+mod cash_ledger;
+mod close_position;
+mod coverage;
+mod event;
+#[cfg(test)]
+mod fixture;
 mod heat_map;
 mod instrument;
+mod instrument_risk;
+mod mover;
+mod options;
 mod portfolio;
 mod position;
+mod priced;
 mod primitive;
+mod realized_gain;
 mod region;
+mod resample;
+mod risk_free;
+mod seasonality;
+mod sleeve;
+mod tag;
+mod wash_sale;
 
+pub use annualized::Annualized;
+pub use beta::{RollingBeta, DEFAULT_BETA_WINDOW_DAYS};
+pub use buy_and_hold::BuyAndHoldIndicator;
+pub use cash_ledger::{CashLedger, CashMovement, CashMovementKind};
+pub use close_position::{ClosePositionIndicator, Lot};
+pub use coverage::CoverageIndicator;
+pub use event::Event;
 pub use heat_map::{HeatMap, HeatMapPeriod};
 pub use instrument::InstrumentIndicator;
+pub use instrument_risk::InstrumentRiskIndicator;
+pub use mover::Mover;
+pub use options::{
+    FeesTreatment, FirstSpot, PricingOptions, ValuationPrice, WeightBase, MIN_OBSERVATIONS,
+    TRADING_DAYS_PER_YEAR,
+};
 pub use portfolio::PortfolioIndicator;
-pub use position::PositionIndicator;
+pub use position::{CashFlow, PnlAttribution, PositionIndicator};
+pub use priced::PricedIter;
+pub use primitive::ActiveReturn;
+pub use realized_gain::RealizedGain;
 pub use region::{RegionIndicator, RegionIndicatorInstrument};
+pub use resample::Step;
+pub use risk_free::RiskFreeRate;
+pub use seasonality::{SeasonalityBucket, SeasonalityIndicator};
+pub use sleeve::SleeveIndicator;
+pub use tag::TagIndicator;
+pub use wash_sale::WashSale;
 
 pub struct PositionIndicators<'a> {
     pub begin: Date,
@@ -25,11 +69,88 @@ pub struct PositionIndicators<'a> {
     pub instrument_name: String,
     pub position_index: usize,
     pub positions: Vec<&'a PositionIndicator>,
+    pub options: &'a PricingOptions,
+}
+
+impl PositionIndicators<'_> {
+    pub fn return_histogram(&self, bucket_width: f64) -> Vec<(f64, usize)> {
+        primitive::histogram(&self.daily_returns_(Date::MIN), bucket_width)
+    }
+
+    pub fn omega_ratio(&self, threshold: f64) -> Option<f64> {
+        primitive::omega_ratio(&self.daily_returns_(trailing_begin_(self.end)), threshold)
+    }
+
+    //
+    // blank under min_observations daily returns
+    pub fn return_quantiles(&self, quantiles: &[f64]) -> Vec<(f64, f64)> {
+        return_quantiles_(
+            &self.daily_returns_(Date::MIN),
+            quantiles,
+            self.options.min_observations,
+        )
+    }
+
+    //
+    // daily pnl changes of the dates after begin
+    fn daily_returns_(&self, begin: Date) -> Vec<f64> {
+        self.positions
+            .iter()
+            .filter(|item| !item.excluded && item.date > begin)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|values| values[1].pnl_percent - values[0].pnl_percent)
+            .collect()
+    }
+}
+
+fn return_quantiles_(
+    daily_returns: &[f64],
+    quantiles: &[f64],
+    min_observations: usize,
+) -> Vec<(f64, f64)> {
+    if daily_returns.len() < min_observations {
+        return Vec::new();
+    }
+    quantiles
+        .iter()
+        .filter_map(|q| primitive::quantile(daily_returns, *q).map(|value| (*q, value)))
+        .collect()
+}
+
+const OMEGA_TRAILING_DAYS: u64 = 365;
+
+fn trailing_begin_(end: Date) -> Date {
+    end.checked_sub_days(chrono::Days::new(OMEGA_TRAILING_DAYS))
+        .unwrap_or(Date::MIN)
+}
+
+//
+// a position without trade is skipped by the pricing, it is often a
+// forgotten data entry so it is reported (or an error on strict)
+fn check_traded_positions_(portfolio: &Portfolio, strict: bool) -> Result<Vec<String>, Error> {
+    let warnings = portfolio
+        .positions
+        .iter()
+        .enumerate()
+        .filter(|(_, position)| position.get_open_date().is_none())
+        .map(|(position_index, position)| {
+            format!(
+                "position {} of instrument {} has no trade, it is skipped",
+                position_index, position.instrument.name
+            )
+        })
+        .collect::<Vec<_>>();
+    if strict && !warnings.is_empty() {
+        return Err(Error::new_portfolio(warnings.join(", ")));
+    }
+    Ok(warnings)
 }
 
 pub struct PortfolioIndicators {
     pub begin: Date,
     pub end: Date,
+    pub options: PricingOptions,
     pub portfolios: Vec<PortfolioIndicator>,
 }
 
@@ -39,45 +160,235 @@ impl PortfolioIndicators {
         begin: Date,
         end: Date,
         spot_provider: &mut P,
+        options: &PricingOptions,
     ) -> Result<PortfolioIndicators, Error>
     where
         P: Provider,
     {
-        info!(
-            "request all market data historical for {} from {} to {} pricing",
-            portfolio.name,
-            begin.format("%Y-%m-%d"),
-            end.format("%Y-%m-%d"),
-        );
+        Self::from_portfolio_with_progress(portfolio, begin, end, spot_provider, options, |_, _| {})
+    }
 
-        for position in portfolio.positions.iter() {
-            if let Some(trade) = position.trades.first() {
-                let instrument_begin = trade.date.date();
-                if instrument_begin <= end {
-                    let instrument_end = position
-                        .get_close_date()
-                        .map(|date_time| date_time.date())
-                        .unwrap_or(end);
-                    spot_provider.fetch(&position.instrument, instrument_begin, instrument_end)?;
-                }
-            }
-        }
-        info!("request all market data historical done");
+    //
+    // progress is called after each position fetch with (done, total)
+    pub fn from_portfolio_with_progress<P, F>(
+        portfolio: &Portfolio,
+        begin: Date,
+        end: Date,
+        spot_provider: &mut P,
+        options: &PricingOptions,
+        progress: F,
+    ) -> Result<PortfolioIndicators, Error>
+    where
+        P: Provider,
+        F: FnMut(usize, usize),
+    {
+        Self::fetch_(portfolio, begin, end, spot_provider, options, progress)?;
 
         info!("start to price portfolios");
         let portfolios =
-            PortfolioIndicators::make_portfolios_(portfolio, begin, end, spot_provider);
+            PortfolioIndicators::iter_priced(portfolio, begin, end, spot_provider, options)
+                .collect();
         info!("price portfolios is finished");
 
         Ok(PortfolioIndicators {
             begin,
             end,
+            options: options.clone(),
             portfolios,
         })
     }
 
-    pub fn get_position_index_list(&self, name: &str) -> HashSet<usize> {
-        let mut result = HashSet::new();
+    //
+    // heat map only, prices the last day of each period instead of each day
+    pub fn from_portfolio_at_period_ends<P>(
+        portfolio: &Portfolio,
+        begin: Date,
+        end: Date,
+        spot_provider: &mut P,
+        options: &PricingOptions,
+        period: HeatMapPeriod,
+    ) -> Result<PortfolioIndicators, Error>
+    where
+        P: Provider,
+    {
+        Self::fetch_(portfolio, begin, end, spot_provider, options, |_, _| {})?;
+
+        info!("start to price portfolios at period ends");
+        let portfolios = PortfolioIndicators::iter_priced_at(
+            portfolio,
+            begin,
+            period.end_dates(begin, end),
+            spot_provider,
+            options,
+        )
+        .collect();
+        info!("price portfolios at period ends is finished");
+
+        Ok(PortfolioIndicators {
+            begin,
+            end,
+            options: options.clone(),
+            portfolios,
+        })
+    }
+
+    fn fetch_<P, F>(
+        portfolio: &Portfolio,
+        begin: Date,
+        end: Date,
+        spot_provider: &mut P,
+        options: &PricingOptions,
+        mut progress: F,
+    ) -> Result<(), Error>
+    where
+        P: Provider,
+        F: FnMut(usize, usize),
+    {
+        info!(
+            "request all market data historical for {} from {} to {} pricing",
+            portfolio.name,
+            begin.format("%Y-%m-%d"),
+            end.format("%Y-%m-%d"),
+        );
+
+        for warning in check_traded_positions_(portfolio, options.strict)? {
+            warn!("{}", warning);
+        }
+
+        let total = portfolio.positions.len();
+        for (position_index, position) in portfolio.positions.iter().enumerate() {
+            Self::fetch_position_(position, begin, end, spot_provider, options)?;
+            progress(position_index + 1, total);
+        }
+
+        Self::fetch_fx_(portfolio, begin, end, spot_provider, options.strict)?;
+        info!("request all market data historical done");
+        Ok(())
+    }
+
+    fn fetch_position_<P>(
+        position: &Position,
+        begin: Date,
+        end: Date,
+        spot_provider: &mut P,
+        options: &PricingOptions,
+    ) -> Result<(), Error>
+    where
+        P: Provider,
+    {
+        //
+        // a bond without market data is valued at its face value
+        let is_face_valued = position.instrument.ticker_yahoo.is_none()
+            && matches!(position.instrument.instrument_type, InstrumentType::Bond(_));
+        let Some(instrument_begin) = position
+            .get_open_date()
+            .filter(|_| !is_face_valued)
+            .map(|date_time| date_time.date())
+        else {
+            return Ok(());
+        };
+        if instrument_begin > end {
+            return Ok(());
+        }
+        let instrument_end = position
+            .get_close_date(end, options.quantity_epsilon)
+            .map(|date_time| date_time.date())
+            .unwrap_or(end)
+            .min(end);
+        //
+        // a position opened before begin is only fetched from begin (--max-history-days),
+        // one closed before begin still needs its close spot
+        let instrument_begin = instrument_begin.max(begin).min(instrument_end);
+        //
+        // a longer history is only cached, pricing starts at the first trade
+        let fetch_begin = position
+            .instrument
+            .history_start
+            .map_or(instrument_begin, |date| date.min(instrument_begin));
+        spot_provider.fetch(&position.instrument, fetch_begin, instrument_end)
+    }
+
+    //
+    // without fx market data the pricing falls back on the trade fx rates,
+    // a position without them is converted at 1.0 (or an error on strict)
+    fn fetch_fx_<P>(
+        portfolio: &Portfolio,
+        begin: Date,
+        end: Date,
+        spot_provider: &mut P,
+        strict: bool,
+    ) -> Result<(), Error>
+    where
+        P: Provider,
+    {
+        for (currency_name, fx_instrument) in Self::make_fx_instruments_(portfolio).iter() {
+            let Err(error) = spot_provider.fetch(fx_instrument, begin, end) else {
+                continue;
+            };
+            warn!(
+                "no fx market data for {}, trade fx rates are used: {}",
+                fx_instrument.name, error
+            );
+            if !strict {
+                continue;
+            }
+            let unconverted = portfolio
+                .positions
+                .iter()
+                .enumerate()
+                .filter(|(_, position)| {
+                    Self::fx_currency_(portfolio, &position.instrument.currency)
+                        .is_some_and(|(currency, _)| &currency.name == currency_name)
+                })
+                .filter(|(_, position)| {
+                    position
+                        .trades
+                        .first()
+                        .is_some_and(|trade| trade.fx_rate.is_none())
+                })
+                .map(|(position_index, position)| {
+                    format!(
+                        "position {} of instrument {} has no fx rate to {}",
+                        position_index, position.instrument.name, portfolio.currency.name
+                    )
+                })
+                .collect::<Vec<_>>();
+            if !unconverted.is_empty() {
+                return Err(Error::new_historical(format!(
+                    "no fx market data for {}: {}",
+                    fx_instrument.name,
+                    unconverted.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    //
+    // the last indicator is carried forward day by day up to end, the
+    // valuations are kept so the tail is flat (zero daily return)
+    pub fn pad_to_end(&mut self) {
+        let Some(last) = self.portfolios.last().cloned() else {
+            return;
+        };
+        for date in last
+            .date
+            .iter_days()
+            .skip(1)
+            .take_while(|date| *date <= self.end)
+        {
+            let mut indicator = last.clone();
+            indicator.date = date;
+            indicator.carried = true;
+            for position in indicator.positions.iter_mut() {
+                position.date = date;
+            }
+            self.portfolios.push(indicator);
+        }
+    }
+
+    pub fn get_position_index_list(&self, name: &str) -> Vec<usize> {
+        let mut result = Vec::new();
         if let Some(indicator) = self.portfolios.last() {
             result = indicator
                 .positions
@@ -86,6 +397,8 @@ impl PortfolioIndicators {
                 .map(|item| item.position_index)
                 .collect();
         }
+        result.sort();
+        result.dedup();
         result
     }
 
@@ -110,77 +423,1111 @@ impl PortfolioIndicators {
             instrument_name: instrument_name.to_string(),
             position_index,
             positions,
+            options: &self.options,
+        }
+    }
+
+    //
+    // daily twr returns over the trailing year
+    pub fn omega_ratio(&self, threshold: f64) -> Option<f64> {
+        primitive::omega_ratio(&self.daily_returns_(trailing_begin_(self.end)), threshold)
+    }
+
+    //
+    // daily twr returns of the dates after begin
+    fn daily_returns_(&self, begin: Date) -> Vec<f64> {
+        self.dated_daily_returns_(begin)
+            .into_iter()
+            .map(|(_, _, value)| value)
+            .collect()
+    }
+
+    //
+    // (previous date, date, twr return) of consecutive not excluded dates after begin
+    fn dated_daily_returns_(&self, begin: Date) -> Vec<(Date, Date, f64)> {
+        self.portfolios
+            .iter()
+            .filter(|indicator| !indicator.excluded && indicator.date > begin)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .filter(|values| (1.0 + values[0].twr).abs() > 1e-7)
+            .map(|values| {
+                (
+                    values[0].date,
+                    values[1].date,
+                    (1.0 + values[1].twr) / (1.0 + values[0].twr) - 1.0,
+                )
+            })
+            .collect()
+    }
+
+    //
+    // quantiles of the daily twr returns ex: 0.05, 0.5, 0.95,
+    // blank under min_observations daily returns
+    pub fn return_quantiles(&self, quantiles: &[f64]) -> Vec<(f64, f64)> {
+        return_quantiles_(
+            &self.daily_returns_(Date::MIN),
+            quantiles,
+            self.options.min_observations,
+        )
+    }
+
+    //
+    // 1 day historical value at risk of the daily twr returns
+    pub fn value_at_risk(&self, confidence: f64) -> Option<f64> {
+        let daily_returns = self.daily_returns_(Date::MIN);
+        if daily_returns.len() < self.options.min_observations {
+            return None;
         }
+        primitive::value_at_risk(&daily_returns, confidence)
+    }
+
+    pub fn get_cash_ledger(&self, portfolio: &Portfolio) -> CashLedger {
+        CashLedger::from_portfolio(portfolio, self)
     }
 
-    fn make_positions_date_<P>(
+    pub fn get_close_position_indicators(
+        &self,
         portfolio: &Portfolio,
-        begin: Date,
+    ) -> Vec<ClosePositionIndicator> {
+        self.portfolios
+            .last()
+            .map(|indicator| {
+                indicator
+                    .positions
+                    .iter()
+                    .filter(|position_indicator| position_indicator.is_close)
+                    .filter_map(|position_indicator| {
+                        portfolio
+                            .positions
+                            .get(position_indicator.position_index)
+                            .and_then(|position| {
+                                ClosePositionIndicator::from_position(
+                                    position,
+                                    position_indicator,
+                                    &self.options,
+                                )
+                            })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn first_available_spot_<P>(
+        spot_provider: &P,
+        position: &Position,
+        date: Date,
         end: Date,
-        spot_provider: &mut P,
-    ) -> HashMap<Date, Vec<PositionIndicator>>
+    ) -> Option<DataFrame>
     where
         P: Provider,
     {
-        let mut result: HashMap<Date, Vec<PositionIndicator>> = Default::default();
-        for (position_index, position) in portfolio.positions.iter().enumerate() {
-            let mut indicators = Vec::new();
-            if let Some(trade) = position.trades.first() {
-                let begin = std::cmp::max(trade.date.date(), begin);
-                for date in begin.iter_days().take_while(|item| item <= &end) {
-                    if let Some(spot) = spot_provider.latest(&position.instrument, date) {
-                        let indicator = PositionIndicator::from_position(
-                            position,
-                            date,
-                            position_index,
-                            spot,
-                            &indicators,
-                        );
-                        indicators.push(indicator);
-                    } else {
-                        error!(
-                            "no spot on {}/{} at {} and before skip position pricing",
-                            position.instrument.name, position_index, date
-                        );
-                    }
-                }
-            }
-            for indicator in indicators {
-                result.entry(indicator.date).or_default().push(indicator);
-            }
+        date.iter_days()
+            .take_while(|item| item <= &end)
+            .find_map(|item| spot_provider.latest(&position.instrument, item))
+            .copied()
+    }
+
+    //
+    // trade price back to the quoted unit, flagged as a synthetic spot
+    fn make_last_trade_spot_(position: &Position, date: Date) -> Option<DataFrame> {
+        let trades = position.priced_trades(date);
+        let trade = trades
+            .iter()
+            .rev()
+            .find(|trade| trade.date.date() <= date && !trade.is_fee_only())?;
+        let price = trade.price / position.instrument.quote_factor;
+        Some(DataFrame::new(date, price, price, price, price).as_synthetic())
+    }
+
+    fn make_face_value_spot_(position: &Position, date: Date) -> Option<DataFrame> {
+        match &position.instrument.instrument_type {
+            InstrumentType::Bond(bond) => Some(DataFrame::new(
+                date,
+                bond.face_value,
+                bond.face_value,
+                bond.face_value,
+                bond.face_value,
+            )),
+            _ => None,
         }
-        result
     }
 
-    fn make_portfolios_<P>(
+    //
+    // currency to convert to the portfolio currency and the factor of its
+    // subunit (ex: GBP and 0.01 for GBp), none for the portfolio currency
+    fn fx_currency_<'a>(
         portfolio: &Portfolio,
-        begin: Date,
-        end: Date,
-        spot_provider: &mut P,
-    ) -> Vec<PortfolioIndicator>
+        currency: &'a Rc<Currency>,
+    ) -> Option<(&'a Rc<Currency>, f64)> {
+        let (currency, factor) = match &currency.parent_currency {
+            Some(parent_currency) => (
+                &parent_currency.currency,
+                1.0 / parent_currency.factor as f64,
+            ),
+            None => (currency, 1.0),
+        };
+        (currency.name != portfolio.currency.name || factor != 1.0).then_some((currency, factor))
+    }
+
+    //
+    // one fx instrument by foreign currency held, keyed by the currency name
+    fn make_fx_instruments_(portfolio: &Portfolio) -> BTreeMap<String, Instrument> {
+        portfolio
+            .positions
+            .iter()
+            .filter_map(|position| Self::fx_currency_(portfolio, &position.instrument.currency))
+            .filter(|(currency, _)| currency.name != portfolio.currency.name)
+            .map(|(currency, _)| {
+                (
+                    currency.name.clone(),
+                    Instrument::new_fx(currency, &portfolio.currency),
+                )
+            })
+            .collect()
+    }
+
+    fn compute_fx_rate_<P>(
+        spot_provider: &P,
+        fx_instruments: &BTreeMap<String, Instrument>,
+        portfolio: &Portfolio,
+        position: &Position,
+        date: Date,
+    ) -> Option<f64>
     where
         P: Provider,
     {
-        let mut indicators = Vec::new();
-        let mut positions_by_date =
-            PortfolioIndicators::make_positions_date_(portfolio, begin, end, spot_provider);
-        for date in begin.iter_days().take_while(|item| item <= &end) {
-            if let Some(position_indicators) = positions_by_date.remove(&date) {
-                if position_indicators.is_empty() {
-                    continue;
-                }
-
-                let indicator = PortfolioIndicator::from_portfolio(
-                    portfolio,
+        let Some((currency, factor)) = Self::fx_currency_(portfolio, &position.instrument.currency)
+        else {
+            return Some(1.0);
+        };
+        if currency.name == portfolio.currency.name {
+            return Some(factor);
+        }
+
+        //
+        // fx market data at date, without it the latest fx rate known from trades
+        fx_instruments
+            .get(&currency.name)
+            .and_then(|fx_instrument| spot_provider.latest(fx_instrument, date))
+            .map(|spot| spot.close * factor)
+            .or_else(|| {
+                position
+                    .trades
+                    .iter()
+                    .rev()
+                    .filter(|trade| trade.date.date() <= date)
+                    .find_map(|trade| trade.fx_rate)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marketdata::{Bond, Dividend, Instrument};
+    use crate::portfolio::{CashVariation, CashVariationSource, Trade, Way, QUANTITY_EPSILON};
+    use crate::pricer::fixture::{self, make_currency_, make_date_, make_instrument_};
+    use assert_float_eq::*;
+    use std::rc::Rc;
+
+    struct MockProvider;
+
+    impl Provider for MockProvider {
+        fn fetch(
+            &mut self,
+            instrument: &Instrument,
+            _begin: Date,
+            _end: Date,
+        ) -> Result<(), Error> {
+            Err(Error::new_historical(format!(
+                "no market data for {}",
+                instrument.name
+            )))
+        }
+
+        fn latest(&self, _instrument: &Instrument, _date: Date) -> Option<&DataFrame> {
+            None
+        }
+    }
+
+    fn make_bond_portfolio_(nb_positions: usize) -> Portfolio {
+        let currency = make_currency_("EUR");
+        let bond = Bond {
+            coupon_rate: 0.05,
+            coupon_frequency: 1,
+            face_value: 100.0,
+            issue_date: make_date_(2020, 3, 20),
+            maturity: make_date_(2025, 3, 20),
+        };
+        let instrument = Rc::new(Instrument {
+            fund_category: String::from("bond"),
+            dividends: Some(bond.coupons()),
+            instrument_type: InstrumentType::Bond(bond),
+            ..make_instrument_("OAT", &currency)
+        });
+        Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: (0..nb_positions)
+                .map(|_| Position {
+                    instrument: instrument.clone(),
+                    trades: vec![Trade {
+                        date: make_date_(2022, 3, 17).and_hms_opt(10, 0, 0).unwrap(),
+                        way: Way::Buy,
+                        quantity: 10.0,
+                        price: 98.0,
+                        fees: 0.0,
+                        order_id: None,
+                        fx_rate: None,
+                    }],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                })
+                .collect(),
+            cash: Default::default(),
+        }
+    }
+
+    #[test]
+    fn progress_by_instrument() {
+        let currency = make_currency_("EUR");
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: Default::default(),
+            cash: Default::default(),
+        };
+        let mut calls = Vec::new();
+        PortfolioIndicators::from_portfolio_with_progress(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2022, 3, 18),
+            &mut MockProvider,
+            &PricingOptions::default(),
+            |done, total| calls.push((done, total)),
+        )
+        .unwrap();
+        assert!(calls.is_empty());
+
+        let portfolio = make_bond_portfolio_(3);
+        PortfolioIndicators::from_portfolio_with_progress(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2022, 3, 18),
+            &mut MockProvider,
+            &PricingOptions::default(),
+            |done, total| calls.push((done, total)),
+        )
+        .unwrap();
+        assert_eq!(calls, vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn pricing_from_inception_date() {
+        let mut portfolio = make_bond_portfolio_(1);
+        let payment_date = make_date_(2022, 3, 10);
+        portfolio.cash.push(CashVariation {
+            position: 1000.0,
+            date: payment_date.and_hms_opt(9, 0, 0).unwrap(),
+            source: CashVariationSource::Payment,
+            account: None,
+            fx_rate: None,
+        });
+        assert_eq!(portfolio.get_trade_date().unwrap(), make_date_(2022, 3, 17));
+        assert_eq!(portfolio.get_inception_date().unwrap(), payment_date);
+
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            portfolio.get_inception_date().unwrap(),
+            make_date_(2022, 3, 18),
+            &mut MockProvider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let first = indicators.portfolios.first().unwrap();
+        assert_eq!(first.date, payment_date);
+        assert_float_absolute_eq!(first.cash, 1000.0, 1e-7);
+        assert!(first.positions.is_empty());
+    }
+
+    #[test]
+    fn pad_to_end_with_flat_tail() {
+        let begin = make_date_(2022, 3, 14);
+        let mut indicators = PortfolioIndicators {
+            begin,
+            end: make_date_(2022, 3, 21),
+            options: Default::default(),
+            portfolios: begin
+                .iter_days()
+                .take(5)
+                .enumerate()
+                .map(|(i, date)| PortfolioIndicator {
                     date,
-                    position_indicators,
-                    &indicators,
-                );
+                    valuation: 100.0 + i as f64,
+                    twr: 0.01 * i as f64,
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        indicators.pad_to_end();
+
+        assert_eq!(indicators.portfolios.len(), 8);
+        let last_priced = &indicators.portfolios[4];
+        assert!(!last_priced.carried);
+        for (indicator, date) in indicators.portfolios[5..]
+            .iter()
+            .zip(make_date_(2022, 3, 19).iter_days())
+        {
+            assert!(indicator.carried);
+            assert_eq!(indicator.date, date);
+            assert_float_absolute_eq!(indicator.valuation, last_priced.valuation, 1e-7);
+            assert_float_absolute_eq!(indicator.twr, last_priced.twr, 1e-7);
+        }
+        assert_eq!(indicators.portfolios.last().unwrap().date, indicators.end);
+
+        indicators.pad_to_end();
+        assert_eq!(indicators.portfolios.len(), 8);
+    }
 
-                indicators.push(indicator);
+    #[test]
+    fn position_without_trade() {
+        let mut portfolio = make_bond_portfolio_(2);
+        portfolio.positions[1].trades.clear();
+
+        let warnings = check_traded_positions_(&portfolio, false).unwrap();
+        assert_eq!(
+            warnings,
+            vec![String::from(
+                "position 1 of instrument OAT has no trade, it is skipped"
+            )]
+        );
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2022, 3, 18),
+            &mut MockProvider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert!(indicators
+            .portfolios
+            .iter()
+            .flat_map(|indicator| indicator.positions.iter())
+            .all(|position| position.position_index == 0));
+
+        let result = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2022, 3, 18),
+            &mut MockProvider,
+            &PricingOptions {
+                strict: true,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(Error::Portfolio(_))));
+    }
+
+    struct MockRecordingProvider {
+        fetches: Vec<(String, Date, Date)>,
+    }
+
+    impl Provider for MockRecordingProvider {
+        fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+            self.fetches.push((instrument.name.clone(), begin, end));
+            Ok(())
+        }
+
+        fn latest(&self, _instrument: &Instrument, _date: Date) -> Option<&DataFrame> {
+            None
+        }
+    }
+
+    fn make_trade_(date: Date, way: Way, quantity: f64, price: f64) -> Trade {
+        Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees: 1.0,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    #[test]
+    fn heat_map_at_period_ends_equals_daily() {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(make_instrument_("ESE", &currency));
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 4, 30);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![
+                Position {
+                    instrument: instrument.clone(),
+                    trades: vec![
+                        make_trade_(begin, Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 10), Way::Buy, 5.0, 21.0),
+                        make_trade_(make_date_(2022, 3, 20), Way::Sell, 15.0, 23.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument,
+                    trades: vec![make_trade_(make_date_(2022, 3, 25), Way::Buy, 8.0, 22.0)],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+            ],
+            cash: Default::default(),
+        };
+        let mut provider = fixture::MockProvider::new(
+            begin
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .enumerate()
+                .map(|(i, date)| {
+                    let value = 20.0 + (i % 5) as f64;
+                    DataFrame::new(date, value, value, value, value)
+                })
+                .collect(),
+        );
+        let options = PricingOptions::default();
+        let indicators =
+            PortfolioIndicators::from_portfolio(&portfolio, begin, end, &mut provider, &options)
+                .unwrap();
+        let period_ends = PortfolioIndicators::from_portfolio_at_period_ends(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &options,
+            HeatMapPeriod::Monthly,
+        )
+        .unwrap();
+        assert_eq!(
+            period_ends
+                .portfolios
+                .iter()
+                .map(|indicator| indicator.date)
+                .collect::<Vec<_>>(),
+            vec![make_date_(2022, 3, 31), end]
+        );
+
+        for period in [HeatMapPeriod::Monthly, HeatMapPeriod::Yearly] {
+            let full =
+                HeatMap::from_portfolios(&indicators, period, |indicator| indicator.pnl_percent);
+            let fast =
+                HeatMap::from_portfolios(&period_ends, period, |indicator| indicator.pnl_percent);
+            assert!(!full.data.is_empty());
+            assert_eq!(full.data.len(), fast.data.len());
+            for ((full_date, full_value), (fast_date, fast_value)) in
+                full.data.iter().zip(fast.data.iter())
+            {
+                assert_eq!(full_date, fast_date);
+                assert_float_absolute_eq!(full_value, fast_value, 1e-7);
             }
         }
+    }
+
+    #[test]
+    fn fetch_from_history_start() {
+        let currency = make_currency_("EUR");
+        let make_position_ = |name: &str, history_start: Option<Date>| Position {
+            instrument: Rc::new(Instrument {
+                history_start,
+                ..make_instrument_(name, &currency)
+            }),
+            trades: vec![make_trade_(make_date_(2022, 3, 1), Way::Buy, 10.0, 20.0)],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                make_position_("ESE", Some(make_date_(2020, 1, 1))),
+                make_position_("PAEEM", None),
+                make_position_("C40", Some(make_date_(2022, 6, 1))),
+            ],
+            cash: Default::default(),
+        };
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 31);
+        let mut provider = MockRecordingProvider {
+            fetches: Vec::new(),
+        };
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            provider.fetches,
+            vec![
+                (String::from("ESE"), make_date_(2020, 1, 1), end),
+                (String::from("PAEEM"), begin, end),
+                (String::from("C40"), begin, end),
+            ]
+        );
+        assert_eq!(indicators.begin, begin);
+    }
+
+    #[test]
+    fn max_history_days_keeps_held_quantity() {
+        let currency = make_currency_("EUR");
+        let make_position_ = |name: &str, trades: Vec<Trade>| Position {
+            instrument: Rc::new(make_instrument_(name, &currency)),
+            trades,
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                make_position_(
+                    "ESE",
+                    vec![
+                        make_trade_(make_date_(2020, 1, 2), Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2023, 6, 1), Way::Buy, 5.0, 20.0),
+                    ],
+                ),
+                make_position_(
+                    "PAEEM",
+                    vec![
+                        make_trade_(make_date_(2021, 1, 4), Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2021, 6, 1), Way::Sell, 10.0, 25.0),
+                    ],
+                ),
+            ],
+            cash: Default::default(),
+        };
+        let end = make_date_(2024, 3, 15);
+        let begin = end.checked_sub_days(chrono::Days::new(365)).unwrap();
+        assert_eq!(begin, make_date_(2023, 3, 16));
+
+        let mut provider = MockRecordingProvider {
+            fetches: Vec::new(),
+        };
+        PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            provider.fetches,
+            vec![
+                (String::from("ESE"), begin, end),
+                (
+                    String::from("PAEEM"),
+                    make_date_(2021, 6, 1),
+                    make_date_(2021, 6, 1)
+                ),
+            ]
+        );
+
+        let mut provider = fixture::MockProvider::new(vec![DataFrame::new(
+            make_date_(2021, 6, 1),
+            22.0,
+            22.0,
+            22.0,
+            22.0,
+        )]);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let first = indicators.portfolios.first().unwrap();
+        assert_eq!(first.date, begin);
+        assert_eq!(
+            first
+                .positions
+                .iter()
+                .map(|position| (position.instrument.name.as_str(), position.quantity))
+                .collect::<Vec<_>>(),
+            vec![("ESE", 10.0), ("PAEEM", 0.0)]
+        );
+        assert_float_absolute_eq!(first.valuation, -201.0 + 249.0 - 201.0 + 220.0, 1e-7);
+        let last = indicators.portfolios.last().unwrap();
+        assert_eq!(last.date, end);
+        assert_float_absolute_eq!(last.positions[0].quantity, 15.0, 1e-7);
+    }
+
+    #[test]
+    fn first_spot_policy() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 5);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("ESE", &currency)),
+                trades: vec![make_trade_(begin, Way::Buy, 10.0, 20.0)],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let mut provider = fixture::MockProvider::new(
+            make_date_(2022, 3, 3)
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .map(|date| DataFrame::new(date, 21.0, 21.0, 21.0, 21.0))
+                .collect(),
+        );
+
+        for (first_spot, wanted) in [
+            (FirstSpot::Skip, vec![3, 4, 5]),
+            (FirstSpot::Backfill, vec![1, 3, 4, 5]),
+            (FirstSpot::Carry, vec![1, 2, 3, 4, 5]),
+        ] {
+            let options = PricingOptions {
+                first_spot,
+                ..Default::default()
+            };
+            let indicators = PortfolioIndicators::from_portfolio(
+                &portfolio,
+                begin,
+                end,
+                &mut provider,
+                &options,
+            )
+            .unwrap();
+            let dates = indicators
+                .portfolios
+                .iter()
+                .map(|indicator| indicator.date)
+                .collect::<Vec<_>>();
+            let wanted = wanted
+                .into_iter()
+                .map(|day| make_date_(2022, 3, day))
+                .collect::<Vec<_>>();
+            assert_eq!(dates, wanted, "{}", first_spot);
+
+            let first = &indicators.portfolios[0].positions[0];
+            assert_eq!(first.date, wanted[0]);
+            assert_float_absolute_eq!(first.valuation, 210.0, 1e-7);
+        }
+    }
+
+    #[test]
+    fn position_return_quantiles_with_min_observations() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 6);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("ESE", &currency)),
+                trades: vec![make_trade_(begin, Way::Buy, 10.0, 20.0)],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let mut provider = fixture::MockProvider::new(
+            [20.0, 21.0, 20.0, 22.0, 21.0, 23.0]
+                .into_iter()
+                .zip(begin.iter_days())
+                .map(|(value, date)| DataFrame::new(date, value, value, value, value))
+                .collect(),
+        );
+
+        for (min_observations, wanted) in [(5, 1), (6, 0)] {
+            let options = PricingOptions {
+                min_observations,
+                ..Default::default()
+            };
+            let indicators = PortfolioIndicators::from_portfolio(
+                &portfolio,
+                begin,
+                end,
+                &mut provider,
+                &options,
+            )
+            .unwrap();
+            let quantiles = indicators
+                .get_position_indicators("ESE", 0)
+                .return_quantiles(&[0.5]);
+            assert_eq!(quantiles.len(), wanted, "{}", min_observations);
+        }
+    }
+
+    #[test]
+    fn bond_valued_at_face_value() {
+        let portfolio = make_bond_portfolio_(1);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2023, 3, 21),
+            &mut MockProvider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let first = &indicators.portfolios.first().unwrap().positions[0];
+        assert_float_absolute_eq!(first.valuation, 1000.0, 1e-7);
+        assert_float_absolute_eq!(first.dividends, 0.0, 1e-7);
+
+        let last = &indicators.portfolios.last().unwrap().positions[0];
+        assert_float_absolute_eq!(last.valuation, 1000.0, 1e-7);
+        assert_float_absolute_eq!(last.dividends, 2.0 * 10.0 * 5.0, 1e-7);
+    }
+
+    #[test]
+    fn only_open_keeps_realized_cash() {
+        let currency = make_currency_("EUR");
+        let make_portfolio_ = || Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                Position {
+                    instrument: Rc::new(Instrument {
+                        dividends: Some(vec![Dividend {
+                            record_date: make_date_(2022, 3, 2).and_hms_opt(0, 0, 0).unwrap(),
+                            payment_date: make_date_(2022, 3, 4).and_hms_opt(0, 0, 0).unwrap(),
+                            value: 0.5,
+                        }]),
+                        ..make_instrument_("ESE", &currency)
+                    }),
+                    trades: vec![
+                        make_trade_(make_date_(2022, 3, 1), Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 3), Way::Sell, 10.0, 25.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument: Rc::new(make_instrument_("PAEEM", &currency)),
+                    trades: vec![make_trade_(make_date_(2022, 3, 2), Way::Buy, 10.0, 20.0)],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+            ],
+            cash: vec![CashVariation {
+                position: 1000.0,
+                date: make_date_(2022, 3, 1).and_hms_opt(9, 0, 0).unwrap(),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        };
+        let spots = vec![
+            DataFrame::new(make_date_(2022, 3, 1), 20.0, 20.0, 20.0, 20.0),
+            DataFrame::new(make_date_(2022, 3, 12), 22.0, 22.0, 22.0, 22.0),
+        ];
+        let begin = make_date_(2022, 3, 10);
+        let end = make_date_(2022, 3, 15);
+
+        let portfolio = make_portfolio_();
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 1),
+            end,
+            &mut fixture::MockProvider::new(spots.clone()),
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let wanted = indicators.portfolios.last().unwrap();
+
+        let mut portfolio = make_portfolio_();
+        assert_eq!(
+            portfolio.retain_open_positions(begin, end, QUANTITY_EPSILON),
+            1
+        );
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut fixture::MockProvider::new(spots),
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let first = indicators.portfolios.first().unwrap();
+        let last = indicators.portfolios.last().unwrap();
+        assert_eq!(last.positions.len(), 1);
+        //
+        // bought 201, sold 249 and 5 of dividends
+        assert_float_absolute_eq!(first.cash, 1000.0 + 53.0 - 201.0, 1e-7);
+        assert_float_absolute_eq!(last.cash, wanted.cash, 1e-7);
+        assert_float_absolute_eq!(last.valuation, wanted.valuation, 1e-7);
+        assert_float_absolute_eq!(last.earning, wanted.earning, 1e-7);
+        assert_float_absolute_eq!(last.invested_capital, wanted.invested_capital, 1e-7);
+    }
+
+    #[test]
+    fn bond_redeemed_after_maturity() {
+        let portfolio = make_bond_portfolio_(1);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2025, 3, 25),
+            &mut MockProvider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let at_maturity = indicators
+            .portfolios
+            .iter()
+            .find(|indicator| indicator.date == make_date_(2025, 3, 20))
+            .unwrap();
+        assert!(!at_maturity.positions[0].is_close);
+        assert_float_absolute_eq!(at_maturity.positions[0].valuation, 1000.0, 1e-7);
+
+        //
+        // 4 coupons and the face value are paid in cash
+        let last = indicators.portfolios.last().unwrap();
+        assert_eq!(last.date, make_date_(2025, 3, 25));
+        assert!(last.positions[0].is_close);
+        assert_float_absolute_eq!(last.positions[0].valuation, 0.0, 1e-7);
+        assert_float_absolute_eq!(last.dividends, 4.0 * 50.0, 1e-7);
+        assert_float_absolute_eq!(last.cash, -980.0 + 1000.0 + 200.0, 1e-7);
+        assert_float_absolute_eq!(last.valuation, last.cash, 1e-7);
+    }
+
+    #[test]
+    fn bond_held_fetched_up_to_end() {
+        let mut portfolio = make_bond_portfolio_(1);
+        //
+        // quoted bond, fetched from yahoo instead of valued at its face value
+        let currency = portfolio.currency.clone();
+        let bond = Bond {
+            coupon_rate: 0.05,
+            coupon_frequency: 1,
+            face_value: 100.0,
+            issue_date: make_date_(2020, 3, 20),
+            maturity: make_date_(2025, 3, 20),
+        };
+        portfolio.positions[0].instrument = Rc::new(Instrument {
+            ticker_yahoo: Some(String::from("OAT.PA")),
+            fund_category: String::from("bond"),
+            dividends: Some(bond.coupons()),
+            instrument_type: InstrumentType::Bond(bond),
+            ..make_instrument_("OAT", &currency)
+        });
+        let end = make_date_(2022, 3, 31);
+        assert_eq!(portfolio.positions[0].priced_trades(end).len(), 1);
+        assert!(portfolio.positions[0]
+            .get_close_date(end, QUANTITY_EPSILON)
+            .is_none());
+        assert_eq!(
+            portfolio.positions[0]
+                .get_close_date(make_date_(2025, 3, 21), QUANTITY_EPSILON)
+                .map(|date_time| date_time.date()),
+            Some(make_date_(2025, 3, 21))
+        );
+
+        let mut provider = MockRecordingProvider {
+            fetches: Vec::new(),
+        };
+        PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            provider.fetches,
+            vec![(String::from("OAT"), make_date_(2022, 3, 17), end)]
+        );
+    }
+
+    #[test]
+    fn foreign_position_in_portfolio_currency() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 2);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("AAPL", &make_currency_("USD"))),
+                trades: vec![Trade {
+                    fx_rate: Some(0.9),
+                    ..make_trade_(begin, Way::Buy, 10.0, 100.0)
+                }],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: vec![CashVariation {
+                position: 1000.0,
+                date: begin.and_hms_opt(9, 0, 0).unwrap(),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        };
+        let spots = vec![DataFrame::new(begin, 100.0, 100.0, 100.0, 100.0)];
+
+        //
+        // the price is unchanged, the usd gain is only fx
+        let mut provider = fixture::MockProvider::by_instrument([
+            ("AAPL", spots.clone()),
+            (
+                "USDEUR",
+                vec![
+                    DataFrame::new(begin, 0.9, 0.9, 0.9, 0.9),
+                    DataFrame::new(end, 1.0, 1.0, 1.0, 1.0),
+                ],
+            ),
+        ]);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let first = &indicators.portfolios[0];
+        let last = &indicators.portfolios[1];
+        assert_float_absolute_eq!(first.positions[0].fx_rate, 0.9, 1e-7);
+        assert_float_absolute_eq!(last.positions[0].fx_rate, 1.0, 1e-7);
+        assert_float_absolute_eq!(
+            first.positions[0].pnl_currency,
+            last.positions[0].pnl_currency,
+            1e-7
+        );
+        for indicator in [first, last] {
+            assert_float_absolute_eq!(indicator.cash, 1000.0 - 1001.0 * 0.9, 1e-7);
+            assert_float_absolute_eq!(indicator.fees, 0.9, 1e-7);
+        }
+        assert_float_absolute_eq!(first.valuation, 1000.0 - 1001.0 * 0.9 + 900.0, 1e-7);
+        assert_float_absolute_eq!(last.valuation, 1000.0 - 1001.0 * 0.9 + 1000.0, 1e-7);
+        assert_float_absolute_eq!(last.pnl_currency - first.pnl_currency, 100.0, 1e-7);
+
+        //
+        // without fx market data the trade fx rate is kept
+        let mut provider = fixture::MockProvider::by_instrument([("AAPL", spots)]);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let last = &indicators.portfolios[1];
+        assert_float_absolute_eq!(last.positions[0].fx_rate, 0.9, 1e-7);
+        assert_float_absolute_eq!(last.valuation, 1000.0 - 1001.0 * 0.9 + 900.0, 1e-7);
+    }
+
+    struct MockNoFxProvider(fixture::MockProvider);
+
+    impl Provider for MockNoFxProvider {
+        fn fetch(&mut self, instrument: &Instrument, begin: Date, end: Date) -> Result<(), Error> {
+            if instrument.fund_category == "fx" {
+                return Err(Error::new_historical("no fx market data"));
+            }
+            self.0.fetch(instrument, begin, end)
+        }
+
+        fn latest(&self, instrument: &Instrument, date: Date) -> Option<&DataFrame> {
+            self.0.latest(instrument, date)
+        }
+    }
+
+    #[test]
+    fn foreign_position_without_fx_rate() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 2);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(make_instrument_("AAPL", &make_currency_("USD"))),
+                trades: vec![make_trade_(begin, Way::Buy, 10.0, 100.0)],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let spots = vec![DataFrame::new(begin, 100.0, 100.0, 100.0, 100.0)];
+
+        //
+        // converted at 1.0 with a warning
+        let mut provider = MockNoFxProvider(fixture::MockProvider::new(spots.clone()));
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        for indicator in indicators.portfolios.iter() {
+            assert_float_absolute_eq!(indicator.positions[0].fx_rate, 1.0, 1e-7);
+        }
+
+        //
+        // an error on strict
+        let mut provider = MockNoFxProvider(fixture::MockProvider::new(spots.clone()));
+        let options = PricingOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &options
+        )
+        .is_err());
 
-        indicators
+        //
+        // but not when the trades give the fx rate
+        let portfolio = Portfolio {
+            positions: vec![Position {
+                instrument: portfolio.positions[0].instrument.clone(),
+                trades: vec![Trade {
+                    fx_rate: Some(0.9),
+                    ..make_trade_(begin, Way::Buy, 10.0, 100.0)
+                }],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            ..portfolio
+        };
+        let mut provider = MockNoFxProvider(fixture::MockProvider::new(spots));
+        let indicators =
+            PortfolioIndicators::from_portfolio(&portfolio, begin, end, &mut provider, &options)
+                .unwrap();
+        assert_float_absolute_eq!(indicators.portfolios[1].positions[0].fx_rate, 0.9, 1e-7);
     }
 }
@@ -0,0 +1,150 @@
+use super::PortfolioIndicators;
+use crate::marketdata::Instrument;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub struct Mover {
+    pub instrument: Rc<Instrument>,
+    pub position_index: usize,
+    pub contribution: f64,
+    pub change_percent: f64,
+}
+
+impl PortfolioIndicators {
+    //
+    // daily contribution of open positions between the two last priced dates,
+    // returns the n best gainers then the n worst losers
+    pub fn top_movers(&self, n: usize) -> (Vec<Mover>, Vec<Mover>) {
+        let [.., previous, last] = self.portfolios.as_slice() else {
+            return Default::default();
+        };
+
+        let mut movers = last
+            .positions
+            .iter()
+            .filter(|position| !position.is_close)
+            .filter_map(|position| {
+                let indicators = self
+                    .get_position_indicators(&position.instrument.name, position.position_index);
+                let [.., before, after] = indicators.positions.as_slice() else {
+                    return None;
+                };
+                if after.date != last.date || before.date != previous.date {
+                    return None;
+                }
+                let contribution = after.earning_latent - before.earning_latent;
+                let change_percent = if before.valuation.abs() < 1e-7 {
+                    0.0
+                } else {
+                    contribution / before.valuation
+                };
+                Some(Mover {
+                    instrument: position.instrument.clone(),
+                    position_index: position.position_index,
+                    contribution,
+                    change_percent,
+                })
+            })
+            .collect::<Vec<_>>();
+        movers.sort_by(|left, right| right.contribution.total_cmp(&left.contribution));
+
+        let losers = movers
+            .iter()
+            .rev()
+            .take_while(|mover| mover.contribution < 0.0)
+            .take(n)
+            .cloned()
+            .collect();
+        let gainers = movers
+            .into_iter()
+            .take_while(|mover| mover.contribution > 0.0)
+            .take(n)
+            .collect();
+        (gainers, losers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::marketdata::Currency;
+    use crate::portfolio::{Portfolio, Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+
+    fn make_position_(name: &str, currency: &Rc<Currency>, quantity: f64) -> Position {
+        Position {
+            instrument: Rc::new(make_instrument_(name, currency)),
+            trades: vec![Trade {
+                date: make_date_(2022, 3, 17).and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity,
+                price: 20.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        }
+    }
+
+    fn make_spots_(values: [f64; 2]) -> Vec<DataFrame> {
+        values
+            .into_iter()
+            .zip(make_date_(2022, 3, 17).iter_days())
+            .map(|(value, date)| DataFrame::new(date, value, value, value, value))
+            .collect()
+    }
+
+    #[test]
+    fn top_movers() {
+        let currency = make_currency_("EUR");
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                make_position_("ESE", &currency, 5.0),
+                make_position_("PAEEM", &currency, 10.0),
+            ],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::by_instrument([
+            ("ESE", make_spots_([20.0, 19.0])),
+            ("PAEEM", make_spots_([20.0, 22.0])),
+        ]);
+
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2022, 3, 17),
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let (gainers, losers) = indicators.top_movers(3);
+        assert!(gainers.is_empty());
+        assert!(losers.is_empty());
+
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            make_date_(2022, 3, 17),
+            make_date_(2022, 3, 18),
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+        let (gainers, losers) = indicators.top_movers(3);
+        assert_eq!(gainers.len(), 1);
+        assert_eq!(gainers[0].instrument.name, "PAEEM");
+        assert_float_absolute_eq!(gainers[0].contribution, 20.0, 1e-7);
+        assert_float_absolute_eq!(gainers[0].change_percent, 0.1, 1e-7);
+        assert_eq!(losers.len(), 1);
+        assert_eq!(losers[0].instrument.name, "ESE");
+        assert_float_absolute_eq!(losers[0].contribution, -5.0, 1e-7);
+        assert_float_absolute_eq!(losers[0].change_percent, -0.05, 1e-7);
+    }
+}
@@ -0,0 +1,206 @@
+use super::RiskFreeRate;
+use crate::alias::Date;
+use crate::historical::DataFrame;
+use crate::marketdata::{Dividend, Instrument};
+use crate::portfolio::{Trade, QUANTITY_EPSILON};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ValuationPrice {
+    #[default]
+    Close,
+    Open,
+    Mid,
+    High,
+    Low,
+}
+
+impl ValuationPrice {
+    pub fn price(&self, spot: &DataFrame) -> f64 {
+        match self {
+            Self::Close => spot.close,
+            Self::Open => spot.open,
+            Self::Mid => (spot.high + spot.low) / 2.0,
+            Self::High => spot.high,
+            Self::Low => spot.low,
+        }
+    }
+}
+
+impl std::fmt::Display for ValuationPrice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+//
+// handling of a position without spot at its first pricing days
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FirstSpot {
+    // pricing starts at the first available spot
+    #[default]
+    Skip,
+    // the first available spot prices the first day only
+    Backfill,
+    // the first available spot prices every missing day before it
+    Carry,
+}
+
+impl std::fmt::Display for FirstSpot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+//
+// denominator of the position weights, the instrument and region
+// distributions are always on the invested valuation
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WeightBase {
+    // open positions valuation, cash excluded
+    #[default]
+    Invested,
+    // open positions valuation with the cash
+    TotalIncludingCash,
+}
+
+impl std::fmt::Display for WeightBase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+//
+// fees of a buy in the unit price (capitalized) or only in the earning
+// (expensed), the nominal is then the pure notional
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FeesTreatment {
+    #[default]
+    Capitalized,
+    Expensed,
+}
+
+impl std::fmt::Display for FeesTreatment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+impl FeesTreatment {
+    pub fn cost(&self, trade: &Trade) -> f64 {
+        match self {
+            Self::Capitalized => trade.price * trade.quantity + trade.fees,
+            Self::Expensed => trade.price * trade.quantity,
+        }
+    }
+}
+
+pub const MIN_OBSERVATIONS: usize = 20;
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+#[derive(Clone, Debug)]
+pub struct PricingOptions {
+    // round monetary values to cents once the whole series is priced,
+    // twr and others compounded values are computed with full precision
+    pub round_to_cents: bool,
+    // each dividend payment buys shares at the payment date spot
+    // instead of being credited as cash
+    pub reinvest_dividends: bool,
+    // number of days between trade date and settlement date,
+    // quantity and cash move on settlement date
+    pub settlement_lag_days: u64,
+    // spot field used to value positions
+    pub valuation_price: ValuationPrice,
+    // absolute quantity below which a position is closed
+    pub quantity_epsilon: f64,
+    // missing spot policy at position start
+    pub first_spot: FirstSpot,
+    // annual risk free rate used by risk adjusted indicators
+    pub risk_free: RiskFreeRate,
+    // dates of bad market data, kept in indicators but flagged and
+    // skipped by volatility, drawdown, heat map and others metrics
+    pub excluded_dates: Vec<Date>,
+    // cagr, sharpe and calmar keep their raw value on a history
+    // shorter than a year
+    pub no_annualize_under_one_year: bool,
+    // daily observations required before reporting a volatility
+    // (and the ratios built on it), blank otherwise
+    pub min_observations: usize,
+    // a position without spot is valued at its last trade price
+    pub mark_to_last_trade: bool,
+    // annual rates of the interest compounded daily on a negative cash (margin)
+    // and earned on a positive cash (deposit), both zero by default
+    pub margin_rate: f64,
+    pub deposit_rate: f64,
+    // a position without trade fails the pricing instead of a warning
+    pub strict: bool,
+    // annualization of daily volatility, sharpe and risk free rate (ex: 250, 252, 260)
+    pub trading_days_per_year: f64,
+    // dividends fetched from yahoo by instrument name, used when the
+    // instrument has none in its json
+    pub fetched_dividends: BTreeMap<String, Vec<Dividend>>,
+    pub weight_base: WeightBase,
+    pub fees_treatment: FeesTreatment,
+}
+
+impl Default for PricingOptions {
+    fn default() -> Self {
+        Self {
+            round_to_cents: false,
+            reinvest_dividends: false,
+            settlement_lag_days: 0,
+            valuation_price: Default::default(),
+            quantity_epsilon: QUANTITY_EPSILON,
+            first_spot: Default::default(),
+            risk_free: Default::default(),
+            excluded_dates: Default::default(),
+            no_annualize_under_one_year: false,
+            min_observations: MIN_OBSERVATIONS,
+            mark_to_last_trade: false,
+            margin_rate: 0.0,
+            deposit_rate: 0.0,
+            strict: false,
+            trading_days_per_year: TRADING_DAYS_PER_YEAR,
+            fetched_dividends: Default::default(),
+            weight_base: Default::default(),
+            fees_treatment: Default::default(),
+        }
+    }
+}
+
+impl PricingOptions {
+    pub fn dividends<'a>(&'a self, instrument: &'a Instrument) -> &'a [Dividend] {
+        match &instrument.dividends {
+            Some(dividends) => dividends,
+            None => self
+                .fetched_dividends
+                .get(&instrument.name)
+                .map_or(&[], Vec::as_slice),
+        }
+    }
+
+    pub fn is_excluded(&self, date: Date) -> bool {
+        self.excluded_dates.contains(&date)
+    }
+
+    pub fn cash_rate(&self, cash: f64) -> f64 {
+        if cash < 0.0 {
+            self.margin_rate
+        } else {
+            self.deposit_rate
+        }
+    }
+}
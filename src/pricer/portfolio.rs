@@ -1,12 +1,15 @@
 use super::position::PositionIndicator;
-use super::primitive;
+use super::{primitive, PricingOptions, WeightBase};
 use crate::alias::Date;
 use crate::portfolio::{CashVariationSource, Portfolio};
+use std::collections::BTreeMap;
 use std::iter::Sum;
 use std::ops::Add;
 
 use log::debug;
 
+//
+// position amounts converted in the portfolio currency
 #[derive(Default)]
 struct PositionAccumulator {
     pub valuation: f64,
@@ -32,12 +35,12 @@ impl PositionAccumulator {
 
     fn from_position_(position: &PositionIndicator) -> Self {
         Self {
-            valuation: position.valuation,
-            nominal: position.nominal,
-            dividends: position.dividends,
-            fees: position.fees,
-            earning: position.earning,
-            earning_latent: position.earning_latent,
+            valuation: position.home_valuation,
+            nominal: position.home_nominal,
+            dividends: position.home_dividends,
+            fees: position.home_fees,
+            earning: position.home_earning,
+            earning_latent: position.home_earning_latent,
         }
     }
 }
@@ -63,7 +66,7 @@ impl Sum for PositionAccumulator {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct PortfolioIndicator {
     pub date: Date,
     pub positions: Vec<PositionIndicator>,
@@ -88,18 +91,55 @@ pub struct PortfolioIndicator {
     pub open_earning_latent: f64,
     pub incoming_transfer: f64,
     pub outcoming_transfer: f64,
+    // cost of the opening positions transferred in, not a payment
+    pub securities_transfer: f64,
     pub cash: f64,
+    // balance of each tagged cash account in its currency, the default
+    // account is the rest of cash, all converted accounts are in cash
+    pub account_cash: BTreeMap<String, f64>,
+    // cumulative interest on cash, negative for a margin cost, already in cash and earning
+    pub cash_interest: f64,
+    // gain of the tagged accounts revalued at their latest fx rate, already in cash
+    pub account_revaluation: f64,
+    // net payment transfers and securities transferred in minus cash not yet
+    // deployed, so realized p&l and dividends kept in cash reduce it (unlike
+    // nominal, cash excluded)
+    pub invested_capital: f64,
+    pub excluded: bool,
+    // copy of the last priced indicator appended up to the pricing end (--pad-to-end)
+    pub carried: bool,
 }
 
 impl PortfolioIndicator {
     pub fn from_portfolio(
         portfolio: &Portfolio,
         date: Date,
-        positions: Vec<PositionIndicator>,
+        mut positions: Vec<PositionIndicator>,
         previous_indicators: &[PortfolioIndicator],
+        options: &PricingOptions,
     ) -> PortfolioIndicator {
         debug!("price portfolio at {}", date);
+        let excluded = positions.iter().any(|position| position.excluded);
 
+        //
+        // an opening position is a transfer in of securities, its cost offsets
+        // the initial buy (converted at the same fx rate) so it is not a cash outflow
+        let securities_transfer = positions
+            .iter()
+            .filter_map(|position_indicator| {
+                let opening_position = portfolio
+                    .positions
+                    .get(position_indicator.position_index)?
+                    .opening_position
+                    .as_ref()?;
+                opening_position
+                    .date
+                    .date()
+                    .checked_add_days(chrono::Days::new(options.settlement_lag_days))
+                    .filter(|settled_date| *settled_date <= date)
+                    .map(|_| opening_position.cost() * position_indicator.fx_rate)
+            })
+            .sum::<f64>();
         let incoming_transfer = portfolio
             .cash
             .iter()
@@ -108,7 +148,7 @@ impl PortfolioIndicator {
                     && variation.source == CashVariationSource::Payment
                     && variation.position.is_sign_positive()
             })
-            .map(|variation| variation.position)
+            .map(|variation| variation.home_position())
             .sum::<f64>();
 
         let outcoming_transfer = portfolio
@@ -119,7 +159,7 @@ impl PortfolioIndicator {
                     && variation.source == CashVariationSource::Payment
                     && variation.position.is_sign_negative()
             })
-            .map(|variation| variation.position)
+            .map(|variation| variation.home_position())
             .sum::<f64>();
 
         let accumulator = positions
@@ -127,12 +167,61 @@ impl PortfolioIndicator {
             .map(PositionAccumulator::from_position)
             .sum::<PositionAccumulator>();
 
+        //
+        // cash of the positions closed before the pricing and not priced (--only-open)
+        let realized = portfolio
+            .cash
+            .iter()
+            .filter(|variation| {
+                variation.date.date() <= date && variation.source == CashVariationSource::Realized
+            })
+            .map(|variation| variation.home_position())
+            .sum::<f64>();
+
         let open_accumulator = positions
             .iter()
             .map(PositionAccumulator::from_open_position)
             .sum::<PositionAccumulator>();
 
-        let cash = outcoming_transfer + incoming_transfer + accumulator.earning;
+        //
+        // interest on the previous cash (with its interest) compounds day after day
+        let cash_interest = previous_indicators
+            .last()
+            .map_or(0.0, |previous_indicator| {
+                let years = (date - previous_indicator.date).num_days() as f64 / 365.0;
+                let rate = options.cash_rate(previous_indicator.cash);
+                previous_indicator.cash_interest
+                    + previous_indicator.cash * ((1.0 + rate).powf(years) - 1.0)
+            });
+
+        //
+        // tagged accounts are revalued at their latest fx rate, transfers are
+        // at the rate of their date
+        let account_balances = portfolio.get_account_balances(date);
+        let account_revaluation = account_balances
+            .values()
+            .map(|(balance, fx_rate)| balance * fx_rate)
+            .sum::<f64>()
+            - portfolio
+                .cash
+                .iter()
+                .filter(|variation| variation.date.date() <= date && variation.account.is_some())
+                .map(|variation| variation.home_position())
+                .sum::<f64>();
+        let account_cash = account_balances
+            .into_iter()
+            .map(|(account, (balance, _))| (account.clone(), balance))
+            .collect();
+
+        let cash = outcoming_transfer
+            + incoming_transfer
+            + securities_transfer
+            + accumulator.earning
+            + realized
+            + cash_interest
+            + account_revaluation;
+        let invested_capital = outcoming_transfer + incoming_transfer + securities_transfer
+            - (cash - cash_interest - account_revaluation);
         let nominal = cash + accumulator.nominal;
         let valuation = cash + accumulator.valuation;
         let fees_percent = if valuation + accumulator.fees == 0.0 {
@@ -140,6 +229,32 @@ impl PortfolioIndicator {
         } else {
             accumulator.fees / (accumulator.fees + valuation)
         };
+        //
+        // weight of each open position in home currency, compared to its weight at cost
+        let weight_cash = match options.weight_base {
+            WeightBase::Invested => 0.0,
+            WeightBase::TotalIncludingCash => cash,
+        };
+        let (home_valuation, home_nominal) =
+            positions.iter().filter(|position| !position.is_close).fold(
+                (weight_cash, weight_cash),
+                |(valuation, nominal), position| {
+                    (
+                        valuation + position.home_valuation,
+                        nominal + position.home_nominal,
+                    )
+                },
+            );
+        for position in positions.iter_mut().filter(|position| !position.is_close) {
+            if home_valuation != 0.0 {
+                position.weight = position.home_valuation / home_valuation;
+            }
+            if home_nominal != 0.0 {
+                position.cost_weight = position.home_nominal / home_nominal;
+            }
+            position.weight_drift = position.weight - position.cost_weight;
+        }
+
         let open_nominal = open_accumulator.nominal;
         let open_valuation = open_accumulator.valuation;
         let (pnl_currency, pnl_percent) = primitive::pnl(valuation, nominal);
@@ -192,23 +307,70 @@ impl PortfolioIndicator {
             open_pnl_percent,
             twr,
             open_twr,
-            earning: accumulator.earning,
+            earning: accumulator.earning + realized + cash_interest,
             open_earning: open_accumulator.earning,
-            earning_latent: accumulator.earning_latent,
+            earning_latent: accumulator.earning_latent + realized,
             open_earning_latent: open_accumulator.earning_latent,
             incoming_transfer,
             outcoming_transfer,
+            securities_transfer,
             cash,
+            account_cash,
+            cash_interest,
+            account_revaluation,
+            invested_capital,
+            excluded,
+            carried: false,
         }
     }
+
+    pub fn stress_test(&self, shocks: &[f64]) -> Vec<(f64, f64)> {
+        let open_valuation = self
+            .positions
+            .iter()
+            .filter(|position| !position.is_close)
+            .map(|position| position.home_valuation)
+            .sum::<f64>();
+        shocks
+            .iter()
+            .map(|shock| (*shock, self.valuation + open_valuation * shock))
+            .collect()
+    }
+
+    pub fn round_to_cents(&mut self) {
+        self.positions
+            .iter_mut()
+            .for_each(PositionIndicator::round_to_cents);
+        self.valuation = primitive::round_to_cents(self.valuation);
+        self.open_valuation = primitive::round_to_cents(self.open_valuation);
+        self.nominal = primitive::round_to_cents(self.nominal);
+        self.open_nominal = primitive::round_to_cents(self.open_nominal);
+        self.dividends = primitive::round_to_cents(self.dividends);
+        self.open_dividends = primitive::round_to_cents(self.open_dividends);
+        self.fees = primitive::round_to_cents(self.fees);
+        self.open_fees = primitive::round_to_cents(self.open_fees);
+        self.pnl_currency = primitive::round_to_cents(self.pnl_currency);
+        self.open_pnl_currency = primitive::round_to_cents(self.open_pnl_currency);
+        self.earning = primitive::round_to_cents(self.earning);
+        self.open_earning = primitive::round_to_cents(self.open_earning);
+        self.earning_latent = primitive::round_to_cents(self.earning_latent);
+        self.open_earning_latent = primitive::round_to_cents(self.open_earning_latent);
+        self.incoming_transfer = primitive::round_to_cents(self.incoming_transfer);
+        self.outcoming_transfer = primitive::round_to_cents(self.outcoming_transfer);
+        self.securities_transfer = primitive::round_to_cents(self.securities_transfer);
+        self.cash = primitive::round_to_cents(self.cash);
+        self.cash_interest = primitive::round_to_cents(self.cash_interest);
+        self.account_revaluation = primitive::round_to_cents(self.account_revaluation);
+        self.invested_capital = primitive::round_to_cents(self.invested_capital);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::historical::DataFrame;
-    use crate::marketdata::{Currency, Instrument, Market};
     use crate::portfolio::{CashVariation, CashVariationSource};
+    use crate::pricer::fixture::{make_currency_, make_instrument_};
     use assert_float_eq::*;
     use std::rc::Rc;
 
@@ -221,25 +383,7 @@ mod tests {
         fees: f64,
     ) -> PositionIndicator {
         let date = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
-        let currency = Rc::new(Currency {
-            name: String::from("EUR"),
-            parent_currency: None,
-        });
-        let market = Rc::new(Market {
-            name: String::from("EPA"),
-            description: String::from("EPA"),
-        });
-        let instrument = Rc::new(Instrument {
-            name: String::from("PAEEM"),
-            isin: String::from("ISIN"),
-            description: String::from("description"),
-            market: market.clone(),
-            currency: currency.clone(),
-            ticker_yahoo: None,
-            region: None,
-            fund_category: String::from("category"),
-            dividends: None,
-        });
+        let instrument = Rc::new(make_instrument_("PAEEM", &make_currency_("EUR")));
         PositionIndicator {
             date,
             spot: DataFrame::new(date, 22.0, 22.0, 22.0, 22.0),
@@ -249,6 +393,7 @@ mod tests {
             quantity_buy: 0.0,
             quantity_sell: 0.0,
             unit_price: 0.0,
+            break_even_price: None,
             valuation,
             nominal,
             cashflow: 0.0,
@@ -260,15 +405,29 @@ mod tests {
             earning,
             earning_latent,
             is_close: false,
+            fx_rate: 1.0,
+            home_valuation: valuation,
+            home_nominal: nominal,
+            home_dividends: dividends,
+            home_fees: fees,
+            home_earning: earning,
+            home_earning_latent: earning_latent,
+            home_pnl_currency: 0.0,
+            home_pnl_percent: 0.0,
+            pnl_attribution: Default::default(),
+            weight: 0.0,
+            cost_weight: 0.0,
+            weight_drift: 0.0,
+            excluded: false,
+            ter_drag: 0.0,
+            ter_realized: 0.0,
+            home_ter_realized: 0.0,
         }
     }
 
     #[test]
     fn compute_portfolio() {
-        let currency = Rc::new(Currency {
-            name: String::from("EUR"),
-            parent_currency: None,
-        });
+        let currency = make_currency_("EUR");
 
         let portfolio = Portfolio {
             name: "TEST".to_string(),
@@ -280,6 +439,8 @@ mod tests {
                     .unwrap()
                     .naive_local(),
                 source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
             }],
         };
 
@@ -295,12 +456,14 @@ mod tests {
                 date,
                 positions_indicators,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
 
             assert_float_absolute_eq!(indicator.incoming_transfer, 1000.0, 1e-7);
             assert_float_absolute_eq!(indicator.outcoming_transfer, 0.0, 1e-7);
             assert_float_absolute_eq!(indicator.nominal, 1000.0, 1e-7);
             assert_float_absolute_eq!(indicator.cash, 810.0, 1e-7);
+            assert_float_absolute_eq!(indicator.invested_capital, 190.0, 1e-7);
             assert_float_absolute_eq!(indicator.valuation, 1010.0, 1e-7);
             assert_float_absolute_eq!(indicator.fees, 2.0, 1e-7);
             assert_float_absolute_eq!(indicator.dividends, 0.0, 1e-7);
@@ -324,6 +487,7 @@ mod tests {
                 date,
                 positions_indicators,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
 
             assert_float_absolute_eq!(indicator.incoming_transfer, 1000.0, 1e-7);
@@ -342,4 +506,279 @@ mod tests {
             previous_indicators.push(indicator);
         }
     }
+
+    #[test]
+    fn margin_interest_compounds() {
+        let portfolio = Portfolio {
+            name: "TEST".to_string(),
+            currency: make_currency_("EUR"),
+            positions: Default::default(),
+            cash: vec![CashVariation {
+                position: 1000.0,
+                date: chrono::DateTime::parse_from_rfc3339("2025-01-01T10:00:00-00:00")
+                    .unwrap()
+                    .naive_local(),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        };
+        let options = PricingOptions {
+            margin_rate: 0.05,
+            deposit_rate: 0.01,
+            ..Default::default()
+        };
+
+        //
+        // 2000 bought with 1000 paid in, so 1000 borrowed for 60 days
+        let mut previous_indicators: Vec<PortfolioIndicator> = Vec::new();
+        for day in [1, 31, 61] {
+            let date = chrono::NaiveDate::from_yo_opt(2025, day).unwrap();
+            let positions_indicators = vec![make_fake_position_indicator_(
+                2000.0, 2000.0, 0.0, -2000.0, -2000.0, 0.0,
+            )];
+            let indicator = PortfolioIndicator::from_portfolio(
+                &portfolio,
+                date,
+                positions_indicators,
+                &previous_indicators,
+                &options,
+            );
+            let wanted = -1000.0 * (1.05_f64.powf((day - 1) as f64 / 365.0) - 1.0);
+            assert_float_absolute_eq!(indicator.cash_interest, wanted, 1e-7);
+            assert_float_absolute_eq!(indicator.cash, -1000.0 + wanted, 1e-7);
+            assert_float_absolute_eq!(indicator.earning, -2000.0 + wanted, 1e-7);
+            assert_float_absolute_eq!(indicator.invested_capital, 2000.0, 1e-7);
+            assert_float_absolute_eq!(indicator.valuation, 1000.0 + wanted, 1e-7);
+            previous_indicators.push(indicator);
+        }
+        assert!(previous_indicators.last().unwrap().cash_interest < -8.0);
+    }
+
+    #[test]
+    fn compute_weight_drift() {
+        let portfolio = Portfolio {
+            name: "TEST".to_string(),
+            currency: make_currency_("EUR"),
+            positions: Default::default(),
+            cash: Default::default(),
+        };
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+        let positions_indicators = vec![
+            make_fake_position_indicator_(300.0, 100.0, 0.0, -100.0, -100.0, 0.0),
+            make_fake_position_indicator_(100.0, 100.0, 0.0, -100.0, -100.0, 0.0),
+        ];
+
+        let indicator = PortfolioIndicator::from_portfolio(
+            &portfolio,
+            date,
+            positions_indicators,
+            &[],
+            &PricingOptions::default(),
+        );
+
+        let tripled = &indicator.positions[0];
+        assert_float_absolute_eq!(tripled.cost_weight, 0.5, 1e-7);
+        assert_float_absolute_eq!(tripled.weight, 0.75, 1e-7);
+        assert_float_absolute_eq!(tripled.weight_drift, 0.25, 1e-7);
+        let flat = &indicator.positions[1];
+        assert_float_absolute_eq!(flat.cost_weight, 0.5, 1e-7);
+        assert_float_absolute_eq!(flat.weight, 0.25, 1e-7);
+        assert_float_absolute_eq!(flat.weight_drift, -0.25, 1e-7);
+    }
+
+    #[test]
+    fn compute_weight_base() {
+        let portfolio = Portfolio {
+            name: "TEST".to_string(),
+            currency: make_currency_("EUR"),
+            positions: Default::default(),
+            cash: vec![CashVariation {
+                position: 1000.0,
+                date: chrono::DateTime::parse_from_rfc3339("2022-03-17T10:00:00-00:00")
+                    .unwrap()
+                    .naive_local(),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        };
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+        let price_ = |weight_base| {
+            PortfolioIndicator::from_portfolio(
+                &portfolio,
+                date,
+                vec![
+                    make_fake_position_indicator_(300.0, 100.0, 0.0, -100.0, -100.0, 0.0),
+                    make_fake_position_indicator_(100.0, 100.0, 0.0, -100.0, -100.0, 0.0),
+                ],
+                &[],
+                &PricingOptions {
+                    weight_base,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let invested = price_(WeightBase::Invested);
+        assert_float_absolute_eq!(invested.cash, 800.0, 1e-7);
+        assert_float_absolute_eq!(
+            invested
+                .positions
+                .iter()
+                .map(|position| position.weight)
+                .sum::<f64>(),
+            1.0,
+            1e-7
+        );
+        assert_float_absolute_eq!(invested.positions[0].weight, 0.75, 1e-7);
+
+        let total = price_(WeightBase::TotalIncludingCash);
+        assert_float_absolute_eq!(total.positions[0].weight, 0.25, 1e-7);
+        assert_float_absolute_eq!(total.positions[1].weight, 100.0 / 1200.0, 1e-7);
+        assert_float_absolute_eq!(total.positions[0].cost_weight, 0.1, 1e-7);
+        assert_float_absolute_eq!(total.valuation, 1200.0, 1e-7);
+    }
+
+    #[test]
+    fn stress_test() {
+        let currency = make_currency_("EUR");
+
+        let portfolio = Portfolio {
+            name: "TEST".to_string(),
+            currency: currency.clone(),
+            positions: Default::default(),
+            cash: vec![CashVariation {
+                position: 1000.0,
+                date: chrono::DateTime::parse_from_rfc3339("2022-03-17T10:00:00-00:00")
+                    .unwrap()
+                    .naive_local(),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        };
+
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+        let positions_indicators = vec![make_fake_position_indicator_(
+            200.0, 190.0, 0.0, -190.0, -190.0, 2.0,
+        )];
+        let indicator = PortfolioIndicator::from_portfolio(
+            &portfolio,
+            date,
+            positions_indicators,
+            &[],
+            &PricingOptions::default(),
+        );
+        assert_float_absolute_eq!(indicator.valuation, 1010.0, 1e-7);
+
+        let result = indicator.stress_test(&[-0.1, 0.1]);
+        assert_eq!(result.len(), 2);
+        assert_float_absolute_eq!(result[0].0, -0.1, 1e-7);
+        assert_float_absolute_eq!(result[0].1, 990.0, 1e-7);
+        assert_float_absolute_eq!(result[1].0, 0.1, 1e-7);
+        assert_float_absolute_eq!(result[1].1, 1030.0, 1e-7);
+    }
+
+    #[test]
+    fn round_portfolio_to_cents() {
+        let currency = make_currency_("EUR");
+
+        let portfolio = Portfolio {
+            name: "TEST".to_string(),
+            currency: currency.clone(),
+            positions: Default::default(),
+            cash: Default::default(),
+        };
+
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+        let positions_indicators = vec![
+            make_fake_position_indicator_(100.004, 100.0, 0.0, 0.0, 0.0, 0.0),
+            make_fake_position_indicator_(200.003, 200.0, 0.0, 0.0, 0.0, 0.0),
+        ];
+        let mut indicator = PortfolioIndicator::from_portfolio(
+            &portfolio,
+            date,
+            positions_indicators,
+            &[],
+            &PricingOptions::default(),
+        );
+        assert_float_absolute_eq!(indicator.valuation, 300.007, 1e-7);
+
+        indicator.round_to_cents();
+        assert_float_absolute_eq!(indicator.positions[0].valuation, 100.0, 1e-7);
+        assert_float_absolute_eq!(indicator.positions[1].valuation, 200.0, 1e-7);
+        assert_float_absolute_eq!(indicator.valuation, 300.01, 1e-7);
+
+        let positions_valuation = indicator
+            .positions
+            .iter()
+            .map(|position| position.valuation)
+            .sum::<f64>();
+        assert!((positions_valuation - indicator.valuation).abs() <= 0.01 + 1e-7);
+    }
+
+    #[test]
+    fn tagged_cash_accounts() {
+        let currency = make_currency_("EUR");
+        let make_variation_ =
+            |day: u32, position: f64, account: &str, fx_rate: Option<f64>| CashVariation {
+                position,
+                date: chrono::NaiveDate::from_ymd_opt(2025, 3, day)
+                    .unwrap()
+                    .and_hms_opt(10, 0, 0)
+                    .unwrap(),
+                source: CashVariationSource::Payment,
+                account: Some(String::from(account)),
+                fx_rate,
+            };
+        let portfolio = Portfolio {
+            name: "TEST".to_string(),
+            currency: currency.clone(),
+            positions: Default::default(),
+            cash: vec![
+                make_variation_(17, 1000.0, "EUR", None),
+                make_variation_(17, 500.0, "USD", Some(0.9)),
+                make_variation_(18, 100.0, "USD", Some(0.8)),
+            ],
+        };
+
+        let indicator = PortfolioIndicator::from_portfolio(
+            &portfolio,
+            chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap(),
+            Vec::new(),
+            &[],
+            &PricingOptions::default(),
+        );
+        assert_eq!(
+            indicator
+                .account_cash
+                .iter()
+                .map(|(account, balance)| (account.as_str(), *balance))
+                .collect::<Vec<_>>(),
+            vec![("EUR", 1000.0), ("USD", 500.0)]
+        );
+        assert_float_absolute_eq!(indicator.cash, 1450.0, 1e-7);
+
+        //
+        // the whole usd balance is converted at the latest rate
+        let indicator = PortfolioIndicator::from_portfolio(
+            &portfolio,
+            chrono::NaiveDate::from_ymd_opt(2025, 3, 18).unwrap(),
+            Vec::new(),
+            &[indicator],
+            &PricingOptions::default(),
+        );
+        assert_eq!(
+            indicator
+                .account_cash
+                .iter()
+                .map(|(account, balance)| (account.as_str(), *balance))
+                .collect::<Vec<_>>(),
+            vec![("EUR", 1000.0), ("USD", 600.0)]
+        );
+        assert_float_absolute_eq!(indicator.incoming_transfer, 1530.0, 1e-7);
+        assert_float_absolute_eq!(indicator.cash, 1000.0 + 600.0 * 0.8, 1e-7);
+        assert_float_absolute_eq!(indicator.invested_capital, 0.0, 1e-7);
+    }
 }
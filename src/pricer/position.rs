@@ -1,12 +1,40 @@
 use super::primitive;
+use super::{FeesTreatment, PricingOptions};
 use crate::alias::Date;
 use crate::historical::DataFrame;
 use crate::marketdata::Instrument;
-use crate::portfolio::{Position, Way};
+use crate::portfolio::{Position, Trade, Way};
 use std::rc::Rc;
 
 use log::debug;
 
+//
+// home currency breakdown of the position earning, cash flows are taken at
+// the average trade fx rate of the held quantity and fx is the revaluation
+// of the held position at the current fx rate
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PnlAttribution {
+    pub price: f64,
+    pub fx: f64,
+    pub dividend: f64,
+    pub fees: f64,
+}
+
+impl PnlAttribution {
+    pub fn total(&self) -> f64 {
+        self.price + self.fx + self.dividend + self.fees
+    }
+}
+
+//
+// dated amount seen from the holder, negative when invested
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CashFlow {
+    pub date: Date,
+    pub amount: f64,
+}
+
+#[derive(Clone)]
 pub struct PositionIndicator {
     pub date: Date,
     pub spot: DataFrame,
@@ -16,6 +44,7 @@ pub struct PositionIndicator {
     pub quantity_buy: f64,
     pub quantity_sell: f64,
     pub unit_price: f64,
+    pub break_even_price: Option<f64>,
     pub valuation: f64,
     pub nominal: f64,
     pub cashflow: f64,
@@ -27,6 +56,26 @@ pub struct PositionIndicator {
     pub earning: f64,
     pub earning_latent: f64,
     pub is_close: bool,
+    pub fx_rate: f64,
+    pub home_valuation: f64,
+    pub home_nominal: f64,
+    pub home_dividends: f64,
+    pub home_fees: f64,
+    pub home_earning: f64,
+    pub home_earning_latent: f64,
+    pub home_pnl_currency: f64,
+    pub home_pnl_percent: f64,
+    pub pnl_attribution: PnlAttribution,
+    pub weight: f64,
+    pub cost_weight: f64,
+    pub weight_drift: f64,
+    pub excluded: bool,
+    // cumulated expense ratio charged on the held valuation, already out of
+    // valuation (not a cash flow), scaled down with the held quantity on a sell
+    pub ter_drag: f64,
+    // drag of the sold quantity, out of earning and of the sell cash flow
+    pub ter_realized: f64,
+    pub home_ter_realized: f64,
 }
 
 impl PositionIndicator {
@@ -35,22 +84,108 @@ impl PositionIndicator {
         date: Date,
         position_index: usize,
         spot: &DataFrame,
+        fx_rate: f64,
         previous_indicators: &[PositionIndicator],
+        options: &PricingOptions,
     ) -> PositionIndicator {
         debug!(
             "price position {} at {} with spot:{}",
             position.instrument.name, date, spot.close
         );
 
-        let (quantity, quantity_buy, quantity_sell, unit_price, fees) =
-            Self::compute_quantity_(position, date);
+        //
+        // a trade is taken into account once settled
+        let settled_date = date
+            .checked_sub_days(chrono::Days::new(options.settlement_lag_days))
+            .unwrap_or(Date::MIN);
+
+        //
+        // built once, it is a copy with an opening position or a bond redemption
+        let trades = position.priced_trades(date);
+
+        let (traded_quantity, quantity_buy, quantity_sell, unit_price, fees) =
+            Self::compute_quantity_(
+                &trades,
+                settled_date,
+                options.quantity_epsilon,
+                options.fees_treatment,
+            );
 
-        let is_close = quantity.abs() < 1e-7;
+        let is_close = traded_quantity.abs() < options.quantity_epsilon;
 
-        let valuation = spot.close * quantity;
-        let nominal = unit_price * quantity;
+        let quantity = if options.reinvest_dividends && !is_close {
+            traded_quantity
+                + Self::compute_reinvested_quantity_(
+                    &position.instrument,
+                    &trades,
+                    date,
+                    spot,
+                    previous_indicators,
+                    options,
+                )
+        } else {
+            traded_quantity
+        };
 
-        let cashflow = Self::compute_cashflow_(position, date);
+        //
+        // reinvested shares are sold with the last traded ones at the same price
+        let (reinvested_proceeds, home_reinvested_proceeds) =
+            if options.reinvest_dividends && is_close {
+                trades
+                    .iter()
+                    .rev()
+                    .find(|trade| trade.date.date() <= settled_date && !trade.is_fee_only())
+                    .map_or((0.0, 0.0), |trade| {
+                        let proceeds = trade.price
+                            * Self::compute_reinvested_quantity_(
+                                &position.instrument,
+                                &trades,
+                                trade.date.date(),
+                                spot,
+                                previous_indicators,
+                                options,
+                            );
+                        (proceeds, proceeds * trade.fx_rate.unwrap_or(fx_rate))
+                    })
+            } else {
+                (0.0, 0.0)
+            };
+
+        //
+        // pro-rata of the annual expense ratio on the valuation held since the previous
+        // date, the drag of a sold quantity is realized (all of it once closed)
+        let (ter_drag, ter_realized) = match previous_indicators.last() {
+            Some(previous_indicator) if !previous_indicator.is_close => {
+                let days = (date - previous_indicator.date).num_days() as f64;
+                let accrued = previous_indicator.ter_drag
+                    + position.instrument.ter.unwrap_or(0.0)
+                        * (previous_indicator.valuation + previous_indicator.ter_drag)
+                        * days
+                        / 365.0;
+                let held = if is_close {
+                    0.0
+                } else {
+                    (quantity / previous_indicator.quantity).min(1.0)
+                };
+                (
+                    accrued * held,
+                    previous_indicator.ter_realized + accrued * (1.0 - held),
+                )
+            }
+            Some(previous_indicator) => (0.0, previous_indicator.ter_realized),
+            None => (0.0, 0.0),
+        };
+
+        let valuation =
+            options.valuation_price.price(spot) * position.instrument.quote_factor * quantity
+                - ter_drag;
+        let nominal = unit_price * traded_quantity;
+
+        //
+        // a sell brings its proceeds net of the realized drag, so the drag
+        // is not given back in the twr
+        let cashflow =
+            Self::compute_cashflow_(&trades, settled_date) - reinvested_proceeds + ter_realized;
         let (pnl_currency, pnl_percent) = primitive::pnl(valuation, nominal);
 
         let (previous_twr, begin_valuation, delta_cashflow) =
@@ -66,11 +201,86 @@ impl PositionIndicator {
 
         let twr = primitive::twr(begin_valuation, valuation, delta_cashflow, previous_twr);
 
-        let dividends = Self::compute_dividends_(position, date);
+        let dividends = Self::compute_dividends_(&position.instrument, &trades, date, options);
 
-        let earning = dividends + Self::compute_earning_without_div_(position, date);
+        let earning = if options.reinvest_dividends {
+            Self::compute_earning_without_div_(&trades, settled_date) + reinvested_proceeds
+                - ter_realized
+        } else {
+            dividends + Self::compute_earning_without_div_(&trades, settled_date) - ter_realized
+        };
         let earning_latent = earning + valuation;
 
+        //
+        // reinvested dividends are already in quantity, expensed fees are not in nominal
+        let expensed_fees = match options.fees_treatment {
+            FeesTreatment::Capitalized => 0.0,
+            FeesTreatment::Expensed => fees,
+        };
+        let break_even_price = if is_close {
+            None
+        } else if options.reinvest_dividends {
+            Some((nominal + expensed_fees) / quantity)
+        } else {
+            Some((nominal + expensed_fees - dividends) / quantity)
+        };
+
+        let home_valuation = valuation * fx_rate;
+        let home_nominal = Self::compute_home_unit_price_(
+            &trades,
+            settled_date,
+            fx_rate,
+            options.quantity_epsilon,
+            options.fees_treatment,
+        ) * traded_quantity;
+        let (home_pnl_currency, home_pnl_percent) = primitive::pnl(home_valuation, home_nominal);
+
+        //
+        // a trade is converted at its own fx rate (the current one without) and
+        // a dividend at the fx rate of the first date it is priced
+        let home_dividends =
+            previous_indicators
+                .last()
+                .map_or(dividends * fx_rate, |previous_indicator| {
+                    previous_indicator.home_dividends
+                        + (dividends - previous_indicator.dividends) * fx_rate
+                });
+        let home_ter_realized =
+            previous_indicators
+                .last()
+                .map_or(ter_realized * fx_rate, |previous_indicator| {
+                    previous_indicator.home_ter_realized
+                        + (ter_realized - previous_indicator.ter_realized) * fx_rate
+                });
+        let (home_earning_without_div, home_fees) =
+            Self::compute_home_earning_without_div_(&trades, settled_date, fx_rate);
+        let home_earning = if options.reinvest_dividends {
+            home_earning_without_div + home_reinvested_proceeds - home_ter_realized
+        } else {
+            home_dividends + home_earning_without_div - home_ter_realized
+        };
+        let home_earning_latent = home_earning + home_valuation;
+
+        let cost_fx_rate = if nominal.abs() < 1e-7 {
+            fx_rate
+        } else {
+            home_nominal / nominal
+        };
+        //
+        // reinvested dividends are part of the valuation
+        let received_dividends = if options.reinvest_dividends {
+            0.0
+        } else {
+            dividends
+        };
+        let ter = ter_drag + ter_realized;
+        let pnl_attribution = PnlAttribution {
+            price: (earning_latent - received_dividends + fees + ter) * cost_fx_rate,
+            fx: valuation * (fx_rate - cost_fx_rate),
+            dividend: received_dividends * cost_fx_rate,
+            fees: -(fees + ter) * cost_fx_rate,
+        };
+
         PositionIndicator {
             date,
             spot: *spot,
@@ -80,6 +290,7 @@ impl PositionIndicator {
             quantity_buy,
             quantity_sell,
             unit_price,
+            break_even_price,
             valuation,
             nominal,
             cashflow,
@@ -91,12 +302,152 @@ impl PositionIndicator {
             earning,
             earning_latent,
             is_close,
+            fx_rate,
+            home_valuation,
+            home_nominal,
+            home_dividends,
+            home_fees,
+            home_earning,
+            home_earning_latent,
+            home_pnl_currency,
+            home_pnl_percent,
+            pnl_attribution,
+            //
+            // weights need the portfolio totals, see PortfolioIndicator::from_portfolio
+            weight: 0.0,
+            cost_weight: 0.0,
+            weight_drift: 0.0,
+            excluded: options.is_excluded(date),
+            ter_drag,
+            ter_realized,
+            home_ter_realized,
         }
     }
 
-    fn compute_quantity_(position: &Position, date: Date) -> (f64, f64, f64, f64, f64) {
-        position
-            .trades
+    pub fn round_to_cents(&mut self) {
+        self.valuation = primitive::round_to_cents(self.valuation);
+        self.nominal = primitive::round_to_cents(self.nominal);
+        self.cashflow = primitive::round_to_cents(self.cashflow);
+        self.dividends = primitive::round_to_cents(self.dividends);
+        self.fees = primitive::round_to_cents(self.fees);
+        self.pnl_currency = primitive::round_to_cents(self.pnl_currency);
+        self.earning = primitive::round_to_cents(self.earning);
+        self.earning_latent = primitive::round_to_cents(self.earning_latent);
+        self.home_valuation = primitive::round_to_cents(self.home_valuation);
+        self.home_nominal = primitive::round_to_cents(self.home_nominal);
+        self.home_dividends = primitive::round_to_cents(self.home_dividends);
+        self.home_fees = primitive::round_to_cents(self.home_fees);
+        self.home_earning = primitive::round_to_cents(self.home_earning);
+        self.home_earning_latent = primitive::round_to_cents(self.home_earning_latent);
+        self.home_pnl_currency = primitive::round_to_cents(self.home_pnl_currency);
+        self.pnl_attribution.price = primitive::round_to_cents(self.pnl_attribution.price);
+        self.pnl_attribution.fx = primitive::round_to_cents(self.pnl_attribution.fx);
+        self.pnl_attribution.dividend = primitive::round_to_cents(self.pnl_attribution.dividend);
+        self.pnl_attribution.fees = primitive::round_to_cents(self.pnl_attribution.fees);
+        self.ter_drag = primitive::round_to_cents(self.ter_drag);
+        self.ter_realized = primitive::round_to_cents(self.ter_realized);
+        self.home_ter_realized = primitive::round_to_cents(self.home_ter_realized);
+    }
+
+    //
+    // settled trades and dividends paid in cash up to the indicator date, an
+    // open position ends with its valuation as if sold at the indicator date
+    pub fn cashflows(&self, position: &Position, options: &PricingOptions) -> Vec<CashFlow> {
+        let priced_trades = position.priced_trades(self.date);
+        let trades = priced_trades
+            .iter()
+            .filter_map(|trade| {
+                let date = trade
+                    .date
+                    .date()
+                    .checked_add_days(chrono::Days::new(options.settlement_lag_days))?;
+                Some(CashFlow {
+                    date,
+                    amount: match trade.way {
+                        Way::Sell => trade.price * trade.quantity - trade.fees,
+                        Way::Buy => -trade.price * trade.quantity - trade.fees,
+                    },
+                })
+            })
+            .filter(|cashflow| cashflow.date <= self.date)
+            .collect::<Vec<_>>();
+
+        //
+        // reinvested dividends stay in the position, they are only cash once
+        // sold with the closing trade (the earning not explained by the trades)
+        let dividends: Vec<CashFlow> = if options.reinvest_dividends {
+            let reinvested_proceeds =
+                self.earning - trades.iter().map(|cashflow| cashflow.amount).sum::<f64>();
+            trades
+                .last()
+                .filter(|_| self.is_close)
+                .map(|cashflow| CashFlow {
+                    date: cashflow.date,
+                    amount: reinvested_proceeds,
+                })
+                .into_iter()
+                .collect()
+        } else {
+            options
+                .dividends(&position.instrument)
+                .iter()
+                .filter(|dividend| dividend.payment_date.date() <= self.date)
+                .map(|dividend| CashFlow {
+                    date: dividend.payment_date.date(),
+                    amount: dividend.value
+                        * Self::compute_settled_quantity_(
+                            &priced_trades,
+                            dividend.record_date.date(),
+                            options,
+                        ),
+                })
+                .collect()
+        };
+
+        let mut result = trades
+            .into_iter()
+            .chain(
+                dividends
+                    .into_iter()
+                    .filter(|cashflow| cashflow.amount.abs() > 1e-7),
+            )
+            .collect::<Vec<_>>();
+        result.sort_by_key(|cashflow| cashflow.date);
+        if !self.is_close {
+            result.push(CashFlow {
+                date: self.date,
+                amount: self.valuation,
+            });
+        }
+        result
+    }
+
+    //
+    // money weighted return of the cashflows, none when it can't be computed
+    // (the reason is logged), computed on demand as it solves on all the cashflows
+    pub fn irr(&self, position: &Position, options: &PricingOptions) -> Option<f64> {
+        let cashflows = self
+            .cashflows(position, options)
+            .iter()
+            .map(|cashflow| (cashflow.date, cashflow.amount))
+            .collect::<Vec<_>>();
+        primitive::xirr(&cashflows)
+            .map_err(|error| {
+                debug!(
+                    "no irr for position {} of instrument {} at {}: {}",
+                    self.position_index, self.instrument.name, self.date, error
+                );
+            })
+            .ok()
+    }
+
+    fn compute_quantity_(
+        trades: &[Trade],
+        date: Date,
+        epsilon: f64,
+        fees_treatment: FeesTreatment,
+    ) -> (f64, f64, f64, f64, f64) {
+        trades
             .iter()
             .filter(|trade| trade.date.date() <= date)
             .fold(
@@ -104,18 +455,18 @@ impl PositionIndicator {
                 |(mut quantity, mut quantity_buy, mut quantity_sell, mut unit_price, mut fees),
                  trade| {
                     match trade.way {
+                        _ if trade.is_fee_only() => {}
                         Way::Sell => {
                             quantity -= trade.quantity;
                             quantity_sell += trade.quantity;
-                            if quantity.abs() < 1e-7 {
+                            if quantity.abs() < epsilon {
                                 quantity = 0.0;
                                 unit_price = 0.0;
                             }
                         }
                         Way::Buy => {
-                            unit_price =
-                                (quantity * unit_price + trade.price * trade.quantity + trade.fees)
-                                    / (quantity + trade.quantity);
+                            unit_price = (quantity * unit_price + fees_treatment.cost(trade))
+                                / (quantity + trade.quantity);
                             quantity += trade.quantity;
                             quantity_buy += trade.quantity;
                         }
@@ -126,9 +477,41 @@ impl PositionIndicator {
             )
     }
 
-    fn compute_cashflow_(position: &Position, date: Date) -> f64 {
-        position
-            .trades
+    fn compute_home_unit_price_(
+        trades: &[Trade],
+        date: Date,
+        fx_rate: f64,
+        epsilon: f64,
+        fees_treatment: FeesTreatment,
+    ) -> f64 {
+        trades
+            .iter()
+            .filter(|trade| trade.date.date() <= date)
+            .fold((0.0, 0.0), |(mut quantity, mut unit_price), trade| {
+                let trade_fx_rate = trade.fx_rate.unwrap_or(fx_rate);
+                match trade.way {
+                    _ if trade.is_fee_only() => {}
+                    Way::Sell => {
+                        quantity -= trade.quantity;
+                        if quantity.abs() < epsilon {
+                            quantity = 0.0;
+                            unit_price = 0.0;
+                        }
+                    }
+                    Way::Buy => {
+                        unit_price = (quantity * unit_price
+                            + fees_treatment.cost(trade) * trade_fx_rate)
+                            / (quantity + trade.quantity);
+                        quantity += trade.quantity;
+                    }
+                };
+                (quantity, unit_price)
+            })
+            .1
+    }
+
+    fn compute_cashflow_(trades: &[Trade], date: Date) -> f64 {
+        trades
             .iter()
             .filter(|trade| trade.date.date() <= date)
             .map(|trade| match trade.way {
@@ -138,30 +521,91 @@ impl PositionIndicator {
             .sum()
     }
 
-    fn compute_dividends_(position: &Position, date: Date) -> f64 {
-        position
-            .instrument
-            .dividends
-            .as_ref()
-            .map_or(0.0, |dividends| {
-                dividends
-                    .iter()
-                    .filter(|dividend| dividend.payment_date.date() <= date)
-                    .map(|dividend| {
-                        let quantity = PositionIndicator::compute_quantity_(
-                            position,
-                            dividend.record_date.date(),
-                        )
-                        .0;
-                        dividend.value * quantity
-                    })
-                    .sum()
+    pub(super) fn compute_settled_quantity_(
+        trades: &[Trade],
+        date: Date,
+        options: &PricingOptions,
+    ) -> f64 {
+        date.checked_sub_days(chrono::Days::new(options.settlement_lag_days))
+            .map_or(0.0, |date| {
+                Self::compute_quantity_(
+                    trades,
+                    date,
+                    options.quantity_epsilon,
+                    options.fees_treatment,
+                )
+                .0
             })
     }
 
-    fn compute_earning_without_div_(position: &Position, date: Date) -> f64 {
-        position
-            .trades
+    fn compute_dividends_(
+        instrument: &Instrument,
+        trades: &[Trade],
+        date: Date,
+        options: &PricingOptions,
+    ) -> f64 {
+        options
+            .dividends(instrument)
+            .iter()
+            .filter(|dividend| dividend.payment_date.date() <= date)
+            .map(|dividend| {
+                let quantity = PositionIndicator::compute_settled_quantity_(
+                    trades,
+                    dividend.record_date.date(),
+                    options,
+                );
+                dividend.value * quantity
+            })
+            .sum()
+    }
+
+    fn compute_reinvested_quantity_(
+        instrument: &Instrument,
+        trades: &[Trade],
+        date: Date,
+        spot: &DataFrame,
+        previous_indicators: &[PositionIndicator],
+        options: &PricingOptions,
+    ) -> f64 {
+        let mut dividends = options
+            .dividends(instrument)
+            .iter()
+            .filter(|dividend| dividend.payment_date.date() <= date)
+            .collect::<Vec<_>>();
+        dividends.sort_by_key(|dividend| dividend.payment_date);
+
+        let mut reinvested: Vec<(Date, f64)> = Vec::new();
+        for dividend in dividends {
+            let payment_date = dividend.payment_date.date();
+            let record_date = dividend.record_date.date();
+            let price = if payment_date == date {
+                Some(options.valuation_price.price(spot))
+            } else {
+                previous_indicators
+                    .iter()
+                    .rev()
+                    .find(|indicator| indicator.date <= payment_date)
+                    .map(|indicator| options.valuation_price.price(&indicator.spot))
+            };
+
+            let quantity = Self::compute_settled_quantity_(trades, record_date, options)
+                + reinvested
+                    .iter()
+                    .filter(|(reinvested_date, _)| *reinvested_date <= record_date)
+                    .map(|(_, quantity)| quantity)
+                    .sum::<f64>();
+
+            let price = price.map(|price| price * instrument.quote_factor);
+            if let Some(price) = price.filter(|price| price.abs() > 1e-7) {
+                reinvested.push((payment_date, dividend.value * quantity / price));
+            }
+        }
+
+        reinvested.iter().map(|(_, quantity)| quantity).sum()
+    }
+
+    fn compute_earning_without_div_(trades: &[Trade], date: Date) -> f64 {
+        trades
             .iter()
             .filter(|trade| trade.date.date() <= date)
             .map(|trade| match trade.way {
@@ -170,49 +614,40 @@ impl PositionIndicator {
             })
             .sum()
     }
+
+    fn compute_home_earning_without_div_(trades: &[Trade], date: Date, fx_rate: f64) -> (f64, f64) {
+        trades
+            .iter()
+            .filter(|trade| trade.date.date() <= date)
+            .fold((0.0, 0.0), |(earning, fees), trade| {
+                let trade_fx_rate = trade.fx_rate.unwrap_or(fx_rate);
+                let amount = match trade.way {
+                    Way::Sell => trade.price * trade.quantity - trade.fees,
+                    Way::Buy => -trade.price * trade.quantity - trade.fees,
+                };
+                (
+                    earning + amount * trade_fx_rate,
+                    fees + trade.fees * trade_fx_rate,
+                )
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::marketdata::{Currency, Instrument, Market};
-    use crate::portfolio::{Position, Trade, Way};
+    use crate::marketdata::Dividend;
+    use crate::portfolio::{Position, Trade, Way, QUANTITY_EPSILON};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_};
+    use crate::pricer::ValuationPrice;
     use assert_float_eq::*;
 
-    fn make_instrument_(name: &str) -> Rc<Instrument> {
-        let currency = Rc::new(Currency {
-            name: String::from("EUR"),
-            parent_currency: None,
-        });
-
-        let market = Rc::new(Market {
-            name: String::from("EPA"),
-            description: String::from("EPA"),
-        });
-
-        Rc::new(Instrument {
-            name: String::from(name),
-            isin: String::from("ISIN"),
-            description: String::from("description"),
-            market,
-            currency,
-            ticker_yahoo: None,
-            region: None,
-            fund_category: String::from("category"),
-            dividends: None,
-        })
-    }
-
-    fn make_date_(year: i32, month: u32, day: u32) -> Date {
-        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
-    }
-
     fn make_spot_(date: Date, value: f64) -> DataFrame {
         DataFrame::new(date, value, value, value, value)
     }
 
     fn make_position_() -> Position {
-        let instrument = make_instrument_("PAEEM");
+        let instrument = Rc::new(make_instrument_("PAEEM", &make_currency_("EUR")));
         Position {
             instrument,
             trades: vec![
@@ -224,6 +659,8 @@ mod tests {
                     quantity: 14.0,
                     price: 21.5,
                     fees: 1.55,
+                    order_id: None,
+                    fx_rate: None,
                 },
                 Trade {
                     date: chrono::DateTime::parse_from_rfc3339("2022-03-19T10:00:00-00:00")
@@ -233,6 +670,8 @@ mod tests {
                     quantity: 20.0,
                     price: 19.5,
                     fees: 1.0,
+                    order_id: None,
+                    fx_rate: None,
                 },
                 Trade {
                     date: chrono::DateTime::parse_from_rfc3339("2022-03-21T10:00:00-00:00")
@@ -242,6 +681,8 @@ mod tests {
                     quantity: 10.0,
                     price: 20.0,
                     fees: 1.2,
+                    order_id: None,
+                    fx_rate: None,
                 },
                 Trade {
                     date: chrono::DateTime::parse_from_rfc3339("2022-03-22T10:00:00-00:00")
@@ -251,17 +692,75 @@ mod tests {
                     quantity: 24.0,
                     price: 21.0,
                     fees: 1.3,
+                    order_id: None,
+                    fx_rate: None,
                 },
             ],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        }
+    }
+
+    #[test]
+    fn compute_position_with_same_instant_trades() {
+        let date = make_date_(2022, 3, 18);
+        let make_trade_ = |day: u32, way: Way, quantity: f64, price: f64| Trade {
+            date: make_date_(2022, 3, day).and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        };
+        let make_sorted_position_ = |mut trades: Vec<Trade>| {
+            trades.sort_by(|left, right| left.cmp_chronological(right));
+            Position {
+                instrument: Rc::new(make_instrument_("PAEEM", &make_currency_("EUR"))),
+                trades,
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }
+        };
+
+        let sell_first = make_sorted_position_(vec![
+            make_trade_(17, Way::Buy, 10.0, 20.0),
+            make_trade_(18, Way::Sell, 10.0, 25.0),
+            make_trade_(18, Way::Buy, 5.0, 30.0),
+        ]);
+        let buy_first = make_sorted_position_(vec![
+            make_trade_(17, Way::Buy, 10.0, 20.0),
+            make_trade_(18, Way::Buy, 5.0, 30.0),
+            make_trade_(18, Way::Sell, 10.0, 25.0),
+        ]);
+        assert_eq!(sell_first.trades, buy_first.trades);
+
+        for position in [sell_first, buy_first] {
+            let indicator = PositionIndicator::from_position(
+                &position,
+                date,
+                0,
+                &make_spot_(date, 30.0),
+                1.0,
+                &[],
+                &Default::default(),
+            );
+            assert_float_absolute_eq!(indicator.quantity, 5.0, 1e-7);
+            assert_float_absolute_eq!(indicator.unit_price, 350.0 / 15.0, 1e-7);
         }
     }
 
     #[test]
     fn compute_position_without_trade() {
-        let instrument = make_instrument_("PAEEM");
+        let instrument = Rc::new(make_instrument_("PAEEM", &make_currency_("EUR")));
         let position = Position {
             instrument,
             trades: Default::default(),
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
         };
         let date = make_date_(2022, 3, 17);
         let indicator = PositionIndicator::from_position(
@@ -269,7 +768,9 @@ mod tests {
             date,
             0,
             &make_spot_(date, 21.92),
+            1.0,
             Default::default(),
+            &PricingOptions::default(),
         );
         check_indicator_(&indicator, 0.0, 0.0, (0.0, 0.0), 0.0, true);
     }
@@ -285,7 +786,9 @@ mod tests {
                 date,
                 0,
                 &make_spot_(date, 21.0),
+                1.0,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
             check_indicator_(
                 &indicator,
@@ -304,7 +807,9 @@ mod tests {
                 date,
                 0,
                 &make_spot_(date, 22.0),
+                1.0,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
             check_indicator_(
                 &indicator,
@@ -323,7 +828,9 @@ mod tests {
                 date,
                 0,
                 &make_spot_(date, 21.5),
+                1.0,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
             check_indicator_(
                 &indicator,
@@ -342,7 +849,9 @@ mod tests {
                 date,
                 0,
                 &make_spot_(date, 21.75),
+                1.0,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
             check_indicator_(
                 &indicator,
@@ -361,7 +870,9 @@ mod tests {
                 date,
                 0,
                 &make_spot_(date, 22.5),
+                1.0,
                 &previous_indicators,
+                &PricingOptions::default(),
             );
             check_indicator_(&indicator, 0.0, 0.0, (0.0, 0.0), 0.1027612640274187, true);
             previous_indicators.push(indicator);
@@ -373,7 +884,12 @@ mod tests {
         let position = make_position_();
         {
             let (quantity, quantity_buy, quantity_sell, unit_price, fees) =
-                PositionIndicator::compute_quantity_(&position, make_date_(2022, 3, 17));
+                PositionIndicator::compute_quantity_(
+                    &position.trades,
+                    make_date_(2022, 3, 17),
+                    QUANTITY_EPSILON,
+                    FeesTreatment::Capitalized,
+                );
             assert_float_absolute_eq!(quantity, 14.0, 1e-7);
             assert_float_absolute_eq!(quantity_buy, 14.0, 1e-7);
             assert_float_absolute_eq!(quantity_sell, 0.0, 1e-7);
@@ -382,7 +898,12 @@ mod tests {
         }
         {
             let (quantity, quantity_buy, quantity_sell, unit_price, fees) =
-                PositionIndicator::compute_quantity_(&position, make_date_(2022, 3, 19));
+                PositionIndicator::compute_quantity_(
+                    &position.trades,
+                    make_date_(2022, 3, 19),
+                    QUANTITY_EPSILON,
+                    FeesTreatment::Capitalized,
+                );
             assert_float_absolute_eq!(quantity, 34.0, 1e-7);
             assert_float_absolute_eq!(quantity_buy, 34.0, 1e-7);
             assert_float_absolute_eq!(quantity_sell, 0.0, 1e-7);
@@ -391,7 +912,12 @@ mod tests {
         }
         {
             let (quantity, quantity_buy, quantity_sell, unit_price, fees) =
-                PositionIndicator::compute_quantity_(&position, make_date_(2022, 3, 20));
+                PositionIndicator::compute_quantity_(
+                    &position.trades,
+                    make_date_(2022, 3, 20),
+                    QUANTITY_EPSILON,
+                    FeesTreatment::Capitalized,
+                );
             assert_float_absolute_eq!(quantity, 34.0, 1e-7);
             assert_float_absolute_eq!(quantity_buy, 34.0, 1e-7);
             assert_float_absolute_eq!(quantity_sell, 0.0, 1e-7);
@@ -400,7 +926,12 @@ mod tests {
         }
         {
             let (quantity, quantity_buy, quantity_sell, unit_price, fees) =
-                PositionIndicator::compute_quantity_(&position, make_date_(2022, 3, 21));
+                PositionIndicator::compute_quantity_(
+                    &position.trades,
+                    make_date_(2022, 3, 21),
+                    QUANTITY_EPSILON,
+                    FeesTreatment::Capitalized,
+                );
             assert_float_absolute_eq!(quantity, 24.0, 1e-7);
             assert_float_absolute_eq!(quantity_buy, 34.0, 1e-7);
             assert_float_absolute_eq!(quantity_sell, 10.0, 1e-7);
@@ -409,7 +940,12 @@ mod tests {
         }
         {
             let (quantity, quantity_buy, quantity_sell, unit_price, fees) =
-                PositionIndicator::compute_quantity_(&position, make_date_(2022, 3, 22));
+                PositionIndicator::compute_quantity_(
+                    &position.trades,
+                    make_date_(2022, 3, 22),
+                    QUANTITY_EPSILON,
+                    FeesTreatment::Capitalized,
+                );
             assert_float_absolute_eq!(quantity, 0.0, 1e-7);
             assert_float_absolute_eq!(quantity_buy, 34.0, 1e-7);
             assert_float_absolute_eq!(quantity_sell, 34.0, 1e-7);
@@ -418,61 +954,907 @@ mod tests {
         }
     }
 
+    #[test]
+    fn compute_unit_price_fees_treatment() {
+        let position = make_position_();
+        let unit_price_ = |date, fees_treatment| {
+            PositionIndicator::compute_quantity_(
+                &position.trades,
+                date,
+                QUANTITY_EPSILON,
+                fees_treatment,
+            )
+        };
+
+        let (_, _, _, unit_price, fees) =
+            unit_price_(make_date_(2022, 3, 17), FeesTreatment::Capitalized);
+        assert_float_absolute_eq!(unit_price, 21.6107142, 1e-7);
+        assert_float_absolute_eq!(fees, 1.55, 1e-7);
+        let (_, _, _, unit_price, fees) =
+            unit_price_(make_date_(2022, 3, 17), FeesTreatment::Expensed);
+        assert_float_absolute_eq!(unit_price, 21.5, 1e-7);
+        assert_float_absolute_eq!(fees, 1.55, 1e-7);
+
+        let (_, _, _, unit_price, _) =
+            unit_price_(make_date_(2022, 3, 19), FeesTreatment::Capitalized);
+        assert_float_absolute_eq!(unit_price, 20.398529411764706, 1e-7);
+        let (_, _, _, unit_price, fees) =
+            unit_price_(make_date_(2022, 3, 19), FeesTreatment::Expensed);
+        assert_float_absolute_eq!(unit_price, 691.0 / 34.0, 1e-7);
+        assert_float_absolute_eq!(fees, 2.55, 1e-7);
+
+        //
+        // the break-even price recovers the fees either way
+        let date = make_date_(2022, 3, 19);
+        for fees_treatment in [FeesTreatment::Capitalized, FeesTreatment::Expensed] {
+            let options = PricingOptions {
+                fees_treatment,
+                ..Default::default()
+            };
+            let indicator = PositionIndicator::from_position(
+                &position,
+                date,
+                0,
+                &make_spot_(date, 20.0),
+                1.0,
+                &[],
+                &options,
+            );
+            assert_float_absolute_eq!(
+                indicator.break_even_price.unwrap(),
+                (691.0 + 2.55) / 34.0,
+                1e-7
+            );
+        }
+    }
+
     #[test]
     fn compute_cashflow() {
         let position = make_position_();
         {
-            let cashflow = PositionIndicator::compute_cashflow_(&position, make_date_(2022, 3, 17));
+            let cashflow =
+                PositionIndicator::compute_cashflow_(&position.trades, make_date_(2022, 3, 17));
             assert_float_absolute_eq!(cashflow, 301.0, 1e-7);
         }
         {
-            let cashflow = PositionIndicator::compute_cashflow_(&position, make_date_(2022, 3, 19));
+            let cashflow =
+                PositionIndicator::compute_cashflow_(&position.trades, make_date_(2022, 3, 19));
             assert_float_absolute_eq!(cashflow, 691.0, 1e-7);
         }
         {
-            let cashflow = PositionIndicator::compute_cashflow_(&position, make_date_(2022, 3, 20));
+            let cashflow =
+                PositionIndicator::compute_cashflow_(&position.trades, make_date_(2022, 3, 20));
             assert_float_absolute_eq!(cashflow, 691.0, 1e-7);
         }
         {
-            let cashflow = PositionIndicator::compute_cashflow_(&position, make_date_(2022, 3, 21));
+            let cashflow =
+                PositionIndicator::compute_cashflow_(&position.trades, make_date_(2022, 3, 21));
             assert_float_absolute_eq!(cashflow, 491.0, 1e-7);
         }
         {
-            let cashflow = PositionIndicator::compute_cashflow_(&position, make_date_(2022, 3, 22));
+            let cashflow =
+                PositionIndicator::compute_cashflow_(&position.trades, make_date_(2022, 3, 22));
             assert_float_absolute_eq!(cashflow, -13.0, 1e-7);
         }
     }
 
+    #[test]
+    fn cashflows_at_close_date() {
+        let position = make_position_();
+        let options = PricingOptions::default();
+        let date = make_date_(2022, 3, 22);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 21.0),
+            1.0,
+            &[],
+            &options,
+        );
+        assert!(indicator.is_close);
+        assert!(indicator.irr(&position, &options).unwrap() > 0.0);
+
+        let cashflows = indicator.cashflows(&position, &options);
+        let wanted = [
+            (make_date_(2022, 3, 17), -302.55),
+            (make_date_(2022, 3, 19), -391.0),
+            (make_date_(2022, 3, 21), 198.8),
+            (date, 502.7),
+        ];
+        assert_eq!(cashflows.len(), wanted.len());
+        for (cashflow, (date, amount)) in cashflows.iter().zip(wanted) {
+            assert_eq!(cashflow.date, date);
+            assert_float_absolute_eq!(cashflow.amount, amount, 1e-7);
+        }
+
+        let date = make_date_(2022, 3, 20);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 20.0),
+            1.0,
+            &[],
+            &options,
+        );
+        let cashflows = indicator.cashflows(&position, &options);
+        assert_eq!(cashflows.len(), 3);
+        assert_eq!(cashflows[2].date, date);
+        assert_float_absolute_eq!(cashflows[2].amount, 680.0, 1e-7);
+
+        //
+        // trades are cash flows once settled
+        let options = PricingOptions {
+            settlement_lag_days: 1,
+            ..Default::default()
+        };
+        let date = make_date_(2022, 3, 22);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 21.0),
+            1.0,
+            &[],
+            &options,
+        );
+        assert!(!indicator.is_close);
+        let cashflows = indicator.cashflows(&position, &options);
+        let wanted = [
+            (make_date_(2022, 3, 18), -302.55),
+            (make_date_(2022, 3, 20), -391.0),
+            (date, 198.8),
+            (date, 504.0),
+        ];
+        assert_eq!(cashflows.len(), wanted.len());
+        for (cashflow, (date, amount)) in cashflows.iter().zip(wanted) {
+            assert_eq!(cashflow.date, date);
+            assert_float_absolute_eq!(cashflow.amount, amount, 1e-7);
+        }
+    }
+
+    #[test]
+    fn compute_position_with_ter() {
+        let instrument = Rc::new(make_instrument_("ESE", &make_currency_("EUR")));
+        let mut position = Position {
+            instrument,
+            trades: vec![Trade {
+                date: make_date_(2022, 1, 1).and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 100.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let options = PricingOptions::default();
+        let price_ = |position: &Position| {
+            let mut previous_indicators: Vec<PositionIndicator> = Vec::new();
+            for date in make_date_(2022, 1, 1).iter_days().take(366) {
+                let indicator = PositionIndicator::from_position(
+                    position,
+                    date,
+                    0,
+                    &make_spot_(date, 100.0),
+                    1.0,
+                    &previous_indicators,
+                    &options,
+                );
+                previous_indicators = vec![indicator];
+            }
+            previous_indicators.pop().unwrap()
+        };
+
+        let (earning, earning_latent, valuation) = {
+            let indicator = price_(&position);
+            assert_float_absolute_eq!(indicator.ter_drag, 0.0, 1e-7);
+            (
+                indicator.earning,
+                indicator.earning_latent,
+                indicator.valuation,
+            )
+        };
+
+        //
+        // 0.3% a year on a constant 1000 valuation
+        Rc::get_mut(&mut position.instrument).unwrap().ter = Some(0.003);
+        let with_ter = price_(&position);
+        assert_float_absolute_eq!(with_ter.ter_drag, 3.0, 1e-7);
+        //
+        // the drag is out of the valuation, not a cash flow
+        assert_float_absolute_eq!(with_ter.earning, earning, 1e-7);
+        assert_float_absolute_eq!(with_ter.earning_latent, earning_latent - 3.0, 1e-7);
+        assert_float_absolute_eq!(with_ter.valuation, valuation - 3.0, 1e-7);
+        assert_float_absolute_eq!(with_ter.pnl_currency, -3.0, 1e-7);
+        assert!(with_ter.twr < 0.0);
+        assert_float_absolute_eq!(
+            with_ter.pnl_attribution.total(),
+            with_ter.earning_latent,
+            1e-7
+        );
+    }
+
+    #[test]
+    fn compute_position_through_zero_valuation() {
+        let begin = make_date_(2022, 1, 1);
+        let make_trade_ = |days: u64, way: Way, price: f64| Trade {
+            date: begin
+                .checked_add_days(chrono::Days::new(days))
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap(),
+            way,
+            quantity: 10.0,
+            price,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        };
+        let position = Position {
+            instrument: Rc::new(make_instrument_("ESE", &make_currency_("EUR"))),
+            trades: vec![
+                make_trade_(0, Way::Buy, 10.0),
+                make_trade_(1, Way::Sell, 11.0),
+                make_trade_(3, Way::Buy, 11.0),
+            ],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let options = PricingOptions::default();
+        let spots = [10.0, 11.0, 11.0, 11.0, 12.1];
+        let indicators = begin
+            .iter_days()
+            .zip(spots)
+            .scan(
+                Vec::<PositionIndicator>::new(),
+                |previous_indicators, (date, spot)| {
+                    let indicator = PositionIndicator::from_position(
+                        &position,
+                        date,
+                        0,
+                        &make_spot_(date, spot),
+                        1.0,
+                        previous_indicators,
+                        &options,
+                    );
+                    *previous_indicators = vec![indicator.clone()];
+                    Some(indicator)
+                },
+            )
+            .collect::<Vec<_>>();
+
+        //
+        // the zero valuation days keep the compounded twr
+        assert_float_absolute_eq!(indicators[1].twr, 0.1, 1e-7);
+        for indicator in indicators[1..3].iter() {
+            assert_float_absolute_eq!(indicator.valuation, 0.0, 1e-7);
+            assert_float_absolute_eq!(indicator.twr, 0.1, 1e-7);
+        }
+        assert_float_absolute_eq!(indicators[3].valuation, 110.0, 1e-7);
+        assert_float_absolute_eq!(indicators[3].twr, 0.1, 1e-7);
+        assert_float_absolute_eq!(indicators[4].twr, 1.1 * 1.1 - 1.0, 1e-7);
+    }
+
+    #[test]
+    fn compute_position_with_ter_partial_sell() {
+        let begin = make_date_(2022, 1, 1);
+        let make_trade_ = |days: u64, way: Way| Trade {
+            date: begin
+                .checked_add_days(chrono::Days::new(days))
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap(),
+            way,
+            quantity: if way == Way::Buy { 10.0 } else { 5.0 },
+            price: 100.0,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        };
+        let position = Position {
+            instrument: Rc::new(Instrument {
+                ter: Some(0.0365),
+                ..make_instrument_("ESE", &make_currency_("EUR"))
+            }),
+            trades: vec![
+                make_trade_(0, Way::Buy),
+                make_trade_(100, Way::Sell),
+                make_trade_(200, Way::Sell),
+            ],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let options = PricingOptions::default();
+        let indicators = begin
+            .iter_days()
+            .take(202)
+            .scan(
+                Vec::<PositionIndicator>::new(),
+                |previous_indicators, date| {
+                    let indicator = PositionIndicator::from_position(
+                        &position,
+                        date,
+                        0,
+                        &make_spot_(date, 100.0),
+                        1.0,
+                        previous_indicators,
+                        &options,
+                    );
+                    *previous_indicators = vec![indicator.clone()];
+                    Some(indicator)
+                },
+            )
+            .collect::<Vec<_>>();
+
+        //
+        // 0.1 a day on 1000 then 0.05 a day on 500, half of the drag is sold on day 100
+        let sell = &indicators[100];
+        assert_float_absolute_eq!(sell.ter_drag, 5.0, 1e-7);
+        assert_float_absolute_eq!(sell.ter_realized, 5.0, 1e-7);
+        assert_float_absolute_eq!(sell.valuation, 495.0, 1e-7);
+        assert_float_absolute_eq!(sell.earning, -505.0, 1e-7);
+
+        let close = &indicators[200];
+        assert!(close.is_close);
+        assert_float_absolute_eq!(close.ter_drag, 0.0, 1e-7);
+        assert_float_absolute_eq!(close.ter_realized, 15.0, 1e-7);
+        assert_float_absolute_eq!(close.earning, -15.0, 1e-7);
+        assert_float_absolute_eq!(close.pnl_attribution.total(), close.earning_latent, 1e-7);
+        assert_float_absolute_eq!(indicators[201].earning, -15.0, 1e-7);
+
+        //
+        // a sell doesn't give the drag back, each daily return is only the day charge
+        for values in indicators[..=200].windows(2) {
+            let daily_return = (1.0 + values[1].twr) / (1.0 + values[0].twr) - 1.0;
+            assert!(daily_return < 0.0, "{} {}", values[1].date, daily_return);
+            assert!(daily_return > -2e-4, "{} {}", values[1].date, daily_return);
+        }
+    }
+
+    #[test]
+    fn compute_position_with_fee_only_trade() {
+        let position = make_position_();
+        let mut with_fee = make_position_();
+        with_fee.trades.insert(
+            1,
+            Trade {
+                date: make_date_(2022, 3, 18).and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Sell,
+                quantity: 0.0,
+                price: 0.0,
+                fees: 2.5,
+                order_id: None,
+                fx_rate: None,
+            },
+        );
+        assert!(with_fee.trades[1].is_fee_only());
+
+        let options = PricingOptions::default();
+        let date = make_date_(2022, 3, 20);
+        let price_ = |position: &Position| {
+            PositionIndicator::from_position(
+                position,
+                date,
+                0,
+                &make_spot_(date, 20.0),
+                1.0,
+                &[],
+                &options,
+            )
+        };
+        let without_fee = price_(&position);
+        let indicator = price_(&with_fee);
+        assert_float_absolute_eq!(indicator.quantity, without_fee.quantity, 1e-7);
+        assert_float_absolute_eq!(indicator.quantity_sell, 0.0, 1e-7);
+        assert_float_absolute_eq!(indicator.unit_price, without_fee.unit_price, 1e-7);
+        assert_float_absolute_eq!(indicator.nominal, without_fee.nominal, 1e-7);
+        assert_float_absolute_eq!(indicator.fees, without_fee.fees + 2.5, 1e-7);
+        assert_float_absolute_eq!(indicator.earning, without_fee.earning - 2.5, 1e-7);
+        assert_float_absolute_eq!(
+            indicator.earning_latent,
+            without_fee.earning_latent - 2.5,
+            1e-7
+        );
+        assert!(indicator.unit_price.is_finite());
+    }
+
     #[test]
     fn compute_earning() {
         let position = make_position_();
         {
-            let earning: f64 =
-                PositionIndicator::compute_earning_without_div_(&position, make_date_(2022, 3, 17));
+            let earning: f64 = PositionIndicator::compute_earning_without_div_(
+                &position.trades,
+                make_date_(2022, 3, 17),
+            );
             assert_float_absolute_eq!(earning, 0.0 - 302.55, 1e-7);
         }
         {
-            let earning =
-                PositionIndicator::compute_earning_without_div_(&position, make_date_(2022, 3, 19));
+            let earning = PositionIndicator::compute_earning_without_div_(
+                &position.trades,
+                make_date_(2022, 3, 19),
+            );
             assert_float_absolute_eq!(earning, 0.0 - 693.55, 1e-7);
         }
         {
-            let earning =
-                PositionIndicator::compute_earning_without_div_(&position, make_date_(2022, 3, 20));
+            let earning = PositionIndicator::compute_earning_without_div_(
+                &position.trades,
+                make_date_(2022, 3, 20),
+            );
             assert_float_absolute_eq!(earning, 0.0 - 693.55, 1e-7);
         }
         {
-            let earning =
-                PositionIndicator::compute_earning_without_div_(&position, make_date_(2022, 3, 21));
+            let earning = PositionIndicator::compute_earning_without_div_(
+                &position.trades,
+                make_date_(2022, 3, 21),
+            );
             assert_float_absolute_eq!(earning, 198.8 - 693.55, 1e-7);
         }
         {
-            let earning =
-                PositionIndicator::compute_earning_without_div_(&position, make_date_(2022, 3, 22));
+            let earning = PositionIndicator::compute_earning_without_div_(
+                &position.trades,
+                make_date_(2022, 3, 22),
+            );
             assert_float_absolute_eq!(earning, 701.5 - 693.55, 1e-7);
         }
     }
 
+    #[test]
+    fn compute_position_break_even_price() {
+        let position = make_position_();
+        let options = PricingOptions::default();
+
+        let date = make_date_(2022, 3, 21);
+        let position_indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 20.0),
+            1.0,
+            &[],
+            &options,
+        );
+        assert_float_absolute_eq!(
+            position_indicator.break_even_price.unwrap(),
+            (14.0 * 21.5 + 1.55 + 20.0 * 19.5 + 1.0) / 34.0,
+            1e-7
+        );
+
+        let date = make_date_(2022, 3, 22);
+        let position_indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 20.0),
+            1.0,
+            &[],
+            &options,
+        );
+        assert!(position_indicator.break_even_price.is_none());
+    }
+
+    #[test]
+    fn compute_position_with_residual_quantity() {
+        let make_position = |residual: f64| {
+            let mut position = make_position_();
+            position.trades.last_mut().unwrap().quantity -= residual;
+            position
+        };
+        let date = make_date_(2022, 3, 22);
+        let options = PricingOptions::default();
+
+        let position = make_position(1e-8);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 20.0),
+            1.0,
+            &[],
+            &options,
+        );
+        assert!(indicator.is_close);
+        assert_float_absolute_eq!(indicator.quantity, 0.0, 1e-10);
+        assert!(position.get_close_date(date, QUANTITY_EPSILON).is_some());
+
+        let position = make_position(1e-4);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 20.0),
+            1.0,
+            &[],
+            &options,
+        );
+        assert!(!indicator.is_close);
+        assert_float_absolute_eq!(indicator.quantity, 1e-4, 1e-10);
+        assert!(position.get_close_date(date, QUANTITY_EPSILON).is_none());
+
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 20.0),
+            1.0,
+            &[],
+            &PricingOptions {
+                quantity_epsilon: 1e-3,
+                ..Default::default()
+            },
+        );
+        assert!(indicator.is_close);
+        assert!(position.get_close_date(date, 1e-3).is_some());
+    }
+
+    #[test]
+    fn compute_position_with_fx() {
+        let instrument = Rc::new(make_instrument_("PAEEM", &make_currency_("EUR")));
+        let position = Position {
+            instrument,
+            trades: vec![Trade {
+                date: chrono::DateTime::parse_from_rfc3339("2022-03-17T10:00:00-00:00")
+                    .unwrap()
+                    .naive_local(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 100.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: Some(1.1),
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let date = make_date_(2022, 3, 18);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 100.0),
+            1.2,
+            &[],
+            &PricingOptions::default(),
+        );
+        assert_float_absolute_eq!(indicator.pnl_currency, 0.0, 1e-7);
+        assert_float_absolute_eq!(indicator.home_nominal, 1100.0, 1e-7);
+        assert_float_absolute_eq!(indicator.home_valuation, 1200.0, 1e-7);
+        assert_float_absolute_eq!(indicator.home_pnl_currency, 100.0, 1e-7);
+        assert_float_absolute_eq!(indicator.home_earning, -1100.0, 1e-7);
+        assert_float_absolute_eq!(indicator.home_earning_latent, 100.0, 1e-7);
+
+        let attribution = indicator.pnl_attribution;
+        assert_float_absolute_eq!(attribution.price, 0.0, 1e-7);
+        assert_float_absolute_eq!(attribution.fx, 100.0, 1e-7);
+        assert_float_absolute_eq!(attribution.dividend, 0.0, 1e-7);
+        assert_float_absolute_eq!(attribution.fees, 0.0, 1e-7);
+        assert_float_absolute_eq!(attribution.total(), indicator.home_pnl_currency, 1e-7);
+    }
+
+    #[test]
+    fn compute_position_with_settlement_lag() {
+        let position = make_position_();
+        let options = PricingOptions {
+            settlement_lag_days: 2,
+            ..Default::default()
+        };
+
+        let quantities = make_date_(2022, 3, 17)
+            .iter_days()
+            .take(3)
+            .map(|date| {
+                PositionIndicator::from_position(
+                    &position,
+                    date,
+                    0,
+                    &make_spot_(date, 21.92),
+                    1.0,
+                    &[],
+                    &options,
+                )
+            })
+            .map(|indicator| (indicator.quantity, indicator.cashflow))
+            .collect::<Vec<_>>();
+        assert_float_absolute_eq!(quantities[0].0, 0.0, 1e-7);
+        assert_float_absolute_eq!(quantities[0].1, 0.0, 1e-7);
+        assert_float_absolute_eq!(quantities[1].0, 0.0, 1e-7);
+        assert_float_absolute_eq!(quantities[2].0, 14.0, 1e-7);
+        assert_float_absolute_eq!(quantities[2].1, 14.0 * 21.5, 1e-7);
+    }
+
+    #[test]
+    fn compute_position_with_valuation_price() {
+        let position = make_position_();
+        let date = make_date_(2022, 3, 17);
+        let spot = DataFrame::new(date, 21.0, 22.0, 25.0, 20.0);
+
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &spot,
+            1.0,
+            &[],
+            &PricingOptions::default(),
+        );
+        assert_float_absolute_eq!(indicator.valuation, 14.0 * 22.0, 1e-7);
+
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &spot,
+            1.0,
+            &[],
+            &PricingOptions {
+                valuation_price: ValuationPrice::Mid,
+                ..Default::default()
+            },
+        );
+        assert_float_absolute_eq!(indicator.valuation, 14.0 * 22.5, 1e-7);
+    }
+
+    #[test]
+    fn compute_position_with_quote_factor() {
+        let mut instrument = make_instrument_("BP", &make_currency_("EUR"));
+        instrument.quote_factor = 0.01;
+        let position = Position {
+            instrument: Rc::new(instrument),
+            trades: vec![Trade {
+                date: chrono::DateTime::parse_from_rfc3339("2022-03-17T10:00:00-00:00")
+                    .unwrap()
+                    .naive_local(),
+                way: Way::Buy,
+                quantity: 100.0,
+                price: 4.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+
+        let date = make_date_(2022, 3, 18);
+        let indicator = PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &make_spot_(date, 450.0),
+            1.0,
+            &[],
+            &PricingOptions::default(),
+        );
+        assert_float_absolute_eq!(indicator.spot.close, 450.0, 1e-7);
+        assert_float_absolute_eq!(indicator.valuation, 450.0 * 100.0 / 100.0, 1e-7);
+        assert_float_absolute_eq!(indicator.nominal, 400.0, 1e-7);
+        assert_float_absolute_eq!(indicator.pnl_currency, 50.0, 1e-7);
+    }
+
+    #[test]
+    fn compute_position_with_reinvested_dividends() {
+        let mut instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        instrument.dividends = Some(vec![
+            Dividend {
+                record_date: make_date_(2022, 3, 18).and_hms_opt(0, 0, 0).unwrap(),
+                payment_date: make_date_(2022, 3, 19).and_hms_opt(0, 0, 0).unwrap(),
+                value: 1.0,
+            },
+            Dividend {
+                record_date: make_date_(2022, 3, 20).and_hms_opt(0, 0, 0).unwrap(),
+                payment_date: make_date_(2022, 3, 21).and_hms_opt(0, 0, 0).unwrap(),
+                value: 1.0,
+            },
+        ]);
+        let position = Position {
+            instrument: Rc::new(instrument),
+            trades: vec![Trade {
+                date: chrono::DateTime::parse_from_rfc3339("2022-03-17T10:00:00-00:00")
+                    .unwrap()
+                    .naive_local(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 20.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+
+        let price_ = |options: &PricingOptions| {
+            let mut indicators: Vec<PositionIndicator> = Vec::new();
+            for date in make_date_(2022, 3, 17)
+                .iter_days()
+                .take_while(|date| *date <= make_date_(2022, 3, 22))
+            {
+                let indicator = PositionIndicator::from_position(
+                    &position,
+                    date,
+                    0,
+                    &make_spot_(date, 20.0),
+                    1.0,
+                    &indicators,
+                    options,
+                );
+                indicators.push(indicator);
+            }
+            indicators
+        };
+
+        let indicators = price_(&PricingOptions::default());
+        for indicator in indicators.iter() {
+            assert_float_absolute_eq!(indicator.quantity, 10.0, 1e-7);
+        }
+        assert_float_absolute_eq!(indicators[5].dividends, 20.0, 1e-7);
+
+        let indicators = price_(&PricingOptions {
+            reinvest_dividends: true,
+            ..Default::default()
+        });
+        for (indicator, quantity) in indicators
+            .iter()
+            .zip([10.0, 10.0, 10.5, 10.5, 11.025, 11.025])
+        {
+            assert_float_absolute_eq!(indicator.quantity, quantity, 1e-7);
+        }
+        assert_float_absolute_eq!(indicators[5].valuation, 220.5, 1e-7);
+        assert_float_absolute_eq!(indicators[5].nominal, 200.0, 1e-7);
+    }
+
+    #[test]
+    fn close_position_with_reinvested_dividends() {
+        let mut instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        instrument.dividends = Some(vec![
+            Dividend {
+                record_date: make_date_(2022, 3, 18).and_hms_opt(0, 0, 0).unwrap(),
+                payment_date: make_date_(2022, 3, 19).and_hms_opt(0, 0, 0).unwrap(),
+                value: 1.0,
+            },
+            Dividend {
+                record_date: make_date_(2022, 3, 20).and_hms_opt(0, 0, 0).unwrap(),
+                payment_date: make_date_(2022, 3, 21).and_hms_opt(0, 0, 0).unwrap(),
+                value: 1.0,
+            },
+        ]);
+        let make_trade_ = |day, way, price| Trade {
+            date: make_date_(2022, 3, day).and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity: 10.0,
+            price,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        };
+        let position = Position {
+            instrument: Rc::new(instrument),
+            trades: vec![
+                make_trade_(17, Way::Buy, 20.0),
+                make_trade_(22, Way::Sell, 22.0),
+            ],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+
+        let price_ = |options: &PricingOptions| {
+            let mut indicators: Vec<PositionIndicator> = Vec::new();
+            for date in make_date_(2022, 3, 17)
+                .iter_days()
+                .take_while(|date| *date <= make_date_(2022, 3, 23))
+            {
+                let indicator = PositionIndicator::from_position(
+                    &position,
+                    date,
+                    0,
+                    &make_spot_(date, 20.0),
+                    1.0,
+                    &indicators,
+                    options,
+                );
+                indicators.push(indicator);
+            }
+            indicators.pop().unwrap()
+        };
+
+        let indicator = price_(&PricingOptions::default());
+        assert!(indicator.is_close);
+        assert_float_absolute_eq!(indicator.earning, -200.0 + 220.0 + 20.0, 1e-7);
+
+        //
+        // the 1.025 reinvested shares are sold at the price of the closing trade
+        let indicator = price_(&PricingOptions {
+            reinvest_dividends: true,
+            ..Default::default()
+        });
+        assert!(indicator.is_close);
+        assert_float_absolute_eq!(indicator.quantity, 0.0, 1e-7);
+        assert_float_absolute_eq!(indicator.valuation, 0.0, 1e-7);
+        assert_float_absolute_eq!(indicator.earning, -200.0 + 220.0 + 1.025 * 22.0, 1e-7);
+        assert_float_absolute_eq!(indicator.home_earning, indicator.earning, 1e-7);
+
+        let options = PricingOptions {
+            reinvest_dividends: true,
+            ..Default::default()
+        };
+        let cashflows = indicator.cashflows(&position, &options);
+        let wanted = [
+            (make_date_(2022, 3, 17), -200.0),
+            (make_date_(2022, 3, 22), 220.0),
+            (make_date_(2022, 3, 22), 1.025 * 22.0),
+        ];
+        assert_eq!(cashflows.len(), wanted.len());
+        for (cashflow, (date, amount)) in cashflows.iter().zip(wanted) {
+            assert_eq!(cashflow.date, date);
+            assert_float_absolute_eq!(cashflow.amount, amount, 1e-7);
+        }
+    }
+
+    #[test]
+    fn compute_position_with_fetched_dividends() {
+        let make_position_ = |instrument: Instrument| Position {
+            instrument: Rc::new(instrument),
+            trades: vec![Trade {
+                date: make_date_(2022, 3, 17).and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 20.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        let make_dividend_ = |day, value| Dividend {
+            record_date: make_date_(2022, 3, day).and_hms_opt(0, 0, 0).unwrap(),
+            payment_date: make_date_(2022, 3, day).and_hms_opt(0, 0, 0).unwrap(),
+            value,
+        };
+        let options = PricingOptions {
+            fetched_dividends: [(String::from("PAEEM"), vec![make_dividend_(19, 0.5)])]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let date = make_date_(2022, 3, 20);
+        let price_ = |position: &Position, options: &PricingOptions| {
+            PositionIndicator::from_position(
+                position,
+                date,
+                0,
+                &make_spot_(date, 20.0),
+                1.0,
+                &[],
+                options,
+            )
+        };
+
+        let position = make_position_(make_instrument_("PAEEM", &make_currency_("EUR")));
+        let without_dividends = price_(&position, &PricingOptions::default());
+        let indicator = price_(&position, &options);
+        assert_float_absolute_eq!(indicator.dividends, 5.0, 1e-7);
+        assert_float_absolute_eq!(indicator.earning, without_dividends.earning + 5.0, 1e-7);
+
+        //
+        // dividends of the json are kept over the fetched ones
+        let mut instrument = make_instrument_("PAEEM", &make_currency_("EUR"));
+        instrument.dividends = Some(vec![make_dividend_(18, 1.0)]);
+        let indicator = price_(&make_position_(instrument), &options);
+        assert_float_absolute_eq!(indicator.dividends, 10.0, 1e-7);
+    }
+
     fn check_indicator_(
         indicator: &PositionIndicator,
         valuation: f64,
@@ -0,0 +1,542 @@
+use super::{
+    ClosePositionIndicator, FirstSpot, PortfolioIndicator, PortfolioIndicators, PositionIndicator,
+    PricingOptions,
+};
+use crate::alias::Date;
+use crate::error::Error;
+use crate::historical::Provider;
+use crate::marketdata::Instrument;
+use crate::portfolio::Portfolio;
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::{error, warn};
+
+//
+// lazy pricing, one portfolio indicator per priced date, market data must
+// be already fetched by the provider
+pub struct PricedIter<'a, P> {
+    portfolio: &'a Portfolio,
+    begin: Date,
+    end: Date,
+    date: Date,
+    // only these dates are priced instead of each day
+    dates: Option<std::vec::IntoIter<Date>>,
+    spot_provider: &'a P,
+    fx_instruments: BTreeMap<String, Instrument>,
+    options: &'a PricingOptions,
+    positions: Vec<Vec<PositionIndicator>>,
+    previous: Option<PortfolioIndicator>,
+    // positions already warned about a missing fx rate
+    fx_warned: BTreeSet<usize>,
+}
+
+impl<P> PricedIter<'_, P>
+where
+    P: Provider,
+{
+    fn price_positions_(&mut self, date: Date) -> Vec<PositionIndicator> {
+        (0..self.portfolio.positions.len())
+            .filter_map(|position_index| self.price_position_(position_index, date))
+            .collect()
+    }
+
+    fn price_position_(&mut self, position_index: usize, date: Date) -> Option<PositionIndicator> {
+        let position = &self.portfolio.positions[position_index];
+        let first_date = position.get_open_date()?.date();
+        let begin = std::cmp::max(first_date, self.begin);
+        if date < begin {
+            return None;
+        }
+
+        let spot = self
+            .spot_provider
+            .latest(&position.instrument, date)
+            .copied()
+            .or_else(|| PortfolioIndicators::make_face_value_spot_(position, date))
+            .or_else(|| {
+                self.options
+                    .mark_to_last_trade
+                    .then(|| PortfolioIndicators::make_last_trade_spot_(position, date))
+                    .flatten()
+            })
+            .or_else(|| match self.options.first_spot {
+                FirstSpot::Skip => None,
+                FirstSpot::Backfill if date != begin => None,
+                _ => PortfolioIndicators::first_available_spot_(
+                    self.spot_provider,
+                    position,
+                    date,
+                    self.end,
+                ),
+            });
+        let Some(spot) = spot else {
+            error!(
+                "no spot on {}/{} at {} and before skip position pricing",
+                position.instrument.name, position_index, date
+            );
+            return None;
+        };
+
+        let fx_rate = PortfolioIndicators::compute_fx_rate_(
+            self.spot_provider,
+            &self.fx_instruments,
+            self.portfolio,
+            position,
+            date,
+        )
+        .unwrap_or_else(|| {
+            if self.fx_warned.insert(position_index) {
+                warn!(
+                    "no fx rate from {} to {} on {}/{} at {}, 1.0 is used",
+                    position.instrument.currency.name,
+                    self.portfolio.currency.name,
+                    position.instrument.name,
+                    position_index,
+                    date
+                );
+            }
+            1.0
+        });
+
+        let previous_indicators = &mut self.positions[position_index];
+        let indicator = PositionIndicator::from_position(
+            position,
+            date,
+            position_index,
+            &spot,
+            fx_rate,
+            previous_indicators,
+            self.options,
+        );
+
+        //
+        // only reinvested dividends look further than the previous indicator
+        if !self.options.reinvest_dividends {
+            previous_indicators.clear();
+        }
+        previous_indicators.push(indicator.clone());
+        Some(indicator)
+    }
+}
+
+impl<P> PricedIter<'_, P> {
+    fn next_date_(&mut self) -> Option<Date> {
+        if let Some(dates) = self.dates.as_mut() {
+            return dates.next();
+        }
+        if self.date > self.end {
+            return None;
+        }
+        let date = self.date;
+        self.date = date.succ_opt()?;
+        Some(date)
+    }
+}
+
+impl<P> Iterator for PricedIter<'_, P>
+where
+    P: Provider,
+{
+    type Item = PortfolioIndicator;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(date) = self.next_date_() {
+            let positions = self.price_positions_(date);
+            if positions.is_empty() {
+                continue;
+            }
+
+            let mut indicator = PortfolioIndicator::from_portfolio(
+                self.portfolio,
+                date,
+                positions,
+                self.previous.as_slice(),
+                self.options,
+            );
+            self.previous = Some(indicator.clone());
+            if self.options.round_to_cents {
+                indicator.round_to_cents();
+            }
+            return Some(indicator);
+        }
+        None
+    }
+}
+
+impl PortfolioIndicators {
+    pub fn iter_priced<'a, P>(
+        portfolio: &'a Portfolio,
+        begin: Date,
+        end: Date,
+        spot_provider: &'a P,
+        options: &'a PricingOptions,
+    ) -> PricedIter<'a, P>
+    where
+        P: Provider,
+    {
+        PricedIter {
+            portfolio,
+            begin,
+            end,
+            date: begin,
+            dates: None,
+            spot_provider,
+            fx_instruments: Self::make_fx_instruments_(portfolio),
+            options,
+            positions: vec![Vec::new(); portfolio.positions.len()],
+            previous: None,
+            fx_warned: BTreeSet::new(),
+        }
+    }
+
+    //
+    // prices only the given dates, a cumulative value (pnl, valuation) is
+    // the same as a daily pricing but not a chained one (twr)
+    pub fn iter_priced_at<'a, P>(
+        portfolio: &'a Portfolio,
+        begin: Date,
+        dates: Vec<Date>,
+        spot_provider: &'a P,
+        options: &'a PricingOptions,
+    ) -> PricedIter<'a, P>
+    where
+        P: Provider,
+    {
+        let end = dates.last().copied().unwrap_or(begin);
+        PricedIter {
+            dates: Some(dates.into_iter()),
+            ..Self::iter_priced(portfolio, begin, end, spot_provider, options)
+        }
+    }
+
+    //
+    // a closed position is priced alone from its first trade to its close date,
+    // and at end for a dividend paid after it, open positions and portfolio
+    // aggregates are skipped
+    pub fn closed_positions_only<P>(
+        portfolio: &Portfolio,
+        end: Date,
+        spot_provider: &mut P,
+        options: &PricingOptions,
+    ) -> Result<Vec<ClosePositionIndicator>, Error>
+    where
+        P: Provider,
+    {
+        //
+        // (position index, first trade date, close date) of the closed positions
+        let closed = portfolio
+            .positions
+            .iter()
+            .enumerate()
+            .filter_map(|(position_index, position)| {
+                let first_date = position.get_open_date()?.date();
+                let close_date = position
+                    .get_close_date(end, options.quantity_epsilon)?
+                    .date();
+                (close_date <= end).then_some((position_index, first_date, close_date))
+            })
+            .collect::<Vec<_>>();
+        let Some(begin) = closed.iter().map(|(_, first_date, _)| *first_date).min() else {
+            return Ok(Vec::new());
+        };
+        for (position_index, _, _) in closed.iter() {
+            Self::fetch_position_(
+                &portfolio.positions[*position_index],
+                begin,
+                end,
+                spot_provider,
+                options,
+            )?;
+        }
+        Self::fetch_fx_(portfolio, begin, end, spot_provider, options.strict)?;
+
+        let mut priced = Self::iter_priced(portfolio, begin, end, spot_provider, options);
+        let mut result = Vec::new();
+        for (position_index, first_date, close_date) in closed {
+            let indicator = first_date
+                .iter_days()
+                .take_while(|date| *date <= close_date)
+                .chain((close_date < end).then_some(end))
+                .filter_map(|date| priced.price_position_(position_index, date))
+                .last();
+            result.extend(indicator.and_then(|indicator| {
+                ClosePositionIndicator::from_position(
+                    &portfolio.positions[position_index],
+                    &indicator,
+                    options,
+                )
+            }));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::marketdata::Dividend;
+    use crate::portfolio::{OpeningPosition, Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use assert_float_eq::*;
+    use std::rc::Rc;
+
+    fn make_trade_(date: Date, way: Way, quantity: f64, price: f64) -> Trade {
+        Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees: 1.0,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    #[test]
+    fn mark_to_last_trade_without_spot() {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(make_instrument_("PRIVATE", &currency));
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 10);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![Position {
+                instrument,
+                trades: vec![
+                    make_trade_(begin, Way::Buy, 10.0, 20.0),
+                    make_trade_(make_date_(2022, 3, 5), Way::Buy, 5.0, 26.0),
+                ],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let provider = MockProvider::new(Vec::new());
+
+        let options = PricingOptions::default();
+        assert_eq!(
+            PortfolioIndicators::iter_priced(&portfolio, begin, end, &provider, &options).count(),
+            0
+        );
+
+        let options = PricingOptions {
+            mark_to_last_trade: true,
+            ..Default::default()
+        };
+        let indicators =
+            PortfolioIndicators::iter_priced(&portfolio, begin, end, &provider, &options)
+                .collect::<Vec<_>>();
+        assert_eq!(indicators.len(), 10);
+        let first = &indicators[0].positions[0];
+        assert!(first.spot.is_synthetic);
+        assert_float_absolute_eq!(first.valuation, 200.0, 1e-7);
+        let last = &indicators[9].positions[0];
+        assert!(last.spot.is_synthetic);
+        assert_float_absolute_eq!(last.spot.close, 26.0, 1e-7);
+        assert_float_absolute_eq!(last.valuation, 390.0, 1e-7);
+    }
+
+    #[test]
+    fn iter_priced_equals_collected() {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(make_instrument_("ESE", &currency));
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 31);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![
+                Position {
+                    instrument: instrument.clone(),
+                    trades: vec![
+                        make_trade_(begin, Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 20), Way::Sell, 10.0, 23.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument,
+                    trades: vec![make_trade_(make_date_(2022, 3, 10), Way::Buy, 3.0, 21.3)],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+            ],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::new(
+            begin
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .enumerate()
+                .map(|(i, date)| {
+                    let value = 20.0 + (i % 7) as f64 / 3.0;
+                    DataFrame::new(date, value, value, value, value)
+                })
+                .collect(),
+        );
+        let options = PricingOptions {
+            round_to_cents: true,
+            ..Default::default()
+        };
+
+        let collected =
+            PortfolioIndicators::from_portfolio(&portfolio, begin, end, &mut provider, &options)
+                .unwrap();
+        let streamed =
+            PortfolioIndicators::iter_priced(&portfolio, begin, end, &provider, &options)
+                .collect::<Vec<_>>();
+
+        assert_eq!(streamed.len(), 31);
+        assert_eq!(streamed.len(), collected.portfolios.len());
+        for (item, wanted) in streamed.iter().zip(collected.portfolios.iter()) {
+            assert_eq!(item.date, wanted.date);
+            assert_eq!(item.positions.len(), wanted.positions.len());
+            assert_float_absolute_eq!(item.valuation, wanted.valuation, 1e-7);
+            assert_float_absolute_eq!(item.nominal, wanted.nominal, 1e-7);
+            assert_float_absolute_eq!(item.pnl_currency, wanted.pnl_currency, 1e-7);
+            assert_float_absolute_eq!(item.twr, wanted.twr, 1e-7);
+            for (position, wanted) in item.positions.iter().zip(wanted.positions.iter()) {
+                assert_eq!(position.position_index, wanted.position_index);
+                assert_float_absolute_eq!(position.twr, wanted.twr, 1e-7);
+            }
+        }
+    }
+
+    #[test]
+    fn opening_position_without_cash_outflow() {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(make_instrument_("ESE", &currency));
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 10);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![Position {
+                instrument,
+                trades: Vec::new(),
+                close_reason: None,
+                sleeve: None,
+                opening_position: Some(OpeningPosition {
+                    quantity: 10.0,
+                    unit_price: 20.0,
+                    date: begin.and_hms_opt(0, 0, 0).unwrap(),
+                    fees: 0.0,
+                }),
+            }],
+            cash: Default::default(),
+        };
+        let provider = MockProvider::new(vec![DataFrame::new(begin, 22.0, 22.0, 22.0, 22.0)]);
+        let options = PricingOptions::default();
+        let indicators =
+            PortfolioIndicators::iter_priced(&portfolio, begin, end, &provider, &options)
+                .collect::<Vec<_>>();
+        assert_eq!(indicators.len(), 10);
+        let last = &indicators[9];
+        assert_float_absolute_eq!(last.positions[0].quantity, 10.0, 1e-7);
+        assert_float_absolute_eq!(last.positions[0].nominal, 200.0, 1e-7);
+        assert_float_absolute_eq!(last.positions[0].valuation, 220.0, 1e-7);
+        assert_float_absolute_eq!(last.incoming_transfer, 0.0, 1e-7);
+        assert_float_absolute_eq!(last.securities_transfer, 200.0, 1e-7);
+        assert_eq!(portfolio.positions[0].get_orders(end).len(), 1);
+        assert_float_absolute_eq!(last.invested_capital, 200.0, 1e-7);
+        assert_float_absolute_eq!(last.cash, 0.0, 1e-7);
+    }
+
+    #[test]
+    fn closed_positions_only_equals_full() {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(Instrument {
+            ter: Some(0.01),
+            dividends: Some(vec![Dividend {
+                record_date: make_date_(2022, 3, 15).and_hms_opt(0, 0, 0).unwrap(),
+                payment_date: make_date_(2022, 3, 25).and_hms_opt(0, 0, 0).unwrap(),
+                value: 0.5,
+            }]),
+            ..make_instrument_("ESE", &currency)
+        });
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 4, 30);
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![
+                Position {
+                    instrument: instrument.clone(),
+                    trades: vec![
+                        make_trade_(begin, Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 10), Way::Buy, 5.0, 21.0),
+                        make_trade_(make_date_(2022, 3, 20), Way::Sell, 15.0, 23.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument: instrument.clone(),
+                    trades: vec![make_trade_(make_date_(2022, 3, 25), Way::Buy, 8.0, 22.0)],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument,
+                    trades: vec![
+                        make_trade_(make_date_(2022, 4, 1), Way::Buy, 4.0, 22.0),
+                        make_trade_(make_date_(2022, 4, 15), Way::Sell, 4.0, 19.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+            ],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::new(
+            begin
+                .iter_days()
+                .take_while(|date| *date <= end)
+                .enumerate()
+                .map(|(i, date)| {
+                    let value = 20.0 + (i % 5) as f64;
+                    DataFrame::new(date, value, value, value, value)
+                })
+                .collect(),
+        );
+        let options = PricingOptions::default();
+        let indicators =
+            PortfolioIndicators::from_portfolio(&portfolio, begin, end, &mut provider, &options)
+                .unwrap();
+        let wanted = indicators.get_close_position_indicators(&portfolio);
+
+        let close_positions =
+            PortfolioIndicators::closed_positions_only(&portfolio, end, &mut provider, &options)
+                .unwrap();
+        assert_eq!(wanted.len(), 2);
+        assert_eq!(close_positions.len(), wanted.len());
+        //
+        // the dividend of the first position is paid after its close
+        assert_float_absolute_eq!(close_positions[0].dividends, 7.5, 1e-7);
+        for (close_position, wanted) in close_positions.iter().zip(wanted.iter()) {
+            assert_eq!(close_position.position_index, wanted.position_index);
+            assert_eq!(close_position.open_date, wanted.open_date);
+            assert_eq!(close_position.close_date, wanted.close_date);
+            assert_float_absolute_eq!(close_position.quantity, wanted.quantity, 1e-7);
+            assert_float_absolute_eq!(close_position.nominal, wanted.nominal, 1e-7);
+            assert_float_absolute_eq!(close_position.dividends, wanted.dividends, 1e-7);
+            assert_float_absolute_eq!(close_position.fees, wanted.fees, 1e-7);
+            assert_float_absolute_eq!(close_position.pnl_currency, wanted.pnl_currency, 1e-7);
+            assert_float_absolute_eq!(close_position.pnl_percent, wanted.pnl_percent, 1e-7);
+            assert_float_absolute_eq!(close_position.holding_days, wanted.holding_days, 1e-7);
+            assert_eq!(close_position.irr.is_some(), wanted.irr.is_some());
+            if let (Some(irr), Some(wanted)) = (close_position.irr, wanted.irr) {
+                assert_float_absolute_eq!(irr, wanted, 1e-7);
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use crate::alias::Date;
+use std::collections::BTreeMap;
+use std::fmt;
+
 pub fn pnl(valuation: f64, nominal: f64) -> (f64, f64) {
     let pnl_currency = valuation - nominal;
     let pnl_percent = if nominal.abs() < 1e-7 {
@@ -18,8 +22,254 @@ pub fn twr(begin_valuation: f64, end_valuation: f64, cashflow: f64, previous_twr
     (previous_twr + 1.0) * (period_twr + 1.0) - 1.0
 }
 
+pub fn round_to_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+pub fn histogram(values: &[f64], bucket_width: f64) -> Vec<(f64, usize)> {
+    let mut buckets: BTreeMap<i64, usize> = Default::default();
+    for value in values {
+        let bucket = (value / bucket_width).floor() as i64;
+        *buckets.entry(bucket).or_default() += 1;
+    }
+    buckets
+        .into_iter()
+        .map(|(bucket, count)| (bucket as f64 * bucket_width, count))
+        .collect()
+}
+
+const MIN_ANNUALIZE_YEARS: f64 = 1.0 / 365.0;
+
+#[derive(Debug)]
+pub struct ActiveReturn {
+    pub tracking_error: f64,
+    pub information_ratio: Option<f64>,
+}
+
+//
+// daily returns against benchmark daily returns, both annualized
+pub fn active_return(
+    returns: &[f64],
+    benchmark_returns: &[f64],
+    trading_days_per_year: f64,
+) -> Option<ActiveReturn> {
+    let mut count = 0.0;
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+    for (value, benchmark_value) in returns.iter().zip(benchmark_returns) {
+        let active = value - benchmark_value;
+        count += 1.0;
+        let delta = active - mean;
+        mean += delta / count;
+        m2 += delta * (active - mean);
+    }
+    if count < 2.0 {
+        return None;
+    }
+
+    let tracking_error = (m2 / (count - 1.0)).sqrt() * trading_days_per_year.sqrt();
+    let information_ratio = if tracking_error < 1e-7 {
+        None
+    } else {
+        Some(mean * trading_days_per_year / tracking_error)
+    };
+    Some(ActiveReturn {
+        tracking_error,
+        information_ratio,
+    })
+}
+
+//
+// annualized standard deviation of daily returns (1 trading day a year for the daily one)
+pub fn volatility(returns: &[f64], trading_days_per_year: f64) -> Option<f64> {
+    if returns.len() < 2 {
+        return None;
+    }
+    let count = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / count;
+    let variance = returns
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / (count - 1.0);
+    Some(variance.sqrt() * trading_days_per_year.sqrt())
+}
+
+//
+// mean of daily excess returns over their volatility, both scaled to
+// the number of periods (trading days per year to annualize)
+pub fn sharpe_ratio(excess_returns: &[f64], periods: f64) -> Option<f64> {
+    let volatility = volatility(excess_returns, 1.0).filter(|value| *value > 1e-7)?;
+    let mean = excess_returns.iter().sum::<f64>() / excess_returns.len() as f64;
+    Some(mean * periods.sqrt() / volatility)
+}
+
+//
+// cumulative return over years to a yearly compounded return, a period
+// shorter than a day (ex: same day round trip) counts as one day
+pub fn annualize(cumulative_return: f64, years: f64) -> f64 {
+    (1.0 + cumulative_return).powf(1.0 / years.max(MIN_ANNUALIZE_YEARS)) - 1.0
+}
+
+//
+// gains above the threshold over the losses below it, none without any
+// loss below the threshold (rather than an infinite ratio)
+pub fn omega_ratio(returns: &[f64], threshold: f64) -> Option<f64> {
+    let gains = returns
+        .iter()
+        .map(|value| (value - threshold).max(0.0))
+        .sum::<f64>();
+    let losses = returns
+        .iter()
+        .map(|value| (threshold - value).max(0.0))
+        .sum::<f64>();
+    (losses > 1e-12).then(|| gains / losses)
+}
+
+//
+// linear interpolation between the closest ranks, q in [0, 1]
+pub fn quantile(values: &[f64], q: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+//
+// historical value at risk, the loss (as a positive return) not exceeded
+// with the confidence ex: 0.95
+pub fn value_at_risk(returns: &[f64], confidence: f64) -> Option<f64> {
+    quantile(returns, 1.0 - confidence).map(|value| -value)
+}
+
+//
+// sample covariance of paired values, none under two pairs
+pub fn covariance(left: &[f64], right: &[f64]) -> Option<f64> {
+    let count = left.len().min(right.len());
+    if count < 2 {
+        return None;
+    }
+    let left_mean = left[..count].iter().sum::<f64>() / count as f64;
+    let right_mean = right[..count].iter().sum::<f64>() / count as f64;
+    Some(
+        left.iter()
+            .zip(right)
+            .map(|(left, right)| (left - left_mean) * (right - right_mean))
+            .sum::<f64>()
+            / (count - 1) as f64,
+    )
+}
+
+//
+// sensitivity of returns to the benchmark returns, none on a flat benchmark
+pub fn beta(returns: &[f64], benchmark_returns: &[f64]) -> Option<f64> {
+    let variance =
+        covariance(benchmark_returns, benchmark_returns).filter(|value| *value > 1e-12)?;
+    Some(covariance(returns, benchmark_returns)? / variance)
+}
+
+pub fn correlation(returns: &[f64], benchmark_returns: &[f64]) -> Option<f64> {
+    let variance =
+        covariance(returns, returns)? * covariance(benchmark_returns, benchmark_returns)?;
+    if variance < 1e-24 {
+        return None;
+    }
+    Some(covariance(returns, benchmark_returns)? / variance.sqrt())
+}
+
+const XIRR_MAX_ITERATIONS: usize = 100;
+const XIRR_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, PartialEq)]
+pub enum XirrError {
+    TooFewFlows,
+    NoSignChange,
+    MaxIterations,
+}
+
+impl fmt::Display for XirrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XirrError::TooFewFlows => write!(f, "too few flows"),
+            XirrError::NoSignChange => write!(f, "no sign change"),
+            XirrError::MaxIterations => write!(f, "max iterations"),
+        }
+    }
+}
+
+//
+// annual rate zeroing the actual/365 discounted cashflows, newton from 10%
+pub fn xirr(cashflows: &[(Date, f64)]) -> Result<f64, XirrError> {
+    let cashflows = cashflows
+        .iter()
+        .filter(|(_, amount)| amount.abs() > 1e-7)
+        .collect::<Vec<_>>();
+    if cashflows.len() < 2 {
+        return Err(XirrError::TooFewFlows);
+    }
+    if !cashflows.iter().any(|(_, amount)| *amount > 0.0)
+        || !cashflows.iter().any(|(_, amount)| *amount < 0.0)
+    {
+        return Err(XirrError::NoSignChange);
+    }
+
+    let first_date = cashflows.iter().map(|(date, _)| *date).min().unwrap();
+    let flows = cashflows
+        .iter()
+        .map(|(date, amount)| ((*date - first_date).num_days() as f64 / 365.0, *amount))
+        .collect::<Vec<_>>();
+    let mut rate: f64 = 0.1;
+    for _ in 0..XIRR_MAX_ITERATIONS {
+        let (value, derivative) =
+            flows
+                .iter()
+                .fold((0.0, 0.0), |(value, derivative), (years, amount)| {
+                    let discount = (1.0 + rate).powf(-years);
+                    (
+                        value + amount * discount,
+                        derivative - years * amount * discount / (1.0 + rate),
+                    )
+                });
+        if value.abs() < XIRR_TOLERANCE {
+            return Ok(rate);
+        }
+        if !derivative.is_finite() || derivative.abs() < 1e-12 {
+            break;
+        }
+        let next_rate = rate - value / derivative;
+        if !next_rate.is_finite() {
+            break;
+        }
+        //
+        // stay above a total loss, the discount is undefined at -100%
+        rate = next_rate.max((rate - 1.0) / 2.0);
+    }
+    Err(XirrError::MaxIterations)
+}
+
+//
+// largest relative loss from a previous peak, as a negative value
+pub fn max_drawdown(values: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut drawdown: f64 = 0.0;
+    for value in values {
+        peak = peak.max(*value);
+        if peak.abs() > 1e-7 {
+            drawdown = drawdown.min((value - peak) / peak);
+        }
+    }
+    drawdown
+}
+
 #[cfg(test)]
 mod tests {
+    use super::XirrError;
+    use crate::alias::Date;
+    use crate::pricer::TRADING_DAYS_PER_YEAR;
     use assert_float_eq::*;
 
     #[test]
@@ -43,4 +293,172 @@ mod tests {
         assert_float_absolute_eq!(super::twr(1000.0, 1500.0, 200.0, 0.5), 0.95, 1e-7);
         assert_float_absolute_eq!(super::twr(1000.0, 200.0, -1000.0, 0.0), 0.20, 1e-7);
     }
+
+    #[test]
+    fn twr_through_zero_valuation() {
+        //
+        // fully sold at +10%, held at zero, bought back then +10% again
+        let periods = [
+            (100.0, 110.0, 0.0),
+            (110.0, 0.0, -110.0),
+            (0.0, 0.0, 0.0),
+            (0.0, 110.0, 110.0),
+            (110.0, 121.0, 0.0),
+        ];
+        let twr = periods
+            .iter()
+            .fold(0.0, |previous_twr, (begin, end, cashflow)| {
+                super::twr(*begin, *end, *cashflow, previous_twr)
+            });
+        assert_float_absolute_eq!(twr, 1.1 * 1.1 - 1.0, 1e-7);
+    }
+
+    #[test]
+    fn histogram() {
+        assert!(super::histogram(&[], 0.01).is_empty());
+
+        let histogram = super::histogram(&[0.005, 0.015, 0.012, -0.003, -0.013], 0.01);
+        assert_eq!(histogram.len(), 4);
+        for (i, (wanted_bucket, wanted_count)) in [(-0.02, 1), (-0.01, 1), (0.0, 1), (0.01, 2)]
+            .into_iter()
+            .enumerate()
+        {
+            assert_float_absolute_eq!(histogram[i].0, wanted_bucket, 1e-7);
+            assert_eq!(histogram[i].1, wanted_count);
+        }
+    }
+
+    #[test]
+    fn round_to_cents() {
+        assert_float_absolute_eq!(super::round_to_cents(10.004), 10.0, 1e-9);
+        assert_float_absolute_eq!(super::round_to_cents(10.006), 10.01, 1e-9);
+        assert_float_absolute_eq!(super::round_to_cents(-10.006), -10.01, 1e-9);
+    }
+
+    #[test]
+    fn active_return() {
+        assert!(super::active_return(&[0.01], &[0.0], TRADING_DAYS_PER_YEAR).is_none());
+
+        let benchmark = (0..100)
+            .map(|i| 0.002 * (i as f64).sin())
+            .collect::<Vec<_>>();
+        let returns = benchmark
+            .iter()
+            .enumerate()
+            .map(|(i, value)| value + 0.001 + if i % 2 == 0 { 0.0001 } else { -0.0001 })
+            .collect::<Vec<_>>();
+        let result = super::active_return(&returns, &benchmark, TRADING_DAYS_PER_YEAR).unwrap();
+        assert!(result.tracking_error > 0.0);
+        assert!(result.information_ratio.unwrap() > 10.0);
+
+        let result = super::active_return(&benchmark, &benchmark, TRADING_DAYS_PER_YEAR).unwrap();
+        assert_float_absolute_eq!(result.tracking_error, 0.0, 1e-7);
+        assert!(result.information_ratio.is_none());
+    }
+
+    #[test]
+    fn volatility() {
+        assert!(super::volatility(&[0.01], TRADING_DAYS_PER_YEAR).is_none());
+        assert_float_absolute_eq!(
+            super::volatility(&[0.01, 0.01, 0.01], TRADING_DAYS_PER_YEAR).unwrap(),
+            0.0,
+            1e-9
+        );
+        assert_float_absolute_eq!(
+            super::volatility(&[0.01, -0.01], TRADING_DAYS_PER_YEAR).unwrap(),
+            0.0002_f64.sqrt() * 252.0_f64.sqrt(),
+            1e-9
+        );
+    }
+
+    #[test]
+    fn omega_ratio() {
+        assert!(super::omega_ratio(&[], 0.0).is_none());
+        assert!(super::omega_ratio(&[0.01, 0.02], 0.0).is_none());
+        assert_float_absolute_eq!(
+            super::omega_ratio(&[0.02, -0.01, 0.03, -0.02, 0.0], 0.0).unwrap(),
+            5.0 / 3.0,
+            1e-9
+        );
+        assert_float_absolute_eq!(
+            super::omega_ratio(&[0.02, -0.01, 0.03, -0.02, 0.0], 0.01).unwrap(),
+            3.0 / 6.0,
+            1e-9
+        );
+    }
+
+    #[test]
+    fn beta() {
+        let benchmark = [0.01, -0.02, 0.015, 0.0, -0.005];
+        let returns = benchmark
+            .iter()
+            .map(|value| 2.0 * value)
+            .collect::<Vec<_>>();
+        assert_float_absolute_eq!(super::beta(&returns, &benchmark).unwrap(), 2.0, 1e-9);
+        assert_float_absolute_eq!(super::correlation(&returns, &benchmark).unwrap(), 1.0, 1e-9);
+        assert!(super::beta(&returns, &[0.01; 5]).is_none());
+        assert!(super::covariance(&[0.01], &[0.01]).is_none());
+    }
+
+    #[test]
+    fn xirr() {
+        let make_date_ = |year, month, day| Date::from_ymd_opt(year, month, day).unwrap();
+        let cashflows = [
+            (make_date_(2022, 1, 1), -1000.0),
+            (make_date_(2023, 1, 1), 1100.0),
+        ];
+        assert_float_absolute_eq!(super::xirr(&cashflows).unwrap(), 0.1, 1e-7);
+
+        let cashflows = [
+            (make_date_(2022, 1, 1), -1000.0),
+            (make_date_(2022, 7, 1), -500.0),
+            (make_date_(2023, 1, 1), 1200.0),
+        ];
+        assert!(super::xirr(&cashflows).unwrap() < 0.0);
+    }
+
+    #[test]
+    fn xirr_no_sign_change() {
+        let make_date_ = |year, month, day| Date::from_ymd_opt(year, month, day).unwrap();
+        let cashflows = [
+            (make_date_(2022, 1, 1), -1000.0),
+            (make_date_(2023, 1, 1), -100.0),
+        ];
+        assert_eq!(super::xirr(&cashflows), Err(XirrError::NoSignChange));
+    }
+
+    #[test]
+    fn xirr_single_flow() {
+        let date = Date::from_ymd_opt(2022, 1, 1).unwrap();
+        assert_eq!(super::xirr(&[(date, -1000.0)]), Err(XirrError::TooFewFlows));
+        assert_eq!(
+            super::xirr(&[(date, -1000.0), (date, 0.0)]),
+            Err(XirrError::TooFewFlows)
+        );
+        assert_eq!(super::xirr(&[]), Err(XirrError::TooFewFlows));
+    }
+
+    #[test]
+    fn quantile() {
+        assert!(super::quantile(&[], 0.5).is_none());
+        let returns = [
+            0.03, -0.02, 0.01, -0.04, 0.0, 0.02, -0.01, 0.05, -0.03, 0.04, 0.01,
+        ];
+        assert_float_absolute_eq!(super::quantile(&returns, 0.5).unwrap(), 0.01, 1e-9);
+        assert_float_absolute_eq!(super::quantile(&returns, 0.05).unwrap(), -0.035, 1e-9);
+        assert_float_absolute_eq!(super::quantile(&returns, 0.0).unwrap(), -0.04, 1e-9);
+        assert_float_absolute_eq!(super::quantile(&returns, 1.0).unwrap(), 0.05, 1e-9);
+        assert_float_absolute_eq!(super::value_at_risk(&returns, 0.95).unwrap(), 0.035, 1e-9);
+    }
+
+    #[test]
+    fn max_drawdown() {
+        assert_float_absolute_eq!(super::max_drawdown(&[]), 0.0, 1e-9);
+        assert_float_absolute_eq!(super::max_drawdown(&[1.0, 1.1, 1.2]), 0.0, 1e-9);
+        assert_float_absolute_eq!(
+            super::max_drawdown(&[1.0, 1.2, 0.9, 1.1, 0.6, 1.3]),
+            -0.5,
+            1e-9
+        );
+    }
 }
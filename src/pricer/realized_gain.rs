@@ -0,0 +1,126 @@
+use super::{Lot, PortfolioIndicators};
+use crate::alias::Date;
+use crate::marketdata::Instrument;
+use crate::portfolio::Portfolio;
+use std::rc::Rc;
+
+pub struct RealizedGain {
+    pub instrument: Rc<Instrument>,
+    pub position_index: usize,
+    pub open_date: Date,
+    pub close_date: Date,
+    pub quantity: f64,
+    pub proceeds: f64,
+    pub cost: f64,
+    pub realized_gain: f64,
+    pub holding_days: i64,
+}
+
+impl PortfolioIndicators {
+    //
+    // one row by fifo lot closed up to the pricing end, ordered by close date
+    pub fn realized_gains(&self, portfolio: &Portfolio) -> Vec<RealizedGain> {
+        let mut result = portfolio
+            .positions
+            .iter()
+            .enumerate()
+            .flat_map(|(position_index, position)| {
+                Lot::from_trades(
+                    &position.priced_trades(self.end),
+                    self.options.quantity_epsilon,
+                )
+                .into_iter()
+                .filter(|lot| lot.close_date <= self.end)
+                .map(move |lot| RealizedGain {
+                    instrument: position.instrument.clone(),
+                    position_index,
+                    open_date: lot.open_date,
+                    close_date: lot.close_date,
+                    quantity: lot.quantity,
+                    proceeds: lot.proceeds,
+                    cost: lot.cost,
+                    realized_gain: lot.proceeds - lot.cost,
+                    holding_days: lot.holding_days(),
+                })
+            })
+            .collect::<Vec<_>>();
+        result.sort_by_key(|item| item.close_date);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+
+    fn make_trade_(date: Date, way: Way, quantity: f64, price: f64, fees: f64) -> Trade {
+        Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    #[test]
+    fn realized_gains_by_lot() {
+        let currency = make_currency_("EUR");
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: Rc::new(Instrument {
+                    isin: String::from("FR0013412020"),
+                    ..make_instrument_("PAEEM", &currency)
+                }),
+                trades: vec![
+                    make_trade_(make_date_(2022, 3, 1), Way::Buy, 10.0, 20.0, 1.0),
+                    make_trade_(make_date_(2022, 3, 11), Way::Buy, 30.0, 22.0, 3.0),
+                    make_trade_(make_date_(2022, 3, 21), Way::Sell, 40.0, 25.0, 4.0),
+                ],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: Default::default(),
+        };
+        let indicators = PortfolioIndicators {
+            begin: make_date_(2022, 3, 1),
+            end: make_date_(2022, 3, 31),
+            options: PricingOptions::default(),
+            portfolios: Vec::new(),
+        };
+
+        let gains = indicators.realized_gains(&portfolio);
+        assert_eq!(gains.len(), 2);
+
+        assert_eq!(gains[0].instrument.isin, "FR0013412020");
+        assert_eq!(gains[0].open_date, make_date_(2022, 3, 1));
+        assert_eq!(gains[0].close_date, make_date_(2022, 3, 21));
+        assert_eq!(gains[0].holding_days, 20);
+        assert_float_absolute_eq!(gains[0].quantity, 10.0, 1e-7);
+        assert_float_absolute_eq!(gains[0].cost, 201.0, 1e-7);
+        assert_float_absolute_eq!(gains[0].proceeds, 249.0, 1e-7);
+        assert_float_absolute_eq!(gains[0].realized_gain, 48.0, 1e-7);
+
+        assert_eq!(gains[1].open_date, make_date_(2022, 3, 11));
+        assert_eq!(gains[1].holding_days, 10);
+        assert_float_absolute_eq!(gains[1].quantity, 30.0, 1e-7);
+        assert_float_absolute_eq!(gains[1].cost, 663.0, 1e-7);
+        assert_float_absolute_eq!(gains[1].proceeds, 747.0, 1e-7);
+        assert_float_absolute_eq!(gains[1].realized_gain, 84.0, 1e-7);
+
+        let indicators = PortfolioIndicators {
+            end: make_date_(2022, 3, 20),
+            ..indicators
+        };
+        assert!(indicators.realized_gains(&portfolio).is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use super::PortfolioIndicator;
 use crate::marketdata::Instrument;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 use std::rc::Rc;
 
 pub struct RegionIndicatorInstrument {
@@ -15,19 +15,21 @@ pub struct RegionIndicator {
 }
 
 impl RegionIndicator {
+    //
+    // percent of the open positions valuation, cash excluded whatever the weight base
     pub fn from_portfolio(indicator: &PortfolioIndicator) -> Vec<Self> {
         let regions = indicator
             .positions
             .iter()
             .filter(|position| !position.is_close && position.instrument.region.is_some())
             .map(|position| position.instrument.region.as_ref().unwrap())
-            .collect::<HashSet<_>>();
+            .collect::<BTreeSet<_>>();
 
         let valuation = indicator
             .positions
             .iter()
             .filter(|position| !position.is_close)
-            .map(|position| &position.valuation)
+            .map(|position| &position.home_valuation)
             .sum::<f64>();
 
         regions
@@ -50,19 +52,21 @@ impl RegionIndicator {
                         let value = valuation_by_instrument
                             .entry(position.instrument.clone())
                             .or_insert(0.0);
-                        *value += position.valuation;
-                        valuation_by_region += position.valuation;
+                        *value += position.home_valuation;
+                        valuation_by_region += position.home_valuation;
                     });
+                let mut instruments = valuation_by_instrument
+                    .iter()
+                    .map(|(key, value)| RegionIndicatorInstrument {
+                        instrument: key.clone(),
+                        valuation_percent: value / valuation_by_region,
+                    })
+                    .collect::<Vec<_>>();
+                instruments.sort_by(|lhs, rhs| lhs.instrument.name.cmp(&rhs.instrument.name));
                 RegionIndicator {
                     region_name: region.to_string(),
                     valuation_percent: valuation_by_region / valuation,
-                    instruments: valuation_by_instrument
-                        .iter()
-                        .map(|(key, value)| RegionIndicatorInstrument {
-                            instrument: key.clone(),
-                            valuation_percent: value / valuation_by_region,
-                        })
-                        .collect(),
+                    instruments,
                 }
             })
             .collect()
@@ -0,0 +1,102 @@
+use chrono::Datelike;
+use clap::ValueEnum;
+
+use super::{PortfolioIndicator, PortfolioIndicators};
+use crate::alias::Date;
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Weekly,
+    Monthly,
+}
+
+impl Step {
+    fn period_(&self, date: Date) -> (i32, u32) {
+        match self {
+            Step::Weekly => (date.iso_week().year(), date.iso_week().week()),
+            Step::Monthly => (date.year(), date.month()),
+        }
+    }
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+impl PortfolioIndicators {
+    //
+    // last indicator of each period, twr and others cumulative
+    // values are kept as of the period end
+    pub fn resample(&self, step: Step) -> Vec<&PortfolioIndicator> {
+        let mut result: Vec<&PortfolioIndicator> = Vec::new();
+        for indicator in self.portfolios.iter() {
+            match result.last_mut() {
+                Some(last) if step.period_(last.date) == step.period_(indicator.date) => {
+                    *last = indicator;
+                }
+                _ => result.push(indicator),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricer::PricingOptions;
+
+    #[test]
+    fn monthly_keeps_last_date() {
+        let begin = Date::from_ymd_opt(2022, 1, 10).unwrap();
+        let end = Date::from_ymd_opt(2022, 3, 25).unwrap();
+        let portfolios = begin
+            .iter_days()
+            .take_while(|date| *date <= end)
+            .filter(|date| date.weekday().number_from_monday() <= 5)
+            .enumerate()
+            .map(|(i, date)| PortfolioIndicator {
+                date,
+                valuation: 100.0 + i as f64,
+                twr: 0.001 * i as f64,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let indicators = PortfolioIndicators {
+            begin,
+            end,
+            options: PricingOptions::default(),
+            portfolios,
+        };
+
+        let monthly = indicators.resample(Step::Monthly);
+        assert_eq!(
+            monthly.iter().map(|item| item.date).collect::<Vec<_>>(),
+            vec![
+                Date::from_ymd_opt(2022, 1, 31).unwrap(),
+                Date::from_ymd_opt(2022, 2, 28).unwrap(),
+                Date::from_ymd_opt(2022, 3, 25).unwrap(),
+            ]
+        );
+        for item in monthly {
+            let daily = indicators
+                .portfolios
+                .iter()
+                .find(|daily| daily.date == item.date)
+                .unwrap();
+            assert_eq!(item.valuation, daily.valuation);
+            assert_eq!(item.twr, daily.twr);
+        }
+
+        let weekly = indicators.resample(Step::Weekly);
+        assert_eq!(weekly.len(), 11);
+        assert!(weekly
+            .iter()
+            .all(|item| item.date.weekday() == chrono::Weekday::Fri));
+    }
+}
@@ -0,0 +1,185 @@
+use super::primitive;
+use super::PortfolioIndicators;
+use crate::alias::Date;
+use crate::error::Error;
+
+use std::fs::File;
+use std::io::BufRead;
+
+#[derive(Clone, Debug)]
+pub enum RiskFreeRate {
+    // annual rate
+    Constant(f64),
+    // annual rates sorted by date, each one applies from its date
+    Series(Vec<(Date, f64)>),
+}
+
+impl Default for RiskFreeRate {
+    fn default() -> Self {
+        Self::Constant(0.0)
+    }
+}
+
+impl RiskFreeRate {
+    //
+    // rows are date;annual_rate, header line is optional
+    pub fn from_csv<R>(reader: R) -> Result<Self, Error>
+    where
+        R: std::io::Read,
+    {
+        let mut series = Vec::new();
+        for (row, line) in std::io::BufReader::new(reader).lines().enumerate() {
+            let row = row + 1;
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || (row == 1 && line.starts_with("date")) {
+                continue;
+            }
+
+            let fields = line.split(';').map(str::trim).collect::<Vec<_>>();
+            if fields.len() != 2 {
+                return Err(Error::new_referential(format!(
+                    "row {row}: expected 2 fields found {}",
+                    fields.len()
+                )));
+            }
+            let date = chrono::NaiveDate::parse_from_str(fields[0], "%Y-%m-%d").map_err(|err| {
+                Error::new_referential(format!(
+                    "row {row}: unable to convert {} into Date because {err}",
+                    fields[0]
+                ))
+            })?;
+            let rate = fields[1].parse::<f64>().map_err(|err| {
+                Error::new_referential(format!(
+                    "row {row}: unable to convert annual_rate {} into f64 because {err}",
+                    fields[1]
+                ))
+            })?;
+            series.push((date, rate));
+        }
+        if series.is_empty() {
+            return Err(Error::new_referential("empty risk free series"));
+        }
+        series.sort_by_key(|(date, _)| *date);
+        Ok(Self::Series(series))
+    }
+
+    pub fn from_csv_file(filename: &str) -> Result<Self, Error> {
+        Self::from_csv(File::open(filename)?)
+    }
+
+    //
+    // before the first date of a series its first rate is used
+    pub fn annual_rate(&self, date: Date) -> f64 {
+        match self {
+            Self::Constant(rate) => *rate,
+            Self::Series(series) => series
+                .iter()
+                .rev()
+                .find(|(item, _)| *item <= date)
+                .or(series.first())
+                .map_or(0.0, |(_, rate)| *rate),
+        }
+    }
+
+    pub fn daily_rate(&self, date: Date, trading_days_per_year: f64) -> f64 {
+        self.annual_rate(date) / trading_days_per_year
+    }
+}
+
+impl PortfolioIndicators {
+    //
+    // daily twr returns in excess of the risk free rate of their date, annualized
+    pub fn sharpe_ratio(&self) -> Option<f64> {
+        let excess_returns = self
+            .dated_daily_returns_(Date::MIN)
+            .into_iter()
+            .map(|(_, date, value)| {
+                value
+                    - self
+                        .options
+                        .risk_free
+                        .daily_rate(date, self.options.trading_days_per_year)
+            })
+            .collect::<Vec<_>>();
+        if excess_returns.len() < self.options.min_observations {
+            return None;
+        }
+        //
+        // over the holding period when annualization is not allowed
+        let periods = if self.is_annualized() {
+            self.options.trading_days_per_year
+        } else {
+            excess_returns.len() as f64
+        };
+        primitive::sharpe_ratio(&excess_returns, periods)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricer::{PortfolioIndicator, PricingOptions};
+    use assert_float_eq::*;
+
+    fn make_indicators_(risk_free: RiskFreeRate) -> PortfolioIndicators {
+        let begin = Date::from_ymd_opt(2022, 1, 1).unwrap();
+        let mut twr: f64 = 0.0;
+        let portfolios = begin
+            .iter_days()
+            .take(60)
+            .enumerate()
+            .map(|(i, date)| {
+                twr = (1.0 + twr) * (1.0 + if i % 2 == 0 { 0.002 } else { -0.001 }) - 1.0;
+                PortfolioIndicator {
+                    date,
+                    twr,
+                    ..Default::default()
+                }
+            })
+            .collect::<Vec<_>>();
+        PortfolioIndicators {
+            begin,
+            end: portfolios.last().unwrap().date,
+            options: PricingOptions {
+                risk_free,
+                ..Default::default()
+            },
+            portfolios,
+        }
+    }
+
+    #[test]
+    fn sharpe_ratio_with_risk_free_series() {
+        let series = RiskFreeRate::from_csv(
+            "date;annual_rate\n2022-02-01;0.10\n2021-06-01;0.02\n".as_bytes(),
+        )
+        .unwrap();
+        assert_float_absolute_eq!(
+            series.annual_rate(Date::from_ymd_opt(2020, 1, 1).unwrap()),
+            0.02,
+            1e-7
+        );
+        assert_float_absolute_eq!(
+            series.annual_rate(Date::from_ymd_opt(2022, 2, 1).unwrap()),
+            0.10,
+            1e-7
+        );
+
+        let constant = make_indicators_(RiskFreeRate::Constant(0.02))
+            .sharpe_ratio()
+            .unwrap();
+        let flat_series = make_indicators_(RiskFreeRate::Series(vec![(
+            Date::from_ymd_opt(2022, 1, 1).unwrap(),
+            0.02,
+        )]))
+        .sharpe_ratio()
+        .unwrap();
+        let varying = make_indicators_(series).sharpe_ratio().unwrap();
+
+        assert_float_absolute_eq!(constant, flat_series, 1e-7);
+        assert!(varying < constant, "{varying} >= {constant}");
+        assert!(RiskFreeRate::from_csv("date;annual_rate\n".as_bytes()).is_err());
+        assert!(RiskFreeRate::from_csv("2022-01-01;x\n".as_bytes()).is_err());
+    }
+}
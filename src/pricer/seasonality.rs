@@ -0,0 +1,211 @@
+use chrono::{Datelike, Weekday};
+
+use super::PortfolioIndicators;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+pub struct SeasonalityBucket<T> {
+    pub period: T,
+    pub average_return: f64,
+    pub count: usize,
+}
+
+pub struct SeasonalityIndicator {
+    pub by_weekday: Vec<SeasonalityBucket<Weekday>>,
+    // calendar month 1-12
+    pub by_month: Vec<SeasonalityBucket<u32>>,
+}
+
+impl SeasonalityIndicator {
+    //
+    // average daily return of the portfolio pnl (%) by weekday and by month,
+    // a day without any spot quoted on it (week end, holiday) is skipped
+    pub fn from_portfolios(indicators: &PortfolioIndicators) -> Self {
+        let portfolios = indicators
+            .portfolios
+            .iter()
+            .filter(|indicator| !indicator.excluded)
+            .collect::<Vec<_>>();
+
+        let mut by_weekday = [(0.0, 0_usize); 7];
+        let mut by_month = [(0.0, 0_usize); 12];
+        for values in portfolios.windows(2) {
+            let (previous, current) = (values[0], values[1]);
+            let is_quoted = current
+                .positions
+                .iter()
+                .any(|position| position.spot.date == current.date);
+            if !is_quoted {
+                continue;
+            }
+            let daily_return = (current.pnl_percent + 1.0) / (previous.pnl_percent + 1.0) - 1.0;
+            for (sum, count) in [
+                &mut by_weekday[current.date.weekday().num_days_from_monday() as usize],
+                &mut by_month[current.date.month0() as usize],
+            ] {
+                *sum += daily_return;
+                *count += 1;
+            }
+        }
+
+        SeasonalityIndicator {
+            by_weekday: by_weekday
+                .into_iter()
+                .enumerate()
+                .filter(|(_, (_, count))| *count > 0)
+                .map(|(i, value)| make_bucket_(WEEKDAYS[i], value))
+                .collect(),
+            by_month: by_month
+                .into_iter()
+                .enumerate()
+                .filter(|(_, (_, count))| *count > 0)
+                .map(|(i, value)| make_bucket_(i as u32 + 1, value))
+                .collect(),
+        }
+    }
+}
+
+fn make_bucket_<T>(period: T, (sum, count): (f64, usize)) -> SeasonalityBucket<T> {
+    SeasonalityBucket {
+        period,
+        average_return: sum / count as f64,
+        count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::Date;
+    use crate::historical::DataFrame;
+    use crate::marketdata::Instrument;
+    use crate::pricer::fixture::{make_currency_, make_instrument_};
+    use crate::pricer::{PortfolioIndicator, PositionIndicator, PricingOptions};
+    use assert_float_eq::*;
+    use std::rc::Rc;
+
+    fn make_position_indicator_(instrument: &Rc<Instrument>, date: Date) -> PositionIndicator {
+        PositionIndicator {
+            date,
+            spot: DataFrame::new(date, 10.0, 10.0, 10.0, 10.0),
+            instrument: instrument.clone(),
+            position_index: 0,
+            quantity: 0.0,
+            quantity_buy: 0.0,
+            quantity_sell: 0.0,
+            unit_price: 0.0,
+            break_even_price: None,
+            valuation: 0.0,
+            nominal: 0.0,
+            cashflow: 0.0,
+            dividends: 0.0,
+            fees: 0.0,
+            pnl_currency: 0.0,
+            pnl_percent: 0.0,
+            twr: 0.0,
+            earning: 0.0,
+            earning_latent: 0.0,
+            is_close: false,
+            fx_rate: 1.0,
+            home_valuation: 0.0,
+            home_nominal: 0.0,
+            home_dividends: 0.0,
+            home_fees: 0.0,
+            home_earning: 0.0,
+            home_earning_latent: 0.0,
+            home_pnl_currency: 0.0,
+            home_pnl_percent: 0.0,
+            pnl_attribution: Default::default(),
+            weight: 0.0,
+            cost_weight: 0.0,
+            weight_drift: 0.0,
+            excluded: false,
+            ter_drag: 0.0,
+            ter_realized: 0.0,
+            home_ter_realized: 0.0,
+        }
+    }
+
+    #[test]
+    fn positive_mondays() {
+        let instrument = Rc::new(make_instrument_("ESE", &make_currency_("EUR")));
+
+        //
+        // 2022-01-03 is a monday, +1% on mondays and -0.1% other week days,
+        // nothing quoted on week ends
+        let begin = Date::from_ymd_opt(2022, 1, 3).unwrap();
+        let mut pnl_percent: f64 = 0.0;
+        let portfolios = begin
+            .iter_days()
+            .take(8 * 7)
+            .map(|date| {
+                let quoted = match date.weekday() {
+                    Weekday::Sat | Weekday::Sun => false,
+                    Weekday::Mon => {
+                        pnl_percent = (1.0 + pnl_percent) * 1.01 - 1.0;
+                        true
+                    }
+                    _ => {
+                        pnl_percent = (1.0 + pnl_percent) * 0.999 - 1.0;
+                        true
+                    }
+                };
+                let spot_date = if quoted {
+                    date
+                } else {
+                    date.pred_opt().unwrap()
+                };
+                let mut position = make_position_indicator_(&instrument, date);
+                position.spot = DataFrame::new(spot_date, 10.0, 10.0, 10.0, 10.0);
+                PortfolioIndicator {
+                    date,
+                    pnl_percent,
+                    positions: vec![position],
+                    ..Default::default()
+                }
+            })
+            .collect::<Vec<_>>();
+        let indicators = PortfolioIndicators {
+            begin,
+            end: portfolios.last().unwrap().date,
+            options: PricingOptions::default(),
+            portfolios,
+        };
+
+        let seasonality = SeasonalityIndicator::from_portfolios(&indicators);
+        assert_eq!(seasonality.by_weekday.len(), 5);
+        let monday = &seasonality.by_weekday[0];
+        assert_eq!(monday.period, Weekday::Mon);
+        assert_eq!(monday.count, 7);
+        assert_float_absolute_eq!(monday.average_return, 0.01, 1e-9);
+        for bucket in &seasonality.by_weekday[1..] {
+            assert_eq!(bucket.count, 8);
+            assert_float_absolute_eq!(bucket.average_return, -0.001, 1e-9);
+        }
+
+        assert_eq!(
+            seasonality
+                .by_month
+                .iter()
+                .map(|bucket| bucket.period)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            seasonality
+                .by_month
+                .iter()
+                .map(|bucket| bucket.count)
+                .sum::<usize>(),
+            39
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use super::{primitive, PortfolioIndicators};
+use crate::alias::Date;
+use crate::portfolio::Portfolio;
+use std::collections::BTreeMap;
+
+pub struct SleeveIndicator {
+    pub date: Date,
+    pub valuation: f64,
+    pub cashflow: f64,
+    pub pnl_currency: f64,
+    pub twr: f64,
+}
+
+impl SleeveIndicator {
+    //
+    // only the positions of each sleeve are aggregated (home currency, cash excluded),
+    // a position without sleeve is ignored
+    pub fn from_portfolios(
+        portfolio: &Portfolio,
+        indicators: &PortfolioIndicators,
+    ) -> BTreeMap<String, Vec<Self>> {
+        let mut result: BTreeMap<String, Vec<Self>> = Default::default();
+        for indicator in &indicators.portfolios {
+            let mut by_sleeve: BTreeMap<&String, (f64, f64, f64)> = Default::default();
+            for position_indicator in &indicator.positions {
+                let Some(sleeve) = portfolio
+                    .positions
+                    .get(position_indicator.position_index)
+                    .and_then(|position| position.sleeve.as_ref())
+                else {
+                    continue;
+                };
+                let fx_rate = position_indicator.fx_rate;
+                let (valuation, cashflow, pnl_currency) =
+                    by_sleeve.entry(sleeve).or_insert((0.0, 0.0, 0.0));
+                *valuation += position_indicator.home_valuation;
+                *cashflow += position_indicator.cashflow * fx_rate;
+                *pnl_currency += position_indicator.earning_latent * fx_rate;
+            }
+
+            for (sleeve, (valuation, cashflow, pnl_currency)) in by_sleeve {
+                let sleeve_indicators = result.entry(sleeve.clone()).or_default();
+                let (previous_twr, begin_valuation, delta_cashflow) =
+                    if let Some(previous) = sleeve_indicators.last() {
+                        (
+                            previous.twr,
+                            previous.valuation,
+                            cashflow - previous.cashflow,
+                        )
+                    } else {
+                        (0.0, cashflow, 0.0)
+                    };
+                sleeve_indicators.push(Self {
+                    date: indicator.date,
+                    valuation,
+                    cashflow,
+                    pnl_currency,
+                    twr: primitive::twr(begin_valuation, valuation, delta_cashflow, previous_twr),
+                });
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::historical::DataFrame;
+    use crate::portfolio::{Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_, MockProvider};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn two_sleeves_independent_twr() {
+        let currency = make_currency_("EUR");
+        let begin = make_date_(2022, 3, 1);
+        let end = make_date_(2022, 3, 3);
+        let make_position_ = |name: &str, sleeve: &str| Position {
+            instrument: Rc::new(make_instrument_(name, &currency)),
+            trades: vec![Trade {
+                date: begin.and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: 10.0,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: Some(String::from(sleeve)),
+            opening_position: None,
+        };
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![
+                make_position_("ESE", "retirement"),
+                make_position_("PAEEM", "trading"),
+            ],
+            cash: Default::default(),
+        };
+        let mut provider = MockProvider::by_instrument([
+            (
+                "ESE",
+                vec![
+                    DataFrame::new(begin, 10.0, 10.0, 10.0, 10.0),
+                    DataFrame::new(end, 11.0, 11.0, 11.0, 11.0),
+                ],
+            ),
+            (
+                "PAEEM",
+                vec![
+                    DataFrame::new(begin, 10.0, 10.0, 10.0, 10.0),
+                    DataFrame::new(end, 8.0, 8.0, 8.0, 8.0),
+                ],
+            ),
+        ]);
+        let indicators = PortfolioIndicators::from_portfolio(
+            &portfolio,
+            begin,
+            end,
+            &mut provider,
+            &PricingOptions::default(),
+        )
+        .unwrap();
+
+        let sleeves = SleeveIndicator::from_portfolios(&portfolio, &indicators);
+        assert_eq!(
+            sleeves
+                .keys()
+                .map(|sleeve| sleeve.as_str())
+                .collect::<Vec<_>>(),
+            vec!["retirement", "trading"]
+        );
+        for (sleeve, valuation, pnl_currency, twr) in [
+            ("retirement", 110.0, 10.0, 0.1),
+            ("trading", 80.0, -20.0, -0.2),
+        ] {
+            let last = sleeves[sleeve].last().unwrap();
+            assert_eq!(last.date, end);
+            assert_float_absolute_eq!(last.valuation, valuation, 1e-7);
+            assert_float_absolute_eq!(last.pnl_currency, pnl_currency, 1e-7);
+            assert_float_absolute_eq!(last.twr, twr, 1e-7);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use super::PortfolioIndicator;
+use std::collections::BTreeMap;
+
+pub struct TagIndicator {
+    pub tag_name: String,
+    pub valuation: f64,
+    // share of the open positions valuation, an instrument with several tags
+    // counts in each of them so the shares may sum above 100%
+    pub valuation_percent: f64,
+}
+
+impl TagIndicator {
+    pub fn from_portfolio(indicator: &PortfolioIndicator) -> Vec<Self> {
+        let open_positions = indicator
+            .positions
+            .iter()
+            .filter(|position| !position.is_close)
+            .collect::<Vec<_>>();
+
+        let valuation = open_positions
+            .iter()
+            .map(|position| position.home_valuation)
+            .sum::<f64>();
+
+        let mut valuation_by_tag: BTreeMap<&String, f64> = Default::default();
+        for position in open_positions {
+            for tag in &position.instrument.tags {
+                *valuation_by_tag.entry(tag).or_insert(0.0) += position.home_valuation;
+            }
+        }
+
+        valuation_by_tag
+            .into_iter()
+            .map(|(tag, valuation_by_tag)| TagIndicator {
+                tag_name: tag.clone(),
+                valuation: valuation_by_tag,
+                valuation_percent: valuation_by_tag / valuation,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::Date;
+    use crate::historical::DataFrame;
+    use crate::marketdata::Instrument;
+    use crate::portfolio::{Position, Trade, Way};
+    use crate::pricer::fixture::{make_currency_, make_instrument_};
+    use crate::pricer::{PositionIndicator, PricingOptions};
+    use assert_float_eq::*;
+    use std::rc::Rc;
+
+    fn make_position_indicator_(name: &str, tags: &[&str], spot: f64) -> PositionIndicator {
+        let date = Date::from_ymd_opt(2022, 3, 1).unwrap();
+        let position = Position {
+            instrument: Rc::new(Instrument {
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..make_instrument_(name, &make_currency_("EUR"))
+            }),
+            trades: vec![Trade {
+                date: date.and_hms_opt(10, 0, 0).unwrap(),
+                way: Way::Buy,
+                quantity: 10.0,
+                price: spot,
+                fees: 0.0,
+                order_id: None,
+                fx_rate: None,
+            }],
+            close_reason: None,
+            sleeve: None,
+            opening_position: None,
+        };
+        PositionIndicator::from_position(
+            &position,
+            date,
+            0,
+            &DataFrame::new(date, spot, spot, spot, spot),
+            1.0,
+            &[],
+            &PricingOptions::default(),
+        )
+    }
+
+    #[test]
+    fn instrument_in_several_tags() {
+        let indicator = PortfolioIndicator {
+            positions: vec![
+                make_position_indicator_("ESE", &["core", "dividend"], 30.0),
+                make_position_indicator_("PAEEM", &["satellite"], 10.0),
+                make_position_indicator_("CASH", &[], 60.0),
+            ],
+            ..Default::default()
+        };
+
+        let tags = TagIndicator::from_portfolio(&indicator);
+        assert_eq!(
+            tags.iter()
+                .map(|tag| tag.tag_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["core", "dividend", "satellite"]
+        );
+        for (tag, valuation, valuation_percent) in [
+            (&tags[0], 300.0, 0.3),
+            (&tags[1], 300.0, 0.3),
+            (&tags[2], 100.0, 0.1),
+        ] {
+            assert_float_absolute_eq!(tag.valuation, valuation, 1e-7);
+            assert_float_absolute_eq!(tag.valuation_percent, valuation_percent, 1e-7);
+        }
+    }
+}
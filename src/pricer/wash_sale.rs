@@ -0,0 +1,152 @@
+use super::{Lot, PortfolioIndicators};
+use crate::alias::Date;
+use crate::marketdata::Instrument;
+use crate::portfolio::{Portfolio, Way};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub struct WashSale {
+    pub instrument: Rc<Instrument>,
+    pub position_index: usize,
+    pub sell_date: Date,
+    pub sell_quantity: f64,
+    pub loss: f64,
+    pub buy_date: Date,
+    pub buy_quantity: f64,
+}
+
+impl PortfolioIndicators {
+    //
+    // a sell closing lots at a loss followed on a later day, within the window,
+    // by a buy of the same instrument whatever the position it opens
+    pub fn wash_sale_candidates(&self, portfolio: &Portfolio, window_days: u64) -> Vec<WashSale> {
+        let mut result = Vec::new();
+        for (position_index, position) in portfolio.positions.iter().enumerate() {
+            let mut sells: BTreeMap<Date, (f64, f64)> = Default::default();
+            for lot in Lot::from_trades(
+                &position.priced_trades(self.end),
+                self.options.quantity_epsilon,
+            )
+            .into_iter()
+            .filter(|lot| lot.close_date <= self.end)
+            {
+                let sell = sells.entry(lot.close_date).or_default();
+                sell.0 += lot.quantity;
+                sell.1 += lot.proceeds - lot.cost;
+            }
+
+            for (sell_date, (sell_quantity, pnl)) in sells {
+                if pnl >= 0.0 {
+                    continue;
+                }
+                let Some(window_end) = sell_date.checked_add_days(chrono::Days::new(window_days))
+                else {
+                    continue;
+                };
+                let rebuy = portfolio
+                    .positions
+                    .iter()
+                    .filter(|item| item.instrument.name == position.instrument.name)
+                    .flat_map(|item| item.trades.iter())
+                    .filter(|trade| {
+                        trade.way == Way::Buy
+                            && trade.date.date() > sell_date
+                            && trade.date.date() <= window_end
+                    })
+                    .min_by_key(|trade| trade.date);
+                if let Some(rebuy) = rebuy {
+                    result.push(WashSale {
+                        instrument: position.instrument.clone(),
+                        position_index,
+                        sell_date,
+                        sell_quantity,
+                        loss: -pnl,
+                        buy_date: rebuy.date.date(),
+                        buy_quantity: rebuy.quantity,
+                    });
+                }
+            }
+        }
+        result.sort_by_key(|item| item.sell_date);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{Position, Trade};
+    use crate::pricer::fixture::{make_currency_, make_date_, make_instrument_};
+    use crate::pricer::PricingOptions;
+    use assert_float_eq::*;
+
+    fn make_trade_(date: Date, way: Way, quantity: f64, price: f64) -> Trade {
+        Trade {
+            date: date.and_hms_opt(10, 0, 0).unwrap(),
+            way,
+            quantity,
+            price,
+            fees: 0.0,
+            order_id: None,
+            fx_rate: None,
+        }
+    }
+
+    fn make_portfolio_(rebuy_date: Date) -> Portfolio {
+        let currency = make_currency_("EUR");
+        let instrument = Rc::new(make_instrument_("ESE", &currency));
+        Portfolio {
+            name: String::from("PEA"),
+            currency,
+            positions: vec![
+                Position {
+                    instrument: instrument.clone(),
+                    trades: vec![
+                        make_trade_(make_date_(2022, 3, 1), Way::Buy, 10.0, 20.0),
+                        make_trade_(make_date_(2022, 3, 10), Way::Sell, 10.0, 15.0),
+                    ],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+                Position {
+                    instrument,
+                    trades: vec![make_trade_(rebuy_date, Way::Buy, 8.0, 16.0)],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                },
+            ],
+            cash: Default::default(),
+        }
+    }
+
+    fn make_indicators_() -> PortfolioIndicators {
+        PortfolioIndicators {
+            begin: make_date_(2022, 3, 1),
+            end: make_date_(2022, 12, 31),
+            options: PricingOptions::default(),
+            portfolios: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn wash_sale_within_window() {
+        let indicators = make_indicators_();
+
+        let portfolio = make_portfolio_(make_date_(2022, 3, 15));
+        let wash_sales = indicators.wash_sale_candidates(&portfolio, 30);
+        assert_eq!(wash_sales.len(), 1);
+        let wash_sale = &wash_sales[0];
+        assert_eq!(wash_sale.instrument.name, "ESE");
+        assert_eq!(wash_sale.position_index, 0);
+        assert_eq!(wash_sale.sell_date, make_date_(2022, 3, 10));
+        assert_eq!(wash_sale.buy_date, make_date_(2022, 3, 15));
+        assert_float_absolute_eq!(wash_sale.sell_quantity, 10.0, 1e-7);
+        assert_float_absolute_eq!(wash_sale.loss, 50.0, 1e-7);
+        assert_float_absolute_eq!(wash_sale.buy_quantity, 8.0, 1e-7);
+
+        let portfolio = make_portfolio_(make_date_(2022, 5, 9));
+        assert!(indicators.wash_sale_candidates(&portfolio, 30).is_empty());
+    }
+}
@@ -1,14 +1,17 @@
 mod cache;
 mod serialize;
+mod trades_csv;
 
 use crate::error::Error;
 use crate::marketdata::{Currency, Instrument, Market};
 use crate::portfolio::Portfolio;
 
 use cache::*;
+use log::{info, warn};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 impl serialize::Resolver for Referential {
@@ -24,7 +27,13 @@ impl serialize::Resolver for Referential {
         })
     }
 
+    //
+    // an instrument already loaded may be referenced by one of its isins
+    // (ex: trades before a fund merger)
     fn resolv_instrument(&mut self, name: &str) -> Result<Rc<Instrument>, Error> {
+        if let Some(instrument) = self.get_instrument_by_isin(name) {
+            return Ok(instrument);
+        }
         self.get_instrument_by_name(name).map_err(|err| {
             Error::new_referential(format!("unable to resolv {name} because {err:?}"))
         })
@@ -34,6 +43,7 @@ impl serialize::Resolver for Referential {
 pub struct Referential {
     marketdata_dir: String,
     cache: Cache,
+    ticker_overrides: HashMap<String, String>,
 }
 
 impl Referential {
@@ -41,6 +51,28 @@ impl Referential {
         Self {
             marketdata_dir: path.into(),
             cache: Default::default(),
+            ticker_overrides: Default::default(),
+        }
+    }
+
+    //
+    // json object instrument name -> yahoo ticker, must be loaded before any instrument
+    pub fn load_ticker_map(&mut self, filename: &str) -> Result<(), Error> {
+        let file = File::open(filename)?;
+        let reader = BufReader::new(file);
+        self.ticker_overrides = serde_json::from_reader(reader)?;
+        Ok(())
+    }
+
+    pub fn check_ticker_map(&self) {
+        for name in self.ticker_overrides.keys() {
+            if self
+                .cache
+                .get_instrument_by(|instrument| &instrument.name == name)
+                .is_none()
+            {
+                warn!("ticker override on unknown instrument {}", name);
+            }
         }
     }
 
@@ -82,18 +114,98 @@ impl Referential {
                 let filename = self.build_marketdata_filename("instrument", name)?;
                 let file = File::open(filename)?;
                 let reader = BufReader::new(file);
-                let instrument = serialize::from_reader(reader, self)?;
+                let mut instrument: Instrument = serialize::from_reader(reader, self)?;
+                if let Some(ticker) = self.ticker_overrides.get(name) {
+                    info!("override yahoo ticker of {} by {}", name, ticker);
+                    instrument.ticker_yahoo = Some(ticker.clone());
+                }
                 Ok(self.cache.add_instrument(instrument))
             }
         }
     }
 
+    //
+    // only loaded instruments are searched, previous isins match too
+    pub fn get_instrument_by_isin(&self, isin: &str) -> Option<Rc<Instrument>> {
+        self.cache
+            .get_instrument_by(|instrument| instrument.has_isin(isin))
+    }
+
+    //
+    // resolve every currency and instrument (so market) referenced by the portfolio
+    // file, all missing items are reported at once
+    pub fn preload(&mut self, filename: &str) -> Result<(), Error> {
+        let file = File::open(filename)?;
+        let reader = BufReader::new(file);
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+
+        let mut errors = Vec::new();
+        if let Some(name) = value.get("currency").and_then(|item| item.as_str()) {
+            if let Err(err) = self.get_currency_by_name(name) {
+                errors.push(format!("currency {name} because {err:?}"));
+            }
+        }
+
+        let mut names = value
+            .get("positions")
+            .and_then(|item| item.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|position| position.get("instrument").and_then(|item| item.as_str()))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        let mut unresolved = Vec::new();
+        for name in names {
+            if let Err(err) = self.get_instrument_by_name(name) {
+                unresolved.push((name, err));
+            }
+        }
+        //
+        // isins are resolved once all instruments named are loaded
+        for (name, err) in unresolved {
+            if self.get_instrument_by_isin(name).is_none() {
+                errors.push(format!("instrument {name} because {err:?}"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new_referential(format!(
+                "unable to resolv {} item(s) of {}: {}",
+                errors.len(),
+                filename,
+                errors.join(", ")
+            )))
+        }
+    }
+
     pub fn load_portfolio(&mut self, filename: &str) -> Result<Portfolio, Error> {
         let file = File::open(filename)?;
         let reader = BufReader::new(file);
         serialize::from_reader(reader, self)
     }
 
+    //
+    // portfolio named after the file of a broker trades export
+    pub fn load_trades_csv(&mut self, filename: &str, currency: &str) -> Result<Portfolio, Error> {
+        let currency = self.get_currency_by_name(currency)?;
+        let name = Path::new(filename)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(filename)
+            .to_string();
+        let file = File::open(filename)?;
+        Portfolio::from_trades_csv(file, &name, currency, self)
+    }
+
+    pub fn save_portfolio(&self, portfolio: &Portfolio, filename: &str) -> Result<(), Error> {
+        let file = File::create(filename)?;
+        let writer = BufWriter::new(file);
+        serialize::to_writer(writer, portfolio)
+    }
+
     fn build_marketdata_filename(&self, kind: &str, name: &str) -> Result<PathBuf, Error> {
         let mut filename = PathBuf::new();
         filename.push(&self.marketdata_dir);
@@ -109,3 +221,211 @@ impl Referential {
         Ok(filename)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alias::Date;
+    use crate::historical::{DataFrame, HistoricalData, Persistance, Provider, Requester};
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct MockRequester {
+        tickers: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Requester for MockRequester {
+        fn request(
+            &self,
+            instrument: &Instrument,
+            _begin: Date,
+            _end: Date,
+        ) -> Result<(Date, Date, Vec<DataFrame>), Error> {
+            self.tickers
+                .borrow_mut()
+                .push(instrument.ticker_yahoo.clone().unwrap_or_default());
+            Ok(Default::default())
+        }
+    }
+
+    struct MockPersistance;
+
+    impl Persistance for MockPersistance {
+        fn save(&self, _instrument: &Instrument, _datas: &[DataFrame]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn load(
+            &self,
+            _instrument: &Instrument,
+        ) -> Result<Option<(Date, Date, Vec<DataFrame>)>, Error> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn ticker_override() {
+        let ticker_map = std::env::temp_dir().join("portfolio_rs_ticker_map.json");
+        std::fs::write(&ticker_map, r#"{"ESE": "ESE.XX", "UNKNOWN": "UNKNOWN.PA"}"#).unwrap();
+
+        let mut referential = Referential::new("data");
+        referential
+            .load_ticker_map(ticker_map.to_str().unwrap())
+            .unwrap();
+        let instrument = referential.get_instrument_by_name("ESE").unwrap();
+        let other_instrument = referential.get_instrument_by_name("C40").unwrap();
+        referential.check_ticker_map();
+        assert_eq!(instrument.ticker_yahoo.as_deref(), Some("ESE.XX"));
+
+        let requester = MockRequester::default();
+        let tickers = requester.tickers.clone();
+        let persistence = MockPersistance;
+        let mut provider = HistoricalData::new(Box::new(requester), &persistence);
+        let date = Date::from_ymd_opt(2022, 3, 17).unwrap();
+        provider.fetch(&instrument, date, date).unwrap();
+        provider.fetch(&other_instrument, date, date).unwrap();
+        assert_eq!(
+            *tickers.borrow(),
+            vec![
+                String::from("ESE.XX"),
+                other_instrument.ticker_yahoo.clone().unwrap()
+            ]
+        );
+
+        std::fs::remove_file(&ticker_map).unwrap();
+    }
+
+    #[test]
+    fn preload_report_missing_instruments() {
+        let portfolio = std::env::temp_dir().join("portfolio_rs_preload.json");
+        std::fs::write(
+            &portfolio,
+            r#"{
+                "name": "PEA",
+                "currency": "EUR",
+                "positions": [
+                    {"instrument": "ESE", "trades": []},
+                    {"instrument": "MISSING1", "trades": []},
+                    {"instrument": "MISSING2", "trades": []}
+                ],
+                "cash": []
+            }"#,
+        )
+        .unwrap();
+
+        let mut referential = Referential::new("data");
+        let result = referential.preload(portfolio.to_str().unwrap());
+        std::fs::remove_file(&portfolio).unwrap();
+
+        match result {
+            Err(Error::Referential(msg)) => {
+                assert!(msg.contains("2 item(s)"), "{msg}");
+                assert!(msg.contains("MISSING1"), "{msg}");
+                assert!(msg.contains("MISSING2"), "{msg}");
+                assert!(!msg.contains("instrument ESE"), "{msg}");
+            }
+            _ => panic!("preload must fail"),
+        }
+        assert!(referential.get_instrument_by_name("ESE").is_ok());
+    }
+
+    #[test]
+    fn load_trades_csv() {
+        let trades = std::env::temp_dir().join("portfolio_rs_trades.csv");
+        std::fs::write(
+            &trades,
+            "date;way;instrument;quantity;price;fees\n\
+             2022-03-17;buy;ESE;10;15;1\n\
+             2022-03-18;buy;C40;5;90;1\n",
+        )
+        .unwrap();
+
+        let mut referential = Referential::new("data");
+        let portfolio = referential
+            .load_trades_csv(trades.to_str().unwrap(), "EUR")
+            .unwrap();
+        std::fs::remove_file(&trades).unwrap();
+
+        assert_eq!(portfolio.name, "portfolio_rs_trades");
+        assert_eq!(portfolio.currency.name, "EUR");
+        assert_eq!(portfolio.positions.len(), 2);
+        assert_eq!(portfolio.positions[1].instrument.name, "C40");
+    }
+
+    #[test]
+    fn instrument_by_previous_isin() {
+        let marketdata_dir = std::env::temp_dir().join("portfolio_rs_previous_isins");
+        let _ = std::fs::remove_dir_all(&marketdata_dir);
+        for kind in ["instrument", "market", "currency"] {
+            std::fs::create_dir_all(marketdata_dir.join(kind)).unwrap();
+        }
+        std::fs::copy(
+            "data/market/EPA.json",
+            marketdata_dir.join("market").join("EPA.json"),
+        )
+        .unwrap();
+        std::fs::copy(
+            "data/currency/EUR.json",
+            marketdata_dir.join("currency").join("EUR.json"),
+        )
+        .unwrap();
+        std::fs::write(
+            marketdata_dir.join("instrument").join("MERGED.json"),
+            r#"{
+                "name": "MERGED",
+                "isin": "FR0000000003",
+                "previous_isins": ["FR0000000001", "FR0000000002"],
+                "description": "merged fund",
+                "market": "EPA",
+                "currency": "EUR",
+                "fund_category": "Equity"
+            }"#,
+        )
+        .unwrap();
+
+        let portfolio = marketdata_dir.join("portfolio.json");
+        std::fs::write(
+            &portfolio,
+            r#"{
+                "name": "PEA",
+                "currency": "EUR",
+                "positions": [
+                    {"instrument": "FR0000000001", "trades": []},
+                    {"instrument": "MERGED", "trades": []}
+                ],
+                "cash": []
+            }"#,
+        )
+        .unwrap();
+
+        //
+        // the previous isin is resolved once the instrument is loaded by name
+        let mut referential = Referential::new(marketdata_dir.to_str().unwrap());
+        referential.preload(portfolio.to_str().unwrap()).unwrap();
+        let positions = referential
+            .load_portfolio(portfolio.to_str().unwrap())
+            .unwrap()
+            .positions;
+        assert_eq!(positions.len(), 2);
+        assert!(Rc::ptr_eq(
+            &positions[0].instrument,
+            &positions[1].instrument
+        ));
+
+        let mut referential = Referential::new(marketdata_dir.to_str().unwrap());
+        assert!(referential.get_instrument_by_isin("FR0000000001").is_none());
+        let instrument = referential.get_instrument_by_name("MERGED").unwrap();
+        std::fs::remove_dir_all(&marketdata_dir).unwrap();
+
+        assert_eq!(instrument.isin, "FR0000000003");
+        assert_eq!(
+            instrument.isins().collect::<Vec<_>>(),
+            vec!["FR0000000003", "FR0000000001", "FR0000000002"]
+        );
+        for isin in ["FR0000000001", "FR0000000002", "FR0000000003"] {
+            let found = referential.get_instrument_by_isin(isin).unwrap();
+            assert!(Rc::ptr_eq(&found, &instrument));
+        }
+        assert!(referential.get_instrument_by_isin("FR0000000004").is_none());
+    }
+}
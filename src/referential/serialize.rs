@@ -1,9 +1,14 @@
 use crate::alias::{Date, DateTime};
 use crate::error::Error;
-use crate::marketdata::{Currency, Dividend, Instrument, Market, ParentCurrency};
-use crate::portfolio::{CashVariation, CashVariationSource, Portfolio, Position, Trade, Way};
+use crate::marketdata::{
+    Bond, Currency, Dividend, Instrument, InstrumentType, Market, ParentCurrency,
+};
+use crate::portfolio::{
+    CashVariation, CashVariationSource, CloseReason, OpeningPosition, Portfolio, Position, Trade,
+    Way,
+};
 
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::rc::Rc;
 
 pub trait Resolver {
@@ -40,6 +45,91 @@ pub trait Deserializer {
     fn resolv_instrument(&mut self, name: &str) -> Result<Rc<Instrument>, Error>;
 }
 
+pub trait Serialize {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer;
+}
+
+pub trait Serializer {
+    fn write<T>(&mut self, name: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized;
+
+    fn write_option<T>(&mut self, name: &str, value: &Option<T>) -> Result<(), Error>
+    where
+        T: Serialize;
+
+    fn write_array<T>(&mut self, values: &[T]) -> Result<(), Error>
+    where
+        T: Serialize;
+
+    fn write_string(&mut self, value: &str) -> Result<(), Error>;
+    fn write_f64(&mut self, value: f64) -> Result<(), Error>;
+}
+
+#[derive(Default)]
+pub struct SerializerValue {
+    value: Value,
+}
+
+impl Serializer for SerializerValue {
+    fn write<T>(&mut self, name: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut sub_serializer = SerializerValue::default();
+        value.serialize(&mut sub_serializer)?;
+        if self.value.is_null() {
+            self.value = Value::Object(Map::new());
+        }
+        self.value
+            .as_object_mut()
+            .ok_or_else(|| Error::new_referential("field must be an object".to_string()))?
+            .insert(name.to_string(), sub_serializer.value);
+        Ok(())
+    }
+
+    fn write_option<T>(&mut self, name: &str, value: &Option<T>) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        match value {
+            Some(value) => self.write(name, value),
+            None => Ok(()),
+        }
+    }
+
+    fn write_array<T>(&mut self, values: &[T]) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        self.value = Value::Array(
+            values
+                .iter()
+                .map(|value| {
+                    let mut serializer = SerializerValue::default();
+                    value.serialize(&mut serializer)?;
+                    Ok(serializer.value)
+                })
+                .collect::<Result<Vec<_>, Error>>()?,
+        );
+        Ok(())
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<(), Error> {
+        self.value = Value::String(value.to_string());
+        Ok(())
+    }
+
+    fn write_f64(&mut self, value: f64) -> Result<(), Error> {
+        self.value = serde_json::Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| Error::new_referential(format!("unable to write {value} as f64")))?;
+        Ok(())
+    }
+}
+
 pub struct DeserializerValue<'a, R: Resolver> {
     value: &'a Value,
     resolver: &'a mut R,
@@ -194,6 +284,23 @@ impl Deserialize for Way {
     }
 }
 
+impl Deserialize for CloseReason {
+    fn deserialize<D>(deserializer: D) -> Result<Self, Error>
+    where
+        D: Deserializer,
+    {
+        let value: String = deserializer.read_string()?;
+        match value.as_str() {
+            "sell" => Ok(Self::Sell),
+            "merger" => Ok(Self::Merger),
+            "delisting" => Ok(Self::Delisting),
+            _ => Err(Error::new_referential(format!(
+                "unable to convert {value} into CloseReason"
+            ))),
+        }
+    }
+}
+
 impl Deserialize for CashVariationSource {
     fn deserialize<D>(deserializer: D) -> Result<Self, Error>
     where
@@ -202,6 +309,7 @@ impl Deserialize for CashVariationSource {
         let value: String = deserializer.read_string()?;
         match value.as_str() {
             "payment" => Ok(Self::Payment),
+            "realized" => Ok(Self::Realized),
             _ => Err(Error::new_referential(format!(
                 "unable to convert {value} into CashVariationSource"
             ))),
@@ -209,15 +317,23 @@ impl Deserialize for CashVariationSource {
     }
 }
 
+//
+// a trade or dividend date time keeps the wall clock of its offset (the local
+// time of the market or the broker), the offset is dropped without converting
+// to utc so 2025-01-01T23:00:00+01:00 and 2025-01-01T00:30:00+01:00 both stay
+// on 2025-01-01 whatever the offset, serialized back with +00:00
+pub(super) fn parse_datetime_(value: &str) -> Result<DateTime, chrono::ParseError> {
+    chrono::DateTime::parse_from_rfc3339(value).map(|value| value.naive_local())
+}
+
 impl Deserialize for DateTime {
     fn deserialize<D>(deserializer: D) -> Result<Self, Error>
     where
         D: Deserializer,
     {
         let value: String = deserializer.read_string()?;
-        let result = chrono::DateTime::parse_from_rfc3339(value.as_str());
-        match result {
-            Ok(value) => Ok(value.naive_local()),
+        match parse_datetime_(value.as_str()) {
+            Ok(value) => Ok(value),
             Err(err) => Err(Error::new_referential(format!(
                 "unable to convert {value} into Date because {err}"
             ))),
@@ -251,12 +367,34 @@ impl Deserialize for Trade {
         let quantity = deserializer.read("quantity")?;
         let price = deserializer.read("price")?;
         let fees = deserializer.read("fees")?;
+        let order_id = deserializer.read_option("order_id")?;
+        let fx_rate = deserializer.read_option("fx_rate")?;
         Ok(Trade {
             date,
             way,
             quantity,
             price,
             fees,
+            order_id,
+            fx_rate,
+        })
+    }
+}
+
+impl Deserialize for OpeningPosition {
+    fn deserialize<D>(mut deserializer: D) -> Result<Self, Error>
+    where
+        D: Deserializer,
+    {
+        let quantity = deserializer.read("quantity")?;
+        let unit_price = deserializer.read("unit_price")?;
+        let date = deserializer.read("date")?;
+        let fees = deserializer.read_option("fees")?.unwrap_or(0.0);
+        Ok(OpeningPosition {
+            quantity,
+            unit_price,
+            date,
+            fees,
         })
     }
 }
@@ -267,9 +405,35 @@ impl Deserialize for Position {
         D: Deserializer,
     {
         let instrument = deserializer.resolv_instrument("instrument")?;
-        let mut trades: Vec<Trade> = deserializer.read("trades")?;
-        trades.sort_by(|left, right| left.date.cmp(&right.date));
-        Ok(Position { instrument, trades })
+        let opening_position: Option<OpeningPosition> =
+            deserializer.read_option("opening_position")?;
+        //
+        // trades are optional on a migrated position
+        let mut trades: Vec<Trade> = if opening_position.is_some() {
+            deserializer.read_option("trades")?.unwrap_or_default()
+        } else {
+            deserializer.read("trades")?
+        };
+        trades.sort_by(|left, right| left.cmp_chronological(right));
+        if let Some((opening_position, trade)) = opening_position
+            .as_ref()
+            .zip(trades.first())
+            .filter(|(opening_position, trade)| trade.date < opening_position.date)
+        {
+            return Err(Error::new_referential(format!(
+                "trade on {} of {} is before its opening position on {}",
+                trade.date, instrument.name, opening_position.date
+            )));
+        }
+        let close_reason = deserializer.read_option("close_reason")?;
+        let sleeve = deserializer.read_option("sleeve")?;
+        Ok(Position {
+            instrument,
+            trades,
+            close_reason,
+            sleeve,
+            opening_position,
+        })
     }
 }
 
@@ -281,10 +445,14 @@ impl Deserialize for CashVariation {
         let position = deserializer.read("position")?;
         let date = deserializer.read("date")?;
         let source = deserializer.read("source")?;
+        let account = deserializer.read_option("account")?;
+        let fx_rate = deserializer.read_option("fx_rate")?;
         Ok(CashVariation {
             position,
             date,
             source,
+            account,
+            fx_rate,
         })
     }
 }
@@ -326,16 +494,33 @@ impl Deserialize for Instrument {
     {
         let name = deserializer.read("name")?;
         let isin = deserializer.read("isin")?;
+        let previous_isins = deserializer
+            .read_option("previous_isins")?
+            .unwrap_or_default();
         let description = deserializer.read("description")?;
         let market = deserializer.resolv_market("market")?;
         let currency = deserializer.resolv_currency("currency")?;
         let ticker_yahoo = deserializer.read_option("ticker_yahoo")?;
         let region = deserializer.read_option("region")?;
         let fund_category = deserializer.read("fund_category")?;
-        let dividends = deserializer.read_option("dividends")?;
+        let mut dividends: Option<Vec<Dividend>> = deserializer.read_option("dividends")?;
+        let quote_factor = deserializer.read_option("quote_factor")?.unwrap_or(1.0);
+        let ter = deserializer.read_option("ter")?;
+        let history_start = deserializer.read_option("history_start")?;
+        let tags = deserializer.read_option("tags")?.unwrap_or_default();
+        let instrument_type = match deserializer.read_option::<Bond>("bond")? {
+            Some(bond) => {
+                dividends
+                    .get_or_insert_with(Vec::new)
+                    .extend(bond.coupons());
+                InstrumentType::Bond(bond)
+            }
+            None => InstrumentType::Equity,
+        };
         Ok(Instrument {
             name,
             isin,
+            previous_isins,
             description,
             market,
             currency,
@@ -343,6 +528,38 @@ impl Deserialize for Instrument {
             region,
             fund_category,
             dividends,
+            quote_factor,
+            ter,
+            history_start,
+            tags,
+            instrument_type,
+        })
+    }
+}
+
+impl Deserialize for Bond {
+    fn deserialize<D>(mut deserializer: D) -> Result<Self, Error>
+    where
+        D: Deserializer,
+    {
+        let coupon_rate = deserializer.read("coupon_rate")?;
+        let coupon_frequency = deserializer.read_option("coupon_frequency")?.unwrap_or(1);
+        //
+        // coupon dates are a whole number of months apart
+        if ![1, 2, 3, 4, 6, 12].contains(&coupon_frequency) {
+            return Err(Error::new_referential(format!(
+                "coupon_frequency {coupon_frequency} must be one of 1, 2, 3, 4, 6 or 12"
+            )));
+        }
+        let face_value = deserializer.read("face_value")?;
+        let issue_date = deserializer.read("issue_date")?;
+        let maturity = deserializer.read("maturity")?;
+        Ok(Bond {
+            coupon_rate,
+            coupon_frequency,
+            face_value,
+            issue_date,
+            maturity,
         })
     }
 }
@@ -400,6 +617,179 @@ where
     }
 }
 
+impl Serialize for str {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write_string(self)
+    }
+}
+
+impl Serialize for String {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write_string(self)
+    }
+}
+
+impl Serialize for f64 {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write_f64(*self)
+    }
+}
+
+impl Serialize for Way {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Buy => serializer.write_string("buy"),
+            Self::Sell => serializer.write_string("sell"),
+        }
+    }
+}
+
+impl Serialize for CloseReason {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Sell => serializer.write_string("sell"),
+            Self::Merger => serializer.write_string("merger"),
+            Self::Delisting => serializer.write_string("delisting"),
+        }
+    }
+}
+
+impl Serialize for CashVariationSource {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Payment => serializer.write_string("payment"),
+            Self::Realized => serializer.write_string("realized"),
+        }
+    }
+}
+
+impl Serialize for DateTime {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write_string(&self.format("%Y-%m-%dT%H:%M:%S%.f+00:00").to_string())
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write_string(&self.format("%Y-%m-%d").to_string())
+    }
+}
+
+impl Serialize for Trade {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write("date", &self.date)?;
+        serializer.write("way", &self.way)?;
+        serializer.write("quantity", &self.quantity)?;
+        serializer.write("price", &self.price)?;
+        serializer.write("fees", &self.fees)?;
+        serializer.write_option("order_id", &self.order_id)?;
+        serializer.write_option("fx_rate", &self.fx_rate)
+    }
+}
+
+impl Serialize for Position {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        let mut trades = self.trades.iter().collect::<Vec<_>>();
+        trades.sort_by(|left, right| left.cmp_chronological(right));
+        serializer.write("instrument", &self.instrument.name)?;
+        serializer.write("trades", &trades)?;
+        serializer.write_option("close_reason", &self.close_reason)?;
+        serializer.write_option("sleeve", &self.sleeve)?;
+        serializer.write_option("opening_position", &self.opening_position)
+    }
+}
+
+impl Serialize for OpeningPosition {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write("quantity", &self.quantity)?;
+        serializer.write("unit_price", &self.unit_price)?;
+        serializer.write("date", &self.date)?;
+        serializer.write("fees", &self.fees)
+    }
+}
+
+impl Serialize for CashVariation {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write("position", &self.position)?;
+        serializer.write("date", &self.date)?;
+        serializer.write("source", &self.source)?;
+        serializer.write_option("account", &self.account)?;
+        serializer.write_option("fx_rate", &self.fx_rate)
+    }
+}
+
+impl Serialize for Portfolio {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write("name", &self.name)?;
+        serializer.write("currency", &self.currency.name)?;
+        serializer.write("positions", &self.positions)?;
+        serializer.write("cash", &self.cash)
+    }
+}
+
+impl<T> Serialize for Vec<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        serializer.write_array(self)
+    }
+}
+
+impl<T> Serialize for &T
+where
+    T: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), Error>
+    where
+        S: Serializer,
+    {
+        T::serialize(self, serializer)
+    }
+}
+
 pub fn from_reader<R, T, O>(reader: R, resolver: &mut O) -> Result<T, Error>
 where
     R: std::io::Read,
@@ -413,3 +803,226 @@ where
     };
     T::deserialize(deserializer)
 }
+
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: std::io::Write,
+    T: Serialize,
+{
+    let mut serializer = SerializerValue::default();
+    value.serialize(&mut serializer)?;
+    serde_json::to_writer_pretty(writer, &serializer.value)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::make_instrument_;
+
+    struct MockResolver {
+        currency: Rc<Currency>,
+        instrument: Rc<Instrument>,
+    }
+
+    impl Resolver for MockResolver {
+        fn resolv_currency(&mut self, _name: &str) -> Result<Rc<Currency>, Error> {
+            Ok(self.currency.clone())
+        }
+
+        fn resolv_market(&mut self, _name: &str) -> Result<Rc<Market>, Error> {
+            Ok(self.instrument.market.clone())
+        }
+
+        fn resolv_instrument(&mut self, _name: &str) -> Result<Rc<Instrument>, Error> {
+            Ok(self.instrument.clone())
+        }
+    }
+
+    fn make_datetime_(value: &str) -> DateTime {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .naive_local()
+    }
+
+    fn make_portfolio_() -> (Portfolio, MockResolver) {
+        let currency = Rc::new(Currency {
+            name: String::from("EUR"),
+            parent_currency: None,
+        });
+        let instrument = Rc::new(make_instrument_("PAEEM", &currency));
+        let portfolio = Portfolio {
+            name: String::from("PEA"),
+            currency: currency.clone(),
+            positions: vec![Position {
+                instrument: instrument.clone(),
+                trades: vec![
+                    Trade {
+                        date: make_datetime_("2022-03-19T10:00:00-00:00"),
+                        way: Way::Sell,
+                        quantity: 4.0,
+                        price: 22.25,
+                        fees: 0.5,
+                        order_id: None,
+                        fx_rate: None,
+                    },
+                    Trade {
+                        date: make_datetime_("2022-03-17T10:00:00-00:00"),
+                        way: Way::Buy,
+                        quantity: 14.0,
+                        price: 21.5,
+                        fees: 1.55,
+                        order_id: Some(String::from("A")),
+                        fx_rate: Some(1.1),
+                    },
+                ],
+                close_reason: None,
+                sleeve: None,
+                opening_position: None,
+            }],
+            cash: vec![CashVariation {
+                position: 1000.0,
+                date: make_datetime_("2022-03-15T10:00:00-00:00"),
+                source: CashVariationSource::Payment,
+                account: None,
+                fx_rate: None,
+            }],
+        };
+        (
+            portfolio,
+            MockResolver {
+                currency,
+                instrument,
+            },
+        )
+    }
+
+    #[test]
+    fn datetime_keeps_offset_wall_clock() {
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        for (value, hour) in [
+            ("2025-01-01T23:00:00+01:00", 23),
+            ("2025-01-01T00:30:00+01:00", 0),
+            ("2025-01-01T20:00:00-05:00", 20),
+        ] {
+            let datetime = parse_datetime_(value).unwrap();
+            assert_eq!(datetime.date(), date, "{value}");
+            assert_eq!(chrono::Timelike::hour(&datetime), hour, "{value}");
+
+            let mut serializer = SerializerValue::default();
+            datetime.serialize(&mut serializer).unwrap();
+            let written = serializer.value.as_str().unwrap().to_string();
+            assert_eq!(parse_datetime_(&written).unwrap(), datetime);
+        }
+        assert!(parse_datetime_("2025-01-01 23:00:00").is_err());
+    }
+
+    #[test]
+    fn instrument_with_tags() {
+        let (_, mut resolver) = make_portfolio_();
+        let make_json_ = |tags: &str| {
+            format!(
+                r#"{{
+                    "name": "ESE",
+                    "isin": "FR0011550185",
+                    "description": "description",
+                    "market": "EPA",
+                    "currency": "EUR",
+                    "fund_category": "Equity"{tags}
+                }}"#
+            )
+        };
+        let json = make_json_(r#", "tags": ["core", "dividend"]"#);
+        let instrument: Instrument = from_reader(json.as_bytes(), &mut resolver).unwrap();
+        assert_eq!(instrument.tags, vec!["core", "dividend"]);
+
+        let json = make_json_("");
+        let instrument: Instrument = from_reader(json.as_bytes(), &mut resolver).unwrap();
+        assert!(instrument.tags.is_empty());
+    }
+
+    #[test]
+    fn bond_coupon_frequency() {
+        let (_, mut resolver) = make_portfolio_();
+        let make_json_ = |coupon_frequency: u32| {
+            format!(
+                r#"{{
+                    "coupon_rate": 0.04,
+                    "coupon_frequency": {coupon_frequency},
+                    "face_value": 100.0,
+                    "issue_date": "2020-06-15",
+                    "maturity": "2022-06-15"
+                }}"#
+            )
+        };
+        for coupon_frequency in [1, 2, 3, 4, 6, 12] {
+            let json = make_json_(coupon_frequency);
+            let bond: Bond = from_reader(json.as_bytes(), &mut resolver).unwrap();
+            assert_eq!(bond.coupons().len(), 2 * coupon_frequency as usize);
+        }
+        for coupon_frequency in [0, 5, 13] {
+            let json = make_json_(coupon_frequency);
+            assert!(from_reader::<_, Bond, _>(json.as_bytes(), &mut resolver).is_err());
+        }
+    }
+
+    #[test]
+    fn trade_before_opening_position() {
+        let (_, mut resolver) = make_portfolio_();
+        let make_json_ = |trade_date: &str| {
+            format!(
+                r#"{{
+                    "instrument": "PAEEM",
+                    "opening_position": {{
+                        "quantity": 10.0,
+                        "unit_price": 20.0,
+                        "date": "2022-03-17T10:00:00+00:00",
+                        "fees": 0.0
+                    }},
+                    "trades": [{{
+                        "date": "{trade_date}",
+                        "way": "buy",
+                        "quantity": 5.0,
+                        "price": 21.0,
+                        "fees": 0.0
+                    }}]
+                }}"#
+            )
+        };
+        let json = make_json_("2022-03-18T10:00:00+00:00");
+        let position: Position = from_reader(json.as_bytes(), &mut resolver).unwrap();
+        assert_eq!(position.trades.len(), 1);
+
+        let json = make_json_("2022-03-16T10:00:00+00:00");
+        assert!(from_reader::<_, Position, _>(json.as_bytes(), &mut resolver).is_err());
+    }
+
+    #[test]
+    fn portfolio_round_trip() {
+        let (portfolio, mut resolver) = make_portfolio_();
+
+        let mut buffer = Vec::new();
+        to_writer(&mut buffer, &portfolio).unwrap();
+        let result: Portfolio = from_reader(buffer.as_slice(), &mut resolver).unwrap();
+
+        assert_eq!(result.name, portfolio.name);
+        assert_eq!(result.currency.name, portfolio.currency.name);
+        assert_eq!(result.cash, portfolio.cash);
+        assert_eq!(result.positions.len(), 1);
+        assert_eq!(
+            result.positions[0].instrument,
+            portfolio.positions[0].instrument
+        );
+
+        let mut trades = portfolio.positions[0].trades.iter().collect::<Vec<_>>();
+        trades.sort_by(|left, right| left.cmp_chronological(right));
+        assert_eq!(
+            result.positions[0].trades.iter().collect::<Vec<_>>(),
+            trades
+        );
+
+        let mut second_buffer = Vec::new();
+        to_writer(&mut second_buffer, &result).unwrap();
+        assert_eq!(buffer, second_buffer);
+    }
+}
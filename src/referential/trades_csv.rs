@@ -0,0 +1,203 @@
+use super::serialize::{parse_datetime_, Resolver};
+use crate::alias::DateTime;
+use crate::error::Error;
+use crate::marketdata::Currency;
+use crate::portfolio::{Portfolio, Position, Trade, Way};
+
+use std::io::BufRead;
+use std::rc::Rc;
+
+impl Portfolio {
+    //
+    // rows are date;way;instrument;quantity;price;fees, header line is optional
+    pub fn from_trades_csv<R, O>(
+        reader: R,
+        name: &str,
+        currency: Rc<Currency>,
+        instrument_resolver: &mut O,
+    ) -> Result<Portfolio, Error>
+    where
+        R: std::io::Read,
+        O: Resolver,
+    {
+        let mut positions: Vec<Position> = Vec::new();
+        for (row, line) in std::io::BufReader::new(reader).lines().enumerate() {
+            let row = row + 1;
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || (row == 1 && line.starts_with("date")) {
+                continue;
+            }
+
+            let fields = line.split(';').map(str::trim).collect::<Vec<_>>();
+            if fields.len() != 6 {
+                return Err(Error::new_referential(format!(
+                    "row {row}: expected 6 fields found {}",
+                    fields.len()
+                )));
+            }
+
+            let instrument = instrument_resolver
+                .resolv_instrument(fields[2])
+                .map_err(|err| {
+                    Error::new_referential(format!(
+                        "row {row}: unknown instrument {} because {err:?}",
+                        fields[2]
+                    ))
+                })?;
+            let trade = Trade {
+                date: parse_date_(row, fields[0])?,
+                way: parse_way_(row, fields[1])?,
+                quantity: parse_f64_(row, "quantity", fields[3])?,
+                price: parse_f64_(row, "price", fields[4])?,
+                fees: parse_f64_(row, "fees", fields[5])?,
+                order_id: None,
+                fx_rate: None,
+            };
+
+            match positions
+                .iter_mut()
+                .find(|position| position.instrument == instrument)
+            {
+                Some(position) => position.trades.push(trade),
+                None => positions.push(Position {
+                    instrument,
+                    trades: vec![trade],
+                    close_reason: None,
+                    sleeve: None,
+                    opening_position: None,
+                }),
+            }
+        }
+
+        for position in positions.iter_mut() {
+            position
+                .trades
+                .sort_by(|left, right| left.cmp_chronological(right));
+        }
+
+        Ok(Portfolio {
+            name: name.to_string(),
+            currency,
+            positions,
+            cash: Default::default(),
+        })
+    }
+}
+
+fn parse_date_(row: usize, value: &str) -> Result<DateTime, Error> {
+    parse_datetime_(value)
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|value| value.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|err| {
+            Error::new_referential(format!(
+                "row {row}: unable to convert {value} into Date because {err}"
+            ))
+        })
+}
+
+fn parse_way_(row: usize, value: &str) -> Result<Way, Error> {
+    match value.to_lowercase().as_str() {
+        "buy" => Ok(Way::Buy),
+        "sell" => Ok(Way::Sell),
+        _ => Err(Error::new_referential(format!(
+            "row {row}: unable to convert {value} into Way"
+        ))),
+    }
+}
+
+fn parse_f64_(row: usize, name: &str, value: &str) -> Result<f64, Error> {
+    value.parse().map_err(|err| {
+        Error::new_referential(format!(
+            "row {row}: unable to convert {name} {value} into f64 because {err}"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{make_currency_, make_instrument_};
+    use crate::marketdata::{Instrument, Market};
+    use assert_float_eq::*;
+
+    struct MockResolver {
+        instruments: Vec<Rc<Instrument>>,
+    }
+
+    impl Resolver for MockResolver {
+        fn resolv_currency(&mut self, name: &str) -> Result<Rc<Currency>, Error> {
+            Err(Error::new_referential(format!("unknown currency {name}")))
+        }
+
+        fn resolv_market(&mut self, name: &str) -> Result<Rc<Market>, Error> {
+            Err(Error::new_referential(format!("unknown market {name}")))
+        }
+
+        fn resolv_instrument(&mut self, name: &str) -> Result<Rc<Instrument>, Error> {
+            self.instruments
+                .iter()
+                .find(|instrument| instrument.name == name)
+                .cloned()
+                .ok_or_else(|| Error::new_referential(format!("unknown instrument {name}")))
+        }
+    }
+
+    fn make_resolver_() -> MockResolver {
+        MockResolver {
+            instruments: ["PAEEM", "ESE"]
+                .into_iter()
+                .map(|name| Rc::new(make_instrument_(name, &make_currency_("EUR"))))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parse_trades_csv() {
+        let csv = "date;way;instrument;quantity;price;fees\n\
+                   2022-03-19T10:00:00+00:00;buy;PAEEM;20;19.5;1.5\n\
+                   2022-03-17;buy;PAEEM;14;21.5;1.55\n\
+                   2022-03-18;buy;ESE;10;15;1\n\
+                   2022-03-22;sell;ESE;10;16;1\n";
+        let portfolio = Portfolio::from_trades_csv(
+            csv.as_bytes(),
+            "PEA",
+            make_currency_("EUR"),
+            &mut make_resolver_(),
+        )
+        .unwrap();
+
+        assert_eq!(portfolio.name, "PEA");
+        assert_eq!(portfolio.positions.len(), 2);
+
+        let position = &portfolio.positions[0];
+        assert_eq!(position.instrument.name, "PAEEM");
+        assert_eq!(position.trades.len(), 2);
+        assert_float_absolute_eq!(position.trades[0].quantity, 14.0, 1e-7);
+        assert_float_absolute_eq!(position.trades[1].quantity, 20.0, 1e-7);
+        assert_eq!(position.trades[1].way, Way::Buy);
+
+        let position = &portfolio.positions[1];
+        assert_eq!(position.instrument.name, "ESE");
+        assert_eq!(position.trades.len(), 2);
+        assert_eq!(position.trades[1].way, Way::Sell);
+        assert_float_absolute_eq!(position.trades[1].fees, 1.0, 1e-7);
+    }
+
+    #[test]
+    fn unknown_instrument_in_trades_csv() {
+        let csv = "2022-03-17;buy;PAEEM;14;21.5;1.55\n2022-03-18;buy;UNKNOWN;10;15;1\n";
+        let result = Portfolio::from_trades_csv(
+            csv.as_bytes(),
+            "PEA",
+            make_currency_("EUR"),
+            &mut make_resolver_(),
+        );
+        match result {
+            Err(Error::Referential(message)) => assert!(message.starts_with("row 2:"), "{message}"),
+            _ => panic!("unknown instrument must fail"),
+        }
+    }
+}